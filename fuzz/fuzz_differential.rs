@@ -0,0 +1,47 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cretonne_codegen;
+extern crate cretonne_filetests;
+extern crate cretonne_fuzzgen;
+use cretonne_codegen::isa::{self, TargetIsa};
+use cretonne_codegen::settings::{self, Configurable};
+use cretonne_codegen::verify_function;
+use cretonne_filetests::CompiledFunction;
+use cretonne_fuzzgen::{Config, TestCase};
+
+fn host_isa(opt_level: &str) -> Box<TargetIsa> {
+    let mut flags = settings::builder();
+    flags.enable("is_64bit").unwrap();
+    flags.set("opt_level", opt_level).unwrap();
+    isa::lookup("x86")
+        .unwrap()
+        .finish(settings::Flags::new(flags))
+}
+
+fn compile(testcase: &TestCase, isa: &TargetIsa) -> CompiledFunction {
+    CompiledFunction::new(&testcase.func, isa)
+        .unwrap_or_else(|e| panic!("{}", e))
+        .expect("generated functions don't need relocations")
+}
+
+fuzz_target!(|data: &[u8]| {
+    let testcase = TestCase::generate(data, &Config::default());
+    let fastest_isa = host_isa("fastest");
+    let best_isa = host_isa("best");
+    if let Err(errors) = verify_function(&testcase.func, &*fastest_isa) {
+        panic!("generated an invalid function:\n{}\n{}", testcase.func, errors);
+    }
+
+    let fastest = compile(&testcase, &*fastest_isa);
+    let best = compile(&testcase, &*best_isa);
+    for args in &testcase.inputs {
+        assert_eq!(
+            fastest.call(args),
+            best.call(args),
+            "different results for {:?} in\n{}",
+            args,
+            testcase.func.display(None)
+        );
+    }
+});
@@ -0,0 +1,70 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cretonne_codegen;
+extern crate cretonne_fuzzgen;
+extern crate cretonne_reader;
+use cretonne_codegen::ir::{Function, SourceLoc};
+use cretonne_codegen::isa::{self, TargetIsa};
+use cretonne_codegen::settings::{self, Configurable};
+use cretonne_codegen::Context;
+use cretonne_fuzzgen::{Config, TestCase};
+use cretonne_reader::{parse_functions, parse_test};
+use std::str;
+
+/// Settings and ISA lines to put in front of a function compiled for `host_isa()`.
+const ISA_HEADER: &str = "set is_64bit\nset opt_level=best\nisa x86\n";
+
+fn host_isa() -> Box<TargetIsa> {
+    let mut flags = settings::builder();
+    flags.enable("is_64bit").unwrap();
+    flags.set("opt_level", "best").unwrap();
+    isa::lookup("x86")
+        .unwrap()
+        .finish(settings::Flags::new(flags))
+}
+
+/// Check that the text produced by writing `func` parses back into the same function.
+fn check_round_trip(func: &Function, isa: Option<&TargetIsa>) {
+    let header = if isa.is_some() { ISA_HEADER } else { "" };
+    let text = format!("{}{}", header, func.display(isa));
+    let parsed = match parse_test(&text) {
+        Ok(parsed) => parsed,
+        Err(e) => panic!("can't parse written function: {}\n{}", e, text),
+    };
+    assert_eq!(parsed.functions.len(), 1);
+    let reparsed = &parsed.functions[0].0;
+    let retext = format!("{}{}", header, reparsed.display(isa));
+    assert_eq!(text, retext, "function changed when written and parsed again");
+}
+
+fuzz_target!(|data: &[u8]| {
+    // Arbitrary text must produce either functions or an error, never a panic.
+    if let Ok(text) = str::from_utf8(data) {
+        if let Ok(funcs) = parse_functions(text) {
+            for func in &funcs {
+                check_round_trip(func, None);
+            }
+        }
+    }
+
+    // A compiled function has encodings, value locations and aliases to write as well.
+    let mut testcase = TestCase::generate(data, &Config::default());
+    let insts = testcase
+        .func
+        .layout
+        .ebbs()
+        .flat_map(|ebb| testcase.func.layout.ebb_insts(ebb))
+        .collect::<Vec<_>>();
+    for (i, inst) in insts.into_iter().enumerate() {
+        testcase.func.srclocs[inst] = SourceLoc::new(i as u32);
+    }
+    check_round_trip(&testcase.func, None);
+
+    let isa = host_isa();
+    let mut ctx = Context::for_function(testcase.func);
+    if let Err(e) = ctx.compile(&*isa) {
+        panic!("can't compile generated function: {}\n{}", e, ctx.func);
+    }
+    check_round_trip(&ctx.func, Some(&*isa));
+});
@@ -10,6 +10,7 @@ from cdsl.formats import InstructionFormat
 from cdsl.operands import VALUE, VARIABLE_ARGS
 from .immediates import imm64, uimm8, uimm32, ieee32, ieee64, offset32
 from .immediates import boolean, intcc, floatcc, memflags, regunit, trapcode
+from .immediates import invoke_targets
 from . import entities
 from .entities import ebb, sig_ref, func_ref, stack_slot, heap
 
@@ -19,6 +20,7 @@ UnaryIeee32 = InstructionFormat(ieee32)
 UnaryIeee64 = InstructionFormat(ieee64)
 UnaryBool = InstructionFormat(boolean)
 UnaryGlobalVar = InstructionFormat(entities.global_var)
+UnaryConst = InstructionFormat(entities.constant)
 
 Binary = InstructionFormat(VALUE, VALUE)
 BinaryImm = InstructionFormat(VALUE, imm64)
@@ -51,9 +53,14 @@ BranchInt = InstructionFormat(intcc, VALUE, ebb, VARIABLE_ARGS)
 BranchFloat = InstructionFormat(floatcc, VALUE, ebb, VARIABLE_ARGS)
 BranchIcmp = InstructionFormat(intcc, VALUE, VALUE, ebb, VARIABLE_ARGS)
 BranchTable = InstructionFormat(VALUE, entities.jump_table)
+BranchTableBase = InstructionFormat(entities.jump_table)
+BranchTableEntry = InstructionFormat(VALUE, VALUE, entities.jump_table)
 
 Call = InstructionFormat(func_ref, VARIABLE_ARGS)
 CallIndirect = InstructionFormat(sig_ref, VALUE, VARIABLE_ARGS)
+Invoke = InstructionFormat(invoke_targets, VARIABLE_ARGS)
+CallLandingPad = InstructionFormat(
+        func_ref, ('landing_pad', ebb), VARIABLE_ARGS)
 FuncAddr = InstructionFormat(func_ref)
 
 Load = InstructionFormat(memflags, VALUE, offset32)
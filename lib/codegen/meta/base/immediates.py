@@ -121,3 +121,13 @@ trapcode = ImmediateKind(
             "int_ovf": 'IntegerOverflow',
             "int_divz": 'IntegerDivisionByZero',
         })
+
+#: The callee, normal destination, and landing pad of an `invoke` instruction.
+#:
+#: These are stored out of line because they don't fit in `InstructionData`
+#: together with the call arguments.
+invoke_targets = ImmediateKind(
+        'invoke_targets',
+        'The callee and destinations of an invoke.',
+        default_member='targets',
+        rust_type='Box<ir::InvokeTargets>')
@@ -11,7 +11,7 @@ from cdsl.instructions import Instruction, InstructionGroup
 from base.types import f32, f64, b1, iflags, fflags
 from base.immediates import imm64, uimm8, uimm32, ieee32, ieee64, offset32
 from base.immediates import boolean, intcc, floatcc, memflags, regunit
-from base.immediates import trapcode
+from base.immediates import trapcode, invoke_targets
 from base import entities
 from cdsl.ti import WiderOrEq
 import base.formats  # noqa
@@ -144,6 +144,41 @@ br_table = Instruction(
         """,
         ins=(x, JT), is_branch=True)
 
+jt_addr = Operand('addr', iAddr, doc='address of the jump table')
+jump_table_base = Instruction(
+        'jump_table_base', r"""
+        Get the address of a jump table.
+
+        Jump tables are emitted after the function's code. Each entry is a
+        32-bit offset from the start of the table to the destination EBB.
+        """,
+        ins=JT, outs=jt_addr)
+
+jt_index = Operand('x', iAddr, doc='index into jump table')
+jt_entry = Operand('entry', iAddr, doc='sign-extended jump table entry')
+jump_table_entry = Instruction(
+        'jump_table_entry', r"""
+        Load an entry from a jump table.
+
+        Load the 32-bit entry at index ``x`` from the jump table ``JT`` whose
+        address is ``addr``, and sign-extend it. The index must be in range;
+        it is not checked.
+        """,
+        ins=(jt_index, jt_addr, JT), outs=jt_entry, can_load=True)
+
+jt_dest = Operand('addr', iAddr, doc='destination address')
+indirect_jump_table_br = Instruction(
+        'indirect_jump_table_br', r"""
+        Branch indirectly via a jump table entry.
+
+        Unconditionally jump to ``addr``, which must be the address of one of
+        the destinations in the jump table ``JT``. This is used to lower
+        :inst:`br_table` after the index has been range checked, and ``JT``
+        tells the control flow graph where the branch can go.
+        """,
+        ins=(jt_dest, JT), is_branch=True, is_indirect_branch=True,
+        is_terminator=True)
+
 code = Operand('code', trapcode)
 trap = Instruction(
         'trap', r"""
@@ -224,13 +259,100 @@ call_indirect = Instruction(
         """,
         ins=(SIG, callee, args), outs=rvals, is_call=True)
 
+return_call = Instruction(
+        'return_call', r"""
+        Direct tail call.
+
+        Call a function which has been declared in the preamble, reusing the
+        stack frame of the current function. The callee returns directly to
+        the caller of the current function, so its return types must match the
+        current function's signature. Control never comes back to the current
+        function.
+
+        Arguments passed on the stack are stored in the current function's
+        incoming argument area, so the callee can't need more stack space for
+        its arguments than the current function received.
+        """,
+        ins=(FN, args), is_call=True, is_terminator=True)
+
+return_call_indirect = Instruction(
+        'return_call_indirect', r"""
+        Indirect tail call.
+
+        Call the function pointed to by `callee` with the given arguments,
+        reusing the stack frame of the current function. The called function
+        must match the specified signature, and its return types must match
+        the current function's signature. See :inst:`return_call`.
+        """,
+        ins=(SIG, callee, args), is_call=True, is_terminator=True)
+
+LP = Operand('LP', entities.ebb, doc='landing pad EBB')
+T = Operand(
+        'T', invoke_targets,
+        doc='function to call, normal destination, and landing pad EBB')
+
+invoke = Instruction(
+        'invoke', r"""
+        Direct function call with an exception landing pad.
+
+        Call a function which has been declared in the preamble, like
+        :inst:`call`. If the callee returns normally, execution continues at
+        the normal destination. If an exception unwinds through the call,
+        execution continues at the landing pad instead. In the text format,
+        the function and its arguments are followed by the normal destination
+        and the landing pad::
+
+            v1 = invoke fn0(v0), ebb1, ebb2
+
+        Neither destination can have any parameters. The return values of the
+        call are available in the normal destination, but not in the landing
+        pad.
+
+        The landing pad EBB can only be reached from :inst:`invoke` and
+        :inst:`call_lp` instructions. It can use :inst:`landingpad` to get the
+        exception values.
+        """,
+        ins=(T, args), outs=rvals,
+        is_call=True, is_branch=True, is_terminator=True)
+
+call_lp = Instruction(
+        'call_lp', r"""
+        Direct function call with an exception landing pad.
+
+        This is the legalized form of :inst:`invoke`. The legalizer splits an
+        :inst:`invoke` into a :inst:`call_lp` instruction followed by a
+        :inst:`jump` to its normal destination, so the call results can be
+        converted to the ABI of the target before the jump.
+
+        If the callee returns normally, execution continues at the following
+        instruction. If an exception unwinds through the call, execution
+        continues at the landing pad ``LP``.
+        """,
+        ins=(FN, LP, args), outs=rvals, is_call=True, is_branch=True)
+
+exn = Operand('exn', iAddr, doc='pointer to the exception object')
+sel = Operand('sel', iAddr, doc='selector from the personality routine')
+
+landingpad = Instruction(
+        'landingpad', r"""
+        Get the exception values in a landing pad.
+
+        The unwinder passes the exception values to a landing pad in
+        registers, so this instruction must be the first instruction in a
+        landing pad EBB. ``exn`` is a pointer to the exception object being
+        thrown, and ``sel`` is the action selector chosen by the personality
+        routine. Rethrowing the exception is done by calling
+        ``_Unwind_Resume(exn)``.
+        """,
+        outs=(exn, sel), other_side_effects=True)
+
 func_addr = Instruction(
         'func_addr', r"""
         Get the address of a function.
 
         Compute the absolute address of a function declared in the preamble.
         The returned address can be used as a ``callee`` argument to
-        :inst:`call_indirect`. This is also a method for calling functions that
+        :inst:`call_indirect` or :inst:`return_call_indirect`. This is also a method for calling functions that
         are too far away to be addressable by a direct :inst:`call`
         instruction.
         """,
@@ -577,6 +699,27 @@ bconst = Instruction(
         """,
         ins=N, outs=a)
 
+C = Operand('C', entities.constant)
+a = Operand('a', TxN, doc='A constant vector value')
+vconst = Instruction(
+        'vconst', r"""
+        SIMD vector constant.
+
+        Create a SIMD vector SSA value whose bytes are taken from the constant
+        ``C`` in the function's constant pool.
+        """,
+        ins=C, outs=a)
+
+addr = Operand('addr', iAddr)
+const_addr = Instruction(
+        'const_addr', r"""
+        Get the address of a constant.
+
+        Compute the address of the constant ``C`` in the function's constant
+        pool. The constant pool is read-only.
+        """,
+        ins=C, outs=addr)
+
 #
 # Generics.
 #
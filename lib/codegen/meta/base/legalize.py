@@ -12,7 +12,7 @@ from . import instructions as insts
 from . import types
 from .instructions import iadd, iadd_cout, iadd_cin, iadd_carry, iadd_imm
 from .instructions import isub, isub_bin, isub_bout, isub_borrow, irsub_imm
-from .instructions import imul, imul_imm
+from .instructions import imul, imul_imm, umulhi
 from .instructions import sdiv, sdiv_imm, udiv, udiv_imm
 from .instructions import srem, srem_imm, urem, urem_imm
 from .instructions import band, bor, bxor, isplit, iconcat
@@ -64,9 +64,12 @@ expand_flags = XFormGroup('expand_flags', """
 # Custom expansions for memory objects.
 expand.custom_legalize(insts.global_addr, 'expand_global_addr')
 expand.custom_legalize(insts.heap_addr, 'expand_heap_addr')
+expand.custom_legalize(insts.stack_load, 'expand_stack_load')
+expand.custom_legalize(insts.stack_store, 'expand_stack_store')
 
 # Custom expansions for calls.
 expand.custom_legalize(insts.call, 'expand_call')
+expand.custom_legalize(insts.return_call, 'expand_call')
 
 # Custom expansions that need to change the CFG.
 # TODO: Add sufficient XForm syntax that we don't need to hand-code these.
@@ -80,6 +83,15 @@ expand.custom_legalize(insts.select, 'expand_select')
 expand.custom_legalize(insts.f32const, 'expand_fconst')
 expand.custom_legalize(insts.f64const, 'expand_fconst')
 
+# Narrowing a constant requires splitting the immediate.
+narrow.custom_legalize(insts.iconst, 'narrow_iconst')
+
+# Wide divisions call runtime library routines, with explicit trap checks.
+narrow.custom_legalize(insts.sdiv, 'narrow_divrem')
+narrow.custom_legalize(insts.udiv, 'narrow_divrem')
+narrow.custom_legalize(insts.srem, 'narrow_divrem')
+narrow.custom_legalize(insts.urem, 'narrow_divrem')
+
 x = Var('x')
 y = Var('y')
 a = Var('a')
@@ -134,6 +146,22 @@ for bitop in [band, bor, bxor]:
                 a << iconcat(al, ah)
             ))
 
+# The high half of the product only needs the high half of the product of the
+# low halves, and the low halves of the cross products.
+narrow.legalize(
+        a << imul(x, y),
+        Rtl(
+            (xl, xh) << isplit(x),
+            (yl, yh) << isplit(y),
+            al << imul(xl, yl),
+            a1 << umulhi(xl, yl),
+            a2 << imul(xl, yh),
+            b1 << imul(xh, yl),
+            b2 << iadd(a1, a2),
+            ah << iadd(b2, b1),
+            a << iconcat(al, ah)
+        ))
+
 narrow.legalize(
         a << select(c, x, y),
         Rtl(
@@ -204,7 +232,7 @@ for inst_imm,      inst in [
         (urem_imm, urem),
         (band_imm, band),
         (bor_imm, bor),
-        (bxor_imm, bor),
+        (bxor_imm, bxor),
         (ifcmp_imm, ifcmp)]:
     expand.legalize(
             a << inst_imm(x, y),
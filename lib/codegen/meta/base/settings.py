@@ -95,7 +95,9 @@ enable_float = BoolSetting(
         """
         Enable the use of floating-point instructions
 
-        Disabling use of floating-point instructions is not yet implemented.
+        When disabled, floating point values are represented as integers of
+        the same size and passed in integer registers, and floating point
+        instructions are legalized into calls to soft-float library routines.
         """,
         default=True)
 
@@ -117,6 +119,30 @@ enable_atomics = BoolSetting(
         """Enable the use of atomic instructions""",
         default=True)
 
+jump_tables_enabled = BoolSetting(
+        """
+        Enable the use of jump tables in generated machine code.
+
+        When disabled, :inst:`br_table` is lowered to a sequence of
+        comparisons and conditional branches instead of a jump table lookup
+        and an indirect branch.
+        """,
+        default=True)
+
+enable_heap_access_spectre_mitigation = BoolSetting(
+        """
+        Harden heap bounds checks against speculative execution.
+
+        When enabled, the address computed by a bounds-checked ``heap_addr``
+        is also clamped to zero with a conditional move on the same CPU flags
+        as the trap, so a mispredicted bounds check can't be used to read
+        outside the heap. This applies to WebAssembly linear memories
+        translated by ``cretonne-wasm``, as well as any other heaps.
+
+        This requires an ISA with encodings for ``ifcmp``, ``trapif`` and
+        ``selectif``.
+        """)
+
 #
 # Settings specific to the `baldrdash` calling convention.
 #
@@ -469,7 +469,9 @@ class Encoding(object):
                 "Format {} must match recipe: {}".format(
                     self.inst.format, recipe.format))
 
-        if self.inst.is_branch:
+        # The landing pad of a call isn't encoded in the instruction.
+        if (self.inst.is_branch and not self.inst.is_indirect_branch and
+                not self.inst.is_call):
             assert recipe.branch_range, (
                     'Recipe {} for {} must have a branch_range'
                     .format(recipe, self.inst.name))
@@ -9,6 +9,7 @@ from .recipes import OPIMM, OPIMM32, OP, OP32, LUI, BRANCH, JALR, JAL
 from .recipes import LOAD, STORE
 from .recipes import R, Rshamt, Ricmp, Ii, Iz, Iicmp, Iret, Icall, Icopy
 from .recipes import U, UJ, UJcall, SB, SBzero, GPsp, GPfi, Irmov
+from .recipes import AIUPC, Ijt, Ujt_base, Rjt_entry
 from .settings import use_m
 from cdsl.ast import Var
 from base.legalize import narrow, expand
@@ -139,6 +140,14 @@ RV64.enc(base.x_return, Iret, JALR())
 RV32.enc(base.call_indirect.i32, Icall, JALR())
 RV64.enc(base.call_indirect.i64, Icall, JALR())
 
+# Jump tables. The entries are 32-bit offsets from the start of the table.
+RV32.enc(base.jump_table_base.i32, Ujt_base, AIUPC())
+RV64.enc(base.jump_table_base.i64, Ujt_base, AIUPC())
+RV32.enc(base.jump_table_entry.i32, Rjt_entry, LOAD(0b010))
+RV64.enc(base.jump_table_entry.i64, Rjt_entry, LOAD(0b010))
+RV32.enc(base.indirect_jump_table_br.i32, Ijt, JALR())
+RV64.enc(base.indirect_jump_table_br.i64, Ijt, JALR())
+
 # Spill and fill.
 RV32.enc(base.spill.i32, GPsp, STORE(0b010))
 RV64.enc(base.spill.i32, GPsp, STORE(0b010))
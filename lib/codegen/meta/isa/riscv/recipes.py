@@ -15,6 +15,7 @@ from cdsl.registers import Stack
 from base.formats import Binary, BinaryImm, MultiAry, IntCompare, IntCompareImm
 from base.formats import Unary, UnaryImm, BranchIcmp, Branch, Jump
 from base.formats import Call, CallIndirect, RegMove
+from base.formats import BranchTable, BranchTableBase, BranchTableEntry
 from .registers import GPR
 
 # The low 7 bits of a RISC-V instruction is the base opcode. All 32-bit
@@ -155,6 +156,21 @@ Icall = EncRecipe(
         ''')
 
 
+# I-type encoding for `jalr` as an indirect jump via a jump table.
+Ijt = EncRecipe(
+        'Ijt', BranchTable, size=4, ins=GPR, outs=(),
+        emit='''
+        // Indirect jumps are jalr with rd=%x0.
+        put_i(
+            bits,
+            in_reg0,
+            0, // no offset.
+            0, // rd = %x0: no return address.
+            sink,
+        );
+        ''')
+
+
 # Copy of a GPR is implemented as addi x, 0.
 Icopy = EncRecipe(
         'Icopy', Unary, size=4, ins=GPR, outs=GPR,
@@ -190,6 +206,29 @@ UJcall = EncRecipe(
         put_uj(bits, 0, 1, sink);
         ''')
 
+# Address of a jump table: auipc followed by addi.
+# The jump tables are emitted right after the function, so the pc-relative
+# displacement is known.
+Ujt_base = EncRecipe(
+        'Ujt_base', BranchTableBase, size=8, ins=(), outs=GPR,
+        emit='''
+        let disp = i64::from(func.jt_offsets[table]) - i64::from(sink.offset());
+        // The low 12 bits are sign-extended by addi, so round the high part.
+        let hi = (disp + 0x800) & !0xfff;
+        put_u(bits, hi, out_reg0, sink);
+        put_i(ADDI_BITS, out_reg0, disp - hi, out_reg0, sink);
+        ''')
+
+# Load of a jump table entry: slli, add, and lw. The index register is
+# overwritten with the result.
+Rjt_entry = EncRecipe(
+        'Rjt_entry', BranchTableEntry, size=12, ins=(GPR, GPR), outs=0,
+        emit='''
+        put_rshamt(SLLI_BITS, in_reg0, 2, in_reg0, sink);
+        put_r(ADD_BITS, in_reg0, in_reg1, in_reg0, sink);
+        put_i(bits, in_reg0, 0, in_reg0, sink);
+        ''')
+
 # SB-type branch instructions.
 SB = EncRecipe(
         'SB', BranchIcmp, size=4,
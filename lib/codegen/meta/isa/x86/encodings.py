@@ -5,7 +5,9 @@ from __future__ import absolute_import
 from cdsl.predicates import IsUnsignedInt, Not, And
 from base.predicates import IsColocatedFunc, IsColocatedData, LengthEquals
 from base import instructions as base
-from base.formats import UnaryImm, FuncAddr, Call, LoadComplex, StoreComplex
+from base import types
+from base.formats import UnaryImm, FuncAddr, Call, CallLandingPad
+from base.formats import LoadComplex, StoreComplex
 from .defs import X86_64, X86_32
 from . import recipes as r
 from . import settings as cfg
@@ -24,7 +26,7 @@ except ImportError:
     pass
 
 
-X86_32.legalize_monomorphic(expand_flags)
+X86_32.legalize_monomorphic(x86_expand)
 X86_32.legalize_type(
     default=narrow,
     b1=expand_flags,
@@ -32,7 +34,7 @@ X86_32.legalize_type(
     f32=x86_expand,
     f64=x86_expand)
 
-X86_64.legalize_monomorphic(expand_flags)
+X86_64.legalize_monomorphic(x86_expand)
 X86_64.legalize_type(
     default=narrow,
     b1=expand_flags,
@@ -412,6 +414,39 @@ X86_64.enc(base.func_addr.i64, *r.pcrel_fnaddr8.rex(0x8d, w=1),
 X86_64.enc(base.func_addr.i64, *r.got_fnaddr8.rex(0x8b, w=1),
            isap=is_pic)
 
+#
+# Jump tables.
+#
+
+# Jump tables are emitted after the function and addressed relative to %rip,
+# which is only possible in 64-bit mode.
+X86_64.enc(base.jump_table_base.i64, *r.jt_base.rex(0x8d, w=1))
+# movslq (base, index, 4), entry
+X86_64.enc(base.jump_table_entry.i64, *r.jt_entry.rex(0x63, w=1))
+# jmp *addr
+enc_x86_64(base.indirect_jump_table_br.i64, r.indirect_jmp, 0xff, rrr=4)
+
+#
+# Stack slot addresses.
+#
+
+X86_32.enc(base.stack_addr.i32, *r.spaddr_id(0x8d))
+X86_64.enc(base.stack_addr.i64, *r.spaddr_id.rex(0x8d, w=1))
+
+#
+# Constant pool.
+#
+
+# The constant pool is emitted after the function and addressed relative to
+# %rip, which is only possible in 64-bit mode.
+X86_64.enc(base.const_addr.i64, *r.const_addr.rex(0x8d, w=1))
+
+# 128-bit vector constants are loaded with movups.
+for lane_ty in (types.i8, types.i16, types.i32, types.i64, types.f32,
+                types.f64):
+    vec_ty = lane_ty.by(16 // lane_ty.membytes)
+    enc_x86_64(base.vconst.bind(vec_ty), r.fld_const, 0x0f, 0x10)
+
 #
 # Global addresses.
 #
@@ -452,9 +487,29 @@ X86_32.enc(base.call_indirect.i32, *r.call_r(0xff, rrr=2))
 X86_64.enc(base.call_indirect.i64, *r.call_r.rex(0xff, rrr=2))
 X86_64.enc(base.call_indirect.i64, *r.call_r(0xff, rrr=2))
 
+# Calls with a landing pad follow the same rules as calls. There is no
+# non-colocated non-PIC version for 64-bit.
+X86_32.enc(base.call_lp, *r.call_lp_id(0xe8))
+X86_64.enc(base.call_lp, *r.call_lp_id(0xe8),
+           instp=IsColocatedFunc(CallLandingPad.func_ref))
+X86_64.enc(base.call_lp, *r.call_lp_plt_id(0xe8), isap=is_pic)
+
+X86_32.enc(base.landingpad.i32, r.landingpad, 0)
+X86_64.enc(base.landingpad.i64, r.landingpad, 0)
+
 X86_32.enc(base.x_return, *r.ret(0xc3))
 X86_64.enc(base.x_return, *r.ret(0xc3))
 
+# Tail calls follow the same rules as calls, using jmp instead of call.
+X86_32.enc(base.return_call, *r.tcall_id(0xe9))
+X86_64.enc(base.return_call, *r.tcall_id(0xe9),
+           instp=IsColocatedFunc(Call.func_ref))
+X86_64.enc(base.return_call, *r.tcall_plt_id(0xe9), isap=is_pic)
+
+X86_32.enc(base.return_call_indirect.i32, *r.tcall_r(0xff, rrr=4))
+X86_64.enc(base.return_call_indirect.i64, *r.tcall_r.rex(0xff, rrr=4))
+X86_64.enc(base.return_call_indirect.i64, *r.tcall_r(0xff, rrr=4))
+
 #
 # Branches
 #
@@ -92,6 +92,13 @@ for cc,               rev_cc in [
 x86_expand.custom_legalize(insts.fmin, 'expand_minmax')
 x86_expand.custom_legalize(insts.fmax, 'expand_minmax')
 
+# Jump tables are only used in 64-bit mode.
+x86_expand.custom_legalize(insts.br_table, 'expand_br_table')
+
+# Float constants are loaded from the constant pool in 64-bit mode.
+x86_expand.custom_legalize(insts.f32const, 'expand_fconst')
+x86_expand.custom_legalize(insts.f64const, 'expand_fconst')
+
 # Conversions from unsigned need special handling.
 x86_expand.custom_legalize(insts.fcvt_from_uint, 'expand_fcvt_from_uint')
 # Conversions from float to int can trap.
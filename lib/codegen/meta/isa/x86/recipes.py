@@ -7,13 +7,15 @@ from cdsl.predicates import IsSignedInt, IsEqual, Or
 from cdsl.registers import RegClass
 from base.formats import Unary, UnaryImm, UnaryBool, Binary, BinaryImm
 from base.formats import MultiAry, NullAry
-from base.formats import Trap, Call, CallIndirect, Store, Load
+from base.formats import Trap, Call, CallIndirect, CallLandingPad
+from base.formats import Store, Load
 from base.formats import IntCompare, IntCompareImm, FloatCompare
 from base.formats import IntCond, FloatCond
 from base.formats import IntSelect, IntCondTrap, FloatCondTrap
 from base.formats import Jump, Branch, BranchInt, BranchFloat
-from base.formats import Ternary, FuncAddr, UnaryGlobalVar
-from base.formats import RegMove, RegSpill, RegFill, CopySpecial
+from base.formats import BranchTable, BranchTableBase, BranchTableEntry
+from base.formats import Ternary, FuncAddr, UnaryGlobalVar, UnaryConst
+from base.formats import RegMove, RegSpill, RegFill, CopySpecial, StackLoad
 from base.formats import LoadComplex, StoreComplex
 from .registers import GPR, ABCD, FPR, GPR_DEREF_SAFE, GPR_ZERO_DEREF_SAFE
 from .registers import GPR8, FPR8, GPR8_DEREF_SAFE, GPR8_ZERO_DEREF_SAFE, FLAG
@@ -387,6 +389,7 @@ urm_noflags = TailRecipe(
 urm_noflags_abcd = TailRecipe(
         'urm_noflags_abcd', Unary, size=1, ins=ABCD, outs=GPR,
         when_prefixed=urm_noflags,
+        clobbers_flags=False,
         emit='''
         PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
         modrm_rr(in_reg0, out_reg0, sink);
@@ -671,6 +674,39 @@ got_fnaddr8 = TailRecipe(
         sink.put4(0);
         ''')
 
+# XX /r with a rip-relative reference to a constant in the constant pool. The
+# constant pool is emitted right after the function, so the displacement is
+# known and no relocation is needed.
+const_addr = TailRecipe(
+        'const_addr', UnaryConst, size=5, ins=(), outs=GPR,
+        emit='''
+        PUT_OP(bits, rex2(0, out_reg0), sink);
+        modrm_riprel(out_reg0, sink);
+        const_disp4(constant_handle, func, sink);
+        ''')
+
+# XX /r float load of a constant in the constant pool.
+fld_const = TailRecipe(
+        'fld_const', UnaryConst, size=5, ins=(), outs=FPR,
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex2(0, out_reg0), sink);
+        modrm_riprel(out_reg0, sink);
+        const_disp4(constant_handle, func, sink);
+        ''')
+
+# XX /r with a rip-relative reference to a jump table. The jump tables are
+# emitted right after the function, so the displacement is known, but the
+# relocation tells the code sink about the reference.
+jt_base = TailRecipe(
+        'jt_base', BranchTableBase, size=5, ins=(), outs=GPR,
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex2(0, out_reg0), sink);
+        modrm_riprel(out_reg0, sink);
+        sink.reloc_jt(Reloc::X86PCRelRodata4, table);
+        jt_disp4(table, func, sink);
+        ''')
 
 # XX+rd id with Abs4 globalsym relocation.
 gvaddr4 = TailRecipe(
@@ -1070,6 +1106,20 @@ fregspill32 = TailRecipe(
         sink.put4(dst.offset as u32);
         ''')
 
+# lea of a stack slot address using RSP-relative addressing.
+spaddr_id = TailRecipe(
+        'spaddr_id', StackLoad, size=6, ins=(), outs=GPR,
+        clobbers_flags=False,
+        emit='''
+        let sp = StackRef::sp(stack_slot, &func.stack_slots);
+        let base = stk_base(sp.base);
+        PUT_OP(bits, rex2(base, out_reg0), sink);
+        modrm_sib_disp32(out_reg0, sink);
+        sib_noindex(base, sink);
+        let offset: i32 = offset.into();
+        sink.put4(sp.offset.wrapping_add(offset) as u32);
+        ''')
+
 #
 # Load recipes
 #
@@ -1353,6 +1403,85 @@ call_r = TailRecipe(
         modrm_r_bits(in_reg0, bits, sink);
         ''')
 
+# Calls with a landing pad are emitted like calls. The call site is recorded
+# in the exception tables, so the landing pad isn't referenced from the code.
+call_lp_id = TailRecipe(
+        'call_lp_id', CallLandingPad, size=4, ins=(), outs=(),
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        PUT_OP(bits, BASE_REX, sink);
+        sink.reloc_external(Reloc::X86PCRel4,
+                            &func.dfg.ext_funcs[func_ref].name,
+                            -4);
+        sink.put4(0);
+        ''')
+
+call_lp_plt_id = TailRecipe(
+        'call_lp_plt_id', CallLandingPad, size=4, ins=(), outs=(),
+        emit='''
+        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+        PUT_OP(bits, BASE_REX, sink);
+        sink.reloc_external(Reloc::X86PLTRel4,
+                            &func.dfg.ext_funcs[func_ref].name,
+                            -4);
+        sink.put4(0);
+        ''')
+
+# The unwinder passes the exception values to a landing pad in %rax and %rdx.
+landingpad = EncRecipe(
+        'landingpad', NullAry, size=0, ins=(), outs=(GPR.rax, GPR.rdx),
+        emit='')
+
+# Tail calls are jumps to the callee. They don't push a return address, so
+# they can't overflow the stack.
+tcall_id = TailRecipe(
+        'tcall_id', Call, size=4, ins=(), outs=(),
+        emit='''
+        PUT_OP(bits, BASE_REX, sink);
+        sink.reloc_external(Reloc::X86PCRel4,
+                            &func.dfg.ext_funcs[func_ref].name,
+                            -4);
+        sink.put4(0);
+        ''')
+
+tcall_plt_id = TailRecipe(
+        'tcall_plt_id', Call, size=4, ins=(), outs=(),
+        emit='''
+        PUT_OP(bits, BASE_REX, sink);
+        sink.reloc_external(Reloc::X86PLTRel4,
+                            &func.dfg.ext_funcs[func_ref].name,
+                            -4);
+        sink.put4(0);
+        ''')
+
+tcall_r = TailRecipe(
+        'tcall_r', CallIndirect, size=1, ins=GPR, outs=(),
+        emit='''
+        PUT_OP(bits, rex1(in_reg0), sink);
+        modrm_r_bits(in_reg0, bits, sink);
+        ''')
+
+# XX /r load of a 32-bit jump table entry with a scaled index.
+jt_entry = TailRecipe(
+        'jt_entry', BranchTableEntry, size=2,
+        ins=(GPR_DEREF_SAFE, GPR_ZERO_DEREF_SAFE),
+        outs=(GPR),
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex3(in_reg1, out_reg0, in_reg0), sink);
+        modrm_sib(out_reg0, sink);
+        sib(2, in_reg0, in_reg1, sink);
+        ''')
+
+# XX /n indirect jump via a jump table.
+indirect_jmp = TailRecipe(
+        'indirect_jmp', BranchTable, size=1, ins=GPR, outs=(),
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex1(in_reg0), sink);
+        modrm_r_bits(in_reg0, bits, sink);
+        ''')
+
 ret = TailRecipe(
         'ret', MultiAry, size=0, ins=(), outs=(),
         emit='''
@@ -0,0 +1,1513 @@
+//! Binary encoding of Cretonne IR functions.
+//!
+//! The textual `.cton` format is the primary persistent form of Cretonne IR, but it is verbose and
+//! comparatively slow to parse. This module provides a compact binary encoding of an
+//! `ir::Function` which preserves everything in the function, including entity numbers, encodings,
+//! value locations, and source locations. Decoding an encoded function produces a function that is
+//! printed identically to the original.
+//!
+//! The encoding starts with a magic number and a format version, followed by a table of the
+//! strings used to name opcodes, condition codes, and other enumerations. Using names rather than
+//! numbers for these keeps the format stable when enumerations are reordered or extended. The rest
+//! of the encoding consists of variable-length integers.
+
+use entity::EntityRef;
+use ir::condcodes::{FloatCC, IntCC};
+use ir::immediates::{Ieee32, Ieee64, Imm64, Offset32, Uimm32};
+use ir::instructions::InstructionFormat;
+use ir::{AbiParam, ArgumentExtension, ArgumentLoc, ConstantData, Ebb, ExtFuncData, ExternalName,
+         Function, GlobalVar, GlobalVarData, HeapBase, HeapData, HeapStyle, Inst, InstructionData,
+         InvokeTargets, JumpTableData, MemFlags, Opcode, SigRef, Signature, SourceLoc,
+         StackSlotData, Type, Value, ValueList, ValueLoc};
+use isa::Encoding;
+use packed_option::ReservedValue;
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::str::{self, FromStr};
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+/// Version of the binary IR format.
+///
+/// Bump this whenever the encoding changes in a way that older decoders can't read.
+const FORMAT_VERSION: u32 = 4;
+
+/// Magic bytes at the start of an encoded function.
+const MAGIC: &[u8; 4] = b"ctnb";
+
+/// The largest number of values accepted by the decoder.
+///
+/// Values are not all mentioned explicitly in the data, so their count can't be checked against
+/// the remaining input like other counts. Like the entity number limit in the text parser, this
+/// prevents a few bytes of corrupt data from exhausting memory.
+const MAX_VALUES: usize = 1 << 20;
+
+/// An error encountered while decoding a binary function.
+#[derive(Fail, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The data doesn't start with the magic bytes of the binary IR format.
+    #[fail(display = "Not a binary Cretonne function")]
+    BadMagic,
+
+    /// The data was encoded with an unsupported version of the format.
+    #[fail(display = "Unsupported binary IR format version {}", _0)]
+    UnsupportedVersion(u32),
+
+    /// The data ended in the middle of the function.
+    #[fail(display = "Unexpected end of binary IR data")]
+    Truncated,
+
+    /// The data is inconsistent.
+    #[fail(display = "Malformed binary IR: {}", _0)]
+    Malformed(&'static str),
+}
+
+/// Result of decoding a binary function.
+pub type DecodeResult<T> = Result<T, DecodeError>;
+
+/// Encode `func` in the binary IR format.
+pub fn encode_function(func: &Function) -> Vec<u8> {
+    let mut enc = Encoder::new();
+    enc.function(func);
+
+    let mut out = Vec::with_capacity(enc.body.len() + 64);
+    out.extend_from_slice(MAGIC);
+    put_uint(&mut out, u64::from(FORMAT_VERSION));
+    put_uint(&mut out, enc.strings.len() as u64);
+    for s in &enc.strings {
+        put_uint(&mut out, s.len() as u64);
+        out.extend_from_slice(s.as_bytes());
+    }
+    out.extend_from_slice(&enc.body);
+    out
+}
+
+/// Decode a function that was encoded by `encode_function()`.
+///
+/// All entity references in the data are checked, so malformed data produces an error rather than
+/// a function with dangling references. The decoded function is not verified.
+pub fn decode_function(data: &[u8]) -> DecodeResult<Function> {
+    if data.len() < MAGIC.len() || &data[0..MAGIC.len()] != MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+    let mut dec = Decoder {
+        data,
+        pos: MAGIC.len(),
+        strings: Vec::new(),
+    };
+    let version = dec.u32()?;
+    if version != FORMAT_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    let num_strings = dec.len()?;
+    for _ in 0..num_strings {
+        let len = dec.len()?;
+        let bytes = dec.bytes(len)?;
+        let s = str::from_utf8(bytes).map_err(|_| DecodeError::Malformed("invalid string"))?;
+        dec.strings.push(s);
+    }
+    let func = dec.function()?;
+    if dec.pos != data.len() {
+        return Err(DecodeError::Malformed("trailing data"));
+    }
+    Ok(func)
+}
+
+/// Append `x` as an unsigned LEB128 number.
+fn put_uint(out: &mut Vec<u8>, mut x: u64) {
+    while x >= 0x80 {
+        out.push(x as u8 | 0x80);
+        x >>= 7;
+    }
+    out.push(x as u8);
+}
+
+/// State for encoding a single function.
+struct Encoder {
+    body: Vec<u8>,
+    strings: Vec<String>,
+    string_ids: HashMap<String, u32>,
+}
+
+impl Encoder {
+    fn new() -> Self {
+        Self {
+            body: Vec::new(),
+            strings: Vec::new(),
+            string_ids: HashMap::new(),
+        }
+    }
+
+    fn uint<T: Into<u64>>(&mut self, x: T) {
+        put_uint(&mut self.body, x.into())
+    }
+
+    /// Signed numbers are zig-zag encoded so small negative numbers stay small.
+    fn int<T: Into<i64>>(&mut self, x: T) {
+        let x = x.into();
+        self.uint(((x << 1) ^ (x >> 63)) as u64)
+    }
+
+    fn len(&mut self, len: usize) {
+        self.uint(len as u64)
+    }
+
+    fn bool(&mut self, b: bool) {
+        self.body.push(b as u8)
+    }
+
+    fn entity<E: EntityRef>(&mut self, e: E) {
+        self.len(e.index())
+    }
+
+    /// Optional entities and numbers are encoded as 0 for `None` and `x + 1` for `Some(x)`.
+    fn opt_entity<E: EntityRef>(&mut self, e: Option<E>) {
+        self.len(e.map_or(0, |e| e.index() + 1))
+    }
+
+    fn opt_uint(&mut self, x: Option<u32>) {
+        self.uint(x.map_or(0, |x| u64::from(x) + 1))
+    }
+
+    fn opt_int(&mut self, x: Option<i32>) {
+        match x {
+            None => self.bool(false),
+            Some(x) => {
+                self.bool(true);
+                self.int(x)
+            }
+        }
+    }
+
+    fn ty(&mut self, ty: Type) {
+        self.len(ty.index())
+    }
+
+    /// Encode a string as an index into the string table.
+    fn string<S: ToString>(&mut self, s: S) {
+        let s = s.to_string();
+        let next_id = self.strings.len() as u32;
+        let id = *self.string_ids.entry(s.clone()).or_insert(next_id);
+        if id == next_id {
+            self.strings.push(s);
+        }
+        self.uint(id)
+    }
+
+    fn name(&mut self, name: &ExternalName) {
+        match *name {
+            ExternalName::User { namespace, index } => {
+                self.body.push(0);
+                self.uint(namespace);
+                self.uint(index);
+            }
+            ExternalName::TestCase { length, ascii } => {
+                self.body.push(1);
+                self.len(length as usize);
+                self.body.extend_from_slice(&ascii[0..length as usize]);
+            }
+            ExternalName::LibCall(lc) => {
+                self.body.push(2);
+                self.string(lc);
+            }
+        }
+    }
+
+    fn values(&mut self, values: &[Value]) {
+        self.len(values.len());
+        for &v in values {
+            self.entity(v);
+        }
+    }
+
+    fn signature(&mut self, sig: &Signature) {
+        self.len(sig.params.len());
+        for param in &sig.params {
+            self.abi_param(param);
+        }
+        self.len(sig.returns.len());
+        for ret in &sig.returns {
+            self.abi_param(ret);
+        }
+        self.string(sig.call_conv);
+        self.opt_uint(sig.fixed_params.map(|n| n as u32));
+        self.opt_uint(sig.argument_bytes);
+    }
+
+    fn abi_param(&mut self, param: &AbiParam) {
+        self.ty(param.value_type);
+        self.string(param.purpose);
+        self.body.push(match param.extension {
+            ArgumentExtension::None => 0,
+            ArgumentExtension::Uext => 1,
+            ArgumentExtension::Sext => 2,
+        });
+        match param.location {
+            ArgumentLoc::Unassigned => self.body.push(0),
+            ArgumentLoc::Reg(ru) => {
+                self.body.push(1);
+                self.uint(ru);
+            }
+            ArgumentLoc::Stack(offset) => {
+                self.body.push(2);
+                self.int(offset);
+            }
+        }
+    }
+
+    fn function(&mut self, func: &Function) {
+        self.name(&func.name);
+        self.signature(&func.signature);
+
+        self.len(func.stack_slots.keys().count());
+        for ss in func.stack_slots.values() {
+            self.string(ss.kind);
+            self.uint(ss.size);
+            self.opt_int(ss.offset);
+        }
+        self.opt_uint(func.stack_slots.frame_size);
+
+        self.len(func.global_vars.len());
+        for gv in func.global_vars.values() {
+            match *gv {
+                GlobalVarData::VMContext { offset } => {
+                    self.body.push(0);
+                    self.int(offset);
+                }
+                GlobalVarData::Deref { base, offset } => {
+                    self.body.push(1);
+                    self.entity(base);
+                    self.int(offset);
+                }
+                GlobalVarData::Sym { ref name, colocated } => {
+                    self.body.push(2);
+                    self.name(name);
+                    self.bool(colocated);
+                }
+            }
+        }
+
+        self.len(func.heaps.len());
+        for heap in func.heaps.values() {
+            self.opt_entity(match heap.base {
+                HeapBase::ReservedReg => None,
+                HeapBase::GlobalVar(gv) => Some(gv),
+            });
+            self.int(heap.min_size);
+            self.int(heap.guard_size);
+            match heap.style {
+                HeapStyle::Dynamic { bound_gv } => {
+                    self.body.push(0);
+                    self.entity(bound_gv);
+                }
+                HeapStyle::Static { bound } => {
+                    self.body.push(1);
+                    self.int(bound);
+                }
+            }
+        }
+
+        self.len(func.dfg.signatures.len());
+        for sig in func.dfg.signatures.values() {
+            self.signature(sig);
+        }
+
+        self.len(func.dfg.ext_funcs.len());
+        for ext in func.dfg.ext_funcs.values() {
+            self.name(&ext.name);
+            // The parser pads the function references with placeholders that have no signature.
+            self.opt_entity(if ext.signature == SigRef::reserved_value() {
+                None
+            } else {
+                Some(ext.signature)
+            });
+            self.bool(ext.colocated);
+        }
+
+        self.len(func.dfg.constants.len());
+        for (_, data) in func.dfg.constants.iter() {
+            self.len(data.len());
+            self.body.extend_from_slice(data.as_slice());
+        }
+
+        self.dfg(func);
+
+        self.len(func.jump_tables.len());
+        for jt in func.jump_tables.values() {
+            self.len(jt.len());
+            for idx in 0..jt.len() {
+                self.opt_entity(jt.get_entry(idx));
+            }
+        }
+
+        self.len(func.layout.ebbs().count());
+        for ebb in func.layout.ebbs() {
+            self.entity(ebb);
+            self.len(func.layout.ebb_insts(ebb).count());
+            for inst in func.layout.ebb_insts(ebb) {
+                self.entity(inst);
+            }
+        }
+
+        let encodings: Vec<_> = func.encodings
+            .iter()
+            .filter(|&(_, enc)| enc.is_legal())
+            .collect();
+        self.len(encodings.len());
+        for (inst, enc) in encodings {
+            self.entity(inst);
+            self.len(enc.recipe());
+            self.uint(enc.bits());
+        }
+
+        let locations: Vec<_> = func.locations
+            .iter()
+            .filter(|&(_, loc)| loc.is_assigned())
+            .collect();
+        self.len(locations.len());
+        for (value, &loc) in locations {
+            self.entity(value);
+            match loc {
+                ValueLoc::Unassigned => unreachable!(),
+                ValueLoc::Reg(ru) => {
+                    self.body.push(0);
+                    self.uint(ru);
+                }
+                ValueLoc::Stack(ss) => {
+                    self.body.push(1);
+                    self.entity(ss);
+                }
+            }
+        }
+
+        self.len(func.offsets.values().len());
+        for &offset in func.offsets.values() {
+            self.uint(offset);
+        }
+
+        let srclocs: Vec<_> = func.srclocs
+            .iter()
+            .filter(|&(_, srcloc)| !srcloc.is_default())
+            .collect();
+        self.len(srclocs.len());
+        for (inst, srcloc) in srclocs {
+            self.entity(inst);
+            self.uint(srcloc.bits());
+        }
+    }
+
+    fn dfg(&mut self, func: &Function) {
+        let dfg = &func.dfg;
+        self.len(dfg.num_ebbs());
+        self.len(dfg.num_values());
+        self.len(dfg.num_insts());
+
+        for idx in 0..dfg.num_ebbs() {
+            let params = dfg.ebb_params(Ebb::new(idx));
+            self.len(params.len());
+            for &param in params {
+                self.entity(param);
+                self.ty(dfg.value_type(param));
+            }
+        }
+
+        for idx in 0..dfg.num_insts() {
+            let inst = Inst::new(idx);
+            self.inst_data(func, inst);
+            let results = dfg.inst_results(inst);
+            self.values(results);
+            if !results.is_empty() {
+                self.ty(dfg.ctrl_typevar(inst));
+            }
+        }
+
+        let aliases: Vec<_> = (0..dfg.num_values())
+            .map(Value::new)
+            .filter_map(|v| dfg.value_alias(v).map(|original| (v, original)))
+            .collect();
+        self.len(aliases.len());
+        for (v, original) in aliases {
+            self.entity(v);
+            self.entity(original);
+        }
+    }
+
+    fn inst_data(&mut self, func: &Function, inst: Inst) {
+        let pool = &func.dfg.value_lists;
+        let data = &func.dfg[inst];
+        self.string(data.opcode());
+
+        use ir::instructions::InstructionData::*;
+        match *data {
+            Unary { arg, .. } => self.entity(arg),
+            UnaryImm { imm, .. } => self.int(imm),
+            UnaryIeee32 { imm, .. } => self.uint(imm.bits()),
+            UnaryIeee64 { imm, .. } => self.uint(imm.bits()),
+            UnaryBool { imm, .. } => self.bool(imm),
+            UnaryGlobalVar { global_var, .. } => self.entity(global_var),
+            UnaryConst { constant_handle, .. } => self.entity(constant_handle),
+            Binary { args, .. } => self.values(&args),
+            BinaryImm { arg, imm, .. } => {
+                self.entity(arg);
+                self.int(imm);
+            }
+            Ternary { args, .. } => self.values(&args),
+            MultiAry { ref args, .. } => self.values(args.as_slice(pool)),
+            NullAry { .. } => {}
+            InsertLane { args, lane, .. } => {
+                self.values(&args);
+                self.uint(lane);
+            }
+            ExtractLane { arg, lane, .. } => {
+                self.entity(arg);
+                self.uint(lane);
+            }
+            IntCompare { args, cond, .. } => {
+                self.string(cond);
+                self.values(&args);
+            }
+            IntCompareImm { arg, cond, imm, .. } => {
+                self.string(cond);
+                self.entity(arg);
+                self.int(imm);
+            }
+            IntCond { arg, cond, .. } => {
+                self.string(cond);
+                self.entity(arg);
+            }
+            FloatCompare { args, cond, .. } => {
+                self.string(cond);
+                self.values(&args);
+            }
+            FloatCond { arg, cond, .. } => {
+                self.string(cond);
+                self.entity(arg);
+            }
+            IntSelect { args, cond, .. } => {
+                self.string(cond);
+                self.values(&args);
+            }
+            Jump {
+                ref args,
+                destination,
+                ..
+            } |
+            Branch {
+                ref args,
+                destination,
+                ..
+            } => {
+                self.entity(destination);
+                self.values(args.as_slice(pool));
+            }
+            BranchInt {
+                ref args,
+                cond,
+                destination,
+                ..
+            } |
+            BranchIcmp {
+                ref args,
+                cond,
+                destination,
+                ..
+            } => {
+                self.string(cond);
+                self.entity(destination);
+                self.values(args.as_slice(pool));
+            }
+            BranchFloat {
+                ref args,
+                cond,
+                destination,
+                ..
+            } => {
+                self.string(cond);
+                self.entity(destination);
+                self.values(args.as_slice(pool));
+            }
+            BranchTable { arg, table, .. } => {
+                self.entity(arg);
+                self.entity(table);
+            }
+            BranchTableBase { table, .. } => self.entity(table),
+            BranchTableEntry { args, table, .. } => {
+                self.values(&args);
+                self.entity(table);
+            }
+            Call {
+                ref args, func_ref, ..
+            } => {
+                self.entity(func_ref);
+                self.values(args.as_slice(pool));
+            }
+            Invoke {
+                ref args,
+                ref targets,
+                ..
+            } => {
+                self.entity(targets.func_ref);
+                self.entity(targets.destination);
+                self.entity(targets.landing_pad);
+                self.values(args.as_slice(pool));
+            }
+            CallLandingPad {
+                ref args,
+                func_ref,
+                landing_pad,
+                ..
+            } => {
+                self.entity(func_ref);
+                self.entity(landing_pad);
+                self.values(args.as_slice(pool));
+            }
+            CallIndirect {
+                ref args, sig_ref, ..
+            } => {
+                self.entity(sig_ref);
+                self.values(args.as_slice(pool));
+            }
+            FuncAddr { func_ref, .. } => self.entity(func_ref),
+            Load {
+                arg, flags, offset, ..
+            } => {
+                self.string(flags);
+                self.entity(arg);
+                self.int(offset);
+            }
+            LoadComplex {
+                ref args,
+                flags,
+                offset,
+                ..
+            } |
+            StoreComplex {
+                ref args,
+                flags,
+                offset,
+                ..
+            } => {
+                self.string(flags);
+                self.values(args.as_slice(pool));
+                self.int(offset);
+            }
+            Store {
+                args,
+                flags,
+                offset,
+                ..
+            } => {
+                self.string(flags);
+                self.values(&args);
+                self.int(offset);
+            }
+            StackLoad {
+                stack_slot, offset, ..
+            } => {
+                self.entity(stack_slot);
+                self.int(offset);
+            }
+            StackStore {
+                arg,
+                stack_slot,
+                offset,
+                ..
+            } => {
+                self.entity(arg);
+                self.entity(stack_slot);
+                self.int(offset);
+            }
+            HeapAddr { arg, heap, imm, .. } => {
+                self.entity(heap);
+                self.entity(arg);
+                self.uint(Into::<u32>::into(imm));
+            }
+            RegMove { arg, src, dst, .. } => {
+                self.entity(arg);
+                self.uint(src);
+                self.uint(dst);
+            }
+            CopySpecial { src, dst, .. } => {
+                self.uint(src);
+                self.uint(dst);
+            }
+            RegSpill { arg, src, dst, .. } => {
+                self.entity(arg);
+                self.uint(src);
+                self.entity(dst);
+            }
+            RegFill { arg, src, dst, .. } => {
+                self.entity(arg);
+                self.entity(src);
+                self.uint(dst);
+            }
+            Trap { code, .. } => self.string(code),
+            CondTrap { arg, code, .. } => {
+                self.entity(arg);
+                self.string(code);
+            }
+            IntCondTrap { arg, cond, code, .. } => {
+                self.string(cond);
+                self.entity(arg);
+                self.string(code);
+            }
+            FloatCondTrap { arg, cond, code, .. } => {
+                self.string(cond);
+                self.entity(arg);
+                self.string(code);
+            }
+        }
+    }
+}
+
+/// State for decoding a single function.
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    strings: Vec<&'a str>,
+}
+
+impl<'a> Decoder<'a> {
+    fn byte(&mut self) -> DecodeResult<u8> {
+        let b = *self.data.get(self.pos).ok_or(DecodeError::Truncated)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn bytes(&mut self, len: usize) -> DecodeResult<&'a [u8]> {
+        if self.data.len() - self.pos < len {
+            return Err(DecodeError::Truncated);
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self) -> DecodeResult<u64> {
+        let mut x = 0;
+        let mut shift = 0;
+        loop {
+            let b = self.byte()?;
+            if shift >= 64 || (shift == 63 && b > 1) {
+                return Err(DecodeError::Malformed("integer overflow"));
+            }
+            x |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(x);
+            }
+            shift += 7;
+        }
+    }
+
+    fn int(&mut self) -> DecodeResult<i64> {
+        let x = self.uint()?;
+        Ok((x >> 1) as i64 ^ -((x & 1) as i64))
+    }
+
+    fn u32(&mut self) -> DecodeResult<u32> {
+        let x = self.uint()?;
+        if x > u64::from(u32::max_value()) {
+            return Err(DecodeError::Malformed("integer overflow"));
+        }
+        Ok(x as u32)
+    }
+
+    fn u16(&mut self) -> DecodeResult<u16> {
+        let x = self.uint()?;
+        if x > u64::from(u16::max_value()) {
+            return Err(DecodeError::Malformed("integer overflow"));
+        }
+        Ok(x as u16)
+    }
+
+    fn u8(&mut self) -> DecodeResult<u8> {
+        let x = self.uint()?;
+        if x > u64::from(u8::max_value()) {
+            return Err(DecodeError::Malformed("integer overflow"));
+        }
+        Ok(x as u8)
+    }
+
+    fn i32(&mut self) -> DecodeResult<i32> {
+        let x = self.int()?;
+        if x < i64::from(i32::min_value()) || x > i64::from(i32::max_value()) {
+            return Err(DecodeError::Malformed("integer overflow"));
+        }
+        Ok(x as i32)
+    }
+
+    /// Read a length or count.
+    ///
+    /// Every counted item occupies at least one byte, so this also guards against huge
+    /// allocations caused by corrupt counts.
+    fn len(&mut self) -> DecodeResult<usize> {
+        let len = self.uint()?;
+        if len > (self.data.len() - self.pos) as u64 {
+            return Err(DecodeError::Truncated);
+        }
+        Ok(len as usize)
+    }
+
+    fn bool(&mut self) -> DecodeResult<bool> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::Malformed("invalid boolean")),
+        }
+    }
+
+    /// Read a reference to an entity, which must be less than `count`.
+    fn entity<E: EntityRef>(&mut self, count: usize) -> DecodeResult<E> {
+        let idx = self.uint()?;
+        if idx >= count as u64 {
+            return Err(DecodeError::Malformed("entity reference out of range"));
+        }
+        Ok(E::new(idx as usize))
+    }
+
+    fn opt_entity<E: EntityRef>(&mut self, count: usize) -> DecodeResult<Option<E>> {
+        let idx = self.uint()?;
+        if idx == 0 {
+            Ok(None)
+        } else if idx > count as u64 {
+            Err(DecodeError::Malformed("entity reference out of range"))
+        } else {
+            Ok(Some(E::new(idx as usize - 1)))
+        }
+    }
+
+    fn opt_u32(&mut self) -> DecodeResult<Option<u32>> {
+        let x = self.uint()?;
+        if x == 0 {
+            Ok(None)
+        } else if x - 1 > u64::from(u32::max_value()) {
+            Err(DecodeError::Malformed("integer overflow"))
+        } else {
+            Ok(Some((x - 1) as u32))
+        }
+    }
+
+    fn opt_i32(&mut self) -> DecodeResult<Option<i32>> {
+        if self.bool()? {
+            self.i32().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn ty(&mut self) -> DecodeResult<Type> {
+        let idx = self.uint()?;
+        Type::from_index(idx as usize).ok_or(DecodeError::Malformed("invalid type"))
+    }
+
+    fn string(&mut self) -> DecodeResult<&'a str> {
+        let id = self.uint()?;
+        self.strings.get(id as usize).cloned().ok_or(
+            DecodeError::Malformed("string reference out of range"),
+        )
+    }
+
+    /// Read a string and parse it as a `T`.
+    fn parse<T: FromStr>(&mut self, what: &'static str) -> DecodeResult<T> {
+        self.string()?.parse().map_err(|_| DecodeError::Malformed(what))
+    }
+
+    fn name(&mut self) -> DecodeResult<ExternalName> {
+        match self.byte()? {
+            0 => Ok(ExternalName::user(self.u32()?, self.u32()?)),
+            1 => {
+                let len = self.len()?;
+                Ok(ExternalName::testcase(self.bytes(len)?))
+            }
+            2 => Ok(ExternalName::LibCall(self.parse("invalid libcall")?)),
+            _ => Err(DecodeError::Malformed("invalid external name")),
+        }
+    }
+
+    fn signature(&mut self) -> DecodeResult<Signature> {
+        let num_params = self.len()?;
+        let mut params = Vec::with_capacity(num_params);
+        for _ in 0..num_params {
+            params.push(self.abi_param()?);
+        }
+        let num_returns = self.len()?;
+        let mut returns = Vec::with_capacity(num_returns);
+        for _ in 0..num_returns {
+            returns.push(self.abi_param()?);
+        }
+        let mut sig = Signature::new(self.parse("invalid calling convention")?);
+        sig.params = params;
+        sig.returns = returns;
+        sig.fixed_params = self.opt_u32()?.map(|n| n as usize);
+        if sig.fixed_params.map_or(false, |n| n > sig.params.len()) {
+            return Err(DecodeError::Malformed("too many fixed parameters"));
+        }
+        sig.argument_bytes = self.opt_u32()?;
+        Ok(sig)
+    }
+
+    fn abi_param(&mut self) -> DecodeResult<AbiParam> {
+        let mut param = AbiParam::new(self.ty()?);
+        param.purpose = self.parse("invalid argument purpose")?;
+        param.extension = match self.byte()? {
+            0 => ArgumentExtension::None,
+            1 => ArgumentExtension::Uext,
+            2 => ArgumentExtension::Sext,
+            _ => return Err(DecodeError::Malformed("invalid argument extension")),
+        };
+        param.location = match self.byte()? {
+            0 => ArgumentLoc::Unassigned,
+            1 => ArgumentLoc::Reg(self.u16()?),
+            2 => ArgumentLoc::Stack(self.i32()?),
+            _ => return Err(DecodeError::Malformed("invalid argument location")),
+        };
+        Ok(param)
+    }
+
+    fn function(&mut self) -> DecodeResult<Function> {
+        let name = self.name()?;
+        let sig = self.signature()?;
+        let mut func = Function::with_name_signature(name, sig);
+
+        let num_stack_slots = self.len()?;
+        for _ in 0..num_stack_slots {
+            let mut data = StackSlotData::new(self.parse("invalid stack slot kind")?, self.u32()?);
+            data.offset = self.opt_i32()?;
+            func.stack_slots.push(data);
+        }
+        func.stack_slots.frame_size = self.opt_u32()?;
+
+        let num_gvs = self.len()?;
+        for _ in 0..num_gvs {
+            let data = match self.byte()? {
+                0 => GlobalVarData::VMContext { offset: Offset32::new(self.i32()?) },
+                1 => GlobalVarData::Deref {
+                    base: self.entity(num_gvs)?,
+                    offset: Offset32::new(self.i32()?),
+                },
+                2 => GlobalVarData::Sym {
+                    name: self.name()?,
+                    colocated: self.bool()?,
+                },
+                _ => return Err(DecodeError::Malformed("invalid global variable")),
+            };
+            func.global_vars.push(data);
+        }
+
+        let num_heaps = self.len()?;
+        for _ in 0..num_heaps {
+            let base = match self.opt_entity::<GlobalVar>(num_gvs)? {
+                None => HeapBase::ReservedReg,
+                Some(gv) => HeapBase::GlobalVar(gv),
+            };
+            let min_size = Imm64::new(self.int()?);
+            let guard_size = Imm64::new(self.int()?);
+            let style = match self.byte()? {
+                0 => HeapStyle::Dynamic { bound_gv: self.entity(num_gvs)? },
+                1 => HeapStyle::Static { bound: Imm64::new(self.int()?) },
+                _ => return Err(DecodeError::Malformed("invalid heap style")),
+            };
+            func.heaps.push(HeapData {
+                base,
+                min_size,
+                guard_size,
+                style,
+            });
+        }
+
+        let num_sigs = self.len()?;
+        for _ in 0..num_sigs {
+            let sig = self.signature()?;
+            func.dfg.signatures.push(sig);
+        }
+
+        let num_ext_funcs = self.len()?;
+        for _ in 0..num_ext_funcs {
+            let data = ExtFuncData {
+                name: self.name()?,
+                signature: self.opt_entity(num_sigs)?.unwrap_or_else(
+                    SigRef::reserved_value,
+                ),
+                colocated: self.bool()?,
+            };
+            func.dfg.ext_funcs.push(data);
+        }
+
+        let num_constants = self.len()?;
+        for _ in 0..num_constants {
+            let len = self.len()?;
+            let data = ConstantData::from(self.bytes(len)?);
+            func.dfg.constants.push(data);
+        }
+
+        self.dfg(&mut func)?;
+        let num_ebbs = func.dfg.num_ebbs();
+
+        let num_jts = self.len()?;
+        for _ in 0..num_jts {
+            let len = self.len()?;
+            let mut data = JumpTableData::with_capacity(len);
+            for idx in 0..len {
+                if let Some(ebb) = self.opt_entity(num_ebbs)? {
+                    data.set_entry(idx, ebb);
+                } else if idx + 1 == len {
+                    // Preserve trailing holes in the table.
+                    data.set_entry(idx, Ebb::new(0));
+                    data.clear_entry(idx);
+                }
+            }
+            func.jump_tables.push(data);
+        }
+        self.check_jump_tables(&func)?;
+
+        let num_insts = func.dfg.num_insts();
+        let num_layout_ebbs = self.len()?;
+        for _ in 0..num_layout_ebbs {
+            let ebb = self.entity(num_ebbs)?;
+            if func.layout.is_ebb_inserted(ebb) {
+                return Err(DecodeError::Malformed("EBB inserted twice"));
+            }
+            func.layout.append_ebb(ebb);
+            let num_ebb_insts = self.len()?;
+            for _ in 0..num_ebb_insts {
+                let inst = self.entity(num_insts)?;
+                if func.layout.inst_ebb(inst).is_some() {
+                    return Err(DecodeError::Malformed("instruction inserted twice"));
+                }
+                func.layout.append_inst(inst, ebb);
+            }
+        }
+
+        let num_encodings = self.len()?;
+        for _ in 0..num_encodings {
+            let inst: Inst = self.entity(num_insts)?;
+            func.encodings[inst] = Encoding::new(self.u16()?, self.u16()?);
+        }
+
+        let num_values = func.dfg.num_values();
+        let num_locations = self.len()?;
+        for _ in 0..num_locations {
+            let value: Value = self.entity(num_values)?;
+            func.locations[value] = match self.byte()? {
+                0 => ValueLoc::Reg(self.u16()?),
+                1 => ValueLoc::Stack(self.entity(num_stack_slots)?),
+                _ => return Err(DecodeError::Malformed("invalid value location")),
+            };
+        }
+
+        let num_offsets = self.len()?;
+        func.offsets.resize(num_offsets);
+        for idx in 0..num_offsets {
+            func.offsets[Ebb::new(idx)] = self.u32()?;
+        }
+
+        let num_srclocs = self.len()?;
+        for _ in 0..num_srclocs {
+            let inst: Inst = self.entity(num_insts)?;
+            func.srclocs[inst] = SourceLoc::new(self.u32()?);
+        }
+
+        Ok(func)
+    }
+
+    /// Decode the data flow graph, reproducing the original entity numbering.
+    fn dfg(&mut self, func: &mut Function) -> DecodeResult<()> {
+        let num_ebbs = self.len()?;
+        // Values are not all mentioned explicitly, so the index space can be larger than the data.
+        let num_values = self.u32()? as usize;
+        if num_values > MAX_VALUES {
+            return Err(DecodeError::Malformed("too many values"));
+        }
+        let num_insts = self.len()?;
+
+        for _ in 0..num_ebbs {
+            func.dfg.make_ebb();
+        }
+        // All values start out as invalid placeholders, and are then defined as EBB parameters,
+        // instruction results, or aliases.
+        for _ in 0..num_values {
+            func.dfg.make_invalid_value_for_parser();
+        }
+        let mut defined = vec![false; num_values];
+
+        for idx in 0..num_ebbs {
+            let ebb = Ebb::new(idx);
+            let num_params = self.len()?;
+            for _ in 0..num_params {
+                let value = self.new_value(&mut defined)?;
+                let ty = self.ty()?;
+                func.dfg.append_ebb_param_for_parser(ebb, ty, value);
+            }
+        }
+
+        for _ in 0..num_insts {
+            let data = self.inst_data(func, num_ebbs, num_values)?;
+            let inst = func.dfg.make_inst(data);
+            let num_results = self.len()?;
+            let mut results = Vec::with_capacity(num_results);
+            for _ in 0..num_results {
+                results.push(self.new_value(&mut defined)?);
+            }
+            if !results.is_empty() {
+                let ctrl_typevar = self.ty()?;
+                func.dfg.make_inst_results_for_parser(
+                    inst,
+                    ctrl_typevar,
+                    &results,
+                );
+                if func.dfg.inst_results(inst) != &results[..] {
+                    return Err(DecodeError::Malformed("wrong number of instruction results"));
+                }
+            }
+        }
+
+        let num_aliases = self.len()?;
+        let mut aliases = Vec::with_capacity(num_aliases);
+        for _ in 0..num_aliases {
+            let value = self.new_value(&mut defined)?;
+            let original: Value = self.entity(num_values)?;
+            aliases.push((value, original));
+        }
+        for &(value, original) in &aliases {
+            if !defined[original.index()] {
+                return Err(DecodeError::Malformed("alias of undefined value"));
+            }
+            func.dfg.make_value_alias_for_parser(original, value);
+        }
+        for &(value, _) in &aliases {
+            if !func.dfg.set_alias_type_for_parser(value) {
+                return Err(DecodeError::Malformed("value alias cycle"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read a reference to a value that is being defined.
+    fn new_value(&mut self, defined: &mut [bool]) -> DecodeResult<Value> {
+        let value: Value = self.entity(defined.len())?;
+        if defined[value.index()] {
+            return Err(DecodeError::Malformed("value defined twice"));
+        }
+        defined[value.index()] = true;
+        Ok(value)
+    }
+
+    fn value_list(&mut self, func: &mut Function, num_values: usize) -> DecodeResult<ValueList> {
+        let len = self.len()?;
+        let mut list = ValueList::new();
+        for _ in 0..len {
+            let v = self.entity(num_values)?;
+            list.push(v, &mut func.dfg.value_lists);
+        }
+        Ok(list)
+    }
+
+    fn fixed_values(&mut self, num_values: usize, args: &mut [Value]) -> DecodeResult<()> {
+        if self.len()? != args.len() {
+            return Err(DecodeError::Malformed("wrong number of instruction arguments"));
+        }
+        for arg in args {
+            *arg = self.entity(num_values)?;
+        }
+        Ok(())
+    }
+
+    fn mem_flags(&mut self) -> DecodeResult<MemFlags> {
+        let mut flags = MemFlags::new();
+        for name in self.string()?.split_whitespace() {
+            if !flags.set_by_name(name) {
+                return Err(DecodeError::Malformed("invalid memory flag"));
+            }
+        }
+        Ok(flags)
+    }
+
+    fn int_cc(&mut self) -> DecodeResult<IntCC> {
+        self.parse("invalid integer condition code")
+    }
+
+    fn float_cc(&mut self) -> DecodeResult<FloatCC> {
+        self.parse("invalid float condition code")
+    }
+
+    fn inst_data(
+        &mut self,
+        func: &mut Function,
+        num_ebbs: usize,
+        num_values: usize,
+    ) -> DecodeResult<InstructionData> {
+        let opcode: Opcode = self.parse("invalid opcode")?;
+        let num_gvs = func.global_vars.len();
+        let num_constants = func.dfg.constants.len();
+        let num_heaps = func.heaps.len();
+        let num_sigs = func.dfg.signatures.len();
+        let num_ext_funcs = func.dfg.ext_funcs.len();
+        let num_stack_slots = func.stack_slots.keys().count();
+        let mut args = [Value::reserved_value(); 3];
+
+        Ok(match opcode.format() {
+            InstructionFormat::Unary => InstructionData::Unary {
+                opcode,
+                arg: self.entity(num_values)?,
+            },
+            InstructionFormat::UnaryImm => InstructionData::UnaryImm {
+                opcode,
+                imm: Imm64::new(self.int()?),
+            },
+            InstructionFormat::UnaryIeee32 => InstructionData::UnaryIeee32 {
+                opcode,
+                imm: Ieee32::with_bits(self.u32()?),
+            },
+            InstructionFormat::UnaryIeee64 => InstructionData::UnaryIeee64 {
+                opcode,
+                imm: Ieee64::with_bits(self.uint()?),
+            },
+            InstructionFormat::UnaryBool => InstructionData::UnaryBool {
+                opcode,
+                imm: self.bool()?,
+            },
+            InstructionFormat::UnaryGlobalVar => InstructionData::UnaryGlobalVar {
+                opcode,
+                global_var: self.entity(num_gvs)?,
+            },
+            InstructionFormat::UnaryConst => InstructionData::UnaryConst {
+                opcode,
+                constant_handle: self.entity(num_constants)?,
+            },
+            InstructionFormat::Binary => {
+                self.fixed_values(num_values, &mut args[0..2])?;
+                InstructionData::Binary {
+                    opcode,
+                    args: [args[0], args[1]],
+                }
+            }
+            InstructionFormat::BinaryImm => InstructionData::BinaryImm {
+                opcode,
+                arg: self.entity(num_values)?,
+                imm: Imm64::new(self.int()?),
+            },
+            InstructionFormat::Ternary => {
+                self.fixed_values(num_values, &mut args)?;
+                InstructionData::Ternary { opcode, args }
+            }
+            InstructionFormat::MultiAry => InstructionData::MultiAry {
+                opcode,
+                args: self.value_list(func, num_values)?,
+            },
+            InstructionFormat::NullAry => InstructionData::NullAry { opcode },
+            InstructionFormat::InsertLane => {
+                self.fixed_values(num_values, &mut args[0..2])?;
+                InstructionData::InsertLane {
+                    opcode,
+                    args: [args[0], args[1]],
+                    lane: self.u8()?,
+                }
+            }
+            InstructionFormat::ExtractLane => InstructionData::ExtractLane {
+                opcode,
+                arg: self.entity(num_values)?,
+                lane: self.u8()?,
+            },
+            InstructionFormat::IntCompare => {
+                let cond = self.int_cc()?;
+                self.fixed_values(num_values, &mut args[0..2])?;
+                InstructionData::IntCompare {
+                    opcode,
+                    args: [args[0], args[1]],
+                    cond,
+                }
+            }
+            InstructionFormat::IntCompareImm => InstructionData::IntCompareImm {
+                opcode,
+                cond: self.int_cc()?,
+                arg: self.entity(num_values)?,
+                imm: Imm64::new(self.int()?),
+            },
+            InstructionFormat::IntCond => InstructionData::IntCond {
+                opcode,
+                cond: self.int_cc()?,
+                arg: self.entity(num_values)?,
+            },
+            InstructionFormat::FloatCompare => {
+                let cond = self.float_cc()?;
+                self.fixed_values(num_values, &mut args[0..2])?;
+                InstructionData::FloatCompare {
+                    opcode,
+                    args: [args[0], args[1]],
+                    cond,
+                }
+            }
+            InstructionFormat::FloatCond => InstructionData::FloatCond {
+                opcode,
+                cond: self.float_cc()?,
+                arg: self.entity(num_values)?,
+            },
+            InstructionFormat::IntSelect => {
+                let cond = self.int_cc()?;
+                self.fixed_values(num_values, &mut args)?;
+                InstructionData::IntSelect { opcode, args, cond }
+            }
+            InstructionFormat::Jump => InstructionData::Jump {
+                opcode,
+                destination: self.entity(num_ebbs)?,
+                args: self.value_list(func, num_values)?,
+            },
+            InstructionFormat::Branch => InstructionData::Branch {
+                opcode,
+                destination: self.entity(num_ebbs)?,
+                args: self.value_list(func, num_values)?,
+            },
+            InstructionFormat::BranchInt => InstructionData::BranchInt {
+                opcode,
+                cond: self.int_cc()?,
+                destination: self.entity(num_ebbs)?,
+                args: self.value_list(func, num_values)?,
+            },
+            InstructionFormat::BranchFloat => InstructionData::BranchFloat {
+                opcode,
+                cond: self.float_cc()?,
+                destination: self.entity(num_ebbs)?,
+                args: self.value_list(func, num_values)?,
+            },
+            InstructionFormat::BranchIcmp => InstructionData::BranchIcmp {
+                opcode,
+                cond: self.int_cc()?,
+                destination: self.entity(num_ebbs)?,
+                args: self.value_list(func, num_values)?,
+            },
+            InstructionFormat::BranchTable => InstructionData::BranchTable {
+                opcode,
+                arg: self.entity(num_values)?,
+                // Jump tables are decoded after the instructions, so the table reference is
+                // checked by `check_jump_tables()`.
+                table: self.entity(usize::max_value())?,
+            },
+            InstructionFormat::BranchTableBase => InstructionData::BranchTableBase {
+                opcode,
+                table: self.entity(usize::max_value())?,
+            },
+            InstructionFormat::BranchTableEntry => {
+                self.fixed_values(num_values, &mut args[0..2])?;
+                InstructionData::BranchTableEntry {
+                    opcode,
+                    args: [args[0], args[1]],
+                    table: self.entity(usize::max_value())?,
+                }
+            }
+            InstructionFormat::Call => InstructionData::Call {
+                opcode,
+                func_ref: self.entity(num_ext_funcs)?,
+                args: self.value_list(func, num_values)?,
+            },
+            InstructionFormat::Invoke => InstructionData::Invoke {
+                opcode,
+                targets: Box::new(InvokeTargets {
+                    func_ref: self.entity(num_ext_funcs)?,
+                    destination: self.entity(num_ebbs)?,
+                    landing_pad: self.entity(num_ebbs)?,
+                }),
+                args: self.value_list(func, num_values)?,
+            },
+            InstructionFormat::CallLandingPad => InstructionData::CallLandingPad {
+                opcode,
+                func_ref: self.entity(num_ext_funcs)?,
+                landing_pad: self.entity(num_ebbs)?,
+                args: self.value_list(func, num_values)?,
+            },
+            InstructionFormat::CallIndirect => InstructionData::CallIndirect {
+                opcode,
+                sig_ref: self.entity(num_sigs)?,
+                args: self.value_list(func, num_values)?,
+            },
+            InstructionFormat::FuncAddr => InstructionData::FuncAddr {
+                opcode,
+                func_ref: self.entity(num_ext_funcs)?,
+            },
+            InstructionFormat::Load => InstructionData::Load {
+                opcode,
+                flags: self.mem_flags()?,
+                arg: self.entity(num_values)?,
+                offset: Offset32::new(self.i32()?),
+            },
+            InstructionFormat::LoadComplex => InstructionData::LoadComplex {
+                opcode,
+                flags: self.mem_flags()?,
+                args: self.value_list(func, num_values)?,
+                offset: Offset32::new(self.i32()?),
+            },
+            InstructionFormat::Store => {
+                let flags = self.mem_flags()?;
+                self.fixed_values(num_values, &mut args[0..2])?;
+                InstructionData::Store {
+                    opcode,
+                    args: [args[0], args[1]],
+                    flags,
+                    offset: Offset32::new(self.i32()?),
+                }
+            }
+            InstructionFormat::StoreComplex => InstructionData::StoreComplex {
+                opcode,
+                flags: self.mem_flags()?,
+                args: self.value_list(func, num_values)?,
+                offset: Offset32::new(self.i32()?),
+            },
+            InstructionFormat::StackLoad => InstructionData::StackLoad {
+                opcode,
+                stack_slot: self.entity(num_stack_slots)?,
+                offset: Offset32::new(self.i32()?),
+            },
+            InstructionFormat::StackStore => InstructionData::StackStore {
+                opcode,
+                arg: self.entity(num_values)?,
+                stack_slot: self.entity(num_stack_slots)?,
+                offset: Offset32::new(self.i32()?),
+            },
+            InstructionFormat::HeapAddr => InstructionData::HeapAddr {
+                opcode,
+                heap: self.entity(num_heaps)?,
+                arg: self.entity(num_values)?,
+                imm: Uimm32::from(self.u32()?),
+            },
+            InstructionFormat::RegMove => InstructionData::RegMove {
+                opcode,
+                arg: self.entity(num_values)?,
+                src: self.u16()?,
+                dst: self.u16()?,
+            },
+            InstructionFormat::CopySpecial => InstructionData::CopySpecial {
+                opcode,
+                src: self.u16()?,
+                dst: self.u16()?,
+            },
+            InstructionFormat::RegSpill => InstructionData::RegSpill {
+                opcode,
+                arg: self.entity(num_values)?,
+                src: self.u16()?,
+                dst: self.entity(num_stack_slots)?,
+            },
+            InstructionFormat::RegFill => InstructionData::RegFill {
+                opcode,
+                arg: self.entity(num_values)?,
+                src: self.entity(num_stack_slots)?,
+                dst: self.u16()?,
+            },
+            InstructionFormat::Trap => InstructionData::Trap {
+                opcode,
+                code: self.parse("invalid trap code")?,
+            },
+            InstructionFormat::CondTrap => InstructionData::CondTrap {
+                opcode,
+                arg: self.entity(num_values)?,
+                code: self.parse("invalid trap code")?,
+            },
+            InstructionFormat::IntCondTrap => InstructionData::IntCondTrap {
+                opcode,
+                cond: self.int_cc()?,
+                arg: self.entity(num_values)?,
+                code: self.parse("invalid trap code")?,
+            },
+            InstructionFormat::FloatCondTrap => InstructionData::FloatCondTrap {
+                opcode,
+                cond: self.float_cc()?,
+                arg: self.entity(num_values)?,
+                code: self.parse("invalid trap code")?,
+            },
+        })
+    }
+
+    /// Check that all instructions referring to jump tables refer to existing ones.
+    fn check_jump_tables(&self, func: &Function) -> DecodeResult<()> {
+        for idx in 0..func.dfg.num_insts() {
+            let table = match func.dfg[Inst::new(idx)] {
+                InstructionData::BranchTable { table, .. } |
+                InstructionData::BranchTableBase { table, .. } |
+                InstructionData::BranchTableEntry { table, .. } => table,
+                _ => continue,
+            };
+            if !func.jump_tables.is_valid(table) {
+                return Err(DecodeError::Malformed("entity reference out of range"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cursor::{Cursor, FuncCursor};
+    use ir::{types, AbiParam, InstBuilder, StackSlotKind, TrapCode};
+    use settings::CallConv;
+    use std::string::ToString;
+
+    fn sample_function() -> Function {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(types::I32).sext());
+        sig.returns.push(AbiParam::new(types::I32));
+        let mut func = Function::with_name_signature(ExternalName::testcase("sample"), sig);
+        let mut printf = Signature::new(CallConv::SystemV);
+        printf.params.push(AbiParam::new(types::I64));
+        printf.params.push(AbiParam::new(types::F64));
+        printf.fixed_params = Some(1);
+        func.import_signature(printf);
+        let ss = func.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 8));
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let arg = func.dfg.append_ebb_param(ebb0, types::I32);
+        let param = func.dfg.append_ebb_param(ebb1, types::I32);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            pos.set_srcloc(SourceLoc::new(42));
+            let c = pos.ins().iconst(types::I32, -7);
+            let sum = pos.ins().iadd(arg, c);
+            pos.ins().stack_store(sum, ss, 4);
+            pos.ins().trapz(sum, TrapCode::User(3));
+            let f = pos.ins().f64const(Ieee64::with_float(1.5));
+            pos.ins().fcmp(FloatCC::Equal, f, f);
+            pos.ins().brz(sum, ebb1, &[c]);
+            pos.ins().jump(ebb1, &[sum]);
+            pos.insert_ebb(ebb1);
+            pos.ins().return_(&[param]);
+        }
+        // Replace the `iconst` result so its uses go through an alias.
+        let old = func.dfg.first_result(Inst::new(0));
+        let new = func.dfg.replace_result(old, types::I32);
+        func.dfg.change_to_alias(old, new);
+        func
+    }
+
+    #[test]
+    fn round_trip() {
+        let func = sample_function();
+        let bytes = encode_function(&func);
+        let decoded = decode_function(&bytes).unwrap();
+        assert_eq!(decoded.to_string(), func.to_string());
+        assert_eq!(decoded.srclocs[Inst::new(0)], SourceLoc::new(42));
+        assert_eq!(encode_function(&decoded), bytes);
+    }
+
+    #[test]
+    fn bad_data() {
+        assert_eq!(decode_function(b"ctn").err(), Some(DecodeError::BadMagic));
+        assert_eq!(
+            decode_function(b"ctnb\x07").err(),
+            Some(DecodeError::UnsupportedVersion(7))
+        );
+
+        let bytes = encode_function(&sample_function());
+        for len in MAGIC.len() + 1..bytes.len() {
+            assert!(decode_function(&bytes[0..len]).is_err());
+        }
+
+        let mut func = sample_function();
+        while func.dfg.num_values() <= MAX_VALUES {
+            func.dfg.make_invalid_value_for_parser();
+        }
+        assert_eq!(
+            decode_function(&encode_function(&func)).err(),
+            Some(DecodeError::Malformed("too many values"))
+        );
+    }
+}
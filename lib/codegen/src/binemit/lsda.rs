@@ -0,0 +1,191 @@
+//! Language-specific data areas for exception handling.
+//!
+//! When an exception unwinds through a function, the personality routine (e.g.
+//! `__gcc_personality_v0`) reads the function's language-specific data area (LSDA) to find out
+//! where to continue execution. The LSDA is emitted in the `.gcc_except_table` section, and it
+//! contains a call-site table mapping each call instruction to its landing pad, if any.
+//!
+//! Every landing pad in a Cretonne function catches all exceptions. The landing pad gets the
+//! exception object from the `landingpad` instruction and can rethrow it with `_Unwind_Resume`.
+
+use ir::Function;
+use isa::TargetIsa;
+use std::vec::Vec;
+
+use super::CodeOffset;
+
+/// A call instruction in the call-site table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CallSite {
+    /// Offset of the call instruction from the beginning of the function.
+    pub start: CodeOffset,
+    /// Size of the call instruction in bytes.
+    pub length: CodeOffset,
+    /// Offset of the landing pad from the beginning of the function, if it has one.
+    pub landing_pad: Option<CodeOffset>,
+}
+
+/// Does `func` contain any calls with a landing pad?
+///
+/// Functions without landing pads don't need an LSDA.
+pub fn has_landing_pads(func: &Function) -> bool {
+    func.layout.ebbs().any(|ebb| {
+        func.layout.ebb_insts(ebb).any(|inst| {
+            func.dfg[inst].landing_pad().is_some()
+        })
+    })
+}
+
+/// Collect the call sites in `func`, in order of increasing code offset.
+///
+/// The function must have been compiled so the EBB offsets and instruction encodings are final.
+/// Compilation lowers every `invoke` to a `call_lp`, which carries the landing pad.
+pub fn call_sites(func: &Function, isa: &TargetIsa) -> Vec<CallSite> {
+    let encinfo = isa.encoding_info();
+    let mut sites = Vec::new();
+    for ebb in func.layout.ebbs() {
+        let mut offset = func.offsets[ebb];
+        for inst in func.layout.ebb_insts(ebb) {
+            let length = encinfo.bytes(func.encodings[inst]);
+            if func.dfg[inst].opcode().is_call() {
+                let landing_pad = func.dfg[inst].landing_pad().map(|lp| func.offsets[lp]);
+                sites.push(CallSite {
+                    start: offset,
+                    length,
+                    landing_pad,
+                });
+            }
+            offset += length;
+        }
+    }
+    sites
+}
+
+/// DWARF pointer encoding: omitted.
+const DW_EH_PE_OMIT: u8 = 0xff;
+/// DWARF pointer encoding: unsigned LEB128.
+const DW_EH_PE_ULEB128: u8 = 0x01;
+/// DWARF pointer encoding: unsigned 4-byte value.
+const DW_EH_PE_UDATA4: u8 = 0x03;
+
+/// Append `value` to `bytes` as an unsigned LEB128 number.
+fn put_uleb128(bytes: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Encode an LSDA for the call sites returned by `call_sites()`.
+///
+/// The LSDA uses the format expected by the GCC personality routines. The landing pads have a
+/// single catch-all action, and the type table contains a single null entry.
+pub fn write_lsda(sites: &[CallSite]) -> Vec<u8> {
+    let mut table = Vec::new();
+    for site in sites {
+        put_uleb128(&mut table, site.start);
+        put_uleb128(&mut table, site.length);
+        put_uleb128(&mut table, site.landing_pad.unwrap_or(0));
+        // The action is a 1-based offset into the action table, or 0 for no action.
+        table.push(if site.landing_pad.is_some() { 1 } else { 0 });
+    }
+
+    let mut call_site_header = Vec::new();
+    call_site_header.push(DW_EH_PE_ULEB128);
+    put_uleb128(&mut call_site_header, table.len() as u32);
+
+    // The action table has a single record: type filter 1, no next action.
+    let actions = [1, 0];
+
+    let mut body = Vec::new();
+    body.extend_from_slice(&call_site_header);
+    body.extend_from_slice(&table);
+    body.extend_from_slice(&actions);
+
+    // The header is 2 bytes plus the ULEB128 offset to the end of the type table, measured from
+    // just after the offset itself. Padding the offset to a fixed size keeps this simple.
+    let header_len = 2 + 4;
+    let unaligned = header_len + body.len();
+    let padding = (4 - unaligned % 4) % 4;
+    let ttype_offset = (body.len() + padding + 4) as u32;
+
+    let mut lsda = Vec::with_capacity(unaligned + padding + 4);
+    lsda.push(DW_EH_PE_OMIT);
+    lsda.push(DW_EH_PE_UDATA4);
+    put_padded_uleb128(&mut lsda, ttype_offset);
+    lsda.extend_from_slice(&body);
+    // The type table is aligned to 4 bytes. Its entries are indexed backwards from its end, and a
+    // null entry catches everything.
+    lsda.extend((0..padding).map(|_| 0));
+    lsda.extend_from_slice(&[0, 0, 0, 0]);
+    lsda
+}
+
+/// Append `value` to `bytes` as a 4-byte unsigned LEB128 number, padded with continuation bytes.
+fn put_padded_uleb128(bytes: &mut Vec<u8>, value: u32) {
+    debug_assert!(value < 1 << 28, "LSDA too large");
+    for i in 0..4 {
+        let byte = ((value >> (7 * i)) & 0x7f) as u8;
+        bytes.push(if i < 3 { byte | 0x80 } else { byte });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uleb128() {
+        let mut bytes = Vec::new();
+        put_uleb128(&mut bytes, 0);
+        put_uleb128(&mut bytes, 127);
+        put_uleb128(&mut bytes, 128);
+        put_uleb128(&mut bytes, 624_485);
+        assert_eq!(bytes, [0x00, 0x7f, 0x80, 0x01, 0xe5, 0x8e, 0x26]);
+
+        bytes.clear();
+        put_padded_uleb128(&mut bytes, 13);
+        assert_eq!(bytes, [0x8d, 0x80, 0x80, 0x00]);
+    }
+
+    #[test]
+    fn lsda() {
+        let sites = [
+            CallSite {
+                start: 4,
+                length: 5,
+                landing_pad: None,
+            },
+            CallSite {
+                start: 9,
+                length: 5,
+                landing_pad: Some(200),
+            },
+        ];
+        let lsda = write_lsda(&sites);
+        assert_eq!(
+            lsda,
+            [
+                // LPStart omitted, udata4 type table.
+                0xff, 0x03,
+                // Offset to the end of the type table.
+                0x92, 0x80, 0x80, 0x00,
+                // ULEB128 call-site table of 9 bytes.
+                0x01, 0x09,
+                0x04, 0x05, 0x00, 0x00,
+                0x09, 0x05, 0xc8, 0x01, 0x01,
+                // Action table.
+                0x01, 0x00,
+                // Padding and the catch-all type table entry.
+                0x00,
+                0x00, 0x00, 0x00, 0x00,
+            ]
+        );
+        assert_eq!(lsda.len() % 4, 0);
+    }
+}
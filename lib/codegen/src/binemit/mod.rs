@@ -3,10 +3,12 @@
 //! The `binemit` module contains code for translating Cretonne's intermediate representation into
 //! binary machine code.
 
+mod lsda;
 mod memorysink;
 mod relaxation;
 mod shrink;
 
+pub use self::lsda::{call_sites, has_landing_pads, write_lsda, CallSite};
 pub use self::memorysink::{MemoryCodeSink, RelocSink, TrapSink, NullTrapSink};
 pub use self::relaxation::relax_branches;
 pub use self::shrink::shrink_instructions;
@@ -25,7 +27,7 @@ pub type CodeOffset = u32;
 pub type Addend = i64;
 
 /// Relocation kinds for every ISA
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Reloc {
     /// absolute 4-byte
     Abs4,
@@ -37,6 +39,12 @@ pub enum Reloc {
     X86GOTPCRel4,
     /// x86 PLT-relative 4-byte
     X86PLTRel4,
+    /// x86 PC-relative 4-byte offset to the read-only data following the function
+    ///
+    /// `emit_rodata()` puts the jump tables right after the function's code, so the offset is
+    /// known and already written when the code is emitted. Code sinks are told about the
+    /// reference, but don't need to patch it.
+    X86PCRelRodata4,
     /// Arm32 call target
     Arm32Call,
     /// Arm64 call target
@@ -55,6 +63,7 @@ impl fmt::Display for Reloc {
             Reloc::X86PCRel4 => write!(f, "PCRel4"),
             Reloc::X86GOTPCRel4 => write!(f, "GOTPCRel4"),
             Reloc::X86PLTRel4 => write!(f, "PLTRel4"),
+            Reloc::X86PCRelRodata4 => write!(f, "PCRelRodata4"),
             Reloc::Arm32Call | Reloc::Arm64Call | Reloc::RiscvCall => write!(f, "Call"),
         }
     }
@@ -91,6 +100,13 @@ pub trait CodeSink {
 
     /// Add trap information for the current offset.
     fn trap(&mut self, TrapCode, SourceLoc);
+
+    /// Start emitting read-only data after the function's code.
+    ///
+    /// This is called once after the last instruction has been emitted and before the bytes of
+    /// the function's jump tables and constant pool are added with the `put*` methods. It is not
+    /// called for functions without any jump tables or constants.
+    fn begin_rodata(&mut self) {}
 }
 
 /// Report a bad encoding error.
@@ -120,4 +136,43 @@ where
             emit_inst(func, inst, &mut divert, sink);
         }
     }
+    emit_rodata(func, sink);
+}
+
+/// Emit the jump tables and the constant pool of `func` to `sink`.
+///
+/// The read-only data must have been laid out by `relax_branches()`, and `sink` must be
+/// positioned at the end of the function's code.
+pub fn emit_rodata<CS: CodeSink>(func: &Function, sink: &mut CS) {
+    if func.jump_tables.is_empty() && func.dfg.constants.is_empty() {
+        return;
+    }
+    sink.begin_rodata();
+
+    // Jump table entries are relative to the start of the table. Empty entries are never used.
+    for (jt, jt_data) in func.jump_tables.iter() {
+        let jt_offset = func.jt_offsets[jt];
+        pad_to(jt_offset, sink);
+        for idx in 0..jt_data.len() {
+            let rel_offset = jt_data.get_entry(idx).map_or(0, |ebb| {
+                func.offsets[ebb].wrapping_sub(jt_offset)
+            });
+            sink.put4(rel_offset);
+        }
+    }
+
+    for (constant, data) in func.dfg.constants.iter() {
+        pad_to(func.dfg.constants.offset(constant), sink);
+        for &byte in data.as_slice() {
+            sink.put1(byte);
+        }
+    }
+}
+
+/// Pad the read-only data with zeros up to `offset`.
+fn pad_to<CS: CodeSink>(offset: CodeOffset, sink: &mut CS) {
+    debug_assert!(sink.offset() <= offset, "Read-only data overlaps");
+    while sink.offset() < offset {
+        sink.put1(0);
+    }
 }
@@ -36,7 +36,9 @@ use result::CtonError;
 
 /// Relax branches and compute the final layout of EBB headers in `func`.
 ///
-/// Fill in the `func.offsets` table so the function is ready for binary emission.
+/// Fill in the `func.offsets` table and lay out the jump tables and the constant pool after the
+/// code so the function is ready for binary emission. Returns the total size of the code and the
+/// read-only data.
 pub fn relax_branches(func: &mut Function, isa: &TargetIsa) -> Result<CodeOffset, CtonError> {
     let encinfo = isa.encoding_info();
 
@@ -92,7 +94,16 @@ pub fn relax_branches(func: &mut Function, isa: &TargetIsa) -> Result<CodeOffset
         }
     }
 
-    Ok(offset)
+    // The jump tables follow the code. Each entry is a 32-bit offset.
+    func.jt_offsets.clear();
+    func.jt_offsets.resize(func.jump_tables.len());
+    for (jt, jt_data) in func.jump_tables.iter() {
+        offset = (offset + 3) & !3;
+        func.jt_offsets[jt] = offset;
+        offset += jt_data.len() as CodeOffset * 4;
+    }
+
+    Ok(func.dfg.constants.layout(offset))
 }
 
 /// Convert `jump` instructions to `fallthrough` instructions where possible and verify that any
@@ -5,7 +5,7 @@
 //! flexibility. However, once register allocation is done, this is no longer important, and we
 //! can switch to smaller encodings when possible.
 
-use ir::Function;
+use ir::{Function, Opcode};
 use isa::TargetIsa;
 use regalloc::RegDiversions;
 
@@ -18,7 +18,13 @@ pub fn shrink_instructions(func: &mut Function, isa: &TargetIsa) {
         divert.clear();
         for inst in func.layout.ebb_insts(ebb) {
             let enc = func.encodings[inst];
-            if enc.is_legal() {
+            // Register moves, spills and fills name their registers with immediate operands
+            // which aren't checked by the recipe constraints, so leave them alone.
+            let is_reg_move = match func.dfg[inst].opcode() {
+                Opcode::Regmove | Opcode::Regspill | Opcode::Regfill => true,
+                _ => false,
+            };
+            if enc.is_legal() && !is_reg_move {
                 let ctrl_type = func.dfg.ctrl_typevar(inst);
 
                 // Pick the last encoding with constraints that are satisfied.
@@ -0,0 +1,597 @@
+//! Content-addressed compilation cache.
+//!
+//! Compiling the same function with the same settings always produces the same machine code, so
+//! the output of the compilation pipeline can be cached across runs. A `CacheKey` is a stable hash
+//! of the function and all the settings that affect code generation. It is used to look up a
+//! serialized `CompiledCode` in a `CacheStore`.
+//!
+//! The `Context::compile_cached()` method consults a cache store before running the pipeline. Any
+//! type implementing `CacheStore` can be used as the storage. A `HashMap` works as an in-memory
+//! cache, and `DiskCache` keeps cache entries as files in a directory.
+
+use binemit::{Addend, CodeOffset, Reloc, RelocSink, TrapSink};
+use entity::EntityRef;
+use ir::{ExternalName, Function, JumpTable, LibCall, SourceLoc, TrapCode};
+use isa::TargetIsa;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::str;
+use std::string::ToString;
+use std::vec::Vec;
+
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+
+/// Version of the cache key and serialized entry formats.
+///
+/// Bump this whenever a change to Cretonne could change the generated code for an unchanged
+/// function, or when the serialized format of `CompiledCode` changes.
+const FORMAT_VERSION: u32 = 1;
+
+/// Magic bytes at the start of a serialized `CompiledCode`.
+const MAGIC: &[u8; 4] = b"ctnc";
+
+/// A stable 128-bit hash identifying a compiled function.
+///
+/// The key covers the function itself as well as the shared settings and the ISA-specific
+/// settings used to compile it. It is computed from the textual representation of these, so it is
+/// the same across runs and hosts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey(u64, u64);
+
+impl CacheKey {
+    /// Compute the cache key for compiling `func` with `isa`.
+    pub fn new(func: &Function, isa: &TargetIsa) -> Self {
+        let mut hasher = Fnv128::new();
+        write!(
+            hasher,
+            "{} {} {}\n{}\n{}",
+            ::VERSION,
+            FORMAT_VERSION,
+            isa.name(),
+            isa,
+            func.display(isa)
+        ).expect("hashing can't fail");
+        let (hi, lo) = hasher.finish();
+        CacheKey(hi, lo)
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}{:016x}", self.0, self.1)
+    }
+}
+
+/// The 128-bit FNV-1a hash function.
+///
+/// This is used rather than the standard library hashers because its output must not change
+/// between releases. The state is kept as high and low 64-bit halves.
+struct Fnv128(u64, u64);
+
+impl Fnv128 {
+    fn new() -> Self {
+        Fnv128(0x6c62_272e_07bb_0142, 0x62b8_2175_6295_c58d)
+    }
+
+    fn finish(&self) -> (u64, u64) {
+        (self.0, self.1)
+    }
+}
+
+impl Write for Fnv128 {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // The FNV prime is `2^88 + 0x13b`.
+        const PRIME_LO: u64 = 0x13b;
+        const PRIME_SHIFT: u32 = 88 - 64;
+
+        for &byte in s.as_bytes() {
+            let (hi, lo) = (self.0, self.1 ^ u64::from(byte));
+
+            // The high 64 bits of `lo * PRIME_LO`, computed in 32-bit halves.
+            let carry = ((lo >> 32) * PRIME_LO + (((lo & 0xffff_ffff) * PRIME_LO) >> 32)) >> 32;
+
+            self.0 = hi.wrapping_mul(PRIME_LO)
+                .wrapping_add(carry)
+                .wrapping_add(lo << PRIME_SHIFT);
+            self.1 = lo.wrapping_mul(PRIME_LO);
+        }
+        Ok(())
+    }
+}
+
+/// The target of a relocation recorded in `CompiledCode`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RelocTarget {
+    /// An EBB at the given offset in the function.
+    Ebb(CodeOffset),
+    /// An external symbol plus an addend.
+    External(ExternalName, Addend),
+    /// A jump table.
+    JumpTable(JumpTable),
+}
+
+/// A relocation recorded in `CompiledCode`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CachedReloc {
+    /// Offset of the relocation in the code.
+    pub offset: CodeOffset,
+    /// Kind of relocation.
+    pub reloc: Reloc,
+    /// What the relocation refers to.
+    pub target: RelocTarget,
+}
+
+/// A trap record in `CompiledCode`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CachedTrap {
+    /// Offset of the trapping instruction in the code.
+    pub offset: CodeOffset,
+    /// Source location of the trapping instruction.
+    pub srcloc: SourceLoc,
+    /// The trap code.
+    pub code: TrapCode,
+}
+
+/// The output of compiling a function: machine code along with its relocations and traps.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompiledCode {
+    /// The machine code, not relocated.
+    pub code: Vec<u8>,
+    /// Relocations in `code`, in emission order.
+    pub relocs: Vec<CachedReloc>,
+    /// Trap records for `code`, in emission order.
+    pub traps: Vec<CachedTrap>,
+}
+
+impl CompiledCode {
+    /// Create an empty `CompiledCode`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replay the recorded relocations and traps into `relocs` and `traps`.
+    ///
+    /// This presents a cached function to the sinks in the same way as `Context::emit_to_memory`
+    /// would have.
+    pub fn emit_records(&self, relocs: &mut RelocSink, traps: &mut TrapSink) {
+        for r in &self.relocs {
+            match r.target {
+                RelocTarget::Ebb(ebb_offset) => relocs.reloc_ebb(r.offset, r.reloc, ebb_offset),
+                RelocTarget::External(ref name, addend) => {
+                    relocs.reloc_external(r.offset, r.reloc, name, addend)
+                }
+                RelocTarget::JumpTable(jt) => relocs.reloc_jt(r.offset, r.reloc, jt),
+            }
+        }
+        for t in &self.traps {
+            traps.trap(t.offset, t.srcloc, t.code);
+        }
+    }
+
+    /// Serialize this compiled code into a byte vector suitable for a `CacheStore`.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.code.len() + 64);
+        out.extend_from_slice(MAGIC);
+        put_u32(&mut out, FORMAT_VERSION);
+        put_bytes(&mut out, &self.code);
+
+        put_u32(&mut out, self.relocs.len() as u32);
+        for r in &self.relocs {
+            put_u32(&mut out, r.offset);
+            out.push(reloc_to_byte(r.reloc));
+            match r.target {
+                RelocTarget::Ebb(ebb_offset) => {
+                    out.push(0);
+                    put_u32(&mut out, ebb_offset);
+                }
+                RelocTarget::External(ref name, addend) => {
+                    out.push(1);
+                    put_name(&mut out, name);
+                    put_u64(&mut out, addend as u64);
+                }
+                RelocTarget::JumpTable(jt) => {
+                    out.push(2);
+                    put_u32(&mut out, jt.index() as u32);
+                }
+            }
+        }
+
+        put_u32(&mut out, self.traps.len() as u32);
+        for t in &self.traps {
+            put_u32(&mut out, t.offset);
+            put_u32(&mut out, t.srcloc.bits());
+            put_bytes(&mut out, t.code.to_string().as_bytes());
+        }
+        out
+    }
+
+    /// Deserialize compiled code produced by `serialize()`.
+    ///
+    /// Returns `None` if `data` is not a valid serialization for this version of Cretonne.
+    pub fn deserialize(data: &[u8]) -> Option<Self> {
+        let mut r = ByteReader { data };
+        if r.take(MAGIC.len())? != MAGIC || r.u32()? != FORMAT_VERSION {
+            return None;
+        }
+        let mut compiled = Self::new();
+        compiled.code = r.bytes()?.to_vec();
+
+        for _ in 0..r.u32()? {
+            let offset = r.u32()?;
+            let reloc = reloc_from_byte(r.u8()?)?;
+            let target = match r.u8()? {
+                0 => RelocTarget::Ebb(r.u32()?),
+                1 => {
+                    let name = r.name()?;
+                    RelocTarget::External(name, r.u64()? as Addend)
+                }
+                2 => RelocTarget::JumpTable(JumpTable::with_number(r.u32()?)?),
+                _ => return None,
+            };
+            compiled.relocs.push(CachedReloc {
+                offset,
+                reloc,
+                target,
+            });
+        }
+
+        for _ in 0..r.u32()? {
+            let offset = r.u32()?;
+            let srcloc = SourceLoc::new(r.u32()?);
+            let code = str::from_utf8(r.bytes()?).ok()?.parse().ok()?;
+            compiled.traps.push(CachedTrap {
+                offset,
+                srcloc,
+                code,
+            });
+        }
+
+        if r.data.is_empty() {
+            Some(compiled)
+        } else {
+            None
+        }
+    }
+}
+
+impl RelocSink for CompiledCode {
+    fn reloc_ebb(&mut self, offset: CodeOffset, reloc: Reloc, ebb_offset: CodeOffset) {
+        self.relocs.push(CachedReloc {
+            offset,
+            reloc,
+            target: RelocTarget::Ebb(ebb_offset),
+        });
+    }
+
+    fn reloc_external(
+        &mut self,
+        offset: CodeOffset,
+        reloc: Reloc,
+        name: &ExternalName,
+        addend: Addend,
+    ) {
+        self.relocs.push(CachedReloc {
+            offset,
+            reloc,
+            target: RelocTarget::External(name.clone(), addend),
+        });
+    }
+
+    fn reloc_jt(&mut self, offset: CodeOffset, reloc: Reloc, jt: JumpTable) {
+        self.relocs.push(CachedReloc {
+            offset,
+            reloc,
+            target: RelocTarget::JumpTable(jt),
+        });
+    }
+}
+
+impl TrapSink for CompiledCode {
+    fn trap(&mut self, offset: CodeOffset, srcloc: SourceLoc, code: TrapCode) {
+        self.traps.push(CachedTrap {
+            offset,
+            srcloc,
+            code,
+        });
+    }
+}
+
+fn reloc_to_byte(reloc: Reloc) -> u8 {
+    match reloc {
+        Reloc::Abs4 => 0,
+        Reloc::Abs8 => 1,
+        Reloc::X86PCRel4 => 2,
+        Reloc::X86GOTPCRel4 => 3,
+        Reloc::X86PLTRel4 => 4,
+        Reloc::Arm32Call => 5,
+        Reloc::Arm64Call => 6,
+        Reloc::RiscvCall => 7,
+        Reloc::X86PCRelRodata4 => 8,
+    }
+}
+
+fn reloc_from_byte(byte: u8) -> Option<Reloc> {
+    Some(match byte {
+        0 => Reloc::Abs4,
+        1 => Reloc::Abs8,
+        2 => Reloc::X86PCRel4,
+        3 => Reloc::X86GOTPCRel4,
+        4 => Reloc::X86PLTRel4,
+        5 => Reloc::Arm32Call,
+        6 => Reloc::Arm64Call,
+        7 => Reloc::RiscvCall,
+        8 => Reloc::X86PCRelRodata4,
+        _ => return None,
+    })
+}
+
+fn put_u32(out: &mut Vec<u8>, x: u32) {
+    for i in 0..4 {
+        out.push((x >> (8 * i)) as u8);
+    }
+}
+
+fn put_u64(out: &mut Vec<u8>, x: u64) {
+    put_u32(out, x as u32);
+    put_u32(out, (x >> 32) as u32);
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    put_u32(out, bytes.len() as u32);
+    out.extend_from_slice(bytes);
+}
+
+fn put_name(out: &mut Vec<u8>, name: &ExternalName) {
+    match *name {
+        ExternalName::User { namespace, index } => {
+            out.push(0);
+            put_u32(out, namespace);
+            put_u32(out, index);
+        }
+        ExternalName::TestCase { length, ascii } => {
+            out.push(1);
+            put_bytes(out, &ascii[0..length as usize]);
+        }
+        ExternalName::LibCall(libcall) => {
+            out.push(2);
+            put_bytes(out, libcall.to_string().as_bytes());
+        }
+    }
+}
+
+/// Little-endian reader for the serialized format.
+struct ByteReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.data.len() < n {
+            return None;
+        }
+        let (head, tail) = self.data.split_at(n);
+        self.data = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(|b| {
+            b.iter().rev().fold(0, |acc, &byte| (acc << 8) | u32::from(byte))
+        })
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let lo = self.u32()?;
+        let hi = self.u32()?;
+        Some(u64::from(lo) | (u64::from(hi) << 32))
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn name(&mut self) -> Option<ExternalName> {
+        match self.u8()? {
+            0 => {
+                let namespace = self.u32()?;
+                let index = self.u32()?;
+                Some(ExternalName::user(namespace, index))
+            }
+            1 => Some(ExternalName::testcase(self.bytes()?)),
+            2 => {
+                let libcall: LibCall = str::from_utf8(self.bytes()?).ok()?.parse().ok()?;
+                Some(ExternalName::LibCall(libcall))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Storage for cached compilation results.
+///
+/// Entries are opaque byte vectors keyed by a `CacheKey`. Implementations are free to evict
+/// entries at any time; a missing entry simply causes the function to be recompiled.
+pub trait CacheStore {
+    /// Look up the entry for `key`.
+    fn get(&mut self, key: &CacheKey) -> Option<Vec<u8>>;
+
+    /// Store `data` as the entry for `key`, replacing any existing entry.
+    fn insert(&mut self, key: &CacheKey, data: Vec<u8>);
+}
+
+/// An in-memory cache store.
+impl CacheStore for HashMap<CacheKey, Vec<u8>> {
+    fn get(&mut self, key: &CacheKey) -> Option<Vec<u8>> {
+        HashMap::get(self, key).cloned()
+    }
+
+    fn insert(&mut self, key: &CacheKey, data: Vec<u8>) {
+        HashMap::insert(self, *key, data);
+    }
+}
+
+/// A cache store that keeps one file per entry in a directory.
+///
+/// The files are named after the hexadecimal cache key. Errors reading or writing the files are
+/// treated as cache misses, so a broken cache directory only costs compile time.
+#[cfg(feature = "std")]
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+#[cfg(feature = "std")]
+impl DiskCache {
+    /// Create a disk cache storing its entries in `dir`, creating the directory if needed.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join(format!("{}.ctnc", key))
+    }
+}
+
+#[cfg(feature = "std")]
+impl CacheStore for DiskCache {
+    fn get(&mut self, key: &CacheKey) -> Option<Vec<u8>> {
+        let mut file = fs::File::open(self.path(key)).ok()?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).ok()?;
+        Some(data)
+    }
+
+    fn insert(&mut self, key: &CacheKey, data: Vec<u8>) {
+        use std::io::Write;
+
+        // Write to a temporary file first so concurrent readers never see a partial entry.
+        let path = self.path(key);
+        let tmp = path.with_extension(format!("tmp{}", temp_suffix()));
+        let written = fs::File::create(&tmp).and_then(|mut file| file.write_all(&data));
+        if written.is_ok() && fs::rename(&tmp, &path).is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+    }
+}
+
+/// A suffix for temporary file names that is unlikely to be used by another writer at the same
+/// time. Writers that do collide are writing the same entry, and so the same bytes.
+#[cfg(feature = "std")]
+fn temp_suffix() -> u32 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cursor::{Cursor, FuncCursor};
+    use ir::{types, AbiParam, InstBuilder, Signature};
+    use isa;
+    use settings::{self, CallConv, Configurable};
+    use std::boxed::Box;
+    use Context;
+
+    fn make_isa(opt_level: &str) -> Box<TargetIsa> {
+        let mut flag_builder = settings::builder();
+        flag_builder.enable("is_64bit").unwrap();
+        flag_builder.set("opt_level", opt_level).unwrap();
+        isa::lookup("x86").unwrap().finish(
+            settings::Flags::new(flag_builder),
+        )
+    }
+
+    fn make_function(imm: i64) -> Function {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let mut func = Function::with_name_signature(ExternalName::testcase("f"), sig);
+        let ebb = func.dfg.make_ebb();
+        let arg = func.dfg.append_ebb_param(ebb, types::I64);
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb);
+        let sum = pos.ins().iadd_imm(arg, imm);
+        pos.ins().trapz(sum, TrapCode::User(7));
+        pos.ins().return_(&[sum]);
+        func
+    }
+
+    #[test]
+    fn keys() {
+        let isa = make_isa("default");
+        let key = CacheKey::new(&make_function(1), &*isa);
+        assert_eq!(key, CacheKey::new(&make_function(1), &*isa));
+        assert_eq!(key.to_string().len(), 32);
+        assert_ne!(key, CacheKey::new(&make_function(2), &*isa));
+        assert_ne!(key, CacheKey::new(&make_function(1), &*make_isa("best")));
+    }
+
+    #[test]
+    fn fnv128() {
+        let hash = |s: &str| {
+            let mut hasher = Fnv128::new();
+            hasher.write_str(s).unwrap();
+            hasher.finish()
+        };
+        assert_eq!(hash(""), (0x6c62272e07bb0142, 0x62b821756295c58d));
+        assert_eq!(hash("a"), (0xd228cb696f1a8caf, 0x78912b704e4a8964));
+        assert_eq!(hash("foobar"), (0x343e1662793c64bf, 0x6f0d3597ba446f18));
+    }
+
+    #[test]
+    fn serialization() {
+        let mut compiled = CompiledCode::new();
+        compiled.code = vec![1, 2, 3, 4, 5];
+        compiled.reloc_ebb(1, Reloc::X86PCRel4, 3);
+        compiled.reloc_external(
+            2,
+            Reloc::Abs8,
+            &ExternalName::LibCall(LibCall::FloorF32),
+            -4,
+        );
+        compiled.reloc_external(2, Reloc::Abs4, &ExternalName::user(1, 2), 0);
+        compiled.reloc_external(2, Reloc::Abs4, &ExternalName::testcase("foo"), 0);
+        compiled.reloc_jt(3, Reloc::Abs4, JumpTable::with_number(1).unwrap());
+        compiled.trap(4, SourceLoc::new(9), TrapCode::HeapOutOfBounds);
+        compiled.trap(4, SourceLoc::default(), TrapCode::User(3));
+
+        let data = compiled.serialize();
+        assert_eq!(CompiledCode::deserialize(&data), Some(compiled));
+        assert_eq!(CompiledCode::deserialize(&data[..data.len() - 1]), None);
+        assert_eq!(CompiledCode::deserialize(b"junk"), None);
+    }
+
+    #[test]
+    fn compile_cached() {
+        let isa = make_isa("default");
+        let mut cache = HashMap::new();
+        let mut ctx = Context::for_function(make_function(1));
+        let first = ctx.compile_cached(&*isa, &mut cache).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert!(!first.code.is_empty());
+        assert!(first.traps.iter().any(|t| t.code == TrapCode::User(7)));
+
+        // A cache hit returns the same code without compiling the function.
+        ctx.clear();
+        ctx.func = make_function(1);
+        let second = ctx.compile_cached(&*isa, &mut cache).unwrap();
+        assert_eq!(first, second);
+        assert!(ctx.func.encodings.is_empty());
+        assert_eq!(cache.len(), 1);
+    }
+}
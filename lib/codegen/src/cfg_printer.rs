@@ -51,6 +51,16 @@ impl<'a> CFGPrinter<'a> {
                     BranchInfo::Table(table) => {
                         write!(w, " | <{}>{} {}", inst, idata.opcode(), table)?
                     }
+                    BranchInfo::Invoke(dest, landing_pad) => {
+                        write!(
+                            w,
+                            " | <{}>{} {}, {}",
+                            inst,
+                            idata.opcode(),
+                            dest,
+                            landing_pad
+                        )?
+                    }
                     BranchInfo::NotABranch => {}
                 }
             }
@@ -10,6 +10,7 @@
 //! single ISA instance.
 
 use binemit::{relax_branches, shrink_instructions, CodeOffset, MemoryCodeSink, RelocSink, TrapSink};
+use cache::{CacheKey, CacheStore, CompiledCode};
 use dce::do_dce;
 use dominator_tree::DominatorTree;
 use flowgraph::ControlFlowGraph;
@@ -110,13 +111,45 @@ impl Context {
         Ok(())
     }
 
+    /// Compile the function and emit machine code, consulting `cache` first.
+    ///
+    /// The function is looked up in `cache` by a `CacheKey` computed from the function and the
+    /// settings of `isa`. On a hit, the compilation pipeline is skipped entirely and `self.func` is
+    /// left unchanged. Otherwise, the function is compiled and emitted as with
+    /// `compile_and_emit`, and the result is added to the cache.
+    pub fn compile_cached(
+        &mut self,
+        isa: &TargetIsa,
+        cache: &mut CacheStore,
+    ) -> Result<CompiledCode, CtonError> {
+        let key = CacheKey::new(&self.func, isa);
+        if let Some(compiled) = cache.get(&key).and_then(
+            |data| CompiledCode::deserialize(&data),
+        )
+        {
+            return Ok(compiled);
+        }
+
+        let mut code = Vec::new();
+        let mut relocs = CompiledCode::new();
+        let mut traps = CompiledCode::new();
+        self.compile_and_emit(isa, &mut code, &mut relocs, &mut traps)?;
+        let compiled = CompiledCode {
+            code,
+            relocs: relocs.relocs,
+            traps: traps.traps,
+        };
+        cache.insert(&key, compiled.serialize());
+        Ok(compiled)
+    }
+
     /// Compile the function.
     ///
     /// Run the function through all the passes necessary to generate code for the target ISA
     /// represented by `isa`. This does not include the final step of emitting machine code into a
     /// code sink.
     ///
-    /// Returns the size of the function's code.
+    /// Returns the size of the function's code, including its constant pool.
     pub fn compile(&mut self, isa: &TargetIsa) -> Result<CodeOffset, CtonError> {
         let _tt = timing::compile();
         self.verify_if(isa)?;
@@ -228,7 +261,7 @@ impl Context {
         // TODO: Avoid doing this when legalization doesn't actually mutate the CFG.
         self.domtree.clear();
         self.loop_analysis.clear();
-        legalize_function(&mut self.func, &mut self.cfg, isa);
+        legalize_function(&mut self.func, &mut self.cfg, isa)?;
         self.verify_if(isa)
     }
 
@@ -272,7 +305,9 @@ impl Context {
 
     /// Perform LICM on the function.
     pub fn licm<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CtonResult {
+        let fisa = fisa.into();
         do_licm(
+            fisa.isa,
             &mut self.func,
             &mut self.cfg,
             &mut self.domtree,
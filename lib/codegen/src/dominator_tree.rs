@@ -355,6 +355,14 @@ impl DominatorTree {
                         }
                     }
                 }
+                BranchInfo::Invoke(dest, landing_pad) => {
+                    for &succ in &[dest, landing_pad] {
+                        if self.nodes[succ].rpo_number == 0 {
+                            self.nodes[succ].rpo_number = SEEN;
+                            self.stack.push(succ);
+                        }
+                    }
+                }
                 BranchInfo::NotABranch => {}
             }
         }
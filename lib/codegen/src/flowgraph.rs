@@ -117,6 +117,10 @@ impl ControlFlowGraph {
                         self.add_edge((ebb, inst), dest);
                     }
                 }
+                BranchInfo::Invoke(dest, landing_pad) => {
+                    self.add_edge((ebb, inst), dest);
+                    self.add_edge((ebb, inst), landing_pad);
+                }
                 BranchInfo::NotABranch => {}
             }
         }
@@ -8,6 +8,7 @@ use ir::types;
 use ir::{DataFlowGraph, InstructionData};
 use ir::{Inst, Opcode, Type, Value};
 use isa;
+use std::boxed::Box;
 
 /// Base trait for instruction builders.
 ///
@@ -0,0 +1,275 @@
+//! Constants.
+//!
+//! The constant pool holds data that is too large or awkward to encode as an immediate operand,
+//! such as SIMD vector constants. Each function has its own constant pool which is emitted after
+//! the function's code, and instructions refer to the constants it contains with `Constant`
+//! entity references.
+
+use binemit::CodeOffset;
+use entity::{self, PrimaryMap};
+use ir::Constant;
+use std::cmp;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::vec::Vec;
+
+/// Maximum alignment of a constant in the constant pool.
+const MAX_ALIGNMENT: CodeOffset = 16;
+
+/// The bytes of a constant, in little-endian order.
+///
+/// In the textual IR format, a constant is written as a single hexadecimal number with two digits
+/// per byte, so the first byte of the constant is the last pair of digits.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ConstantData(Vec<u8>);
+
+impl ConstantData {
+    /// Get the number of bytes in the constant.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Is this an empty constant?
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Get the bytes of the constant.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Get the required alignment of the constant in the constant pool.
+    ///
+    /// Constants are naturally aligned, up to a maximum of 16 bytes.
+    pub fn alignment(&self) -> CodeOffset {
+        cmp::min(self.0.len().next_power_of_two() as CodeOffset, MAX_ALIGNMENT)
+    }
+}
+
+impl From<Vec<u8>> for ConstantData {
+    fn from(bytes: Vec<u8>) -> Self {
+        ConstantData(bytes)
+    }
+}
+
+impl<'a> From<&'a [u8]> for ConstantData {
+    fn from(bytes: &'a [u8]) -> Self {
+        ConstantData(bytes.to_vec())
+    }
+}
+
+impl fmt::Display for ConstantData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0x")?;
+        for b in self.0.iter().rev() {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ConstantData {
+    type Err = &'static str;
+
+    /// Parse a hexadecimal constant like `0x0001`.
+    ///
+    /// The constant has one byte for every two hexadecimal digits, rounded up. Leading zeros are
+    /// significant since they determine the size of the constant.
+    fn from_str(s: &str) -> Result<Self, &'static str> {
+        if !s.starts_with("0x") {
+            return Err("Constant must be a hexadecimal number starting with 0x");
+        }
+        let digits = &s[2..];
+        if digits.is_empty() {
+            return Err("Constant has no digits");
+        }
+        let mut bytes = Vec::with_capacity((digits.len() + 1) / 2);
+        let mut byte = 0;
+        let mut high = false;
+        for c in digits.chars().rev() {
+            let value = c.to_digit(16).ok_or(
+                "Invalid character in hexadecimal constant",
+            )? as u8;
+            if high {
+                bytes.push(byte | value << 4);
+                byte = 0;
+            } else {
+                byte = value;
+            }
+            high = !high;
+        }
+        if high {
+            bytes.push(byte);
+        }
+        Ok(ConstantData(bytes))
+    }
+}
+
+/// An entry in the constant pool.
+#[derive(Clone)]
+struct ConstantPoolEntry {
+    data: ConstantData,
+    offset: Option<CodeOffset>,
+}
+
+/// The constant pool of a function.
+///
+/// Identical constants are shared when they are added with `insert()`. The offsets of the
+/// constants relative to the start of the function are computed by `layout()`, which is called
+/// during branch relaxation once the code size is known.
+#[derive(Clone)]
+pub struct ConstantPool {
+    entries: PrimaryMap<Constant, ConstantPoolEntry>,
+    handles: HashMap<ConstantData, Constant>,
+}
+
+impl ConstantPool {
+    /// Create an empty constant pool.
+    pub fn new() -> Self {
+        Self {
+            entries: PrimaryMap::new(),
+            handles: HashMap::new(),
+        }
+    }
+
+    /// Clear out everything.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.handles.clear();
+    }
+
+    /// Get the number of constants in the pool.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Is the constant pool empty?
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Check if `c` is a valid constant reference.
+    pub fn is_valid(&self, c: Constant) -> bool {
+        self.entries.is_valid(c)
+    }
+
+    /// Add `data` to the constant pool, reusing an existing identical constant if there is one.
+    pub fn insert(&mut self, data: ConstantData) -> Constant {
+        if let Some(&c) = self.handles.get(&data) {
+            return c;
+        }
+        self.push(data)
+    }
+
+    /// Add `data` as a new constant, even if an identical constant already exists.
+    ///
+    /// This function should be primarily used by the text format parser.
+    pub fn push(&mut self, data: ConstantData) -> Constant {
+        let c = self.entries.push(ConstantPoolEntry {
+            data: data.clone(),
+            offset: None,
+        });
+        self.handles.entry(data).or_insert(c);
+        c
+    }
+
+    /// Replace the data of an existing constant.
+    ///
+    /// This function should be primarily used by the text format parser.
+    pub fn set(&mut self, c: Constant, data: ConstantData) {
+        if self.handles.get(&self.entries[c].data) == Some(&c) {
+            self.handles.remove(&self.entries[c].data);
+        }
+        self.handles.entry(data.clone()).or_insert(c);
+        self.entries[c] = ConstantPoolEntry { data, offset: None };
+    }
+
+    /// Get a reference to the next constant that would be created by `push()`.
+    pub fn next_key(&self) -> Constant {
+        self.entries.next_key()
+    }
+
+    /// Get the data of the constant `c`.
+    pub fn get(&self, c: Constant) -> &ConstantData {
+        &self.entries[c].data
+    }
+
+    /// Get the offset of the constant `c` relative to the start of the function.
+    ///
+    /// This is only available after `layout()` has been called.
+    pub fn offset(&self, c: Constant) -> CodeOffset {
+        self.entries[c].offset.expect("Constant pool has not been laid out")
+    }
+
+    /// Iterate over the constants in the pool.
+    pub fn iter(&self) -> Iter {
+        Iter(self.entries.iter())
+    }
+
+    /// Assign offsets to all the constants, placing the constant pool after `code_size` bytes of
+    /// code.
+    ///
+    /// Returns the total size of the code and constant pool.
+    pub fn layout(&mut self, code_size: CodeOffset) -> CodeOffset {
+        let mut offset = code_size;
+        for entry in self.entries.values_mut() {
+            let align = entry.data.alignment();
+            offset = (offset + align - 1) & !(align - 1);
+            entry.offset = Some(offset);
+            offset += entry.data.len() as CodeOffset;
+        }
+        offset
+    }
+}
+
+/// Iterator over the constants in a constant pool.
+pub struct Iter<'a>(entity::Iter<'a, Constant, ConstantPoolEntry>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (Constant, &'a ConstantData);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(c, entry)| (c, &entry.data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn parse_display() {
+        let c: ConstantData = "0x0102".parse().unwrap();
+        assert_eq!(c.as_slice(), &[2, 1]);
+        assert_eq!(c.to_string(), "0x0102");
+
+        let c: ConstantData = "0x00000000000000ff".parse().unwrap();
+        assert_eq!(c.len(), 8);
+        assert_eq!(c.to_string(), "0x00000000000000ff");
+
+        let c: ConstantData = "0x123".parse().unwrap();
+        assert_eq!(c.as_slice(), &[0x23, 0x01]);
+
+        assert!("0x".parse::<ConstantData>().is_err());
+        assert!("12".parse::<ConstantData>().is_err());
+        assert!("0x1g".parse::<ConstantData>().is_err());
+    }
+
+    #[test]
+    fn pool() {
+        let mut pool = ConstantPool::new();
+        let c0 = pool.insert(ConstantData::from(&[1u8, 2, 3, 4][..]));
+        let c1 = pool.insert(ConstantData::from(vec![0u8; 16]));
+        assert_eq!(pool.insert(ConstantData::from(vec![1, 2, 3, 4])), c0);
+        assert_eq!(pool.len(), 2);
+
+        // The pool starts after the code, with each constant naturally aligned.
+        assert_eq!(pool.layout(5), 32);
+        assert_eq!(pool.offset(c0), 8);
+        assert_eq!(pool.offset(c1), 16);
+        assert_eq!(ConstantPool::new().layout(5), 5);
+    }
+}
@@ -3,6 +3,7 @@
 use entity::{EntityMap, PrimaryMap};
 use ir;
 use ir::builder::ReplaceBuilder;
+use ir::constant::ConstantPool;
 use ir::extfunc::ExtFuncData;
 use ir::instructions::{BranchInfo, CallInfo, InstructionData};
 use ir::types;
@@ -60,6 +61,10 @@ pub struct DataFlowGraph {
 
     /// External function references. These are functions that can be called directly.
     pub ext_funcs: PrimaryMap<FuncRef, ExtFuncData>,
+
+    /// Constants used by the function. These are referenced by instructions like `vconst` and
+    /// emitted after the function's code.
+    pub constants: ConstantPool,
 }
 
 impl DataFlowGraph {
@@ -73,6 +78,7 @@ impl DataFlowGraph {
             values: PrimaryMap::new(),
             signatures: PrimaryMap::new(),
             ext_funcs: PrimaryMap::new(),
+            constants: ConstantPool::new(),
         }
     }
 
@@ -85,6 +91,7 @@ impl DataFlowGraph {
         self.values.clear();
         self.signatures.clear();
         self.ext_funcs.clear();
+        self.constants.clear();
     }
 
     /// Get the total number of instructions created in this function, whether they are currently
@@ -172,6 +179,18 @@ impl DataFlowGraph {
         }
     }
 
+    /// Change the type of a value.
+    ///
+    /// The instruction or EBB defining `v` and all of its uses must be updated to match the new
+    /// type, which is not checked here.
+    pub fn change_value_type(&mut self, v: Value, t: Type) {
+        match self.values[v] {
+            ValueData::Inst { ref mut ty, .. } |
+            ValueData::Param { ref mut ty, .. } |
+            ValueData::Alias { ref mut ty, .. } => *ty = t,
+        }
+    }
+
     /// Get the definition of a value.
     ///
     /// This is either the instruction that defined it or the Ebb that has the value as an
@@ -210,6 +229,18 @@ impl DataFlowGraph {
         resolve_aliases(&self.values, value)
     }
 
+    /// Get the value that `v` is directly aliased to, if `v` is a value alias.
+    ///
+    /// Unlike `resolve_aliases()`, this only follows a single link in a chain of aliases.
+    pub fn value_alias(&self, v: Value) -> Option<Value> {
+        match self.values[v] {
+            ValueData::Alias { original, .. } if original != Value::reserved_value() => {
+                Some(original)
+            }
+            _ => None,
+        }
+    }
+
     /// Resolve all aliases among inst's arguments.
     ///
     /// For each argument of inst which is defined by an alias, replace the
@@ -454,7 +485,7 @@ impl DataFlowGraph {
         self.results[inst].clear(&mut self.value_lists);
 
         // Get the call signature if this is a function call.
-        if let Some(sig) = self.call_signature(inst) {
+        if let Some(sig) = self.call_result_signature(inst) {
             // Create result values corresponding to the call return types.
             debug_assert_eq!(self.insts[inst].opcode().constraints().fixed_results(), 0);
             let num_results = self.signatures[sig].returns.len();
@@ -610,6 +641,17 @@ impl DataFlowGraph {
         }
     }
 
+    /// Get the signature providing the result types of the call instruction `inst`.
+    ///
+    /// Tail calls don't return to the calling function, so they never have any results.
+    fn call_result_signature(&self, inst: Inst) -> Option<SigRef> {
+        if self.insts[inst].opcode().is_tail_call() {
+            None
+        } else {
+            self.call_signature(inst)
+        }
+    }
+
     /// Check if `inst` is a branch.
     pub fn analyze_branch(&self, inst: Inst) -> BranchInfo {
         self.insts[inst].analyze_branch(&self.value_lists)
@@ -636,7 +678,7 @@ impl DataFlowGraph {
         }
 
         // Not a fixed result, try to extract a return type from the call signature.
-        self.call_signature(inst).and_then(|sigref| {
+        self.call_result_signature(inst).and_then(|sigref| {
             self.signatures[sigref]
                 .returns
                 .get(result_idx - fixed_results)
@@ -895,7 +937,7 @@ impl DataFlowGraph {
         reuse: &[Value],
     ) -> usize {
         // Get the call signature if this is a function call.
-        if let Some(sig) = self.call_signature(inst) {
+        if let Some(sig) = self.call_result_signature(inst) {
             assert_eq!(self.insts[inst].opcode().constraints().fixed_results(), 0);
             for res_idx in 0..self.signatures[sig].returns.len() {
                 let ty = self.signatures[sig].returns[res_idx].value_type;
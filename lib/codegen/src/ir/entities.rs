@@ -156,6 +156,24 @@ impl Heap {
     }
 }
 
+/// A reference to a constant in the function's constant pool.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Constant(u32);
+entity_impl!(Constant, "const");
+
+impl Constant {
+    /// Create a new constant reference from its number.
+    ///
+    /// This method is for use by the parser.
+    pub fn with_number(n: u32) -> Option<Self> {
+        if n < u32::MAX {
+            Some(Constant(n))
+        } else {
+            None
+        }
+    }
+}
+
 /// A reference to any of the entities defined in this module.
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub enum AnyEntity {
@@ -179,6 +197,8 @@ pub enum AnyEntity {
     SigRef(SigRef),
     /// A heap.
     Heap(Heap),
+    /// A constant in the constant pool.
+    Constant(Constant),
 }
 
 impl fmt::Display for AnyEntity {
@@ -194,6 +214,7 @@ impl fmt::Display for AnyEntity {
             AnyEntity::FuncRef(r) => r.fmt(f),
             AnyEntity::SigRef(r) => r.fmt(f),
             AnyEntity::Heap(r) => r.fmt(f),
+            AnyEntity::Constant(r) => r.fmt(f),
         }
     }
 }
@@ -258,6 +279,12 @@ impl From<Heap> for AnyEntity {
     }
 }
 
+impl From<Constant> for AnyEntity {
+    fn from(r: Constant) -> Self {
+        AnyEntity::Constant(r)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
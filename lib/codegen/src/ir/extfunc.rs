@@ -30,6 +30,13 @@ pub struct Signature {
     /// Calling convention.
     pub call_conv: CallConv,
 
+    /// For a variadic function, the number of fixed parameters at the front of `params`.
+    ///
+    /// The remaining parameters are the variadic arguments passed at a particular call site, so a
+    /// variadic callee needs a separate signature for each combination of variadic argument types.
+    /// This is `None` for a function that isn't variadic.
+    pub fixed_params: Option<usize>,
+
     /// When the signature has been legalized to a specific ISA, this holds the size of the
     /// argument array on the stack. Before legalization, this is `None`.
     ///
@@ -45,6 +52,7 @@ impl Signature {
             params: Vec::new(),
             returns: Vec::new(),
             call_conv,
+            fixed_params: None,
             argument_bytes: None,
         }
     }
@@ -54,9 +62,15 @@ impl Signature {
         self.params.clear();
         self.returns.clear();
         self.call_conv = call_conv;
+        self.fixed_params = None;
         self.argument_bytes = None;
     }
 
+    /// Is this the signature of a variadic function?
+    pub fn is_variadic(&self) -> bool {
+        self.fixed_params.is_some()
+    }
+
     /// Compute the size of the stack arguments and mark signature as legalized.
     ///
     /// Even if there are no stack arguments, this will set `params` to `Some(0)` instead
@@ -104,7 +118,20 @@ fn write_list(f: &mut fmt::Formatter, args: &[AbiParam], regs: Option<&RegInfo>)
 impl<'a> fmt::Display for DisplaySignature<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "(")?;
-        write_list(f, &self.0.params, self.1)?;
+        match self.0.fixed_params {
+            None => write_list(f, &self.0.params, self.1)?,
+            Some(fixed) => {
+                let (fixed, variadic) = self.0.params.split_at(fixed);
+                write_list(f, fixed, self.1)?;
+                if !fixed.is_empty() {
+                    write!(f, ", ")?;
+                }
+                write!(f, "...")?;
+                for arg in variadic {
+                    write!(f, ", {}", arg.display(self.1))?;
+                }
+            }
+        }
         write!(f, ")")?;
         if !self.0.returns.is_empty() {
             write!(f, " -> ")?;
@@ -292,10 +319,43 @@ pub enum ArgumentPurpose {
     /// This is a special-purpose argument used to identify the calling convention expected by the
     /// caller in an indirect call. The callee can verify that the expected signature ID matches.
     SignatureId,
+
+    /// The number of floating point registers used to pass arguments to a variadic function.
+    ///
+    /// The System V x86-64 ABI requires the caller of a variadic function to pass an upper bound
+    /// on the number of vector registers used in `%al`. This argument is added to call signatures
+    /// by `legalize_signature()`, and the legalizer computes its value at each call site.
+    FloatRegCount,
+
+    /// A copy of the preceding floating point argument, passed in an integer register.
+    ///
+    /// The Windows x64 ABI requires variadic floating point arguments to be passed in both the
+    /// floating point register and the integer register for their argument position. The
+    /// legalizer passes the bits of the preceding argument for this parameter.
+    FloatShadow,
+
+    /// A pointer-sized part of an aggregate passed by value in the stack argument area.
+    ///
+    /// Some C ABIs pass aggregates by copying them to the stack argument area. A front end
+    /// represents such an aggregate as a sequence of `ByVal` parameters holding consecutive
+    /// pointer-sized parts of it. They are always assigned stack locations, never registers. See
+    /// `AggregateClass::ByVal`.
+    ByVal,
 }
 
 /// Text format names of the `ArgumentPurpose` variants.
-static PURPOSE_NAMES: [&str; 7] = ["normal", "sret", "link", "fp", "csr", "vmctx", "sigid"];
+static PURPOSE_NAMES: [&str; 10] = [
+    "normal",
+    "sret",
+    "link",
+    "fp",
+    "csr",
+    "vmctx",
+    "sigid",
+    "fprcount",
+    "shadow",
+    "byval",
+];
 
 impl fmt::Display for ArgumentPurpose {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -314,6 +374,9 @@ impl FromStr for ArgumentPurpose {
             "csr" => Ok(ArgumentPurpose::CalleeSaved),
             "vmctx" => Ok(ArgumentPurpose::VMContext),
             "sigid" => Ok(ArgumentPurpose::SignatureId),
+            "fprcount" => Ok(ArgumentPurpose::FloatRegCount),
+            "shadow" => Ok(ArgumentPurpose::FloatShadow),
+            "byval" => Ok(ArgumentPurpose::ByVal),
             _ => Err(()),
         }
     }
@@ -369,6 +432,10 @@ mod tests {
             ArgumentPurpose::FramePointer,
             ArgumentPurpose::CalleeSaved,
             ArgumentPurpose::VMContext,
+            ArgumentPurpose::SignatureId,
+            ArgumentPurpose::FloatRegCount,
+            ArgumentPurpose::FloatShadow,
+            ArgumentPurpose::ByVal,
         ];
         for (&e, &n) in all_purpose.iter().zip(PURPOSE_NAMES.iter()) {
             assert_eq!(e.to_string(), n);
@@ -420,4 +487,20 @@ mod tests {
             "(i32 [24], i32x4 [8]) -> f32, b8 baldrdash"
         );
     }
+
+    #[test]
+    fn variadic_signatures() {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.fixed_params = Some(0);
+        assert!(sig.is_variadic());
+        assert_eq!(sig.to_string(), "(...) system_v");
+        sig.params.push(AbiParam::new(I32));
+        assert_eq!(sig.to_string(), "(..., i32) system_v");
+        sig.fixed_params = Some(1);
+        assert_eq!(sig.to_string(), "(i32, ...) system_v");
+        sig.params.push(AbiParam::new(F32));
+        assert_eq!(sig.to_string(), "(i32, ..., f32) system_v");
+        sig.clear(CallConv::SystemV);
+        assert!(!sig.is_variadic());
+    }
 }
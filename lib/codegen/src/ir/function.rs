@@ -9,7 +9,8 @@ use ir;
 use ir::{DataFlowGraph, ExternalName, Layout, Signature};
 use ir::{Ebb, ExtFuncData, FuncRef, GlobalVar, GlobalVarData, Heap, HeapData, JumpTable,
          JumpTableData, SigRef, StackSlot, StackSlotData};
-use ir::{EbbOffsets, InstEncodings, JumpTables, SourceLocs, StackSlots, ValueLocations};
+use ir::{EbbOffsets, InstEncodings, JumpTableOffsets, JumpTables, SourceLocs, StackSlots,
+         ValueLocations};
 use isa::{EncInfo, Legalize, TargetIsa, Encoding};
 use settings::CallConv;
 use std::fmt;
@@ -59,6 +60,12 @@ pub struct Function {
     /// in the textual IR format.
     pub offsets: EbbOffsets,
 
+    /// Code offsets of the jump tables.
+    ///
+    /// Like `offsets`, this is computed by `binemit::relax_branches`. The jump tables are placed
+    /// after the function's code.
+    pub jt_offsets: JumpTableOffsets,
+
     /// Source locations.
     ///
     /// Track the original source location for each instruction. The source locations are not
@@ -81,6 +88,7 @@ impl Function {
             encodings: EntityMap::new(),
             locations: EntityMap::new(),
             offsets: EntityMap::new(),
+            jt_offsets: EntityMap::new(),
             srclocs: EntityMap::new(),
         }
     }
@@ -97,6 +105,7 @@ impl Function {
         self.encodings.clear();
         self.locations.clear();
         self.offsets.clear();
+        self.jt_offsets.clear();
         self.srclocs.clear();
     }
 
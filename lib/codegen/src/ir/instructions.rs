@@ -6,6 +6,7 @@
 //! A large part of this module is auto-generated from the instruction descriptions in the meta
 //! directory.
 
+use std::boxed::Box;
 use std::fmt::{self, Display, Formatter};
 use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
@@ -62,6 +63,14 @@ impl Opcode {
     pub fn constraints(self) -> OpcodeConstraints {
         OPCODE_CONSTRAINTS[self as usize - 1]
     }
+
+    /// Is this a tail call which transfers control to the callee without returning?
+    ///
+    /// An `invoke` is a call terminator too, but it branches to its destinations when the callee
+    /// returns.
+    pub fn is_tail_call(self) -> bool {
+        self.is_call() && self.is_terminator() && !self.is_branch()
+    }
 }
 
 // This trait really belongs in lib/reader where it is used by the `.cton` file parser, but since
@@ -195,6 +204,13 @@ impl InstructionData {
                 ..
             } => BranchInfo::SingleDest(destination, &args.as_slice(pool)[2..]),
             InstructionData::BranchTable { table, .. } => BranchInfo::Table(table),
+            InstructionData::Invoke { ref targets, .. } => {
+                BranchInfo::Invoke(targets.destination, targets.landing_pad)
+            }
+            // The landing pad of a `call_lp` doesn't take any arguments.
+            InstructionData::CallLandingPad { landing_pad, .. } => {
+                BranchInfo::SingleDest(landing_pad, &[])
+            }
             _ => {
                 debug_assert!(!self.opcode().is_branch());
                 BranchInfo::NotABranch
@@ -205,7 +221,7 @@ impl InstructionData {
     /// Get the single destination of this branch instruction, if it is a single destination
     /// branch or jump.
     ///
-    /// Multi-destination branches like `br_table` return `None`.
+    /// Multi-destination branches like `br_table` and `invoke` return `None`.
     pub fn branch_destination(&self) -> Option<Ebb> {
         match *self {
             InstructionData::Jump { destination, .. } |
@@ -213,7 +229,9 @@ impl InstructionData {
             InstructionData::BranchInt { destination, .. } |
             InstructionData::BranchFloat { destination, .. } |
             InstructionData::BranchIcmp { destination, .. } => Some(destination),
-            InstructionData::BranchTable { .. } => None,
+            InstructionData::CallLandingPad { landing_pad, .. } => Some(landing_pad),
+            InstructionData::BranchTable { .. } |
+            InstructionData::Invoke { .. } => None,
             _ => {
                 debug_assert!(!self.opcode().is_branch());
                 None
@@ -224,7 +242,7 @@ impl InstructionData {
     /// Get a mutable reference to the single destination of this branch instruction, if it is a
     /// single destination branch or jump.
     ///
-    /// Multi-destination branches like `br_table` return `None`.
+    /// Multi-destination branches like `br_table` and `invoke` return `None`.
     pub fn branch_destination_mut(&mut self) -> Option<&mut Ebb> {
         match *self {
             InstructionData::Jump { ref mut destination, .. } |
@@ -232,7 +250,9 @@ impl InstructionData {
             InstructionData::BranchInt { ref mut destination, .. } |
             InstructionData::BranchFloat { ref mut destination, .. } |
             InstructionData::BranchIcmp { ref mut destination, .. } => Some(destination),
-            InstructionData::BranchTable { .. } => None,
+            InstructionData::CallLandingPad { ref mut landing_pad, .. } => Some(landing_pad),
+            InstructionData::BranchTable { .. } |
+            InstructionData::Invoke { .. } => None,
             _ => {
                 debug_assert!(!self.opcode().is_branch());
                 None
@@ -240,14 +260,27 @@ impl InstructionData {
         }
     }
 
+    /// Get the landing pad of an `invoke` or `call_lp` instruction.
+    pub fn landing_pad(&self) -> Option<Ebb> {
+        match *self {
+            InstructionData::Invoke { ref targets, .. } => Some(targets.landing_pad),
+            InstructionData::CallLandingPad { landing_pad, .. } => Some(landing_pad),
+            _ => None,
+        }
+    }
+
     /// Return information about a call instruction.
     ///
     /// Any instruction that can call another function reveals its call signature here.
     pub fn analyze_call<'a>(&'a self, pool: &'a ValueListPool) -> CallInfo<'a> {
         match *self {
-            InstructionData::Call { func_ref, ref args, .. } => {
+            InstructionData::Call { func_ref, ref args, .. } |
+            InstructionData::CallLandingPad { func_ref, ref args, .. } => {
                 CallInfo::Direct(func_ref, args.as_slice(pool))
             }
+            InstructionData::Invoke { ref targets, ref args, .. } => {
+                CallInfo::Direct(targets.func_ref, args.as_slice(pool))
+            }
             InstructionData::CallIndirect { sig_ref, ref args, .. } => {
                 CallInfo::Indirect(sig_ref, &args.as_slice(pool)[1..])
             }
@@ -259,6 +292,19 @@ impl InstructionData {
     }
 }
 
+/// The callee and destinations of an `invoke` instruction.
+///
+/// These are kept out of line to keep `InstructionData` small.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct InvokeTargets {
+    /// The function to call.
+    pub func_ref: FuncRef,
+    /// Where execution continues when the callee returns normally.
+    pub destination: Ebb,
+    /// Where execution continues when an exception unwinds through the call.
+    pub landing_pad: Ebb,
+}
+
 /// Information about branch and jump instructions.
 pub enum BranchInfo<'a> {
     /// This is not a branch or jump instruction.
@@ -271,6 +317,10 @@ pub enum BranchInfo<'a> {
 
     /// This is a jump table branch which can have many destination EBBs.
     Table(JumpTable),
+
+    /// This is an `invoke` with a normal destination and a landing pad. Neither destination takes
+    /// any arguments.
+    Invoke(Ebb, Ebb),
 }
 
 /// Information about call instructions.
@@ -15,7 +15,7 @@ use std::str::FromStr;
 /// convention in the embedding VM's runtime library.
 ///
 /// This list is likely to grow over time.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum LibCall {
     /// probe for stack overflow. These are emitted for functions which need
     /// when the `probestack_enabled` setting is true.
@@ -36,9 +36,119 @@ pub enum LibCall {
     NearestF32,
     /// nearest.f64
     NearestF64,
+    /// udiv.i64
+    UdivI64,
+    /// sdiv.i64
+    SdivI64,
+    /// urem.i64
+    UremI64,
+    /// srem.i64
+    SremI64,
+    /// libc.memcpy
+    Memcpy,
+    /// libc.memset
+    Memset,
+    /// libc.memmove
+    Memmove,
+    /// fadd.f32
+    AddF32,
+    /// fadd.f64
+    AddF64,
+    /// fsub.f32
+    SubF32,
+    /// fsub.f64
+    SubF64,
+    /// fmul.f32
+    MulF32,
+    /// fmul.f64
+    MulF64,
+    /// fdiv.f32
+    DivF32,
+    /// fdiv.f64
+    DivF64,
+    /// sqrt.f32
+    SqrtF32,
+    /// sqrt.f64
+    SqrtF64,
+    /// fma.f32
+    FmaF32,
+    /// fma.f64
+    FmaF64,
+    /// fmin.f32
+    FminF32,
+    /// fmin.f64
+    FminF64,
+    /// fmax.f32
+    FmaxF32,
+    /// fmax.f64
+    FmaxF64,
+    /// Compare f32, returning zero if equal
+    EqF32,
+    /// Compare f64, returning zero if equal
+    EqF64,
+    /// Compare f32, returning non-zero if unordered or not equal
+    NeF32,
+    /// Compare f64, returning non-zero if unordered or not equal
+    NeF64,
+    /// Compare f32, returning negative if less than
+    LtF32,
+    /// Compare f64, returning negative if less than
+    LtF64,
+    /// Compare f32, returning non-positive if less than or equal
+    LeF32,
+    /// Compare f64, returning non-positive if less than or equal
+    LeF64,
+    /// Compare f32, returning positive if greater than
+    GtF32,
+    /// Compare f64, returning positive if greater than
+    GtF64,
+    /// Compare f32, returning non-negative if greater than or equal
+    GeF32,
+    /// Compare f64, returning non-negative if greater than or equal
+    GeF64,
+    /// Compare f32, returning non-zero if unordered
+    UnordF32,
+    /// Compare f64, returning non-zero if unordered
+    UnordF64,
+    /// fpromote.f64.f32
+    PromoteF32,
+    /// fdemote.f32.f64
+    DemoteF64,
+    /// fcvt_to_sint.i32.f32
+    F32ToI32,
+    /// fcvt_to_sint.i64.f32
+    F32ToI64,
+    /// fcvt_to_sint.i32.f64
+    F64ToI32,
+    /// fcvt_to_sint.i64.f64
+    F64ToI64,
+    /// fcvt_to_uint.i32.f32
+    F32ToU32,
+    /// fcvt_to_uint.i64.f32
+    F32ToU64,
+    /// fcvt_to_uint.i32.f64
+    F64ToU32,
+    /// fcvt_to_uint.i64.f64
+    F64ToU64,
+    /// fcvt_from_sint.f32.i32
+    I32ToF32,
+    /// fcvt_from_sint.f64.i32
+    I32ToF64,
+    /// fcvt_from_sint.f32.i64
+    I64ToF32,
+    /// fcvt_from_sint.f64.i64
+    I64ToF64,
+    /// fcvt_from_uint.f32.i32
+    U32ToF32,
+    /// fcvt_from_uint.f64.i32
+    U32ToF64,
+    /// fcvt_from_uint.f32.i64
+    U64ToF32,
+    /// fcvt_from_uint.f64.i64
+    U64ToF64,
 }
 
-const NAME: [&str; 9] = [
+const NAME: [&str; 64] = [
     "Probestack",
     "CeilF32",
     "CeilF64",
@@ -48,6 +158,61 @@ const NAME: [&str; 9] = [
     "TruncF64",
     "NearestF32",
     "NearestF64",
+    "UdivI64",
+    "SdivI64",
+    "UremI64",
+    "SremI64",
+    "Memcpy",
+    "Memset",
+    "Memmove",
+    "AddF32",
+    "AddF64",
+    "SubF32",
+    "SubF64",
+    "MulF32",
+    "MulF64",
+    "DivF32",
+    "DivF64",
+    "SqrtF32",
+    "SqrtF64",
+    "FmaF32",
+    "FmaF64",
+    "FminF32",
+    "FminF64",
+    "FmaxF32",
+    "FmaxF64",
+    "EqF32",
+    "EqF64",
+    "NeF32",
+    "NeF64",
+    "LtF32",
+    "LtF64",
+    "LeF32",
+    "LeF64",
+    "GtF32",
+    "GtF64",
+    "GeF32",
+    "GeF64",
+    "UnordF32",
+    "UnordF64",
+    "PromoteF32",
+    "DemoteF64",
+    "F32ToI32",
+    "F32ToI64",
+    "F64ToI32",
+    "F64ToI64",
+    "F32ToU32",
+    "F32ToU64",
+    "F64ToU32",
+    "F64ToU64",
+    "I32ToF32",
+    "I32ToF64",
+    "I64ToF32",
+    "I64ToF64",
+    "U32ToF32",
+    "U32ToF64",
+    "U64ToF32",
+    "U64ToF64",
 ];
 
 impl fmt::Display for LibCall {
@@ -70,6 +235,61 @@ impl FromStr for LibCall {
             "TruncF64" => Ok(LibCall::TruncF64),
             "NearestF32" => Ok(LibCall::NearestF32),
             "NearestF64" => Ok(LibCall::NearestF64),
+            "UdivI64" => Ok(LibCall::UdivI64),
+            "SdivI64" => Ok(LibCall::SdivI64),
+            "UremI64" => Ok(LibCall::UremI64),
+            "SremI64" => Ok(LibCall::SremI64),
+            "Memcpy" => Ok(LibCall::Memcpy),
+            "Memset" => Ok(LibCall::Memset),
+            "Memmove" => Ok(LibCall::Memmove),
+            "AddF32" => Ok(LibCall::AddF32),
+            "AddF64" => Ok(LibCall::AddF64),
+            "SubF32" => Ok(LibCall::SubF32),
+            "SubF64" => Ok(LibCall::SubF64),
+            "MulF32" => Ok(LibCall::MulF32),
+            "MulF64" => Ok(LibCall::MulF64),
+            "DivF32" => Ok(LibCall::DivF32),
+            "DivF64" => Ok(LibCall::DivF64),
+            "SqrtF32" => Ok(LibCall::SqrtF32),
+            "SqrtF64" => Ok(LibCall::SqrtF64),
+            "FmaF32" => Ok(LibCall::FmaF32),
+            "FmaF64" => Ok(LibCall::FmaF64),
+            "FminF32" => Ok(LibCall::FminF32),
+            "FminF64" => Ok(LibCall::FminF64),
+            "FmaxF32" => Ok(LibCall::FmaxF32),
+            "FmaxF64" => Ok(LibCall::FmaxF64),
+            "EqF32" => Ok(LibCall::EqF32),
+            "EqF64" => Ok(LibCall::EqF64),
+            "NeF32" => Ok(LibCall::NeF32),
+            "NeF64" => Ok(LibCall::NeF64),
+            "LtF32" => Ok(LibCall::LtF32),
+            "LtF64" => Ok(LibCall::LtF64),
+            "LeF32" => Ok(LibCall::LeF32),
+            "LeF64" => Ok(LibCall::LeF64),
+            "GtF32" => Ok(LibCall::GtF32),
+            "GtF64" => Ok(LibCall::GtF64),
+            "GeF32" => Ok(LibCall::GeF32),
+            "GeF64" => Ok(LibCall::GeF64),
+            "UnordF32" => Ok(LibCall::UnordF32),
+            "UnordF64" => Ok(LibCall::UnordF64),
+            "PromoteF32" => Ok(LibCall::PromoteF32),
+            "DemoteF64" => Ok(LibCall::DemoteF64),
+            "F32ToI32" => Ok(LibCall::F32ToI32),
+            "F32ToI64" => Ok(LibCall::F32ToI64),
+            "F64ToI32" => Ok(LibCall::F64ToI32),
+            "F64ToI64" => Ok(LibCall::F64ToI64),
+            "F32ToU32" => Ok(LibCall::F32ToU32),
+            "F32ToU64" => Ok(LibCall::F32ToU64),
+            "F64ToU32" => Ok(LibCall::F64ToU32),
+            "F64ToU64" => Ok(LibCall::F64ToU64),
+            "I32ToF32" => Ok(LibCall::I32ToF32),
+            "I32ToF64" => Ok(LibCall::I32ToF64),
+            "I64ToF32" => Ok(LibCall::I64ToF32),
+            "I64ToF64" => Ok(LibCall::I64ToF64),
+            "U32ToF32" => Ok(LibCall::U32ToF32),
+            "U32ToF64" => Ok(LibCall::U32ToF64),
+            "U64ToF32" => Ok(LibCall::U64ToF32),
+            "U64ToF64" => Ok(LibCall::U64ToF64),
             _ => Err(()),
         }
     }
@@ -100,6 +320,15 @@ impl LibCall {
                     _ => return None,
                 }
             }
+            types::I64 => {
+                match opcode {
+                    Opcode::Udiv => LibCall::UdivI64,
+                    Opcode::Sdiv => LibCall::SdivI64,
+                    Opcode::Urem => LibCall::UremI64,
+                    Opcode::Srem => LibCall::SremI64,
+                    _ => return None,
+                }
+            }
             _ => return None,
         })
     }
@@ -118,6 +347,24 @@ pub fn get_libcall_funcref(
     find_funcref(libcall, func).unwrap_or_else(|| make_funcref_for_inst(libcall, func, inst, isa))
 }
 
+/// Get a function reference for `libcall` in `func`, taking `params` and returning `returns`.
+///
+/// If there is an existing reference, use it, otherwise make a new one.
+pub fn get_libcall_funcref_for_types(
+    libcall: LibCall,
+    func: &mut Function,
+    params: &[Type],
+    returns: &[Type],
+    isa: &TargetIsa,
+) -> FuncRef {
+    find_funcref(libcall, func).unwrap_or_else(|| {
+        let mut sig = Signature::new(isa.flags().call_conv());
+        sig.params.extend(params.iter().map(|&ty| AbiParam::new(ty)));
+        sig.returns.extend(returns.iter().map(|&ty| AbiParam::new(ty)));
+        make_legalized_funcref(libcall, func, sig, isa)
+    })
+}
+
 /// Get a function reference for the probestack function in `func`.
 ///
 /// If there is an existing reference, use it, otherwise make a new one.
@@ -132,6 +379,36 @@ pub fn get_probestack_funcref(
     })
 }
 
+/// Get a function reference for one of the memory routines `Memcpy`, `Memset` or `Memmove`.
+///
+/// The routines follow the C library signatures, except that the returned pointer is ignored.
+/// The size operand and the pointers have the target's pointer type, and the fill value of
+/// `Memset` is an `i32`.
+///
+/// If there is an existing reference, use it, otherwise make a new one.
+pub fn get_memory_libcall_funcref(
+    libcall: LibCall,
+    func: &mut Function,
+    isa: &TargetIsa,
+) -> FuncRef {
+    find_funcref(libcall, func).unwrap_or_else(|| {
+        let pointer_type = if isa.flags().is_64bit() {
+            types::I64
+        } else {
+            types::I32
+        };
+        let mut sig = Signature::new(isa.flags().call_conv());
+        sig.params.push(AbiParam::new(pointer_type));
+        sig.params.push(AbiParam::new(match libcall {
+            LibCall::Memcpy | LibCall::Memmove => pointer_type,
+            LibCall::Memset => types::I32,
+            _ => panic!("{} is not a memory routine", libcall),
+        }));
+        sig.params.push(AbiParam::new(pointer_type));
+        make_funcref(libcall, func, sig, isa)
+    })
+}
+
 /// Get the existing function reference for `libcall` in `func` if it exists.
 fn find_funcref(libcall: LibCall, func: &Function) -> Option<FuncRef> {
     // We're assuming that all libcall function decls are at the end.
@@ -181,6 +458,21 @@ fn make_funcref_for_inst(
         sig.returns.push(AbiParam::new(func.dfg.value_type(v)));
     }
 
+    make_legalized_funcref(libcall, func, sig, isa)
+}
+
+/// Create a funcref for `libcall` that is inserted by the legalizer.
+///
+/// The existing signatures have already been legalized at that point, so the new signature must
+/// be legalized too.
+fn make_legalized_funcref(
+    libcall: LibCall,
+    func: &mut Function,
+    mut sig: Signature,
+    isa: &TargetIsa,
+) -> FuncRef {
+    isa.legalize_signature(&mut sig, false);
+    sig.compute_argument_bytes();
     make_funcref(libcall, func, sig, isa)
 }
 
@@ -204,10 +496,13 @@ mod test {
     fn display() {
         assert_eq!(LibCall::CeilF32.to_string(), "CeilF32");
         assert_eq!(LibCall::NearestF64.to_string(), "NearestF64");
+        assert_eq!(LibCall::Memmove.to_string(), "Memmove");
     }
 
     #[test]
     fn parsing() {
         assert_eq!("FloorF32".parse(), Ok(LibCall::FloorF32));
+        assert_eq!("Memcpy".parse(), Ok(LibCall::Memcpy));
+        assert_eq!("U64ToF32".parse(), Ok(LibCall::U64ToF32));
     }
 }
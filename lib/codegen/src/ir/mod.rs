@@ -2,6 +2,7 @@
 
 mod builder;
 pub mod condcodes;
+pub mod constant;
 pub mod dfg;
 pub mod entities;
 mod extfunc;
@@ -23,17 +24,21 @@ pub mod types;
 mod valueloc;
 
 pub use ir::builder::{InsertBuilder, InstBuilder, InstBuilderBase, InstInserterBase};
+pub use ir::constant::{ConstantData, ConstantPool};
 pub use ir::dfg::{DataFlowGraph, ValueDef};
-pub use ir::entities::{Ebb, FuncRef, GlobalVar, Heap, Inst, JumpTable, SigRef, StackSlot, Value};
+pub use ir::entities::{Constant, Ebb, FuncRef, GlobalVar, Heap, Inst, JumpTable, SigRef, StackSlot,
+                       Value};
 pub use ir::extfunc::{AbiParam, ArgumentExtension, ArgumentPurpose, ExtFuncData, Signature};
 pub use ir::extname::ExternalName;
 pub use ir::function::Function;
 pub use ir::globalvar::GlobalVarData;
 pub use ir::heap::{HeapBase, HeapData, HeapStyle};
-pub use ir::instructions::{InstructionData, Opcode, ValueList, ValueListPool, VariableArgs};
+pub use ir::instructions::{InstructionData, InvokeTargets, Opcode, ValueList, ValueListPool,
+                           VariableArgs};
 pub use ir::jumptable::JumpTableData;
 pub use ir::layout::Layout;
-pub use ir::libcall::{LibCall, get_libcall_funcref, get_probestack_funcref};
+pub use ir::libcall::{LibCall, get_libcall_funcref, get_libcall_funcref_for_types,
+                      get_memory_libcall_funcref, get_probestack_funcref};
 pub use ir::memflags::MemFlags;
 pub use ir::progpoint::{ExpandedProgramPoint, ProgramOrder, ProgramPoint};
 pub use ir::sourceloc::SourceLoc;
@@ -58,5 +63,8 @@ pub type InstEncodings = EntityMap<Inst, isa::Encoding>;
 /// Code offsets for EBBs.
 pub type EbbOffsets = EntityMap<Ebb, binemit::CodeOffset>;
 
+/// Code offsets for jump tables.
+pub type JumpTableOffsets = EntityMap<JumpTable, binemit::CodeOffset>;
+
 /// Source locations for instructions.
 pub type SourceLocs = EntityMap<Inst, SourceLoc>;
@@ -264,6 +264,19 @@ impl Type {
         usize::from(self.0)
     }
 
+    /// Get the type with the given `index()`, or `None` if `index` doesn't identify a valid type.
+    pub fn from_index(index: usize) -> Option<Self> {
+        if index > usize::from(u8::max_value()) {
+            return None;
+        }
+        let ty = Type(index as u8);
+        match ty.lane_type() {
+            VOID | IFLAGS | FFLAGS => Some(ty),
+            lane if lane.is_bool() || lane.is_int() || lane.is_float() => Some(ty),
+            _ => None,
+        }
+    }
+
     /// True iff:
     ///
     /// 1. `self.lane_count() == other.lane_count()` and
@@ -414,6 +427,15 @@ mod tests {
         assert_eq!(F64.by(8), Some(F64X8));
     }
 
+    #[test]
+    fn from_index() {
+        for &ty in &[VOID, IFLAGS, FFLAGS, B1, I8, I64, F32, I32X4, F64.by(256).unwrap()] {
+            assert_eq!(Type::from_index(ty.index()), Some(ty));
+        }
+        assert_eq!(Type::from_index(0x6f), None);
+        assert_eq!(Type::from_index(0x100), None);
+    }
+
     #[test]
     fn format_scalars() {
         assert_eq!(VOID.to_string(), "void");
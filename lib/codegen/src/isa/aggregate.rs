@@ -0,0 +1,306 @@
+//! Classification of C aggregates passed by value.
+//!
+//! Cretonne signatures only contain scalar and vector parameters, so a front end passing a C
+//! `struct` or `union` by value must decide how it maps to those parameters. The rules depend on
+//! the target's C ABI. A front end describes the memory layout of the aggregate with an
+//! `AggregateLayout` and asks `TargetIsa::classify_aggregate()` for an `AggregateClass` telling it
+//! how to pass the aggregate.
+
+use ir::{AbiParam, ArgumentPurpose, Type};
+use std::cmp;
+use std::vec::Vec;
+
+/// A scalar field in an aggregate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AggregateField {
+    /// Byte offset of the field from the start of the aggregate.
+    pub offset: u32,
+    /// Type of the field.
+    pub value_type: Type,
+}
+
+/// The memory layout of a C aggregate.
+///
+/// Nested structs and arrays are described by flattening them into their scalar fields. The fields
+/// of a union overlap.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AggregateLayout {
+    /// The scalar fields of the aggregate.
+    pub fields: Vec<AggregateField>,
+    /// Size of the aggregate in bytes, including any padding at the end.
+    pub size: u32,
+    /// Alignment of the aggregate in bytes.
+    pub align: u32,
+}
+
+impl AggregateLayout {
+    /// Create an empty layout with the given size and alignment.
+    pub fn new(size: u32, align: u32) -> Self {
+        Self {
+            fields: Vec::new(),
+            size,
+            align,
+        }
+    }
+
+    /// Compute the layout of a C struct with fields of the given types, in order.
+    ///
+    /// Every field is naturally aligned, as C compilers do by default.
+    pub fn c_struct(types: &[Type]) -> Self {
+        let mut layout = Self::new(0, 1);
+        for &ty in types {
+            let bytes = ty.bytes();
+            let offset = align_to(layout.size, bytes);
+            layout.push_field(offset, ty);
+            layout.size = offset + bytes;
+            layout.align = cmp::max(layout.align, bytes);
+        }
+        layout.size = align_to(layout.size, layout.align);
+        layout
+    }
+
+    /// Add a field of type `ty` at `offset` bytes.
+    pub fn push_field(&mut self, offset: u32, ty: Type) {
+        self.fields.push(AggregateField {
+            offset,
+            value_type: ty,
+        });
+    }
+
+    /// Are all the fields naturally aligned?
+    pub fn is_naturally_aligned(&self) -> bool {
+        self.fields.iter().all(
+            |field| field.offset % field.value_type.bytes() == 0,
+        )
+    }
+}
+
+/// Round `x` up to a multiple of `align`.
+fn align_to(x: u32, align: u32) -> u32 {
+    (x + align - 1) / align * align
+}
+
+/// A part of an aggregate which is passed as a single scalar parameter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AggregatePart {
+    /// The parameter to add to the signature.
+    pub param: AbiParam,
+    /// The byte offset in the aggregate where the parameter value is loaded from or stored to.
+    ///
+    /// The parameter type can be larger than the remaining bytes of the aggregate when their number
+    /// isn't a power of two. The extra bytes are ignored by the callee, but they must not be loaded
+    /// from memory past the end of the aggregate.
+    pub offset: u32,
+}
+
+/// How a C aggregate is passed as a function argument or return value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AggregateClass {
+    /// The aggregate is passed as a sequence of scalar parameters.
+    ///
+    /// The parts are listed in the order they should appear in the signature. An empty aggregate
+    /// has no parts.
+    Direct(Vec<AggregatePart>),
+
+    /// The aggregate argument is copied to the stack argument area.
+    ///
+    /// The aggregate is split into pointer-sized integer parts with the `ArgumentPurpose::ByVal`
+    /// purpose, listed in the order they should appear in the signature. These parameters are
+    /// always assigned consecutive stack locations, so the callee sees the same memory layout as
+    /// if the aggregate had been copied there as a whole.
+    ///
+    /// The stack locations are only aligned to the pointer size. Aggregates that require a larger
+    /// alignment in the argument area, such as x86-64 aggregates containing 16-byte aligned
+    /// fields, are not supported.
+    ByVal(Vec<AggregatePart>),
+
+    /// The caller makes a copy of the aggregate argument and passes a pointer to it.
+    ByRef,
+
+    /// The aggregate is returned in memory provided by the caller.
+    ///
+    /// The caller passes a pointer to the memory as an `ArgumentPurpose::StructReturn` argument.
+    StructReturn,
+}
+
+impl AggregateClass {
+    /// Split an aggregate into the pointer-sized `ByVal` parts copied to the stack argument area.
+    pub fn by_val(layout: &AggregateLayout, pointer_type: Type) -> Self {
+        let bytes = pointer_type.bytes();
+        let parts = (0..align_to(layout.size, bytes) / bytes)
+            .map(|i| {
+                AggregatePart {
+                    param: AbiParam::special(pointer_type, ArgumentPurpose::ByVal),
+                    offset: i * bytes,
+                }
+            })
+            .collect();
+        AggregateClass::ByVal(parts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ir::types::{F32, F64, I16, I32, I8};
+
+    #[test]
+    fn c_struct() {
+        let layout = AggregateLayout::c_struct(&[I8, I32, I16]);
+        assert_eq!(layout.size, 12);
+        assert_eq!(layout.align, 4);
+        let offsets: Vec<u32> = layout.fields.iter().map(|f| f.offset).collect();
+        assert_eq!(offsets, [0, 4, 8]);
+        assert!(layout.is_naturally_aligned());
+
+        let layout = AggregateLayout::c_struct(&[F64, F32]);
+        assert_eq!(layout.size, 16);
+        assert_eq!(layout.align, 8);
+
+        let layout = AggregateLayout::c_struct(&[]);
+        assert_eq!(layout.size, 0);
+        assert_eq!(layout.align, 1);
+
+        let mut packed = AggregateLayout::new(5, 1);
+        packed.push_field(0, I8);
+        packed.push_field(1, I32);
+        assert!(!packed.is_naturally_aligned());
+    }
+
+    #[cfg(build_x86)]
+    mod x86 {
+        use super::super::*;
+        use ir::types::{F32, F32X2, F64, I16X4, I32, I32X4, I64, I8};
+        use ir::{AbiParam, ArgumentPurpose};
+        use isa::{self, TargetIsa};
+        use settings::{self, CallConv, Configurable};
+        use std::boxed::Box;
+
+        fn x86(is_64bit: bool) -> Box<TargetIsa> {
+            let mut flags = settings::builder();
+            if is_64bit {
+                flags.enable("is_64bit").unwrap();
+            }
+            isa::lookup("x86")
+                .unwrap()
+                .finish(settings::Flags::new(flags))
+        }
+
+        fn direct(parts: &[(Type, u32)]) -> AggregateClass {
+            AggregateClass::Direct(
+                parts
+                    .iter()
+                    .map(|&(ty, offset)| {
+                        AggregatePart {
+                            param: AbiParam::new(ty),
+                            offset,
+                        }
+                    })
+                    .collect(),
+            )
+        }
+
+        fn by_val(parts: &[(Type, u32)]) -> AggregateClass {
+            AggregateClass::ByVal(
+                parts
+                    .iter()
+                    .map(|&(ty, offset)| {
+                        AggregatePart {
+                            param: AbiParam::special(ty, ArgumentPurpose::ByVal),
+                            offset,
+                        }
+                    })
+                    .collect(),
+            )
+        }
+
+        #[test]
+        fn system_v() {
+            let isa = x86(true);
+            let classify = |types: &[Type], ret| {
+                isa.classify_aggregate(&AggregateLayout::c_struct(types), CallConv::SystemV, ret)
+            };
+
+            assert_eq!(classify(&[], false), direct(&[]));
+            assert_eq!(classify(&[I32, I32], false), direct(&[(I64, 0)]));
+            assert_eq!(classify(&[I8, I32, I8], false), direct(&[(I64, 0), (I8, 8)]));
+            assert_eq!(classify(&[F64, F64], false), direct(&[(F64, 0), (F64, 8)]));
+            assert_eq!(classify(&[F32, F32, F32], false), direct(&[(F64, 0), (F32, 8)]));
+            assert_eq!(classify(&[F32, I32, F64], true), direct(&[(I64, 0), (F64, 8)]));
+
+            // Aggregates larger than 16 bytes are passed in memory.
+            assert_eq!(
+                classify(&[I64, I64, I64], false),
+                by_val(&[(I64, 0), (I64, 8), (I64, 16)])
+            );
+            assert_eq!(classify(&[I64, I64, I64], true), AggregateClass::StructReturn);
+
+            // Unaligned fields force the aggregate into memory.
+            let mut packed = AggregateLayout::new(5, 1);
+            packed.push_field(0, I8);
+            packed.push_field(1, I32);
+            assert_eq!(
+                isa.classify_aggregate(&packed, CallConv::SystemV, false),
+                by_val(&[(I64, 0)])
+            );
+
+            // Vectors up to 8 bytes are passed in SSE registers, but 128-bit vectors are passed in
+            // memory.
+            assert_eq!(classify(&[F32X2, I64], false), direct(&[(F64, 0), (I64, 8)]));
+            let mut mixed = AggregateLayout::new(8, 8);
+            mixed.push_field(0, I16X4);
+            mixed.push_field(0, I64);
+            assert_eq!(
+                isa.classify_aggregate(&mixed, CallConv::SystemV, false),
+                direct(&[(I64, 0)])
+            );
+            assert_eq!(classify(&[I32X4], false), by_val(&[(I64, 0), (I64, 8)]));
+            let mut vector = AggregateLayout::new(16, 16);
+            vector.push_field(0, I32X4);
+            assert_eq!(
+                isa.classify_aggregate(&vector, CallConv::SystemV, true),
+                AggregateClass::StructReturn
+            );
+
+            // A union of a float and an integer is passed in an integer register.
+            let mut union = AggregateLayout::new(8, 8);
+            union.push_field(0, F64);
+            union.push_field(0, I64);
+            assert_eq!(
+                isa.classify_aggregate(&union, CallConv::SystemV, false),
+                direct(&[(I64, 0)])
+            );
+        }
+
+        #[test]
+        fn windows_fastcall() {
+            let isa = x86(true);
+            let classify = |types: &[Type], ret| {
+                isa.classify_aggregate(
+                    &AggregateLayout::c_struct(types),
+                    CallConv::WindowsFastcall,
+                    ret,
+                )
+            };
+
+            assert_eq!(classify(&[F32], false), direct(&[(I32, 0)]));
+            assert_eq!(classify(&[I32, F32], false), direct(&[(I64, 0)]));
+            assert_eq!(classify(&[F32, F32, F32], false), AggregateClass::ByRef);
+            assert_eq!(classify(&[F32, F32, F32], true), AggregateClass::StructReturn);
+        }
+
+        #[test]
+        fn x86_32() {
+            let isa = x86(false);
+            let layout = AggregateLayout::c_struct(&[I32]);
+            assert_eq!(
+                isa.classify_aggregate(&layout, CallConv::SystemV, false),
+                by_val(&[(I32, 0)])
+            );
+            assert_eq!(
+                isa.classify_aggregate(&layout, CallConv::SystemV, true),
+                AggregateClass::StructReturn
+            );
+        }
+    }
+}
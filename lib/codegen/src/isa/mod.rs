@@ -40,6 +40,7 @@
 //! The configured target ISA trait object is a `Box<TargetIsa>` which can be used for multiple
 //! concurrent function compilations.
 
+pub use isa::aggregate::{AggregateClass, AggregateField, AggregateLayout, AggregatePart};
 pub use isa::constraints::{BranchRange, ConstraintKind, OperandConstraint, RecipeConstraints};
 pub use isa::encoding::{EncInfo, Encoding};
 pub use isa::registers::{regs_overlap, RegClass, RegClassIndex, RegInfo, RegUnit};
@@ -69,6 +70,7 @@ mod arm32;
 #[cfg(build_arm64)]
 mod arm64;
 
+mod aggregate;
 mod constraints;
 mod enc_tables;
 mod encoding;
@@ -150,7 +152,10 @@ pub type Legalize = fn(ir::Inst,
 
 /// Methods that are specialized to a target ISA. Implies a Display trait that shows the
 /// shared flags, as well as any isa-specific flags.
-pub trait TargetIsa: fmt::Display {
+///
+/// A `TargetIsa` is immutable once built, so it can be shared by threads compiling functions in
+/// parallel.
+pub trait TargetIsa: fmt::Display + Send + Sync {
     /// Get the name of this ISA.
     fn name(&self) -> &'static str;
 
@@ -232,6 +237,36 @@ pub trait TargetIsa: fmt::Display {
     /// allocation.
     fn legalize_signature(&self, sig: &mut ir::Signature, current: bool);
 
+    /// Classify how a C aggregate with `layout` is passed to or from functions with the calling
+    /// convention `call_conv`.
+    ///
+    /// When `ret` is true, the aggregate is a return value, otherwise it is an argument.
+    ///
+    /// The classification only depends on the aggregate itself. When an ABI passes an aggregate
+    /// in memory because there aren't enough registers left for all its parts, that must be
+    /// handled by the front end.
+    ///
+    /// The default implementation passes all aggregates in memory, which doesn't follow the C ABI
+    /// of any particular target.
+    fn classify_aggregate(
+        &self,
+        layout: &AggregateLayout,
+        call_conv: CallConv,
+        ret: bool,
+    ) -> AggregateClass {
+        let _ = call_conv;
+        if ret {
+            AggregateClass::StructReturn
+        } else {
+            let pointer_type = if self.flags().is_64bit() {
+                ir::types::I64
+            } else {
+                ir::types::I32
+            };
+            AggregateClass::by_val(layout, pointer_type)
+        }
+    }
+
     /// Get the register class that should be used to represent an ABI argument or return value of
     /// type `ty`. This should be the top-level register class that contains the argument
     /// registers.
@@ -67,7 +67,8 @@ impl ArgAssigner for Args {
             }
         }
 
-        if self.regs < self.reg_limit {
+        // Parts of an aggregate passed by value always go in the stack argument area.
+        if self.regs < self.reg_limit && arg.purpose != ArgumentPurpose::ByVal {
             // Assign to a register.
             let reg = if ty.is_float() {
                 FPR.unit(10 + self.regs as usize)
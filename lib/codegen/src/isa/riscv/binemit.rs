@@ -9,6 +9,11 @@ use std::u32;
 
 include!(concat!(env!("OUT_DIR"), "/binemit-riscv.rs"));
 
+/// Encoding bits of the instructions used by multi-instruction recipes.
+const ADDI_BITS: u16 = 0b00100;
+const SLLI_BITS: u16 = 0b00100 | (0b001 << 5);
+const ADD_BITS: u16 = 0b01100;
+
 /// R-type instructions.
 ///
 ///   31     24  19  14     11 6
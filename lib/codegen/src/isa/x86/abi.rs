@@ -7,14 +7,16 @@ use ir;
 use ir::immediates::Imm64;
 use ir::stackslot::{StackOffset, StackSize};
 use ir::{AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose, InstBuilder, ValueLoc,
-         get_probestack_funcref};
-use isa::{RegClass, RegUnit, TargetIsa};
-use regalloc::RegisterSet;
+         get_probestack_funcref, types};
+use isa::{AggregateClass, AggregateLayout, AggregatePart, RegClass, RegUnit, TargetIsa};
+use regalloc::{RegDiversions, RegisterSet};
 use result;
 use settings as shared_settings;
 use settings::CallConv;
 use stack_layout::layout_stack;
+use std::cmp;
 use std::i32;
+use std::vec::Vec;
 
 /// Argument registers for x86-64
 static ARG_GPRS: [RU; 6] = [RU::rdi, RU::rsi, RU::rdx, RU::rcx, RU::r8, RU::r9];
@@ -38,6 +40,9 @@ struct Args {
     fpr_used: usize,
     offset: u32,
     call_conv: CallConv,
+    /// Are the argument registers assigned by position, so an argument uses up both an integer
+    /// and a floating point register? This is how the Windows x64 ABI works.
+    positional: bool,
 }
 
 impl Args {
@@ -60,6 +65,7 @@ impl Args {
             fpr_used: 0,
             offset,
             call_conv,
+            positional: false,
         }
     }
 }
@@ -105,17 +111,26 @@ impl ArgAssigner for Args {
             }
         }
 
+        // Parts of an aggregate passed by value always go in the stack argument area.
+        let in_reg = arg.purpose != ArgumentPurpose::ByVal;
+
         // Try to use a GPR.
-        if !ty.is_float() && self.gpr_used < self.gpr.len() {
+        if in_reg && !ty.is_float() && self.gpr_used < self.gpr.len() {
             let reg = self.gpr[self.gpr_used] as RegUnit;
             self.gpr_used += 1;
+            if self.positional {
+                self.fpr_used = self.gpr_used;
+            }
             return ArgumentLoc::Reg(reg).into();
         }
 
         // Try to use an FPR.
-        if ty.is_float() && self.fpr_used < self.fpr_limit {
+        if in_reg && ty.is_float() && self.fpr_used < self.fpr_limit {
             let reg = FPR.unit(self.fpr_used);
             self.fpr_used += 1;
+            if self.positional {
+                self.gpr_used = self.fpr_used;
+            }
             return ArgumentLoc::Reg(reg).into();
         }
 
@@ -128,14 +143,16 @@ impl ArgAssigner for Args {
 }
 
 /// Legalize `sig`.
-pub fn legalize_signature(sig: &mut ir::Signature, flags: &shared_settings::Flags, _current: bool) {
+pub fn legalize_signature(sig: &mut ir::Signature, flags: &shared_settings::Flags, current: bool) {
     let bits;
     let mut args;
 
     if flags.is_64bit() {
         bits = 64;
         args = if sig.call_conv == CallConv::WindowsFastcall {
-            Args::new(bits, &ARG_GPRS_WIN_FASTCALL_X64[..], 4, sig.call_conv)
+            let mut args = Args::new(bits, &ARG_GPRS_WIN_FASTCALL_X64[..], 4, sig.call_conv);
+            args.positional = true;
+            args
         } else {
             Args::new(bits, &ARG_GPRS[..], 8, sig.call_conv)
         };
@@ -144,7 +161,21 @@ pub fn legalize_signature(sig: &mut ir::Signature, flags: &shared_settings::Flag
         args = Args::new(bits, &[], 0, sig.call_conv);
     }
 
-    legalize_args(&mut sig.params, &mut args);
+    match sig.fixed_params {
+        None => legalize_args(&mut sig.params, &mut args),
+        Some(fixed) => {
+            // Legalizing the fixed parameters can change their number, so legalize the variadic
+            // parameters separately.
+            let mut varargs = sig.params.split_off(fixed);
+            legalize_args(&mut sig.params, &mut args);
+            sig.fixed_params = Some(sig.params.len());
+            legalize_args(&mut varargs, &mut args);
+            sig.params.extend(varargs);
+            if !current {
+                legalize_variadic_call(sig, bits);
+            }
+        }
+    }
 
     let regs = if sig.call_conv == CallConv::WindowsFastcall {
         &RET_GPRS_WIN_FASTCALL_X64[..]
@@ -156,6 +187,134 @@ pub fn legalize_signature(sig: &mut ir::Signature, flags: &shared_settings::Flag
     legalize_args(&mut sig.returns, &mut rets);
 }
 
+/// Add the special parameters needed to call the variadic function with signature `sig`.
+///
+/// The values for these parameters are computed at each call site by the legalizer.
+fn legalize_variadic_call(sig: &mut ir::Signature, bits: u16) {
+    // The signature may have been legalized before.
+    let is_special = |arg: &AbiParam| match arg.purpose {
+        ArgumentPurpose::FloatRegCount | ArgumentPurpose::FloatShadow => true,
+        _ => false,
+    };
+    if sig.params.iter().any(is_special) {
+        return;
+    }
+
+    if sig.call_conv == CallConv::WindowsFastcall {
+        // Variadic floating point arguments are also passed in the integer register for their
+        // position, so the callee can spill all the argument registers to the home area.
+        let fixed = sig.fixed_params.unwrap_or(0);
+        let mut argno = fixed;
+        while argno < sig.params.len() {
+            let arg = sig.params[argno];
+            argno += 1;
+            if let ArgumentLoc::Reg(reg) = arg.location {
+                if arg.value_type.is_float() {
+                    let gpr = ARG_GPRS_WIN_FASTCALL_X64[(reg - FPR.first) as usize];
+                    let shadow = AbiParam::special_reg(
+                        ir::Type::int(arg.value_type.bits()).unwrap(),
+                        ArgumentPurpose::FloatShadow,
+                        gpr as RegUnit,
+                    );
+                    sig.params.insert(argno, shadow);
+                    argno += 1;
+                }
+            }
+        }
+    } else if bits == 64 {
+        // The System V ABI passes an upper bound on the number of vector registers used in `%al`.
+        sig.params.push(AbiParam::special_reg(
+            types::I32,
+            ArgumentPurpose::FloatRegCount,
+            RU::rax as RegUnit,
+        ));
+    }
+}
+
+/// Classify how a C aggregate is passed according to the x86 C ABIs.
+pub fn classify_aggregate(
+    layout: &AggregateLayout,
+    flags: &shared_settings::Flags,
+    call_conv: CallConv,
+    ret: bool,
+) -> AggregateClass {
+    let in_memory = if ret {
+        AggregateClass::StructReturn
+    } else {
+        let pointer_type = if flags.is_64bit() {
+            types::I64
+        } else {
+            types::I32
+        };
+        AggregateClass::by_val(layout, pointer_type)
+    };
+
+    if !flags.is_64bit() {
+        // The i386 System V ABI passes and returns all aggregates in memory.
+        return in_memory;
+    }
+
+    if call_conv == CallConv::WindowsFastcall {
+        // Aggregates of 1, 2, 4, or 8 bytes are passed as integers of the same size. All others
+        // are passed by reference and returned in memory.
+        return match layout.size {
+            1 | 2 | 4 | 8 => AggregateClass::Direct(vec![
+                AggregatePart {
+                    param: AbiParam::new(ir::Type::int(layout.size as u16 * 8).unwrap()),
+                    offset: 0,
+                },
+            ]),
+            _ if ret => AggregateClass::StructReturn,
+            _ => AggregateClass::ByRef,
+        };
+    }
+
+    // System V x86-64: Aggregates larger than two eightbytes or with unaligned fields are passed
+    // in memory.
+    if layout.size > 16 || !layout.is_naturally_aligned() {
+        return in_memory;
+    }
+
+    // A 128-bit vector field should be passed in a single SSE register, but Cretonne splits vector
+    // arguments into their lanes. Pass such aggregates in memory instead, which doesn't follow the
+    // C ABI.
+    if layout.fields.iter().any(|field| field.value_type.bytes() > 8) {
+        return in_memory;
+    }
+
+    // Classify each eightbyte by the fields it contains. An eightbyte containing only floating
+    // point and vector fields is passed in an SSE register, otherwise it is passed in a GPR.
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while start < layout.size {
+        let end = start + 8;
+        let mut is_sse = true;
+        let mut used = 0;
+        for field in &layout.fields {
+            if field.offset >= start && field.offset < end {
+                is_sse &= field.value_type.is_float() || field.value_type.is_vector();
+                used = cmp::max(used, field.offset + field.value_type.bytes() - start);
+            }
+        }
+        // An eightbyte containing only padding is passed in a GPR.
+        if used == 0 {
+            is_sse = false;
+            used = cmp::min(8, layout.size - start);
+        }
+        let ty = if is_sse {
+            if used <= 4 { types::F32 } else { types::F64 }
+        } else {
+            ir::Type::int((used.next_power_of_two() * 8) as u16).unwrap()
+        };
+        parts.push(AggregatePart {
+            param: AbiParam::new(ty),
+            offset: start,
+        });
+        start = end;
+    }
+    AggregateClass::Direct(parts)
+}
+
 /// Get register class for a type appearing in a legalized signature.
 pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
     if ty.is_int() || ty.is_bool() {
@@ -492,7 +651,7 @@ fn insert_common_prologue(
     }
 }
 
-/// Find all `return` instructions and insert epilogues before them.
+/// Find all `return` and tail call instructions and insert epilogues before them.
 fn insert_common_epilogues(
     pos: &mut EncCursor,
     stack_size: i64,
@@ -502,14 +661,15 @@ fn insert_common_epilogues(
     while let Some(ebb) = pos.next_ebb() {
         pos.goto_last_inst(ebb);
         if let Some(inst) = pos.current_inst() {
-            if pos.func.dfg[inst].opcode().is_return() {
+            let opcode = pos.func.dfg[inst].opcode();
+            if opcode.is_return() || opcode.is_tail_call() {
                 insert_common_epilogue(inst, stack_size, pos, reg_type, csrs);
             }
         }
     }
 }
 
-/// Insert an epilogue given a specific `return` instruction.
+/// Insert an epilogue given a specific `return` or tail call instruction.
 /// This is used by common calling conventions such as System V.
 fn insert_common_epilogue(
     inst: ir::Inst,
@@ -518,6 +678,13 @@ fn insert_common_epilogue(
     reg_type: ir::types::Type,
     csrs: &RegisterSet,
 ) {
+    // The values popped by the epilogue are passed to a `return` to keep them live. A tail call
+    // passes its own arguments instead, and the callee restores the registers when it returns.
+    let is_return = pos.func.dfg[inst].opcode().is_return();
+    if !is_return {
+        move_tail_callee(inst, pos, csrs);
+    }
+
     if stack_size > 0 {
         pos.ins().adjust_sp_up_imm(Imm64::new(stack_size));
     }
@@ -528,13 +695,47 @@ fn insert_common_epilogue(
     pos.prev_inst();
 
     pos.func.locations[fp_ret] = ir::ValueLoc::Reg(RU::rbp as RegUnit);
-    pos.func.dfg.append_inst_arg(inst, fp_ret);
+    if is_return {
+        pos.func.dfg.append_inst_arg(inst, fp_ret);
+    }
 
     for reg in csrs.iter(GPR) {
         let csr_ret = pos.ins().x86_pop(reg_type);
         pos.prev_inst();
 
         pos.func.locations[csr_ret] = ir::ValueLoc::Reg(reg);
-        pos.func.dfg.append_inst_arg(inst, csr_ret);
+        if is_return {
+            pos.func.dfg.append_inst_arg(inst, csr_ret);
+        }
+    }
+}
+
+/// Make sure the callee of an indirect tail call survives the epilogue.
+///
+/// The register allocator may have placed the callee address in a callee-saved register which is
+/// about to be restored. Move it to a scratch register that isn't used for arguments instead.
+fn move_tail_callee(inst: ir::Inst, pos: &mut EncCursor, csrs: &RegisterSet) {
+    let callee = match pos.func.dfg[inst] {
+        ir::InstructionData::CallIndirect { ref args, .. } => {
+            args.first(&pos.func.dfg.value_lists).unwrap()
+        }
+        _ => return,
+    };
+
+    // Find the current register of the callee, taking diversions in the EBB into account.
+    let ebb = pos.func.layout.inst_ebb(inst).unwrap();
+    let mut divert = RegDiversions::new();
+    for i in pos.func.layout.ebb_insts(ebb).take_while(|&i| i != inst) {
+        divert.apply(&pos.func.dfg[i]);
+    }
+    let reg = divert.reg(callee, &pos.func.locations);
+
+    if csrs.iter(GPR).any(|csr| csr == reg) {
+        let scratch = if pos.func.dfg.value_type(callee).bits() == 64 {
+            RU::r11
+        } else {
+            RU::rcx
+        };
+        pos.ins().regmove(callee, reg, scratch as RegUnit);
     }
 }
@@ -3,7 +3,7 @@
 use super::registers::RU;
 use binemit::{bad_encoding, CodeSink, Reloc};
 use ir::condcodes::{CondCode, FloatCC, IntCC};
-use ir::{Ebb, Function, Inst, InstructionData, Opcode, TrapCode};
+use ir::{Constant, Ebb, Function, Inst, InstructionData, JumpTable, Opcode, TrapCode};
 use isa::{RegUnit, StackBase, StackBaseMask, StackRef};
 use regalloc::RegDiversions;
 
@@ -332,3 +332,21 @@ fn disp4<CS: CodeSink + ?Sized>(destination: Ebb, func: &Function, sink: &mut CS
     let delta = func.offsets[destination].wrapping_sub(sink.offset() + 4);
     sink.put4(delta);
 }
+
+/// Emit a four-byte rip-relative displacement to `constant` in the constant pool.
+///
+/// The displacement must be the last field of the instruction.
+fn const_disp4<CS: CodeSink + ?Sized>(constant: Constant, func: &Function, sink: &mut CS) {
+    let delta = func.dfg.constants.offset(constant).wrapping_sub(
+        sink.offset() + 4,
+    );
+    sink.put4(delta);
+}
+
+/// Emit a four-byte rip-relative displacement to the jump table `jt`.
+///
+/// The displacement must be the last field of the instruction.
+fn jt_disp4<CS: CodeSink + ?Sized>(jt: JumpTable, func: &Function, sink: &mut CS) {
+    let delta = func.jt_offsets[jt].wrapping_sub(sink.offset() + 4);
+    sink.put4(delta);
+}
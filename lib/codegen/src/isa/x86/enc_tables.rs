@@ -10,11 +10,69 @@ use isa;
 use isa::constraints::*;
 use isa::enc_tables::*;
 use isa::encoding::RecipeSizing;
+use legalizer;
 use predicates;
+use std::vec::Vec;
 
 include!(concat!(env!("OUT_DIR"), "/encoding-x86.rs"));
 include!(concat!(env!("OUT_DIR"), "/legalize-x86.rs"));
 
+/// Expand `br_table` instructions.
+///
+/// Jump tables are addressed relative to `%rip`, so this uses conditional branches instead in
+/// 32-bit mode.
+fn expand_br_table(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    isa: &isa::TargetIsa,
+) {
+    if isa.flags().is_64bit() {
+        legalizer::expand_br_table(inst, func, cfg, isa);
+    } else {
+        legalizer::expand_br_table_conds(inst, func, cfg, isa);
+    }
+}
+
+/// Expand `f32const` and `f64const` instructions as loads from the constant pool.
+///
+/// The constant pool is addressed relative to `%rip`, so this is only possible in 64-bit mode.
+/// In 32-bit mode, fall back to the generic expansion.
+fn expand_fconst(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    isa: &isa::TargetIsa,
+) {
+    if !isa.flags().is_64bit() {
+        return legalizer::expand_fconst(inst, func, cfg, isa);
+    }
+
+    let bits = match func.dfg[inst] {
+        ir::InstructionData::UnaryIeee32 {
+            opcode: ir::Opcode::F32const,
+            imm,
+        } => u64::from(imm.bits()),
+        ir::InstructionData::UnaryIeee64 {
+            opcode: ir::Opcode::F64const,
+            imm,
+        } => imm.bits(),
+        _ => panic!("Expected fconst: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.value_type(func.dfg.first_result(inst));
+    let bytes: Vec<u8> = (0..ty.bytes()).map(|i| (bits >> (8 * i)) as u8).collect();
+    let constant = func.dfg.constants.insert(bytes.into());
+
+    let mut flags = ir::MemFlags::new();
+    flags.set_notrap();
+    flags.set_aligned();
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let addr = pos.ins().const_addr(ir::types::I64, constant);
+    pos.func.dfg.replace(inst).load(ty, flags, addr, 0);
+}
+
 /// Expand the `sdiv` and `srem` instructions using `x86_sdivmodx`.
 fn expand_sdivrem(
     inst: ir::Inst,
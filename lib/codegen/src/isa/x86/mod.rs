@@ -11,7 +11,7 @@ use binemit::{emit_function, CodeSink, MemoryCodeSink};
 use ir;
 use isa::Builder as IsaBuilder;
 use isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
-use isa::{EncInfo, RegClass, RegInfo, TargetIsa};
+use isa::{AggregateClass, AggregateLayout, EncInfo, RegClass, RegInfo, TargetIsa};
 use regalloc;
 use result;
 use std::boxed::Box;
@@ -98,6 +98,15 @@ impl TargetIsa for Isa {
         abi::legalize_signature(sig, &self.shared_flags, current)
     }
 
+    fn classify_aggregate(
+        &self,
+        layout: &AggregateLayout,
+        call_conv: shared_settings::CallConv,
+        ret: bool,
+    ) -> AggregateClass {
+        abi::classify_aggregate(layout, &self.shared_flags, call_conv, ret)
+    }
+
     fn regclass_for_abi_type(&self, ty: ir::Type) -> RegClass {
         abi::regclass_for_abi_type(ty)
     }
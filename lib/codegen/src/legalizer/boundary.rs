@@ -21,8 +21,9 @@ use abi::{legalize_abi_value, ValueConversion};
 use cursor::{Cursor, FuncCursor};
 use flowgraph::ControlFlowGraph;
 use ir::instructions::CallInfo;
+use ir::stackslot::StackOffset;
 use ir::{AbiParam, ArgumentLoc, ArgumentPurpose, DataFlowGraph, Ebb, Function, Inst, InstBuilder,
-         SigRef, Signature, Type, Value, ValueLoc};
+         SigRef, Signature, StackSlotData, StackSlotKind, Type, Value, ValueLoc};
 use isa::TargetIsa;
 use legalizer::split::{isplit, vsplit};
 use std::vec::Vec;
@@ -83,6 +84,7 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
             pos.func.dfg.attach_ebb_param(entry, arg);
             match abi_type.purpose {
                 ArgumentPurpose::Normal => {}
+                ArgumentPurpose::ByVal => {}
                 ArgumentPurpose::FramePointer => {}
                 ArgumentPurpose::CalleeSaved => {}
                 ArgumentPurpose::StructReturn => {
@@ -128,7 +130,8 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
     for &arg in &pos.func.signature.params[abi_arg..] {
         match arg.purpose {
             // Any normal parameters should have been processed above.
-            ArgumentPurpose::Normal => {
+            ArgumentPurpose::Normal |
+            ArgumentPurpose::ByVal => {
                 panic!("Leftover arg: {}", arg);
             }
             // The callee-save parameters should not appear until after register allocation is
@@ -154,6 +157,11 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
                 debug_assert!(!has_sigid, "Multiple sigid parameters found");
                 has_sigid = true;
             }
+            // These are only added to the signatures of variadic callees.
+            ArgumentPurpose::FloatRegCount |
+            ArgumentPurpose::FloatShadow => {
+                panic!("Unexpected variadic call arg {}", arg);
+            }
         }
 
         // Just create entry block values to match here. We will use them in `handle_return_abi()`
@@ -377,8 +385,10 @@ fn check_call_signature(dfg: &DataFlowGraph, inst: Inst) -> Result<(), SigRef> {
     };
     let sig = &dfg.signatures[sig_ref];
 
+    // Tail calls don't have any results.
     if check_arg_types(dfg, args, &sig.params[..]) &&
-        check_arg_types(dfg, dfg.inst_results(inst), &sig.returns[..])
+        (dfg[inst].opcode().is_tail_call() ||
+             check_arg_types(dfg, dfg.inst_results(inst), &sig.returns[..]))
     {
         // All types check out.
         Ok(())
@@ -501,12 +511,29 @@ pub fn handle_call_abi(mut inst: Inst, func: &mut Function, cfg: &ControlFlowGra
     };
 
     // OK, we need to fix the call arguments to match the ABI signature.
-    let abi_args = pos.func.dfg.signatures[sig_ref].params.len();
+    // The special parameters of a variadic call are computed from the other arguments afterwards.
+    let abi_args = pos.func.dfg.signatures[sig_ref]
+        .params
+        .iter()
+        .filter(|&arg| !is_variadic_call_param(arg))
+        .count();
     legalize_inst_arguments(pos, cfg, abi_args, |func, abi_arg| {
-        func.dfg.signatures[sig_ref].params[abi_arg]
+        *func.dfg.signatures[sig_ref]
+            .params
+            .iter()
+            .filter(|&arg| !is_variadic_call_param(arg))
+            .nth(abi_arg)
+            .unwrap()
     });
+    if abi_args != pos.func.dfg.signatures[sig_ref].params.len() {
+        pos.goto_inst(inst);
+        insert_variadic_call_args(pos, inst, sig_ref);
+    }
 
-    if !pos.func.dfg.signatures[sig_ref].returns.is_empty() {
+    // Tail calls don't have any results to convert.
+    if !pos.func.dfg[inst].opcode().is_tail_call() &&
+        !pos.func.dfg.signatures[sig_ref].returns.is_empty()
+    {
         inst = legalize_inst_results(pos, |func, abi_res| {
             func.dfg.signatures[sig_ref].returns[abi_res]
         });
@@ -528,6 +555,57 @@ pub fn handle_call_abi(mut inst: Inst, func: &mut Function, cfg: &ControlFlowGra
     true
 }
 
+/// Is `arg` one of the special parameters added to the signature of a variadic callee?
+fn is_variadic_call_param(arg: &AbiParam) -> bool {
+    match arg.purpose {
+        ArgumentPurpose::FloatRegCount |
+        ArgumentPurpose::FloatShadow => true,
+        _ => false,
+    }
+}
+
+/// Insert the values of the special parameters of the variadic call at `pos`.
+///
+/// The other arguments of the call instruction `inst` must already match the signature.
+fn insert_variadic_call_args(pos: &mut FuncCursor, inst: Inst, sig_ref: SigRef) {
+    let params = pos.func.dfg.signatures[sig_ref].params.clone();
+    let fixed_values = pos.func.dfg[inst]
+        .opcode()
+        .constraints()
+        .fixed_value_arguments();
+    let mut vlist = pos.func.dfg[inst].take_value_list().expect(
+        "Call must have a value list",
+    );
+    let old_args = vlist.as_slice(&pos.func.dfg.value_lists).to_vec();
+    let (fixed, mut call_args) = (&old_args[..fixed_values], old_args[fixed_values..].iter());
+
+    let mut args = Vec::with_capacity(params.len());
+    for param in &params {
+        let arg = match param.purpose {
+            ArgumentPurpose::FloatRegCount => {
+                let count = params
+                    .iter()
+                    .filter(|arg| arg.value_type.is_float() && arg.location.is_reg())
+                    .count();
+                pos.ins().iconst(param.value_type, count as i64)
+            }
+            ArgumentPurpose::FloatShadow => {
+                let float = *args.last().expect("Shadow argument without a float argument");
+                pos.ins().bitcast(param.value_type, float)
+            }
+            _ => *call_args.next().expect("Missing call argument"),
+        };
+        args.push(arg);
+    }
+
+    vlist.clear(&mut pos.func.dfg.value_lists);
+    vlist.extend(
+        fixed.iter().chain(args.iter()).cloned(),
+        &mut pos.func.dfg.value_lists,
+    );
+    pos.func.dfg[inst].put_value_list(vlist);
+}
+
 /// Insert ABI conversion code before and after the return instruction at `inst`.
 ///
 /// Return `true` if any instructions were inserted.
@@ -633,6 +711,9 @@ fn spill_call_arguments(pos: &mut FuncCursor) -> bool {
     let inst = pos.current_inst().expect(
         "Cursor must point to a call instruction",
     );
+    if pos.func.dfg[inst].opcode().is_tail_call() {
+        return spill_tail_call_arguments(pos);
+    }
     let sig_ref = pos.func.dfg.call_signature(inst).expect(
         "Call instruction expected.",
     );
@@ -681,3 +762,79 @@ fn spill_call_arguments(pos: &mut FuncCursor) -> bool {
     // We changed stuff.
     true
 }
+
+/// Assign stack slots to the stack arguments of a tail call.
+///
+/// A tail call reuses the stack frame of the current function, so its stack arguments overwrite
+/// our own incoming arguments in the `IncomingArg` stack slots at the same offsets. Since the
+/// arguments to the tail call may themselves be read from those slots, they are all copied before
+/// any of the slots are written.
+fn spill_tail_call_arguments(pos: &mut FuncCursor) -> bool {
+    let inst = pos.current_inst().expect(
+        "Cursor must point to a call instruction",
+    );
+    let sig_ref = pos.func.dfg.call_signature(inst).expect(
+        "Call instruction expected.",
+    );
+
+    // Collect the stack arguments that aren't already in the right incoming argument slot. The
+    // legalization needs to be idempotent, so we should see correct slots on the second pass.
+    let arglist = {
+        let locations = &pos.func.locations;
+        let stack_slots = &pos.func.stack_slots;
+        pos.func
+            .dfg
+            .inst_variable_args(inst)
+            .iter()
+            .zip(&pos.func.dfg.signatures[sig_ref].params)
+            .enumerate()
+            .filter_map(|(idx, (&arg, abi))| match abi.location {
+                ArgumentLoc::Stack(offset) => {
+                    let in_place = match locations[arg] {
+                        ValueLoc::Stack(ss) => {
+                            is_incoming_arg(&stack_slots[ss], abi.value_type, offset)
+                        }
+                        _ => false,
+                    };
+                    if in_place {
+                        None
+                    } else {
+                        Some((idx, arg, abi.value_type, offset))
+                    }
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    if arglist.is_empty() {
+        return false;
+    }
+
+    let copies = arglist
+        .iter()
+        .map(|&(_, arg, _, _)| pos.ins().copy(arg))
+        .collect::<Vec<_>>();
+
+    for (&(idx, _, ty, offset), &copy) in arglist.iter().zip(&copies) {
+        let existing = pos.func.stack_slots.keys().find(|&ss| {
+            is_incoming_arg(&pos.func.stack_slots[ss], ty, offset)
+        });
+        let ss = match existing {
+            Some(ss) => ss,
+            None => pos.func.stack_slots.make_incoming_arg(ty, offset),
+        };
+        let stack_val = pos.ins().spill(copy);
+        pos.func.locations[stack_val] = ValueLoc::Stack(ss);
+        pos.func.dfg.inst_variable_args_mut(inst)[idx] = stack_val;
+    }
+
+    // We changed stuff.
+    true
+}
+
+/// Is `slot` the incoming argument slot for a `ty` value at `offset`?
+fn is_incoming_arg(slot: &StackSlotData, ty: Type, offset: StackOffset) -> bool {
+    slot.kind == StackSlotKind::IncomingArg && slot.offset == Some(offset) &&
+        slot.size == ty.bytes()
+}
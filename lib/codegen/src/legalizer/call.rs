@@ -1,14 +1,61 @@
 //! Legalization of calls.
 //!
-//! This module exports the `expand_call` function which transforms a `call`
-//! instruction into `func_addr` and `call_indirect` instructions.
+//! This module exports the `expand_call` function which transforms a `call` or `return_call`
+//! instruction into `func_addr` and `call_indirect` or `return_call_indirect` instructions, and
+//! the `expand_invoke` function which splits an `invoke` terminator into a `call_lp` followed by a
+//! `jump` to the normal destination.
 
 use cursor::{Cursor, FuncCursor};
 use flowgraph::ControlFlowGraph;
 use ir::{self, InstBuilder};
 use isa::TargetIsa;
 
-/// Expand a `call` instruction.
+/// Expand an `invoke` instruction into a `call_lp` and a `jump` to the normal destination.
+///
+/// The `call_lp` is an ordinary call in the middle of the EBB, so its arguments and results can be
+/// legalized for the ABI like any other call.
+pub fn expand_invoke(inst: ir::Inst, func: &mut ir::Function, cfg: &mut ControlFlowGraph) {
+    let (func_ref, destination, landing_pad, args) = match func.dfg[inst] {
+        ir::InstructionData::Invoke {
+            ref targets,
+            ref args,
+            ..
+        } => (
+            targets.func_ref,
+            targets.destination,
+            targets.landing_pad,
+            args.clone(),
+        ),
+        _ => panic!("Wanted invoke: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    // Split the `invoke`:
+    //
+    //     v1 = invoke fn0(v0), ebb1, ebb2
+    //
+    // Becomes:
+    //
+    //     v1 = call_lp fn0(v0), ebb2
+    //     jump ebb1
+    //
+    func.dfg.replace(inst).CallLandingPad(
+        ir::Opcode::CallLp,
+        ir::types::VOID,
+        func_ref,
+        landing_pad,
+        args,
+    );
+
+    let ebb = {
+        let mut pos = FuncCursor::new(func).after_inst(inst);
+        pos.use_srcloc(inst);
+        pos.ins().jump(destination, &[]);
+        pos.current_ebb().unwrap()
+    };
+    cfg.recompute_ebb(func, ebb);
+}
+
+/// Expand a `call` or `return_call` instruction.
 pub fn expand_call(
     inst: ir::Inst,
     func: &mut ir::Function,
@@ -16,14 +63,18 @@ pub fn expand_call(
     isa: &TargetIsa,
 ) {
     // Unpack the instruction.
-    let (func_ref, old_args) = match func.dfg[inst] {
+    let (indirect_opcode, func_ref, old_args) = match func.dfg[inst] {
         ir::InstructionData::Call {
             opcode,
             ref args,
             func_ref,
         } => {
-            debug_assert_eq!(opcode, ir::Opcode::Call);
-            (func_ref, args.clone())
+            let indirect_opcode = match opcode {
+                ir::Opcode::Call => ir::Opcode::CallIndirect,
+                ir::Opcode::ReturnCall => ir::Opcode::ReturnCallIndirect,
+                _ => panic!("Wanted call: {}", func.dfg.display_inst(inst, None)),
+            };
+            (indirect_opcode, func_ref, args.clone())
         }
         _ => panic!("Wanted call: {}", func.dfg.display_inst(inst, None)),
     };
@@ -52,7 +103,7 @@ pub fn expand_call(
     }
 
     func.dfg.replace(inst).CallIndirect(
-        ir::Opcode::CallIndirect,
+        indirect_opcode,
         ptr_ty,
         sig,
         new_args,
@@ -14,8 +14,10 @@ pub fn expand_heap_addr(
     inst: ir::Inst,
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
-    _isa: &TargetIsa,
+    isa: &TargetIsa,
 ) {
+    let spectre = isa.flags().enable_heap_access_spectre_mitigation();
+
     // Unpack the instruction.
     let (heap, offset, size) = match func.dfg[inst] {
         ir::InstructionData::HeapAddr {
@@ -32,10 +34,10 @@ pub fn expand_heap_addr(
 
     match func.heaps[heap].style {
         ir::HeapStyle::Dynamic { bound_gv } => {
-            dynamic_addr(inst, heap, offset, size, bound_gv, func)
+            dynamic_addr(inst, heap, offset, size, bound_gv, spectre, func)
         }
         ir::HeapStyle::Static { bound } => {
-            static_addr(inst, heap, offset, size, bound.into(), func, cfg)
+            static_addr(inst, heap, offset, size, bound.into(), spectre, func, cfg)
         }
     }
 }
@@ -47,6 +49,7 @@ fn dynamic_addr(
     offset: ir::Value,
     size: u32,
     bound_gv: ir::GlobalVar,
+    spectre: bool,
     func: &mut ir::Function,
 ) {
     let size = i64::from(size);
@@ -64,37 +67,23 @@ fn dynamic_addr(
     mflags.set_aligned();
     let bound = pos.ins().load(offset_ty, mflags, bound_addr, 0);
 
-    let oob;
-    if size == 1 {
+    let check = if size == 1 {
         // `offset > bound - 1` is the same as `offset >= bound`.
-        oob = pos.ins().icmp(
-            IntCC::UnsignedGreaterThanOrEqual,
-            offset,
-            bound,
-        );
+        BoundsCheck::Value(IntCC::UnsignedGreaterThanOrEqual, offset, bound)
     } else if size <= min_size {
         // We know that bound >= min_size, so here we can compare `offset > bound - size` without
         // wrapping.
         let adj_bound = pos.ins().iadd_imm(bound, -size);
-        oob = pos.ins().icmp(
-            IntCC::UnsignedGreaterThan,
-            offset,
-            adj_bound,
-        );
+        BoundsCheck::Value(IntCC::UnsignedGreaterThan, offset, adj_bound)
     } else {
         // We need an overflow check for the adjusted offset.
         let size_val = pos.ins().iconst(offset_ty, size);
         let (adj_offset, overflow) = pos.ins().iadd_cout(offset, size_val);
         pos.ins().trapnz(overflow, ir::TrapCode::HeapOutOfBounds);
-        oob = pos.ins().icmp(
-            IntCC::UnsignedGreaterThan,
-            adj_offset,
-            bound,
-        );
-    }
-    pos.ins().trapnz(oob, ir::TrapCode::HeapOutOfBounds);
+        BoundsCheck::Value(IntCC::UnsignedGreaterThan, adj_offset, bound)
+    };
 
-    offset_addr(inst, heap, addr_ty, offset, offset_ty, pos.func);
+    checked_offset_addr(inst, heap, addr_ty, offset, offset_ty, check, spectre, pos.func);
 }
 
 /// Expand a `heap_addr` for a static heap.
@@ -104,6 +93,7 @@ fn static_addr(
     offset: ir::Value,
     size: u32,
     bound: i64,
+    spectre: bool,
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
 ) {
@@ -134,38 +124,117 @@ fn static_addr(
     // We may be able to omit the check entirely for 32-bit offsets if the heap bound is 4 GB or
     // more.
     if offset_ty != ir::types::I32 || limit < 0xffff_ffff {
-        let oob = if limit & 1 == 1 {
+        let check = if limit & 1 == 1 {
             // Prefer testing `offset >= limit - 1` when limit is odd because an even number is
             // likely to be a convenient constant on ARM and other RISC architectures.
-            pos.ins().icmp_imm(
-                IntCC::UnsignedGreaterThanOrEqual,
-                offset,
-                limit - 1,
-            )
+            BoundsCheck::Imm(IntCC::UnsignedGreaterThanOrEqual, offset, limit - 1)
         } else {
-            pos.ins().icmp_imm(
-                IntCC::UnsignedGreaterThan,
-                offset,
-                limit,
-            )
+            BoundsCheck::Imm(IntCC::UnsignedGreaterThan, offset, limit)
+        };
+        checked_offset_addr(inst, heap, addr_ty, offset, offset_ty, check, spectre, pos.func);
+    } else {
+        offset_addr(inst, heap, addr_ty, offset, offset_ty, pos.func);
+    }
+}
+
+/// An out-of-bounds condition for a heap access: `x cond y`.
+enum BoundsCheck {
+    /// Compare two values.
+    Value(IntCC, ir::Value, ir::Value),
+    /// Compare a value with an immediate.
+    Imm(IntCC, ir::Value, i64),
+}
+
+/// Emit code for a bounds check followed by the base address computation of a `heap_addr`
+/// instruction.
+///
+/// Normally, this traps if `check` holds and then computes the address. With `spectre` set, the
+/// comparison is done with CPU flags after the address has been computed, and those same flags
+/// are used both to trap and to clamp the address to zero with a `selectif`. That way, a
+/// mispredicted bounds check can't speculatively access memory outside the heap.
+fn checked_offset_addr(
+    inst: ir::Inst,
+    heap: ir::Heap,
+    addr_ty: ir::Type,
+    offset: ir::Value,
+    offset_ty: ir::Type,
+    check: BoundsCheck,
+    spectre: bool,
+    func: &mut ir::Function,
+) {
+    if !spectre {
+        let mut pos = FuncCursor::new(func).at_inst(inst);
+        pos.use_srcloc(inst);
+        let oob = match check {
+            BoundsCheck::Value(cond, x, y) => pos.ins().icmp(cond, x, y),
+            BoundsCheck::Imm(cond, x, imm) => pos.ins().icmp_imm(cond, x, imm),
         };
         pos.ins().trapnz(oob, ir::TrapCode::HeapOutOfBounds);
+        offset_addr(inst, heap, addr_ty, offset, offset_ty, pos.func);
+        return;
     }
 
-    offset_addr(inst, heap, addr_ty, offset, offset_ty, pos.func);
+    // Compute the unchecked address into a new value, leaving `inst` in place for the `selectif`.
+    let addr = compute_addr(inst, heap, addr_ty, offset, offset_ty, func);
+
+    // No instructions that clobber the flags can be placed between the compare and the
+    // `selectif`, so materialize the zero address first.
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let zero = pos.ins().iconst(addr_ty, 0);
+    let (cond, flags) = match check {
+        BoundsCheck::Value(cond, x, y) => (cond, pos.ins().ifcmp(x, y)),
+        BoundsCheck::Imm(cond, x, imm) => (cond, pos.ins().ifcmp_imm(x, imm)),
+    };
+    pos.ins().trapif(cond, flags, ir::TrapCode::HeapOutOfBounds);
+    pos.func
+        .dfg
+        .replace(inst)
+        .selectif(addr_ty, cond, flags, zero, addr);
 }
 
 /// Emit code for the base address computation of a `heap_addr` instruction.
 ///
-///
+/// The `heap_addr` instruction is replaced by the final `iadd`.
 fn offset_addr(
     inst: ir::Inst,
     heap: ir::Heap,
     addr_ty: ir::Type,
-    mut offset: ir::Value,
+    offset: ir::Value,
     offset_ty: ir::Type,
     func: &mut ir::Function,
 ) {
+    let (base, offset) = addr_operands(inst, heap, addr_ty, offset, offset_ty, func);
+    func.dfg.replace(inst).iadd(base, offset);
+}
+
+/// Emit code for the base address computation of a `heap_addr` instruction, returning the
+/// computed address as a new value inserted before `inst`.
+fn compute_addr(
+    inst: ir::Inst,
+    heap: ir::Heap,
+    addr_ty: ir::Type,
+    offset: ir::Value,
+    offset_ty: ir::Type,
+    func: &mut ir::Function,
+) -> ir::Value {
+    let (base, offset) = addr_operands(inst, heap, addr_ty, offset, offset_ty, func);
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    pos.ins().iadd(base, offset)
+}
+
+/// Emit code computing the heap base address and the `offset` converted to `addr_ty`.
+///
+/// Returns `(base, offset)` whose sum is the address produced by `inst`.
+fn addr_operands(
+    inst: ir::Inst,
+    heap: ir::Heap,
+    addr_ty: ir::Type,
+    mut offset: ir::Value,
+    offset_ty: ir::Type,
+    func: &mut ir::Function,
+) -> (ir::Value, ir::Value) {
     let mut pos = FuncCursor::new(func).at_inst(inst);
     pos.use_srcloc(inst);
 
@@ -184,7 +253,7 @@ fn offset_addr(
             mflags.set_notrap();
             mflags.set_aligned();
             let base = pos.ins().load(addr_ty, mflags, base_addr, 0);
-            pos.func.dfg.replace(inst).iadd(base, offset);
+            (base, offset)
         }
     }
 }
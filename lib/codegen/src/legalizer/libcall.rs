@@ -21,7 +21,5 @@ pub fn expand_as_libcall(inst: ir::Inst, func: &mut ir::Function, isa: &TargetIs
     let funcref = get_libcall_funcref(libcall, func, inst, isa);
     func.dfg.replace(inst).call(funcref, &args);
 
-    // TODO: ask the ISA to legalize the signature.
-
     true
 }
@@ -18,6 +18,7 @@ use cursor::{Cursor, FuncCursor};
 use flowgraph::ControlFlowGraph;
 use ir::{self, InstBuilder};
 use isa::TargetIsa;
+use result::CtonResult;
 use timing;
 
 mod boundary;
@@ -25,6 +26,7 @@ mod call;
 mod globalvar;
 mod heap;
 mod libcall;
+mod softfloat;
 mod split;
 
 use self::globalvar::expand_global_addr;
@@ -42,6 +44,13 @@ fn legalize_inst(
 ) -> bool {
     let opcode = pos.func.dfg[inst].opcode();
 
+    // An `invoke` is a terminator, so there is no room after it for the ABI conversion of its
+    // results. Split it into a `call_lp` and a `jump` first.
+    if opcode == ir::Opcode::Invoke {
+        call::expand_invoke(inst, pos.func, cfg);
+        return true;
+    }
+
     // Check for ABI boundaries that need to be converted to the legalized signature.
     if opcode.is_call() {
         if boundary::handle_call_abi(inst, pos.func, cfg) {
@@ -80,10 +89,19 @@ fn legalize_inst(
 /// - Transform any instructions that don't have a legal representation in `isa`.
 /// - Fill out `func.encodings`.
 ///
-pub fn legalize_function(func: &mut ir::Function, cfg: &mut ControlFlowGraph, isa: &TargetIsa) {
+/// Returns an error if `func` uses a feature which can't be legalized for `isa`.
+pub fn legalize_function(
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    isa: &TargetIsa,
+) -> CtonResult {
     let _tt = timing::legalize();
     debug_assert!(cfg.is_valid());
 
+    if !isa.flags().enable_float() {
+        softfloat::legalize_soft_float(func, isa)?;
+    }
+
     boundary::legalize_signatures(func, isa);
 
     func.encodings.resize(func.dfg.num_insts());
@@ -107,6 +125,7 @@ pub fn legalize_function(func: &mut ir::Function, cfg: &mut ControlFlowGraph, is
             }
         }
     }
+    Ok(())
 }
 
 // Include legalization patterns that were generated by `gen_legalizer.py` from the `XForms` in
@@ -166,8 +185,110 @@ fn expand_cond_trap(
     cfg.recompute_ebb(pos.func, new_ebb);
 }
 
-/// Jump tables.
-fn expand_br_table(
+/// Expand the `br_table` instruction.
+///
+/// Use a jump table and an indirect branch when the target supports it, and a sequence of
+/// conditional branches otherwise.
+pub fn expand_br_table(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    isa: &TargetIsa,
+) {
+    if isa.flags().jump_tables_enabled() {
+        expand_br_table_jt(inst, func, cfg, isa);
+    } else {
+        expand_br_table_conds(inst, func, cfg, isa);
+    }
+}
+
+/// Expand `br_table` into a range check, a jump table lookup, and an indirect branch.
+fn expand_br_table_jt(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    isa: &TargetIsa,
+) {
+    use ir::condcodes::IntCC;
+
+    let (arg, table) = match func.dfg[inst] {
+        ir::InstructionData::BranchTable {
+            opcode: ir::Opcode::BrTable,
+            arg,
+            table,
+        } => (arg, table),
+        _ => panic!("Expected br_table: {}", func.dfg.display_inst(inst, None)),
+    };
+    let table_size = func.jump_tables[table].len();
+    let old_ebb = func.layout.pp_ebb(inst);
+
+    // An empty table always falls through.
+    if table_size == 0 {
+        func.layout.remove_inst(inst);
+        cfg.recompute_ebb(func, old_ebb);
+        return;
+    }
+
+    let ptr_ty = if isa.flags().is_64bit() {
+        ir::types::I64
+    } else {
+        ir::types::I32
+    };
+
+    // The code following the `br_table` is moved into a new EBB so the out-of-range indices and
+    // the empty table entries have somewhere to go.
+    let next_inst = func.layout.next_inst(inst).expect(
+        "br_table can't be the last instruction in an EBB",
+    );
+    let fallthrough_ebb = func.dfg.make_ebb();
+    func.layout.split_ebb(fallthrough_ebb, next_inst);
+
+    // Empty entries fall through. The table may be shared with other `br_table` instructions
+    // that fall through to other EBBs, so fill in the holes in a copy of the table.
+    let table = if func.jump_tables[table].entries().count() < table_size {
+        let mut data = func.jump_tables[table].clone();
+        for idx in 0..table_size {
+            if data.get_entry(idx).is_none() {
+                data.set_entry(idx, fallthrough_ebb);
+            }
+        }
+        func.create_jump_table(data)
+    } else {
+        table
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    let oob = pos.ins().icmp_imm(
+        IntCC::UnsignedGreaterThanOrEqual,
+        arg,
+        table_size as i64,
+    );
+    pos.ins().brnz(oob, fallthrough_ebb, &[]);
+
+    let arg_ty = pos.func.dfg.value_type(arg);
+    let index = if arg_ty == ptr_ty {
+        arg
+    } else if arg_ty.bits() < ptr_ty.bits() {
+        pos.ins().uextend(ptr_ty, arg)
+    } else {
+        // The range check above makes this lossless.
+        pos.ins().ireduce(ptr_ty, arg)
+    };
+
+    let base = pos.ins().jump_table_base(ptr_ty, table);
+    let entry = pos.ins().jump_table_entry(index, base, table);
+    let addr = pos.ins().iadd(base, entry);
+    pos.ins().indirect_jump_table_br(addr, table);
+    pos.remove_inst();
+
+    cfg.recompute_ebb(pos.func, old_ebb);
+    cfg.recompute_ebb(pos.func, fallthrough_ebb);
+}
+
+/// Expand `br_table` into a sequence of conditional branches.
+pub fn expand_br_table_conds(
     inst: ir::Inst,
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
@@ -184,8 +305,6 @@ fn expand_br_table(
         _ => panic!("Expected br_table: {}", func.dfg.display_inst(inst, None)),
     };
 
-    // This is a poor man's jump table using just a sequence of conditional branches.
-    // TODO: Lower into a jump table load and indirect branch.
     let table_size = func.jump_tables[table].len();
     let mut pos = FuncCursor::new(func).at_inst(inst);
     pos.use_srcloc(inst);
@@ -243,7 +362,7 @@ fn expand_select(
 }
 
 /// Expand illegal `f32const` and `f64const` instructions.
-fn expand_fconst(
+pub fn expand_fconst(
     inst: ir::Inst,
     func: &mut ir::Function,
     _cfg: &mut ControlFlowGraph,
@@ -252,8 +371,8 @@ fn expand_fconst(
     let ty = func.dfg.value_type(func.dfg.first_result(inst));
     debug_assert!(!ty.is_vector(), "Only scalar fconst supported: {}", ty);
 
-    // In the future, we may want to generate constant pool entries for these constants, but for
-    // now use an `iconst` and a bit cast.
+    // Targets with a constant pool can load the constant from there instead, but the generic
+    // expansion uses an `iconst` and a bit cast.
     let mut pos = FuncCursor::new(func).at_inst(inst);
     pos.use_srcloc(inst);
     let ival = match pos.func.dfg[inst] {
@@ -270,6 +389,153 @@ fn expand_fconst(
     pos.func.dfg.replace(inst).bitcast(ty, ival);
 }
 
+/// Split a wide integer constant into two constants of half the width.
+fn narrow_iconst(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &TargetIsa,
+) {
+    let imm: i64 = match func.dfg[inst] {
+        ir::InstructionData::UnaryImm {
+            opcode: ir::Opcode::Iconst,
+            imm,
+        } => imm.into(),
+        _ => panic!("Expected iconst: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.ctrl_typevar(inst);
+    let half_ty = ty.half_width().expect("Can't narrow iconst");
+    let half_bits = half_ty.bits();
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    // Sign-extend the low half, like any other immediate of type `half_ty`.
+    let low = pos.ins().iconst(
+        half_ty,
+        (imm << (64 - half_bits)) >> (64 - half_bits),
+    );
+    let high = pos.ins().iconst(half_ty, imm >> half_bits);
+    pos.func.dfg.replace(inst).iconcat(low, high);
+}
+
+/// Narrow a wide division or remainder into a call to a runtime library routine.
+///
+/// The library routines don't trap, so explicit checks are inserted for a zero divisor and for
+/// the overflowing `sdiv` of `INT_MIN` by -1.
+fn narrow_divrem(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    isa: &TargetIsa,
+) {
+    use ir::condcodes::IntCC;
+
+    let (opcode, x, y) = match func.dfg[inst] {
+        ir::InstructionData::Binary { opcode, args } => (opcode, args[0], args[1]),
+        _ => panic!("Expected div/rem: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.ctrl_typevar(inst);
+    let libcall = ir::LibCall::for_inst(opcode, ty).expect("No library routine for division");
+    let half_bits = ty.half_width().expect("Can't narrow division").bits();
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    // The divisor is zero if neither half has any bits set.
+    let (yl, yh) = isplit_at(&mut pos, cfg, y);
+    let y_bits = pos.ins().bor(yl, yh);
+    pos.ins().trapz(y_bits, ir::TrapCode::IntegerDivisionByZero);
+
+    if opcode == ir::Opcode::Sdiv {
+        // `INT_MIN` has a zero low half and `INT_MIN` of the half type as the high half.
+        let (xl, xh) = isplit_at(&mut pos, cfg, x);
+        let x_min_lo = pos.ins().icmp_imm(IntCC::Equal, xl, 0);
+        let x_min_hi = pos.ins().icmp_imm(IntCC::Equal, xh, -1 << (half_bits - 1));
+        let y_ones = pos.ins().band(yl, yh);
+        let y_m1 = pos.ins().icmp_imm(IntCC::Equal, y_ones, -1);
+        let x_min = pos.ins().band(x_min_lo, x_min_hi);
+        let overflow = pos.ins().band(x_min, y_m1);
+        pos.ins().trapnz(overflow, ir::TrapCode::IntegerOverflow);
+    }
+
+    // The replace builder will preserve the instruction result value.
+    let funcref = ir::get_libcall_funcref(libcall, pos.func, inst, isa);
+    pos.func.dfg.replace(inst).call(funcref, &[x, y]);
+}
+
+/// Split `value` into halves at the current position of `pos`.
+fn isplit_at(
+    pos: &mut FuncCursor,
+    cfg: &ControlFlowGraph,
+    value: ir::Value,
+) -> (ir::Value, ir::Value) {
+    let curpos = pos.position();
+    let srcloc = pos.srcloc();
+    split::isplit(pos.func, cfg, curpos, srcloc, value)
+}
+
+/// Expand a `stack_load` into a `stack_addr` and a `load`.
+fn expand_stack_load(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    isa: &TargetIsa,
+) {
+    let (stack_slot, offset) = match func.dfg[inst] {
+        ir::InstructionData::StackLoad {
+            stack_slot, offset, ..
+        } => (stack_slot, offset),
+        _ => panic!("Want stack_load: {}", func.dfg.display_inst(inst, isa)),
+    };
+    let ty = func.dfg.value_type(func.dfg.first_result(inst));
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    let addr = pos.ins().stack_addr(pointer_type(isa), stack_slot, offset);
+    pos.func.dfg.replace(inst).load(ty, stack_flags(), addr, 0);
+}
+
+/// Expand a `stack_store` into a `stack_addr` and a `store`.
+fn expand_stack_store(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    isa: &TargetIsa,
+) {
+    let (arg, stack_slot, offset) = match func.dfg[inst] {
+        ir::InstructionData::StackStore {
+            arg,
+            stack_slot,
+            offset,
+            ..
+        } => (arg, stack_slot, offset),
+        _ => panic!("Want stack_store: {}", func.dfg.display_inst(inst, isa)),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    let addr = pos.ins().stack_addr(pointer_type(isa), stack_slot, offset);
+    pos.func.dfg.replace(inst).store(stack_flags(), arg, addr, 0);
+}
+
+/// Get the type of addresses on `isa`.
+fn pointer_type(isa: &TargetIsa) -> ir::Type {
+    if isa.flags().is_64bit() {
+        ir::types::I64
+    } else {
+        ir::types::I32
+    }
+}
+
+/// Memory flags for accessing a stack slot, which can't trap. The offset may not be aligned.
+fn stack_flags() -> ir::MemFlags {
+    let mut mflags = ir::MemFlags::new();
+    mflags.set_notrap();
+    mflags
+}
+
 /// Expand the stack check instruction.
 pub fn expand_stack_check(
     inst: ir::Inst,
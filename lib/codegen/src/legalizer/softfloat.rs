@@ -0,0 +1,474 @@
+//! Soft-float legalization.
+//!
+//! When the `enable_float` setting is false, floating point values are represented as integers of
+//! the same size, and floating point instructions are expanded into integer bit manipulation or
+//! calls to runtime library routines following the compiler-rt conventions. Since the function
+//! signatures are rewritten too, floats are passed and returned in integer registers.
+//!
+//! The conversions to integers check their input with the comparison routines before calling
+//! the conversion routine, so they trap on NaN and out-of-range inputs just like with hardware
+//! floats. The `ffcmp` instruction is removed, and the instructions testing its flags compare its
+//! arguments with the comparison routines instead. Float vectors are not supported.
+
+use cursor::{Cursor, FuncCursor};
+use entity::EntityRef;
+use ir::condcodes::{FloatCC, IntCC};
+use ir::immediates::{Ieee32, Ieee64};
+use ir::{self, InstBuilder, LibCall, Opcode, Type, Value, get_libcall_funcref_for_types, types};
+use isa::TargetIsa;
+use result::{CtonError, CtonResult};
+use std::vec::Vec;
+
+/// Replace the floats in `func` with integers, and expand all floating point instructions.
+///
+/// Returns an error if `func` uses float vectors.
+pub fn legalize_soft_float(func: &mut ir::Function, isa: &TargetIsa) -> CtonResult {
+    for v in 0..func.dfg.num_values() {
+        let ty = func.dfg.value_type(Value::new(v));
+        if ty.is_vector() && ty.lane_type().is_float() {
+            return Err(CtonError::Unsupported(
+                format!("{} values when enable_float is false", ty),
+            ));
+        }
+    }
+
+    legalize_signature(&mut func.signature);
+    for sig in func.dfg.signatures.values_mut() {
+        legalize_signature(sig);
+    }
+
+    for v in 0..func.dfg.num_values() {
+        let v = Value::new(v);
+        let ty = func.dfg.value_type(v);
+        if let Some(int_ty) = int_type(ty) {
+            func.dfg.change_value_type(v, int_ty);
+        }
+    }
+
+    let mut pos = FuncCursor::new(func);
+    while let Some(_ebb) = pos.next_ebb() {
+        while let Some(inst) = pos.next_inst() {
+            pos.use_srcloc(inst);
+            expand_inst(inst, &mut pos, isa);
+        }
+    }
+    Ok(())
+}
+
+/// Get the integer type representing the float type `ty`, or `None` if `ty` is not a scalar float.
+fn int_type(ty: Type) -> Option<Type> {
+    match ty {
+        types::F32 => Some(types::I32),
+        types::F64 => Some(types::I64),
+        _ => None,
+    }
+}
+
+/// Pass floats as integers in `sig`.
+fn legalize_signature(sig: &mut ir::Signature) {
+    for param in sig.params.iter_mut().chain(sig.returns.iter_mut()) {
+        if let Some(int_ty) = int_type(param.value_type) {
+            param.value_type = int_ty;
+        }
+    }
+}
+
+/// Insert a constant with only the sign bit of the float represented by the integer `x` set, or
+/// with all bits but the sign bit set if `invert` is true.
+fn sign_mask(pos: &mut FuncCursor, x: Value, invert: bool) -> Value {
+    let ty = pos.func.dfg.value_type(x);
+    let sign = match ty {
+        types::I32 => i64::from(i32::min_value()),
+        _ => i64::min_value(),
+    };
+    pos.ins().iconst(ty, if invert { !sign } else { sign })
+}
+
+/// Pick the `f32` or `f64` version of a library routine, depending on the integer type `ty`
+/// representing the float.
+fn pick(ty: Type, f32: LibCall, f64: LibCall) -> LibCall {
+    match ty {
+        types::I32 => f32,
+        _ => f64,
+    }
+}
+
+/// Get the library routine implementing `opcode`, given the types of its first argument and its
+/// result after the floats have been replaced by integers.
+fn libcall_for(opcode: Opcode, arg_ty: Type, result_ty: Type) -> Option<LibCall> {
+    Some(match opcode {
+        Opcode::Fadd => pick(arg_ty, LibCall::AddF32, LibCall::AddF64),
+        Opcode::Fsub => pick(arg_ty, LibCall::SubF32, LibCall::SubF64),
+        Opcode::Fmul => pick(arg_ty, LibCall::MulF32, LibCall::MulF64),
+        Opcode::Fdiv => pick(arg_ty, LibCall::DivF32, LibCall::DivF64),
+        Opcode::Sqrt => pick(arg_ty, LibCall::SqrtF32, LibCall::SqrtF64),
+        Opcode::Fma => pick(arg_ty, LibCall::FmaF32, LibCall::FmaF64),
+        Opcode::Fmin => pick(arg_ty, LibCall::FminF32, LibCall::FminF64),
+        Opcode::Fmax => pick(arg_ty, LibCall::FmaxF32, LibCall::FmaxF64),
+        Opcode::Ceil => pick(arg_ty, LibCall::CeilF32, LibCall::CeilF64),
+        Opcode::Floor => pick(arg_ty, LibCall::FloorF32, LibCall::FloorF64),
+        Opcode::Trunc => pick(arg_ty, LibCall::TruncF32, LibCall::TruncF64),
+        Opcode::Nearest => pick(arg_ty, LibCall::NearestF32, LibCall::NearestF64),
+        Opcode::Fpromote => LibCall::PromoteF32,
+        Opcode::Fdemote => LibCall::DemoteF64,
+        Opcode::FcvtToSint => {
+            match (arg_ty, result_ty) {
+                (types::I32, types::I32) => LibCall::F32ToI32,
+                (types::I32, _) => LibCall::F32ToI64,
+                (_, types::I32) => LibCall::F64ToI32,
+                _ => LibCall::F64ToI64,
+            }
+        }
+        Opcode::FcvtToUint => {
+            match (arg_ty, result_ty) {
+                (types::I32, types::I32) => LibCall::F32ToU32,
+                (types::I32, _) => LibCall::F32ToU64,
+                (_, types::I32) => LibCall::F64ToU32,
+                _ => LibCall::F64ToU64,
+            }
+        }
+        Opcode::FcvtFromSint => {
+            match (arg_ty, result_ty) {
+                (types::I32, types::I32) => LibCall::I32ToF32,
+                (types::I32, _) => LibCall::I32ToF64,
+                (_, types::I32) => LibCall::I64ToF32,
+                _ => LibCall::I64ToF64,
+            }
+        }
+        Opcode::FcvtFromUint => {
+            match (arg_ty, result_ty) {
+                (types::I32, types::I32) => LibCall::U32ToF32,
+                (types::I32, _) => LibCall::U32ToF64,
+                (_, types::I32) => LibCall::U64ToF32,
+                _ => LibCall::U64ToF64,
+            }
+        }
+        _ => return None,
+    })
+}
+
+/// Expand `inst` if it is a floating point instruction.
+fn expand_inst(inst: ir::Inst, pos: &mut FuncCursor, isa: &TargetIsa) {
+    let opcode = pos.func.dfg[inst].opcode();
+    let mut args: Vec<Value> = pos.func.dfg.inst_args(inst).to_vec();
+
+    match pos.func.dfg[inst] {
+        ir::InstructionData::UnaryIeee32 { imm, .. } => {
+            pos.func.dfg.replace(inst).iconst(
+                types::I32,
+                i64::from(imm.bits()),
+            );
+            return;
+        }
+        ir::InstructionData::UnaryIeee64 { imm, .. } => {
+            pos.func.dfg.replace(inst).iconst(types::I64, imm.bits() as i64);
+            return;
+        }
+        ir::InstructionData::FloatCompare { cond, .. } => {
+            expand_fcmp(inst, cond, args[0], args[1], pos, isa);
+            return;
+        }
+        ir::InstructionData::FloatCond { cond, arg, .. } => {
+            // A `trueff` is the `fcmp` of the `ffcmp` arguments.
+            let (x, y) = ffcmp_args(pos.func, arg);
+            expand_fcmp(inst, cond, x, y, pos, isa);
+            return;
+        }
+        ir::InstructionData::BranchFloat { cond, destination, .. } => {
+            let cmp = insert_fcmp(cond, args[0], pos, isa);
+            pos.func.dfg.replace(inst).brnz(cmp, destination, &args[1..]);
+            return;
+        }
+        ir::InstructionData::FloatCondTrap { cond, arg, code, .. } => {
+            let cmp = insert_fcmp(cond, arg, pos, isa);
+            pos.func.dfg.replace(inst).trapnz(cmp, code);
+            return;
+        }
+        _ => {}
+    }
+
+    match opcode {
+        Opcode::Fneg => {
+            let sign = sign_mask(pos, args[0], false);
+            pos.func.dfg.replace(inst).bxor(args[0], sign);
+        }
+        Opcode::Fabs => {
+            let mask = sign_mask(pos, args[0], true);
+            pos.func.dfg.replace(inst).band(args[0], mask);
+        }
+        Opcode::Fcopysign => {
+            let mask = sign_mask(pos, args[0], true);
+            let magnitude = pos.ins().band(args[0], mask);
+            let sign = sign_mask(pos, args[0], false);
+            let sign = pos.ins().band(args[1], sign);
+            pos.func.dfg.replace(inst).bor(magnitude, sign);
+        }
+        Opcode::Ffcmp => {
+            // The instructions using the flags look up the arguments of the removed `ffcmp`.
+            pos.remove_inst_and_step_back();
+        }
+        Opcode::Bitcast => {
+            // A bitcast between a float and an integer is now a no-op.
+            let result = pos.func.dfg.first_result(inst);
+            if pos.func.dfg.value_type(result) == pos.func.dfg.value_type(args[0]) {
+                pos.func.dfg.clear_results(inst);
+                pos.func.dfg.change_to_alias(result, args[0]);
+                pos.remove_inst_and_step_back();
+            }
+        }
+        _ => {
+            let results = pos.func.dfg.inst_results(inst);
+            if results.len() != 1 || args.is_empty() {
+                return;
+            }
+            let result_ty = pos.func.dfg.value_type(results[0]);
+            let mut arg_ty = pos.func.dfg.value_type(args[0]);
+            if arg_ty.bits() < 32 {
+                // There are no library routines converting from `i8` or `i16`.
+                match opcode {
+                    Opcode::FcvtFromSint => args[0] = pos.ins().sextend(types::I32, args[0]),
+                    Opcode::FcvtFromUint => args[0] = pos.ins().uextend(types::I32, args[0]),
+                    _ => {}
+                }
+                arg_ty = pos.func.dfg.value_type(args[0]);
+            }
+            if let Some(libcall) = libcall_for(opcode, arg_ty, result_ty) {
+                match opcode {
+                    Opcode::FcvtToSint => check_fcvt_to_int(true, args[0], result_ty, pos, isa),
+                    Opcode::FcvtToUint => check_fcvt_to_int(false, args[0], result_ty, pos, isa),
+                    _ => {}
+                }
+                let params: Vec<Type> = args.iter().map(|&v| pos.func.dfg.value_type(v)).collect();
+                let funcref =
+                    get_libcall_funcref_for_types(libcall, pos.func, &params, &[result_ty], isa);
+                // The replace builder will preserve the instruction result value.
+                pos.func.dfg.replace(inst).call(funcref, &args);
+            }
+        }
+    }
+}
+
+/// Insert the checks that make a `fcvt_to_sint` or `fcvt_to_uint` of `x` to `result_ty` trap like
+/// the hardware float version does: `bad_toint` if `x` is NaN, and `int_ovf` if it is out of
+/// range.
+fn check_fcvt_to_int(
+    signed: bool,
+    x: Value,
+    result_ty: Type,
+    pos: &mut FuncCursor,
+    isa: &TargetIsa,
+) {
+    let ty = pos.func.dfg.value_type(x);
+    let unord = pick(ty, LibCall::UnordF32, LibCall::UnordF64);
+    let is_nan = compare(unord, IntCC::NotEqual, x, x, pos, isa);
+    pos.ins().trapnz(is_nan, ir::TrapCode::BadConversionToInteger);
+
+    // Determine the bits of the floats bounding the range of `result_ty`. The input overflows if
+    // it is less than `lower`, or also equal to it if `lower_inclusive` is set, and if it is
+    // greater than or equal to `upper`.
+    let output_bits = result_ty.lane_bits();
+    let upper_bits = if signed { output_bits - 1 } else { output_bits };
+    let mut lower_inclusive = true;
+    let (lower, upper) = match ty {
+        types::I32 => {
+            let lower = if !signed {
+                Ieee32::with_float(-1.0)
+            } else if output_bits < 32 {
+                // An f32 can represent the integers just below the range exactly, so there are
+                // values less than -2^(N-1) that convert correctly to INT_MIN.
+                Ieee32::fcvt_to_sint_negative_overflow(output_bits)
+            } else {
+                lower_inclusive = false;
+                Ieee32::pow2(output_bits - 1).neg()
+            };
+            (
+                i64::from(lower.bits()),
+                i64::from(Ieee32::pow2(upper_bits).bits()),
+            )
+        }
+        _ => {
+            let lower = if !signed {
+                Ieee64::with_float(-1.0)
+            } else if output_bits < 64 {
+                Ieee64::fcvt_to_sint_negative_overflow(output_bits)
+            } else {
+                lower_inclusive = false;
+                Ieee64::pow2(output_bits - 1).neg()
+            };
+            (lower.bits() as i64, Ieee64::pow2(upper_bits).bits() as i64)
+        }
+    };
+
+    let lower = pos.ins().iconst(ty, lower);
+    let too_small = if lower_inclusive {
+        let le = pick(ty, LibCall::LeF32, LibCall::LeF64);
+        compare(le, IntCC::SignedLessThanOrEqual, x, lower, pos, isa)
+    } else {
+        let lt = pick(ty, LibCall::LtF32, LibCall::LtF64);
+        compare(lt, IntCC::SignedLessThan, x, lower, pos, isa)
+    };
+    pos.ins().trapnz(too_small, ir::TrapCode::IntegerOverflow);
+
+    let upper = pos.ins().iconst(ty, upper);
+    let ge = pick(ty, LibCall::GeF32, LibCall::GeF64);
+    let too_large = compare(ge, IntCC::SignedGreaterThanOrEqual, x, upper, pos, isa);
+    pos.ins().trapnz(too_large, ir::TrapCode::IntegerOverflow);
+}
+
+/// Expand a `fcmp` instruction into calls to the comparison routines.
+///
+/// The compiler-rt comparison routines return an `i32` that is compared to zero. For unordered
+/// inputs, `__eqsf2`, `__nesf2`, `__ltsf2` and `__lesf2` return 1, and `__gtsf2` and `__gesf2`
+/// return -1, which makes it possible to implement most conditions with a single call.
+fn expand_fcmp(
+    inst: ir::Inst,
+    cond: FloatCC,
+    x: Value,
+    y: Value,
+    pos: &mut FuncCursor,
+    isa: &TargetIsa,
+) {
+    let ty = pos.func.dfg.value_type(x);
+    let (libcall, int_cc) = match cond {
+        FloatCC::Ordered => (pick(ty, LibCall::UnordF32, LibCall::UnordF64), IntCC::Equal),
+        FloatCC::Unordered => (pick(ty, LibCall::UnordF32, LibCall::UnordF64), IntCC::NotEqual),
+        FloatCC::Equal => (pick(ty, LibCall::EqF32, LibCall::EqF64), IntCC::Equal),
+        FloatCC::NotEqual => (pick(ty, LibCall::NeF32, LibCall::NeF64), IntCC::NotEqual),
+        FloatCC::LessThan => (
+            pick(ty, LibCall::LtF32, LibCall::LtF64),
+            IntCC::SignedLessThan,
+        ),
+        FloatCC::LessThanOrEqual => (
+            pick(ty, LibCall::LeF32, LibCall::LeF64),
+            IntCC::SignedLessThanOrEqual,
+        ),
+        FloatCC::GreaterThan => (
+            pick(ty, LibCall::GtF32, LibCall::GtF64),
+            IntCC::SignedGreaterThan,
+        ),
+        FloatCC::GreaterThanOrEqual => (
+            pick(ty, LibCall::GeF32, LibCall::GeF64),
+            IntCC::SignedGreaterThanOrEqual,
+        ),
+        FloatCC::UnorderedOrLessThan => (
+            pick(ty, LibCall::GeF32, LibCall::GeF64),
+            IntCC::SignedLessThan,
+        ),
+        FloatCC::UnorderedOrLessThanOrEqual => (
+            pick(ty, LibCall::GtF32, LibCall::GtF64),
+            IntCC::SignedLessThanOrEqual,
+        ),
+        FloatCC::UnorderedOrGreaterThan => (
+            pick(ty, LibCall::LeF32, LibCall::LeF64),
+            IntCC::SignedGreaterThan,
+        ),
+        FloatCC::UnorderedOrGreaterThanOrEqual => (
+            pick(ty, LibCall::LtF32, LibCall::LtF64),
+            IntCC::SignedGreaterThanOrEqual,
+        ),
+        FloatCC::OrderedNotEqual => {
+            let unord = pick(ty, LibCall::UnordF32, LibCall::UnordF64);
+            let ordered = compare(unord, IntCC::Equal, x, y, pos, isa);
+            let ne = pick(ty, LibCall::NeF32, LibCall::NeF64);
+            let not_equal = compare(ne, IntCC::NotEqual, x, y, pos, isa);
+            pos.func.dfg.replace(inst).band(ordered, not_equal);
+            return;
+        }
+        FloatCC::UnorderedOrEqual => {
+            let unord = pick(ty, LibCall::UnordF32, LibCall::UnordF64);
+            let unordered = compare(unord, IntCC::NotEqual, x, y, pos, isa);
+            let eq = pick(ty, LibCall::EqF32, LibCall::EqF64);
+            let equal = compare(eq, IntCC::Equal, x, y, pos, isa);
+            pos.func.dfg.replace(inst).bor(unordered, equal);
+            return;
+        }
+    };
+
+    let result = call_compare(libcall, x, y, pos, isa);
+    pos.func.dfg.replace(inst).icmp_imm(int_cc, result, 0);
+}
+
+/// Get the arguments of the `ffcmp` instruction defining `flags`.
+fn ffcmp_args(func: &ir::Function, flags: Value) -> (Value, Value) {
+    let ffcmp = func.dfg.value_def(flags).unwrap_inst();
+    let args = func.dfg.inst_args(ffcmp);
+    debug_assert_eq!(func.dfg[ffcmp].opcode(), Opcode::Ffcmp);
+    (args[0], args[1])
+}
+
+/// Insert the expansion of a `fcmp` of the arguments of the `ffcmp` defining `flags`, testing the
+/// condition `cond`, and return its result.
+fn insert_fcmp(cond: FloatCC, flags: Value, pos: &mut FuncCursor, isa: &TargetIsa) -> Value {
+    let (x, y) = ffcmp_args(pos.func, flags);
+    let result = pos.ins().fcmp(cond, x, y);
+    let fcmp = pos.func.dfg.value_def(result).unwrap_inst();
+
+    // Expand the new `fcmp` in place, then come back to the current instruction.
+    let inst = pos.current_inst().unwrap();
+    pos.goto_inst(fcmp);
+    expand_fcmp(fcmp, cond, x, y, pos, isa);
+    pos.goto_inst(inst);
+    result
+}
+
+/// Insert a call to the comparison routine `libcall`, and compare its result to zero.
+fn compare(
+    libcall: LibCall,
+    int_cc: IntCC,
+    x: Value,
+    y: Value,
+    pos: &mut FuncCursor,
+    isa: &TargetIsa,
+) -> Value {
+    let result = call_compare(libcall, x, y, pos, isa);
+    pos.ins().icmp_imm(int_cc, result, 0)
+}
+
+/// Insert a call to the comparison routine `libcall`.
+fn call_compare(
+    libcall: LibCall,
+    x: Value,
+    y: Value,
+    pos: &mut FuncCursor,
+    isa: &TargetIsa,
+) -> Value {
+    let ty = pos.func.dfg.value_type(x);
+    let funcref = get_libcall_funcref_for_types(libcall, pos.func, &[ty, ty], &[types::I32], isa);
+    let call = pos.ins().call(funcref, &[x, y]);
+    pos.func.dfg.first_result(call)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ir::Function;
+    use isa;
+    use settings::{self, Configurable};
+    use std::string::ToString;
+
+    #[test]
+    fn float_vectors() {
+        let mut shared_builder = settings::builder();
+        shared_builder.enable("is_64bit").unwrap();
+        shared_builder.set("enable_float", "false").unwrap();
+        let isa = match isa::lookup("x86") {
+            Ok(isa_builder) => isa_builder.finish(settings::Flags::new(shared_builder)),
+            Err(_) => return,
+        };
+
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::F32X4);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            pos.ins().return_(&[v0]);
+        }
+        assert_eq!(
+            legalize_soft_float(&mut func, &*isa),
+            Err(CtonError::Unsupported(
+                "f32x4 values when enable_float is false".to_string(),
+            ))
+        );
+    }
+}
@@ -72,7 +72,9 @@ pub extern crate cretonne_entity as entity;
 pub mod dbg;
 
 pub mod bforest;
+pub mod binary;
 pub mod binemit;
+pub mod cache;
 pub mod cfg_printer;
 pub mod cursor;
 pub mod dominator_tree;
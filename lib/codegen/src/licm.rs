@@ -6,6 +6,7 @@ use entity::{EntityList, ListPool};
 use flowgraph::ControlFlowGraph;
 use fx::FxHashSet;
 use ir::{DataFlowGraph, Ebb, Function, Inst, InstBuilder, Layout, Opcode, Type, Value};
+use isa::TargetIsa;
 use loop_analysis::{Loop, LoopAnalysis};
 use std::vec::Vec;
 use timing;
@@ -13,7 +14,11 @@ use timing;
 /// Performs the LICM pass by detecting loops within the CFG and moving
 /// loop-invariant instructions out of them.
 /// Changes the CFG and domtree in-place during the operation.
+///
+/// If `isa` is given, the jumps in new loop pre-headers are encoded for it, so the pass can run
+/// after legalization.
 pub fn do_licm(
+    isa: Option<&TargetIsa>,
     func: &mut Function,
     cfg: &mut ControlFlowGraph,
     domtree: &mut DominatorTree,
@@ -36,7 +41,7 @@ pub fn do_licm(
             match has_pre_header(&func.layout, cfg, domtree, loop_analysis.loop_header(lp)) {
                 None => {
                     let pre_header =
-                        create_pre_header(loop_analysis.loop_header(lp), func, cfg, domtree);
+                        create_pre_header(isa, loop_analysis.loop_header(lp), func, cfg, domtree);
                     pos = FuncCursor::new(func).at_last_inst(pre_header);
                 }
                 // If there is a natural pre-header we insert new instructions just before the
@@ -60,6 +65,7 @@ pub fn do_licm(
 // Insert a pre-header before the header, modifying the function layout and CFG to reflect it.
 // A jump instruction to the header is placed at the end of the pre-header.
 fn create_pre_header(
+    isa: Option<&TargetIsa>,
     header: Ebb,
     func: &mut Function,
     cfg: &mut ControlFlowGraph,
@@ -88,7 +94,12 @@ fn create_pre_header(
         // Inserts the pre-header at the right place in the layout.
         pos.insert_ebb(pre_header);
         pos.next_inst();
-        pos.ins().jump(header, pre_header_args_value.as_slice(pool));
+        let jump = pos.ins().jump(header, pre_header_args_value.as_slice(pool));
+        if let Some(isa) = isa {
+            if pos.func.update_encoding(jump, isa).is_err() {
+                panic!("can't encode {}", pos.func.dfg.display_inst(jump, isa));
+            }
+        }
     }
     pre_header
 }
@@ -7,6 +7,7 @@ use divconst_magic_numbers::{MS32, MS64, MU32, MU64};
 use divconst_magic_numbers::{magicS32, magicS64, magicU32, magicU64};
 use ir::Inst;
 use ir::dfg::ValueDef;
+use ir::immediates::Imm64;
 use ir::instructions::Opcode;
 use ir::types::{I32, I64};
 use ir::{DataFlowGraph, Function, InstBuilder, InstructionData, Type, Value};
@@ -448,6 +449,16 @@ fn do_divrem_transformation(divrem_info: &DivRemByConstInfo, pos: &mut FuncCurso
     }
 }
 
+/// Can `imm` be the divisor of a `_imm` division or remainder of type `ty`?
+///
+/// The `_imm` forms don't allow the divisors that can trap: zero, and -1 for signed division.
+fn is_imm_divisor(imm: Imm64, ty: Type, signed: bool) -> bool {
+    let shift = 64 - ty.lane_bits();
+    let imm: i64 = imm.into();
+    let divisor = imm << shift >> shift;
+    divisor != 0 && !(signed && divisor == -1)
+}
+
 /// Apply basic simplifications.
 ///
 /// This folds constants with arithmetic to form `_imm` instructions, and other
@@ -461,13 +472,14 @@ fn simplify(pos: &mut FuncCursor, inst: Inst) {
                     mut imm,
                 } = pos.func.dfg[iconst_inst]
                 {
+                    let ty = pos.func.dfg.ctrl_typevar(inst);
                     let new_opcode = match opcode {
                         Opcode::Iadd => Opcode::IaddImm,
                         Opcode::Imul => Opcode::ImulImm,
-                        Opcode::Sdiv => Opcode::SdivImm,
-                        Opcode::Udiv => Opcode::UdivImm,
-                        Opcode::Srem => Opcode::SremImm,
-                        Opcode::Urem => Opcode::UremImm,
+                        Opcode::Sdiv if is_imm_divisor(imm, ty, true) => Opcode::SdivImm,
+                        Opcode::Udiv if is_imm_divisor(imm, ty, false) => Opcode::UdivImm,
+                        Opcode::Srem if is_imm_divisor(imm, ty, true) => Opcode::SremImm,
+                        Opcode::Urem if is_imm_divisor(imm, ty, false) => Opcode::UremImm,
                         Opcode::Band => Opcode::BandImm,
                         Opcode::Bor => Opcode::BorImm,
                         Opcode::Bxor => Opcode::BxorImm,
@@ -482,7 +494,6 @@ fn simplify(pos: &mut FuncCursor, inst: Inst) {
                         }
                         _ => return,
                     };
-                    let ty = pos.func.dfg.ctrl_typevar(inst);
                     pos.func.dfg.replace(inst).BinaryImm(
                         new_opcode,
                         ty,
@@ -1,29 +1,78 @@
 //! Utility routines for pretty-printing error messages.
 
 use ir;
+use ir::entities::AnyEntity;
 use isa::TargetIsa;
 use result::CtonError;
-use std::fmt::Write;
+use std::fmt::{self, Write};
 use std::string::{String, ToString};
+use std::vec::Vec;
 use verifier;
+use write::decorate_function;
 
-/// Pretty-print a verifier error.
+/// Pretty-print verifier errors.
+///
+/// The function is printed with each error message inserted as a comment after the line defining
+/// the entity where the error was found. Errors for entities that don't appear in the function
+/// listing are printed after it.
 pub fn pretty_verifier_error(
     func: &ir::Function,
     isa: Option<&TargetIsa>,
-    err: &verifier::Error,
+    errors: &verifier::Errors,
 ) -> String {
-    let mut msg = err.to_string();
-    match err.location {
-        ir::entities::AnyEntity::Inst(inst) => {
-            write!(msg, "\n{}: {}\n\n", inst, func.dfg.display_inst(inst, isa)).unwrap()
+    let errors = &errors.0;
+    let mut printed = vec![false; errors.len()];
+    let mut msg = String::new();
+
+    decorate_function(&mut msg, func, isa, &mut |w, entity| {
+        for (err, printed) in errors.iter().zip(printed.iter_mut()) {
+            if !*printed && listed_entity(func, err.location) == entity {
+                *printed = true;
+                write_error(w, err)?;
+            }
+        }
+        Ok(())
+    }).unwrap();
+
+    let unlisted: Vec<_> = errors
+        .iter()
+        .zip(&printed)
+        .filter(|&(_, &printed)| !printed)
+        .collect();
+    if !unlisted.is_empty() {
+        msg.push('\n');
+        for (err, _) in unlisted {
+            write_error(&mut msg, err).unwrap();
         }
-        _ => msg.push('\n'),
     }
-    write!(msg, "{}", func.display(isa)).unwrap();
+
+    match errors.len() {
+        1 => msg.push_str("\n; 1 verifier error detected (see above).\n"),
+        n => write!(msg, "\n; {} verifier errors detected (see above).\n", n).unwrap(),
+    }
     msg
 }
 
+/// Write a single error as a comment line.
+fn write_error(w: &mut Write, err: &verifier::Error) -> fmt::Result {
+    writeln!(w, "; error: {}", err)
+}
+
+/// Get the entity in the function listing where errors for `entity` should be shown.
+///
+/// Values are shown where they are defined.
+fn listed_entity(func: &ir::Function, entity: AnyEntity) -> AnyEntity {
+    match entity {
+        AnyEntity::Value(value) if func.dfg.value_is_valid(value) => {
+            match func.dfg.value_def(value) {
+                ir::ValueDef::Result(inst, _) => inst.into(),
+                ir::ValueDef::Param(ebb, _) => ebb.into(),
+            }
+        }
+        _ => entity,
+    }
+}
+
 /// Pretty-print a Cretonne error.
 pub fn pretty_error(func: &ir::Function, isa: Option<&TargetIsa>, err: CtonError) -> String {
     if let CtonError::Verifier(e) = err {
@@ -32,3 +81,49 @@ pub fn pretty_error(func: &ir::Function, isa: Option<&TargetIsa>, err: CtonError
         err.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cursor::{Cursor, FuncCursor};
+    use ir::{Function, InstBuilder};
+    use verifier::{Error, Errors};
+
+    #[test]
+    fn annotate_errors() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        let v0 = pos.ins().iconst(ir::types::I32, 1);
+        let ret = pos.ins().return_(&[]);
+
+        let errors = Errors(vec![
+            Error {
+                location: ret.into(),
+                message: "bad return".to_string(),
+            },
+            Error {
+                location: v0.into(),
+                message: "bad value".to_string(),
+            },
+            Error {
+                location: AnyEntity::Function,
+                message: "bad function".to_string(),
+            },
+        ]);
+        assert_eq!(
+            pretty_verifier_error(&func, None, &errors),
+            "function u0:0() fast {\n\
+             ; error: function: bad function\n\
+             ebb0:\n    \
+             v0 = iconst.i32 1\n\
+             ; error: v0: bad value\n    \
+             return\n\
+             ; error: inst1: bad return\n\
+             }\n\
+             \n\
+             ; 3 verifier errors detected (see above).\n"
+        );
+    }
+}
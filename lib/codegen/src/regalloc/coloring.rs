@@ -400,7 +400,9 @@ impl<'a> Context<'a> {
                 &regs.global,
             );
         }
-        if let Some(sig) = call_sig {
+        // Tail calls don't have any return values to program.
+        let is_tail_call = self.cur.func.dfg[inst].opcode().is_tail_call();
+        if let (Some(sig), false) = (call_sig, is_tail_call) {
             self.program_output_abi(
                 sig,
                 defs,
@@ -916,6 +918,10 @@ impl<'a> Context<'a> {
                         lr.is_livein(ebb, ctx)
                     })
             }
+            Invoke(dest, landing_pad) => {
+                let lr = &self.liveness[value];
+                lr.is_livein(dest, ctx) || lr.is_livein(landing_pad, ctx)
+            }
         }
     }
 
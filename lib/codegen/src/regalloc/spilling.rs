@@ -246,10 +246,10 @@ impl<'a> Context<'a> {
         debug_assert!(self.reg_uses.is_empty());
         self.collect_reg_uses(inst, ebb, constraints);
 
-        // Calls usually have fixed register uses.
+        // Calls and returns usually have fixed register uses.
         let call_sig = self.cur.func.dfg.call_signature(inst);
-        if let Some(sig) = call_sig {
-            self.collect_abi_reg_uses(inst, sig);
+        if call_sig.is_some() || self.cur.func.dfg[inst].opcode().is_return() {
+            self.collect_abi_reg_uses(inst, call_sig);
         }
 
         if !self.reg_uses.is_empty() {
@@ -259,6 +259,23 @@ impl<'a> Context<'a> {
         // Update the live value tracker with this instruction.
         let (throughs, kills, defs) = tracker.process_inst(inst, &self.cur.func.dfg, self.liveness);
 
+        // The unwinder doesn't preserve any registers when it transfers control to the landing pad
+        // of a `call_lp`, so values that are live into the landing pad must be spilled. Values
+        // that are also live after the call are spilled below. Do this before freeing the kills
+        // so the pressure tracker stays consistent.
+        if let Some(landing_pad) = self.cur.func.dfg[inst].landing_pad() {
+            for lv in kills {
+                if lv.affinity.is_reg() && !self.spills.contains(&lv.value) &&
+                    self.liveness[lv.value].is_livein(
+                        landing_pad,
+                        self.liveness.context(&self.cur.func.layout),
+                    )
+                {
+                    self.spill_reg(lv.value);
+                }
+            }
+        }
+
         // Remove kills from the pressure tracker.
         self.free_regs(kills);
 
@@ -343,19 +360,20 @@ impl<'a> Context<'a> {
     }
 
     // Collect register uses from the ABI input constraints.
-    fn collect_abi_reg_uses(&mut self, inst: Inst, sig: SigRef) {
+    //
+    // These are the parameters of `sig` for a call, or the function's return values when `sig` is
+    // `None`.
+    fn collect_abi_reg_uses(&mut self, inst: Inst, sig: Option<SigRef>) {
         let fixed_args = self.cur.func.dfg[inst]
             .opcode()
             .constraints()
             .fixed_value_arguments();
         let args = self.cur.func.dfg.inst_variable_args(inst);
-        for (idx, (abi, &arg)) in
-            self.cur.func.dfg.signatures[sig]
-                .params
-                .iter()
-                .zip(args)
-                .enumerate()
-        {
+        let abi_args = match sig {
+            Some(sig) => &self.cur.func.dfg.signatures[sig].params,
+            None => &self.cur.func.signature.returns,
+        };
+        for (idx, (abi, &arg)) in abi_args.iter().zip(args).enumerate() {
             if abi.location.is_reg() {
                 let (rci, spilled) = match self.liveness[arg].affinity {
                     Affinity::Reg(rci) => (rci, false),
@@ -417,9 +435,11 @@ impl<'a> Context<'a> {
                     // Do allow spilling of EBB arguments on branches. This is safe since we spill
                     // the whole virtual register which includes the matching EBB parameter value
                     // at the branch destination. It is also necessary since there can be
-                    // arbitrarily many EBB arguments.
+                    // arbitrarily many EBB arguments. The arguments of an `invoke` are call
+                    // arguments, not EBB arguments.
                     match {
-                        let args = if self.cur.func.dfg[inst].opcode().is_branch() {
+                        let opcode = self.cur.func.dfg[inst].opcode();
+                        let args = if opcode.is_branch() && !opcode.is_call() {
                             self.cur.func.dfg.inst_fixed_args(inst)
                         } else {
                             self.cur.func.dfg.inst_args(inst)
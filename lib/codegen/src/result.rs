@@ -1,5 +1,6 @@
 //! Result and error types representing the outcome of compiling a function.
 
+use std::string::String;
 use verifier;
 
 /// A compilation error.
@@ -7,14 +8,14 @@ use verifier;
 /// When Cretonne fails to compile a function, it will return one of these error codes.
 #[derive(Fail, Debug, PartialEq, Eq)]
 pub enum CtonError {
-    /// An IR verifier error.
+    /// IR verifier errors.
     ///
     /// This always represents a bug, either in the code that generated IR for Cretonne, or a bug
     /// in Cretonne itself.
-    #[fail(display = "Verifier error: {}", _0)]
+    #[fail(display = "Verifier errors:\n{}", _0)]
     Verifier(
         #[cause]
-        verifier::Error
+        verifier::Errors
     ),
 
     /// An implementation limit was exceeded.
@@ -32,13 +33,25 @@ pub enum CtonError {
     /// is exceeded, compilation fails.
     #[fail(display = "Code for function is too large")]
     CodeTooLarge,
+
+    /// The function uses a feature which isn't supported with the current settings.
+    ///
+    /// For example, float vectors can't be compiled when the `enable_float` setting is false.
+    #[fail(display = "Unsupported feature: {}", _0)]
+    Unsupported(String),
 }
 
 /// A Cretonne compilation result.
 pub type CtonResult = Result<(), CtonError>;
 
+impl From<verifier::Errors> for CtonError {
+    fn from(e: verifier::Errors) -> Self {
+        CtonError::Verifier(e)
+    }
+}
+
 impl From<verifier::Error> for CtonError {
     fn from(e: verifier::Error) -> Self {
-        CtonError::Verifier(e)
+        CtonError::Verifier(e.into())
     }
 }
@@ -372,6 +372,8 @@ mod tests {
              enable_nan_canonicalization = false\n\
              enable_simd = true\n\
              enable_atomics = true\n\
+             jump_tables_enabled = true\n\
+             enable_heap_access_spectre_mitigation = false\n\
              baldrdash_prologue_words = 0\n\
              allones_funcaddrs = false\n\
              probestack_enabled = true\n\
@@ -3,11 +3,12 @@
 use dbg::DisplayList;
 use dominator_tree::{DominatorTree, DominatorTreePreorder};
 use flowgraph::ControlFlowGraph;
+use ir::instructions::BranchInfo;
 use ir::{ExpandedProgramPoint, Function};
 use regalloc::liveness::Liveness;
 use regalloc::virtregs::VirtRegs;
 use timing;
-use verifier::Result;
+use verifier::{CheckResult, Result};
 
 /// Verify conventional SSA form for `func`.
 ///
@@ -58,7 +59,7 @@ struct CssaVerifier<'a> {
 }
 
 impl<'a> CssaVerifier<'a> {
-    fn check_virtregs(&self) -> Result {
+    fn check_virtregs(&self) -> CheckResult {
         for vreg in self.virtregs.all_virtregs() {
             let values = self.virtregs.values(vreg);
 
@@ -135,11 +136,14 @@ impl<'a> CssaVerifier<'a> {
         Ok(())
     }
 
-    fn check_cssa(&self) -> Result {
+    fn check_cssa(&self) -> CheckResult {
         for ebb in self.func.layout.ebbs() {
             let ebb_params = self.func.dfg.ebb_params(ebb);
             for (_, pred) in self.cfg.pred_iter(ebb) {
-                let pred_args = self.func.dfg.inst_variable_args(pred);
+                let pred_args = match self.func.dfg.analyze_branch(pred) {
+                    BranchInfo::SingleDest(_, args) => args,
+                    _ => &[],
+                };
                 // This should have been caught by an earlier verifier pass.
                 assert_eq!(
                     ebb_params.len(),
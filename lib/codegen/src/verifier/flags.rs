@@ -8,7 +8,7 @@ use isa;
 use packed_option::PackedOption;
 use std::result;
 use timing;
-use verifier::{Error, Result};
+use verifier::{CheckResult, Error};
 
 /// Verify that CPU flags are used correctly.
 ///
@@ -26,7 +26,7 @@ pub fn verify_flags(
     func: &ir::Function,
     cfg: &ControlFlowGraph,
     isa: Option<&isa::TargetIsa>,
-) -> Result {
+) -> CheckResult {
     let _tt = timing::verify_flags();
     let mut verifier = FlagsVerifier {
         func,
@@ -47,7 +47,7 @@ struct FlagsVerifier<'a> {
 }
 
 impl<'a> FlagsVerifier<'a> {
-    fn check(&mut self) -> Result {
+    fn check(&mut self) -> CheckResult {
         // List of EBBs that need to be processed. EBBs may be re-added to this list when we detect
         // that one of their successor blocks needs a live-in flags value.
         let mut worklist = SparseSet::new();
@@ -130,6 +130,13 @@ impl<'a> FlagsVerifier<'a> {
                         }
                     }
                 }
+                BranchInfo::Invoke(dest, landing_pad) => {
+                    for &dest in &[dest, landing_pad] {
+                        if let Some(val) = self.livein[dest].expand() {
+                            merge(&mut live_val, val, inst)?;
+                        }
+                    }
+                }
             }
         }
 
@@ -139,7 +146,7 @@ impl<'a> FlagsVerifier<'a> {
 }
 
 // Merge live flags values, or return an error on conflicting values.
-fn merge(a: &mut Option<ir::Value>, b: ir::Value, inst: ir::Inst) -> Result {
+fn merge(a: &mut Option<ir::Value>, b: ir::Value, inst: ir::Inst) -> CheckResult {
     if let Some(va) = *a {
         if b != va {
             return err!(inst, "conflicting live CPU flags: {} and {}", va, b);
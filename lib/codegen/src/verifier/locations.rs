@@ -5,7 +5,7 @@ use isa;
 use regalloc::RegDiversions;
 use regalloc::liveness::Liveness;
 use timing;
-use verifier::Result;
+use verifier::{CheckResult, Result};
 
 /// Verify value locations for `func`.
 ///
@@ -45,7 +45,7 @@ struct LocationVerifier<'a> {
 
 impl<'a> LocationVerifier<'a> {
     /// Check that the assigned value locations match the operand constraints of their uses.
-    fn check_constraints(&self) -> Result {
+    fn check_constraints(&self) -> CheckResult {
         let dfg = &self.func.dfg;
         let mut divert = RegDiversions::new();
 
@@ -88,7 +88,7 @@ impl<'a> LocationVerifier<'a> {
         inst: ir::Inst,
         enc: isa::Encoding,
         divert: &RegDiversions,
-    ) -> Result {
+    ) -> CheckResult {
         let constraints = self.encinfo.operand_constraints(enc).expect(
             "check_enc_constraints requires a legal encoding",
         );
@@ -107,7 +107,7 @@ impl<'a> LocationVerifier<'a> {
 
     /// Check that the result values produced by a ghost instruction are not assigned a value
     /// location.
-    fn check_ghost_results(&self, inst: ir::Inst) -> Result {
+    fn check_ghost_results(&self, inst: ir::Inst) -> CheckResult {
         let results = self.func.dfg.inst_results(inst);
 
         for &res in results {
@@ -126,18 +126,30 @@ impl<'a> LocationVerifier<'a> {
     }
 
     /// Check the ABI argument and result locations for a call.
-    fn check_call_abi(&self, inst: ir::Inst, sig: ir::SigRef, divert: &RegDiversions) -> Result {
+    fn check_call_abi(
+        &self,
+        inst: ir::Inst,
+        sig: ir::SigRef,
+        divert: &RegDiversions,
+    ) -> CheckResult {
         let sig = &self.func.dfg.signatures[sig];
         let varargs = self.func.dfg.inst_variable_args(inst);
         let results = self.func.dfg.inst_results(inst);
 
+        // Tail calls pass stack arguments in the incoming argument area of the current function.
+        let arg_kind = if self.func.dfg[inst].opcode().is_tail_call() {
+            ir::StackSlotKind::IncomingArg
+        } else {
+            ir::StackSlotKind::OutgoingArg
+        };
+
         for (abi, &value) in sig.params.iter().zip(varargs) {
             self.check_abi_location(
                 inst,
                 value,
                 abi,
                 divert.get(value, &self.func.locations),
-                ir::StackSlotKind::OutgoingArg,
+                arg_kind,
             )?;
         }
 
@@ -155,7 +167,7 @@ impl<'a> LocationVerifier<'a> {
     }
 
     /// Check the ABI argument locations for a return.
-    fn check_return_abi(&self, inst: ir::Inst, divert: &RegDiversions) -> Result {
+    fn check_return_abi(&self, inst: ir::Inst, divert: &RegDiversions) -> CheckResult {
         let sig = &self.func.signature;
         let varargs = self.func.dfg.inst_variable_args(inst);
 
@@ -180,7 +192,7 @@ impl<'a> LocationVerifier<'a> {
         abi: &ir::AbiParam,
         loc: ir::ValueLoc,
         want_kind: ir::StackSlotKind,
-    ) -> Result {
+    ) -> CheckResult {
         match abi.location {
             ir::ArgumentLoc::Unassigned => {}
             ir::ArgumentLoc::Reg(reg) => {
@@ -233,7 +245,7 @@ impl<'a> LocationVerifier<'a> {
     }
 
     /// Update diversions to reflect the current instruction and check their consistency.
-    fn update_diversions(&self, inst: ir::Inst, divert: &mut RegDiversions) -> Result {
+    fn update_diversions(&self, inst: ir::Inst, divert: &mut RegDiversions) -> CheckResult {
         let (arg, src) = match self.func.dfg[inst] {
             ir::InstructionData::RegMove { arg, src, .. } |
             ir::InstructionData::RegSpill { arg, src, .. } => (arg, ir::ValueLoc::Reg(src)),
@@ -264,7 +276,7 @@ impl<'a> LocationVerifier<'a> {
 
     /// We have active diversions before a branch. Make sure none of the diverted values are live
     /// on the outgoing CFG edges.
-    fn check_cfg_edges(&self, inst: ir::Inst, divert: &RegDiversions) -> Result {
+    fn check_cfg_edges(&self, inst: ir::Inst, divert: &RegDiversions) -> CheckResult {
         use ir::instructions::BranchInfo::*;
 
         // We can only check CFG edges if we have a liveness analysis.
@@ -311,6 +323,22 @@ impl<'a> LocationVerifier<'a> {
                     }
                 }
             }
+            Invoke(dest, landing_pad) => {
+                for d in divert.all() {
+                    let lr = &liveness[d.value];
+                    for &ebb in &[dest, landing_pad] {
+                        if lr.is_livein(ebb, liveness.context(&self.func.layout)) {
+                            return err!(
+                                inst,
+                                "{} is diverted to {} and live in to {}",
+                                d.value,
+                                d.to.display(&self.reginfo),
+                                ebb
+                            );
+                        }
+                    }
+                }
+            }
         }
 
         Ok(())
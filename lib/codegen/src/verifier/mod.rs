@@ -41,19 +41,31 @@
 //! - All return instructions must have return value operands matching the current
 //!   function signature.
 //!
+//! Exception handling
+//!
+//! - Landing pads can only be reached from `invoke` and `call_lp` instructions, and they can't
+//!   have parameters.
+//! - The normal destination of an `invoke` can't have parameters, and it must be different from
+//!   the landing pad.
+//! - A `landingpad` instruction must be the first instruction in a landing pad.
+//! - The results of an `invoke` can't be used in its landing pad.
+//!
 //! Global variables
 //!
 //! - Detect cycles in deref(base) declarations.
 //!
-//! TODO:
 //! Ad hoc checking
 //!
 //! - Stack slot loads and stores must be in-bounds.
 //! - Immediate constraints for certain opcodes, like `udiv_imm v3, 0`.
 //! - `Insertlane` and `extractlane` instructions have immediate lane numbers that must be in
 //!   range for their polymorphic type.
+//!
+//! TODO:
+//!
 //! - Swizzle and shuffle instructions take a variable number of lane arguments. The number
 //!   of arguments must match the destination type, and the lane indexes must be in range.
+//!   There are no such instructions yet.
 
 use self::flags::verify_flags;
 use dbg::DisplayList;
@@ -117,13 +129,70 @@ impl Display for Error {
     }
 }
 
+/// All the errors found by the verifier, in the order they were found.
+#[derive(Fail, Debug, Default, PartialEq, Eq)]
+pub struct Errors(pub Vec<Error>);
+
+impl Errors {
+    /// Create an empty list of errors.
+    pub fn new() -> Self {
+        Errors(Vec::new())
+    }
+
+    /// Were no errors found?
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Record the error in `result`, if any.
+    ///
+    /// Returns `true` if `result` is `Ok`.
+    fn report(&mut self, result: CheckResult) -> bool {
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                self.0.push(e);
+                false
+            }
+        }
+    }
+
+    /// Convert to a `Result` which is `Ok` if no errors were found.
+    fn into_result(self) -> Result {
+        if self.is_empty() { Ok(()) } else { Err(self) }
+    }
+}
+
+impl From<Error> for Errors {
+    fn from(e: Error) -> Self {
+        Errors(vec![e])
+    }
+}
+
+impl Display for Errors {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (i, err) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", err)?;
+        }
+        Ok(())
+    }
+}
+
 /// Verifier result.
-pub type Result = result::Result<(), Error>;
+pub type Result = result::Result<(), Errors>;
+
+/// Result of a single verifier check, which stops at the first error.
+type CheckResult = result::Result<(), Error>;
 
-/// Verify `func`.
+/// Verify `func`, returning all the errors found.
 pub fn verify_function<'a, FOI: Into<FlagsOrIsa<'a>>>(func: &Function, fisa: FOI) -> Result {
     let _tt = timing::verifier();
-    Verifier::new(func, fisa.into()).run()
+    let mut errors = Errors::new();
+    Verifier::new(func, fisa.into()).run(&mut errors);
+    errors.into_result()
 }
 
 /// Verify `func` after checking the integrity of associated context data structures `cfg` and
@@ -136,13 +205,15 @@ pub fn verify_context<'a, FOI: Into<FlagsOrIsa<'a>>>(
 ) -> Result {
     let _tt = timing::verifier();
     let verifier = Verifier::new(func, fisa.into());
+    let mut errors = Errors::new();
     if cfg.is_valid() {
-        verifier.cfg_integrity(cfg)?;
+        errors.report(verifier.cfg_integrity(cfg));
     }
     if domtree.is_valid() {
-        verifier.domtree_integrity(domtree)?;
+        errors.report(verifier.domtree_integrity(domtree));
     }
-    verifier.run()
+    verifier.run(&mut errors);
+    errors.into_result()
 }
 
 struct Verifier<'a> {
@@ -166,28 +237,59 @@ impl<'a> Verifier<'a> {
         }
     }
 
-    // Check for cycles in the global variable declarations.
-    fn verify_global_vars(&self) -> Result {
+    // Check for cycles in the global variable declarations starting at `gv`.
+    fn verify_global_var_cycle(&self, gv: GlobalVar) -> CheckResult {
         let mut seen = SparseSet::new();
+        seen.insert(gv);
 
-        for gv in self.func.global_vars.keys() {
-            seen.clear();
-            seen.insert(gv);
+        let mut cur = gv;
+        while let ir::GlobalVarData::Deref { base, .. } = self.func.global_vars[cur] {
+            if seen.insert(base).is_some() {
+                return err!(gv, "deref cycle: {}", DisplayList(seen.as_slice()));
+            }
 
-            let mut cur = gv;
-            while let ir::GlobalVarData::Deref { base, .. } = self.func.global_vars[cur] {
-                if seen.insert(base).is_some() {
-                    return err!(gv, "deref cycle: {}", DisplayList(seen.as_slice()));
-                }
+            cur = base;
+        }
+
+        Ok(())
+    }
+
+    // Check the rules for landing pads of `invoke` and `call_lp` instructions in `ebb`.
+    fn verify_landing_pad(&self, ebb: Ebb) -> CheckResult {
+        let mut invokes = 0;
+        let mut branches = 0;
+        for (_, pred) in self.expected_cfg.pred_iter(ebb) {
+            if self.func.dfg[pred].landing_pad() == Some(ebb) {
+                invokes += 1;
+            } else {
+                branches += 1;
+            }
+        }
+        let is_landing_pad = invokes > 0;
 
-                cur = base;
+        if is_landing_pad {
+            if branches > 0 {
+                return err!(ebb, "landing pad can only be reached from invoke instructions");
+            }
+            if !self.func.dfg.ebb_params(ebb).is_empty() {
+                return err!(ebb, "landing pad can't have parameters");
             }
         }
 
+        for inst in self.func.layout.ebb_insts(ebb) {
+            if self.func.dfg[inst].opcode() == Opcode::Landingpad &&
+                (!is_landing_pad || self.func.layout.first_inst(ebb) != Some(inst))
+            {
+                return err!(
+                    inst,
+                    "landingpad must be the first instruction in a landing pad"
+                );
+            }
+        }
         Ok(())
     }
 
-    fn ebb_integrity(&self, ebb: Ebb, inst: Inst) -> Result {
+    fn ebb_integrity(&self, ebb: Ebb, inst: Inst) -> CheckResult {
         let is_terminator = self.func.dfg[inst].opcode().is_terminator();
         let is_last_inst = self.func.layout.last_inst(ebb) == Some(inst);
 
@@ -226,7 +328,7 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
-    fn instruction_integrity(&self, inst: Inst) -> Result {
+    fn instruction_integrity(&self, inst: Inst) -> CheckResult {
         let inst_data = &self.func.dfg[inst];
         let dfg = &self.func.dfg;
 
@@ -236,10 +338,11 @@ impl<'a> Verifier<'a> {
         }
 
         let fixed_results = inst_data.opcode().constraints().fixed_results();
-        // var_results is 0 if we aren't a call instruction
-        let var_results = dfg.call_signature(inst).map_or(0, |sig| {
-            dfg.signatures[sig].returns.len()
-        });
+        // var_results is 0 if we aren't a call instruction, or if this is a tail call
+        let var_results = match dfg.call_signature(inst) {
+            Some(sig) if !inst_data.opcode().is_tail_call() => dfg.signatures[sig].returns.len(),
+            _ => 0,
+        };
         let total_results = fixed_results + var_results;
 
         // All result values for multi-valued instructions are created
@@ -256,7 +359,7 @@ impl<'a> Verifier<'a> {
         self.verify_entity_references(inst)
     }
 
-    fn verify_entity_references(&self, inst: Inst) -> Result {
+    fn verify_entity_references(&self, inst: Inst) -> CheckResult {
         use ir::instructions::InstructionData::*;
 
         for &arg in self.func.dfg.inst_args(inst) {
@@ -305,13 +408,35 @@ impl<'a> Verifier<'a> {
                 self.verify_ebb(inst, destination)?;
                 self.verify_value_list(inst, args)?;
             }
-            BranchTable { table, .. } => {
+            BranchTable { table, .. } |
+            BranchTableBase { table, .. } |
+            BranchTableEntry { table, .. } => {
                 self.verify_jump_table(inst, table)?;
             }
             Call { func_ref, ref args, .. } => {
                 self.verify_func_ref(inst, func_ref)?;
                 self.verify_value_list(inst, args)?;
             }
+            Invoke {
+                ref targets,
+                ref args,
+                ..
+            } => {
+                self.verify_func_ref(inst, targets.func_ref)?;
+                self.verify_ebb(inst, targets.destination)?;
+                self.verify_ebb(inst, targets.landing_pad)?;
+                self.verify_value_list(inst, args)?;
+            }
+            CallLandingPad {
+                func_ref,
+                landing_pad,
+                ref args,
+                ..
+            } => {
+                self.verify_func_ref(inst, func_ref)?;
+                self.verify_ebb(inst, landing_pad)?;
+                self.verify_value_list(inst, args)?;
+            }
             CallIndirect { sig_ref, ref args, .. } => {
                 self.verify_sig_ref(inst, sig_ref)?;
                 self.verify_value_list(inst, args)?;
@@ -326,6 +451,9 @@ impl<'a> Verifier<'a> {
             UnaryGlobalVar { global_var, .. } => {
                 self.verify_global_var(inst, global_var)?;
             }
+            UnaryConst { constant_handle, .. } => {
+                self.verify_constant(inst, constant_handle)?;
+            }
             HeapAddr { heap, .. } => {
                 self.verify_heap(inst, heap)?;
             }
@@ -373,7 +501,7 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
-    fn verify_ebb(&self, inst: Inst, e: Ebb) -> Result {
+    fn verify_ebb(&self, inst: Inst, e: Ebb) -> CheckResult {
         if !self.func.dfg.ebb_is_valid(e) || !self.func.layout.is_ebb_inserted(e) {
             return err!(inst, "invalid ebb reference {}", e);
         }
@@ -385,7 +513,7 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
-    fn verify_sig_ref(&self, inst: Inst, s: SigRef) -> Result {
+    fn verify_sig_ref(&self, inst: Inst, s: SigRef) -> CheckResult {
         if !self.func.dfg.signatures.is_valid(s) {
             err!(inst, "invalid signature reference {}", s)
         } else {
@@ -393,7 +521,7 @@ impl<'a> Verifier<'a> {
         }
     }
 
-    fn verify_func_ref(&self, inst: Inst, f: FuncRef) -> Result {
+    fn verify_func_ref(&self, inst: Inst, f: FuncRef) -> CheckResult {
         if !self.func.dfg.ext_funcs.is_valid(f) {
             err!(inst, "invalid function reference {}", f)
         } else {
@@ -401,7 +529,7 @@ impl<'a> Verifier<'a> {
         }
     }
 
-    fn verify_stack_slot(&self, inst: Inst, ss: StackSlot) -> Result {
+    fn verify_stack_slot(&self, inst: Inst, ss: StackSlot) -> CheckResult {
         if !self.func.stack_slots.is_valid(ss) {
             err!(inst, "invalid stack slot {}", ss)
         } else {
@@ -409,7 +537,7 @@ impl<'a> Verifier<'a> {
         }
     }
 
-    fn verify_global_var(&self, inst: Inst, gv: GlobalVar) -> Result {
+    fn verify_global_var(&self, inst: Inst, gv: GlobalVar) -> CheckResult {
         if !self.func.global_vars.is_valid(gv) {
             err!(inst, "invalid global variable {}", gv)
         } else {
@@ -417,7 +545,15 @@ impl<'a> Verifier<'a> {
         }
     }
 
-    fn verify_heap(&self, inst: Inst, heap: ir::Heap) -> Result {
+    fn verify_constant(&self, inst: Inst, constant: ir::Constant) -> CheckResult {
+        if !self.func.dfg.constants.is_valid(constant) {
+            err!(inst, "invalid constant {}", constant)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn verify_heap(&self, inst: Inst, heap: ir::Heap) -> CheckResult {
         if !self.func.heaps.is_valid(heap) {
             err!(inst, "invalid heap {}", heap)
         } else {
@@ -425,7 +561,7 @@ impl<'a> Verifier<'a> {
         }
     }
 
-    fn verify_value_list(&self, inst: Inst, l: &ValueList) -> Result {
+    fn verify_value_list(&self, inst: Inst, l: &ValueList) -> CheckResult {
         if !l.is_valid(&self.func.dfg.value_lists) {
             err!(inst, "invalid value list reference {:?}", l)
         } else {
@@ -433,7 +569,7 @@ impl<'a> Verifier<'a> {
         }
     }
 
-    fn verify_jump_table(&self, inst: Inst, j: JumpTable) -> Result {
+    fn verify_jump_table(&self, inst: Inst, j: JumpTable) -> CheckResult {
         if !self.func.jump_tables.is_valid(j) {
             err!(inst, "invalid jump table reference {}", j)
         } else {
@@ -441,7 +577,7 @@ impl<'a> Verifier<'a> {
         }
     }
 
-    fn verify_value(&self, loc_inst: Inst, v: Value) -> Result {
+    fn verify_value(&self, loc_inst: Inst, v: Value) -> CheckResult {
         let dfg = &self.func.dfg;
         if !dfg.value_is_valid(v) {
             err!(loc_inst, "invalid value reference {}", v)
@@ -450,7 +586,7 @@ impl<'a> Verifier<'a> {
         }
     }
 
-    fn verify_inst_arg(&self, loc_inst: Inst, v: Value) -> Result {
+    fn verify_inst_arg(&self, loc_inst: Inst, v: Value) -> CheckResult {
         self.verify_value(loc_inst, v)?;
 
         let dfg = &self.func.dfg;
@@ -496,6 +632,12 @@ impl<'a> Verifier<'a> {
                             loc_inst
                         );
                     }
+                    // The results of an `invoke` are not available in its landing pad.
+                    if let Some(lp) = dfg[def_inst].landing_pad() {
+                        if self.expected_domtree.dominates(lp, loc_inst, &self.func.layout) {
+                            return err!(loc_inst, "uses result of {} in its landing pad", def_inst);
+                        }
+                    }
                 }
             }
             ValueDef::Param(ebb, _) => {
@@ -527,7 +669,7 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
-    fn verify_inst_result(&self, loc_inst: Inst, v: Value) -> Result {
+    fn verify_inst_result(&self, loc_inst: Inst, v: Value) -> CheckResult {
         self.verify_value(loc_inst, v)?;
 
         match self.func.dfg.value_def(v) {
@@ -552,7 +694,7 @@ impl<'a> Verifier<'a> {
         }
     }
 
-    fn domtree_integrity(&self, domtree: &DominatorTree) -> Result {
+    fn domtree_integrity(&self, domtree: &DominatorTree) -> CheckResult {
         // We consider two `DominatorTree`s to be equal if they return the same immediate
         // dominator for each EBB. Therefore the current domtree is valid if it matches the freshly
         // computed one.
@@ -612,7 +754,7 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
-    fn typecheck_entry_block_params(&self) -> Result {
+    fn typecheck_entry_block_params(&self) -> CheckResult {
         if let Some(ebb) = self.func.layout.entry_block() {
             let expected_types = &self.func.signature.params;
             let ebb_param_count = self.func.dfg.num_ebb_params(ebb);
@@ -642,7 +784,7 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
-    fn typecheck(&self, inst: Inst) -> Result {
+    fn typecheck(&self, inst: Inst) -> CheckResult {
         let inst_data = &self.func.dfg[inst];
         let constraints = inst_data.opcode().constraints();
 
@@ -663,6 +805,7 @@ impl<'a> Verifier<'a> {
 
         self.typecheck_results(inst, ctrl_type)?;
         self.typecheck_fixed_args(inst, ctrl_type)?;
+        self.typecheck_tail_call(inst)?;
         self.typecheck_variable_args(inst)?;
         self.typecheck_return(inst)?;
         self.typecheck_special(inst, ctrl_type)?;
@@ -670,7 +813,7 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
-    fn typecheck_results(&self, inst: Inst, ctrl_type: Type) -> Result {
+    fn typecheck_results(&self, inst: Inst, ctrl_type: Type) -> CheckResult {
         let mut i = 0;
         for &result in self.func.dfg.inst_results(inst) {
             let result_type = self.func.dfg.value_type(result);
@@ -699,7 +842,7 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
-    fn typecheck_fixed_args(&self, inst: Inst, ctrl_type: Type) -> Result {
+    fn typecheck_fixed_args(&self, inst: Inst, ctrl_type: Type) -> CheckResult {
         let constraints = self.func.dfg[inst].opcode().constraints();
 
         for (i, &arg) in self.func.dfg.inst_fixed_args(inst).iter().enumerate() {
@@ -734,8 +877,11 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
-    fn typecheck_variable_args(&self, inst: Inst) -> Result {
+    fn typecheck_variable_args(&self, inst: Inst) -> CheckResult {
         match self.func.dfg.analyze_branch(inst) {
+            // The variable arguments of a `call_lp` are call arguments. Its landing pad can't have
+            // parameters, which is checked by `verify_landing_pad()`.
+            BranchInfo::SingleDest(_, _) if self.func.dfg[inst].opcode().is_call() => {}
             BranchInfo::SingleDest(ebb, _) => {
                 let iter = self.func.dfg.ebb_params(ebb).iter().map(|&v| {
                     self.func.dfg.value_type(v)
@@ -755,6 +901,20 @@ impl<'a> Verifier<'a> {
                     }
                 }
             }
+            BranchInfo::Invoke(ebb, landing_pad) => {
+                if ebb == landing_pad {
+                    return err!(inst, "can't use {} as both destinations", ebb);
+                }
+                let arg_count = self.func.dfg.num_ebb_params(ebb);
+                if arg_count != 0 {
+                    return err!(
+                        inst,
+                        "takes no arguments, but had target {} with {} arguments",
+                        ebb,
+                        arg_count
+                    );
+                }
+            }
             BranchInfo::NotABranch => {}
         }
 
@@ -783,7 +943,7 @@ impl<'a> Verifier<'a> {
         &self,
         inst: Inst,
         iter: I,
-    ) -> Result {
+    ) -> CheckResult {
         let variable_args = self.func.dfg.inst_variable_args(inst);
         let mut i = 0;
 
@@ -821,8 +981,9 @@ impl<'a> Verifier<'a> {
     /// Check the locations assigned to outgoing call arguments.
     ///
     /// When a signature has been legalized, all values passed as outgoing arguments on the stack
-    /// must be assigned to a matching `OutgoingArg` stack slot.
-    fn check_outgoing_args(&self, inst: Inst, sig_ref: SigRef) -> Result {
+    /// must be assigned to a matching `OutgoingArg` stack slot. Tail calls pass their stack
+    /// arguments in the `IncomingArg` slots of the current function instead.
+    fn check_outgoing_args(&self, inst: Inst, sig_ref: SigRef) -> CheckResult {
         let sig = &self.func.dfg.signatures[sig_ref];
 
         // Before legalization, there's nothing to check.
@@ -830,6 +991,12 @@ impl<'a> Verifier<'a> {
             return Ok(());
         }
 
+        let slot_kind = if self.func.dfg[inst].opcode().is_tail_call() {
+            StackSlotKind::IncomingArg
+        } else {
+            StackSlotKind::OutgoingArg
+        };
+
         let args = self.func.dfg.inst_variable_args(inst);
         let expected_args = &sig.params[..];
 
@@ -841,7 +1008,7 @@ impl<'a> Verifier<'a> {
                     // Argument value is assigned to a stack slot as expected.
                     self.verify_stack_slot(inst, ss)?;
                     let slot = &self.func.stack_slots[ss];
-                    if slot.kind != StackSlotKind::OutgoingArg {
+                    if slot.kind != slot_kind {
                         return err!(
                             inst,
                             "Outgoing stack argument {} in wrong stack slot: {} = {}",
@@ -884,7 +1051,7 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
-    fn typecheck_return(&self, inst: Inst) -> Result {
+    fn typecheck_return(&self, inst: Inst) -> CheckResult {
         if self.func.dfg[inst].opcode().is_return() {
             let args = self.func.dfg.inst_variable_args(inst);
             let expected_types = &self.func.signature.returns;
@@ -908,9 +1075,58 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
+    /// Check that a tail call can reuse the stack frame of the current function.
+    ///
+    /// The callee returns directly to our caller, so it must use the same calling convention and
+    /// produce the same return values as the current function. Its stack arguments are passed in
+    /// our incoming argument area, so they must fit there.
+    fn typecheck_tail_call(&self, inst: Inst) -> CheckResult {
+        if !self.func.dfg[inst].opcode().is_tail_call() {
+            return Ok(());
+        }
+        let sig_ref = self.func.dfg.call_signature(inst).unwrap();
+        let sig = &self.func.dfg.signatures[sig_ref];
+
+        if sig.call_conv != self.func.signature.call_conv {
+            return err!(
+                inst,
+                "tail call to {} function from {} function",
+                sig.call_conv,
+                self.func.signature.call_conv
+            );
+        }
+
+        // The prologue insertion adds frame pointer and callee-saved register returns to the
+        // current function's signature. Those are restored by the epilogue before the tail call.
+        let callee_returns = sig.returns.iter().filter(|r| !is_frame_return(r));
+        let caller_returns = self.func.signature.returns.iter().filter(|r| !is_frame_return(r));
+        if !callee_returns.eq(caller_returns) {
+            return err!(
+                inst,
+                "tail call return values must match function signature, got {}",
+                sig_ref
+            );
+        }
+
+        if let (Some(callee_bytes), Some(caller_bytes)) =
+            (sig.argument_bytes, self.func.signature.argument_bytes)
+        {
+            if callee_bytes > caller_bytes {
+                return err!(
+                    inst,
+                    "tail call needs {} bytes of stack arguments, but only {} are available",
+                    callee_bytes,
+                    caller_bytes
+                );
+            }
+        }
+
+        Ok(())
+    }
+
     // Check special-purpose type constraints that can't be expressed in the normal opcode
     // constraints.
-    fn typecheck_special(&self, inst: Inst, ctrl_type: Type) -> Result {
+    fn typecheck_special(&self, inst: Inst, ctrl_type: Type) -> CheckResult {
         if let ir::InstructionData::Unary { opcode, arg } = self.func.dfg[inst] {
             let arg_type = self.func.dfg.value_type(arg);
             match opcode {
@@ -956,7 +1172,85 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
-    fn cfg_integrity(&self, cfg: &ControlFlowGraph) -> Result {
+    // Check the constraints on immediate operands that can't be expressed by their types.
+    fn verify_immediates(&self, inst: Inst) -> CheckResult {
+        let dfg = &self.func.dfg;
+        match dfg[inst] {
+            ir::InstructionData::StackLoad {
+                opcode,
+                stack_slot,
+                offset,
+            } => {
+                // A `stack_addr` only needs the addressed byte to be inside the slot.
+                let ty = if opcode == Opcode::StackAddr {
+                    types::I8
+                } else {
+                    dfg.ctrl_typevar(inst)
+                };
+                self.verify_stack_access(inst, stack_slot, offset, ty)
+            }
+            ir::InstructionData::StackStore {
+                arg,
+                stack_slot,
+                offset,
+                ..
+            } => self.verify_stack_access(inst, stack_slot, offset, dfg.value_type(arg)),
+            ir::InstructionData::BinaryImm { opcode, arg, imm } => {
+                let bits = dfg.value_type(arg).lane_bits();
+                // Sign-extend the immediate from the width of the type.
+                let shift = 64 - bits;
+                let imm: i64 = imm.into();
+                let divisor = imm << shift >> shift;
+                match opcode {
+                    Opcode::UdivImm | Opcode::UremImm if divisor == 0 => {
+                        err!(inst, "{} by zero is not allowed", opcode)
+                    }
+                    Opcode::SdivImm | Opcode::SremImm if divisor == 0 || divisor == -1 => {
+                        err!(inst, "{} by {} is not allowed", opcode, divisor)
+                    }
+                    _ => Ok(()),
+                }
+            }
+            ir::InstructionData::InsertLane { lane, args, .. } => {
+                self.verify_lane(inst, lane, dfg.value_type(args[0]))
+            }
+            ir::InstructionData::ExtractLane { lane, arg, .. } => {
+                self.verify_lane(inst, lane, dfg.value_type(arg))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn verify_stack_access(
+        &self,
+        inst: Inst,
+        ss: StackSlot,
+        offset: ir::immediates::Offset32,
+        ty: Type,
+    ) -> CheckResult {
+        let offset: i32 = offset.into();
+        let size = self.func.stack_slots[ss].size;
+        if offset < 0 || offset as u64 + u64::from(ty.bytes()) > u64::from(size) {
+            return err!(
+                inst,
+                "{} access at offset {} is out of bounds for {} with size {}",
+                ty,
+                offset,
+                ss,
+                size
+            );
+        }
+        Ok(())
+    }
+
+    fn verify_lane(&self, inst: Inst, lane: u8, ty: Type) -> CheckResult {
+        if u16::from(lane) >= ty.lane_count() {
+            return err!(inst, "lane {} is out of range for {}", lane, ty);
+        }
+        Ok(())
+    }
+
+    fn cfg_integrity(&self, cfg: &ControlFlowGraph) -> CheckResult {
         let mut expected_succs = BTreeSet::<Ebb>::new();
         let mut got_succs = BTreeSet::<Ebb>::new();
         let mut expected_preds = BTreeSet::<Inst>::new();
@@ -1007,7 +1301,7 @@ impl<'a> Verifier<'a> {
 
     /// If the verifier has been set up with an ISA, make sure that the recorded encoding for the
     /// instruction (if any) matches how the ISA would encode it.
-    fn verify_encoding(&self, inst: Inst) -> Result {
+    fn verify_encoding(&self, inst: Inst) -> CheckResult {
         // When the encodings table is empty, we don't require any instructions to be encoded.
         //
         // Once some instructions are encoded, we require all side-effecting instructions to have a
@@ -1116,7 +1410,7 @@ impl<'a> Verifier<'a> {
 
     /// Verify the `return_at_end` property which requires that there are no internal return
     /// instructions.
-    fn verify_return_at_end(&self) -> Result {
+    fn verify_return_at_end(&self) -> CheckResult {
         for ebb in self.func.layout.ebbs() {
             let inst = self.func.layout.last_inst(ebb).unwrap();
             if self.func.dfg[inst].opcode().is_return() &&
@@ -1129,46 +1423,71 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
-    pub fn run(&self) -> Result {
-        self.verify_global_vars()?;
-        self.typecheck_entry_block_params()?;
+    /// Run all the checks, adding any errors found to `errors`.
+    ///
+    /// Each instruction is checked independently, so an error in one instruction doesn't hide the
+    /// errors in the rest of the function. The checks of a single instruction stop at the first
+    /// error since the later checks depend on the earlier ones.
+    pub fn run(&self, errors: &mut Errors) {
+        for gv in self.func.global_vars.keys() {
+            errors.report(self.verify_global_var_cycle(gv));
+        }
+        errors.report(self.typecheck_entry_block_params());
         for ebb in self.func.layout.ebbs() {
+            errors.report(self.verify_landing_pad(ebb));
             for inst in self.func.layout.ebb_insts(ebb) {
-                self.ebb_integrity(ebb, inst)?;
-                self.instruction_integrity(inst)?;
-                self.typecheck(inst)?;
-                self.verify_encoding(inst)?;
+                errors.report(self.verify_inst(ebb, inst));
             }
         }
 
         if self.flags.return_at_end() {
-            self.verify_return_at_end()?;
+            errors.report(self.verify_return_at_end());
+        }
+
+        // The flags analysis assumes that the function is otherwise valid.
+        if errors.is_empty() {
+            errors.report(verify_flags(self.func, &self.expected_cfg, self.isa));
         }
+    }
 
-        verify_flags(self.func, &self.expected_cfg, self.isa)?;
+    fn verify_inst(&self, ebb: Ebb, inst: Inst) -> CheckResult {
+        self.ebb_integrity(ebb, inst)?;
+        self.instruction_integrity(inst)?;
+        self.typecheck(inst)?;
+        self.verify_immediates(inst)?;
+        self.verify_encoding(inst)
+    }
+}
 
-        Ok(())
+/// Is `abi` a return value that is only added to hold on to a register restored by the epilogue?
+fn is_frame_return(abi: &ir::AbiParam) -> bool {
+    match abi.purpose {
+        ir::ArgumentPurpose::FramePointer | ir::ArgumentPurpose::CalleeSaved => true,
+        _ => false,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Error, Verifier};
+    use super::{verify_function, Errors};
+    use cursor::{Cursor, FuncCursor};
     use entity::EntityList;
     use ir::instructions::{InstructionData, Opcode};
-    use ir::Function;
+    use ir::immediates::Ieee32;
+    use ir::types::I32;
+    use ir::{AbiParam, ArgumentLoc, ExtFuncData, ExternalName, Function, InstBuilder, Signature};
     use settings;
 
     macro_rules! assert_err_with_msg {
         ($e:expr, $msg:expr) => {
             match $e {
                 Ok(_) => panic!("Expected an error"),
-                Err(Error { message, .. }) => {
-                    if !message.contains($msg) {
+                Err(Errors(errors)) => {
+                    if !errors.iter().any(|e| e.message.contains($msg)) {
                         #[cfg(feature = "std")]
                         panic!(format!(
-                            "'{}' did not contain the substring '{}'",
-                            message, $msg
+                            "'{:?}' did not contain the substring '{}'",
+                            errors, $msg
                         ));
                         #[cfg(not(feature = "std"))]
                         panic!("error message did not contain the expected substring");
@@ -1182,8 +1501,7 @@ mod tests {
     fn empty() {
         let func = Function::new();
         let flags = &settings::Flags::new(settings::builder());
-        let verifier = Verifier::new(&func, flags.into());
-        assert_eq!(verifier.run(), Ok(()));
+        assert_eq!(verify_function(&func, flags), Ok(()));
     }
 
     #[test]
@@ -1205,7 +1523,71 @@ mod tests {
             ebb0,
         );
         let flags = &settings::Flags::new(settings::builder());
-        let verifier = Verifier::new(&func, flags.into());
-        assert_err_with_msg!(verifier.run(), "instruction format");
+        assert_err_with_msg!(verify_function(&func, flags), "instruction format");
+    }
+
+    #[test]
+    fn tail_call_stack_arguments() {
+        // The callee needs 8 bytes of stack arguments, but we only received 4.
+        let mut func = Function::new();
+        func.signature.params.push(AbiParam {
+            location: ArgumentLoc::Stack(0),
+            ..AbiParam::new(I32)
+        });
+        func.signature.compute_argument_bytes();
+
+        let mut sig = Signature::new(func.signature.call_conv);
+        for offset in 0..2 {
+            sig.params.push(AbiParam {
+                location: ArgumentLoc::Stack(offset * 4),
+                ..AbiParam::new(I32)
+            });
+        }
+        sig.compute_argument_bytes();
+        let sig = func.import_signature(sig);
+        let callee = func.import_function(ExtFuncData {
+            name: ExternalName::testcase("callee"),
+            signature: sig,
+            colocated: false,
+        });
+
+        let ebb0 = func.dfg.make_ebb();
+        let arg = func.dfg.append_ebb_param(ebb0, I32);
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        pos.ins().return_call(callee, &[arg, arg]);
+
+        let flags = &settings::Flags::new(settings::builder());
+        assert_err_with_msg!(
+            verify_function(&func, flags),
+            "tail call needs 8 bytes of stack arguments, but only 4 are available"
+        );
+    }
+
+    #[test]
+    fn multiple_errors() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        let bad1 = pos.ins().f32const(Ieee32::with_float(0.0));
+        let bad2 = pos.ins().f32const(Ieee32::with_float(0.0));
+        pos.ins().return_(&[]);
+
+        // Corrupt two independent instructions.
+        let inst1 = pos.func.dfg.value_def(bad1).unwrap_inst();
+        let inst2 = pos.func.dfg.value_def(bad2).unwrap_inst();
+        for &inst in &[inst1, inst2] {
+            pos.func.dfg[inst] = InstructionData::UnaryImm {
+                opcode: Opcode::F32const,
+                imm: 0.into(),
+            };
+        }
+
+        let flags = &settings::Flags::new(settings::builder());
+        let errors = verify_function(&func, flags).unwrap_err().0;
+        let locations: Vec<_> = errors.iter().map(|e| e.location).collect();
+        assert!(locations.contains(&inst1.into()), "{:?}", errors);
+        assert!(locations.contains(&inst2.into()), "{:?}", errors);
     }
 }
@@ -3,9 +3,11 @@
 //! The `write` module provides the `write_function` function which converts an IR `Function` to an
 //! equivalent textual form. This textual form can be read back by the `cretonne-reader` crate.
 
-use ir::{DataFlowGraph, Ebb, Function, Inst, SigRef, Type, Value, ValueDef};
+use ir::entities::AnyEntity;
+use ir::{DataFlowGraph, Ebb, Function, Inst, ProgramOrder, SigRef, Type, Value, ValueDef};
 use isa::{RegInfo, TargetIsa};
 use packed_option::ReservedValue;
+use std::cmp::Ordering;
 use std::fmt::{self, Error, Result, Write};
 use std::result;
 use std::string::String;
@@ -13,18 +15,35 @@ use std::string::String;
 /// Write `func` to `w` as equivalent text.
 /// Use `isa` to emit ISA-dependent annotations.
 pub fn write_function(w: &mut Write, func: &Function, isa: Option<&TargetIsa>) -> Result {
+    decorate_function(w, func, isa, &mut |_, _| Ok(()))
+}
+
+/// Write `func` to `w` like `write_function()`, calling `annotate` after each line that defines
+/// an entity.
+///
+/// The `annotate` callback can write extra lines describing the entity, for example comments with
+/// error messages. It is called with `AnyEntity::Function` after the function header, with the
+/// preamble entities and EBBs after their definitions, and with each instruction after it is
+/// written.
+pub fn decorate_function(
+    w: &mut Write,
+    func: &Function,
+    isa: Option<&TargetIsa>,
+    annotate: &mut FnMut(&mut Write, AnyEntity) -> Result,
+) -> Result {
     let regs = isa.map(TargetIsa::register_info);
     let regs = regs.as_ref();
 
     write!(w, "function ")?;
     write_spec(w, func, regs)?;
     writeln!(w, " {{")?;
-    let mut any = write_preamble(w, func, regs)?;
+    annotate(w, AnyEntity::Function)?;
+    let mut any = write_preamble(w, func, regs, annotate)?;
     for ebb in &func.layout {
         if any {
             writeln!(w)?;
         }
-        write_ebb(w, func, isa, ebb)?;
+        decorate_ebb(w, func, isa, ebb, annotate)?;
         any = true;
     }
     writeln!(w, "}}")
@@ -42,22 +61,26 @@ fn write_preamble(
     w: &mut Write,
     func: &Function,
     regs: Option<&RegInfo>,
+    annotate: &mut FnMut(&mut Write, AnyEntity) -> Result,
 ) -> result::Result<bool, Error> {
     let mut any = false;
 
     for (ss, slot) in func.stack_slots.iter() {
         any = true;
         writeln!(w, "    {} = {}", ss, slot)?;
+        annotate(w, ss.into())?;
     }
 
     for (gv, gv_data) in func.global_vars.iter() {
         any = true;
         writeln!(w, "    {} = {}", gv, gv_data)?;
+        annotate(w, gv.into())?;
     }
 
     for (heap, heap_data) in func.heaps.iter() {
         any = true;
         writeln!(w, "    {} = {}", heap, heap_data)?;
+        annotate(w, heap.into())?;
     }
 
     // Write out all signatures before functions since function declarations can refer to
@@ -65,18 +88,27 @@ fn write_preamble(
     for (sig, sig_data) in func.dfg.signatures.iter() {
         any = true;
         writeln!(w, "    {} = {}", sig, sig_data.display(regs))?;
+        annotate(w, sig.into())?;
     }
 
     for (fnref, ext_func) in func.dfg.ext_funcs.iter() {
         any = true;
         if ext_func.signature != SigRef::reserved_value() {
             writeln!(w, "    {} = {}", fnref, ext_func)?;
+            annotate(w, fnref.into())?;
         }
     }
 
     for (jt, jt_data) in func.jump_tables.iter() {
         any = true;
         writeln!(w, "    {} = {}", jt, jt_data)?;
+        annotate(w, jt.into())?;
+    }
+
+    for (constant, data) in func.dfg.constants.iter() {
+        any = true;
+        writeln!(w, "    {} = {}", constant, data)?;
+        annotate(w, constant.into())?;
     }
 
     Ok(any)
@@ -132,7 +164,13 @@ pub fn write_ebb_header(
     writeln!(w, "):")
 }
 
-pub fn write_ebb(w: &mut Write, func: &Function, isa: Option<&TargetIsa>, ebb: Ebb) -> Result {
+fn decorate_ebb(
+    w: &mut Write,
+    func: &Function,
+    isa: Option<&TargetIsa>,
+    ebb: Ebb,
+    annotate: &mut FnMut(&mut Write, AnyEntity) -> Result,
+) -> Result {
     // Indent all instructions if any encodings are present.
     let indent = if func.encodings.is_empty() && func.srclocs.is_empty() {
         4
@@ -141,8 +179,10 @@ pub fn write_ebb(w: &mut Write, func: &Function, isa: Option<&TargetIsa>, ebb: E
     };
 
     write_ebb_header(w, func, isa, ebb, indent)?;
+    annotate(w, ebb.into())?;
     for inst in func.layout.ebb_insts(ebb) {
         write_instruction(w, func, isa, inst, indent)?;
+        annotate(w, inst.into())?;
     }
     Ok(())
 }
@@ -165,14 +205,19 @@ fn type_suffix(func: &Function, inst: Inst) -> Option<Type> {
     }
 
     // If the controlling type variable can be inferred from the type of the designated value input
-    // operand, we don't need the type suffix.
+    // operand, we don't need the type suffix. The parser can only do that when the operand is
+    // defined before `inst`.
     if constraints.use_typevar_operand() {
         let ctrl_var = inst_data.typevar_operand(&func.dfg.value_lists).unwrap();
-        let def_ebb = match func.dfg.value_def(ctrl_var) {
-            ValueDef::Result(instr, _) => func.layout.inst_ebb(instr),
-            ValueDef::Param(ebb, _) => Some(ebb),
+        let ebb = func.layout.inst_ebb(inst);
+        let defined_before = match func.dfg.value_def(ctrl_var) {
+            ValueDef::Result(instr, _) => {
+                func.layout.inst_ebb(instr) == ebb &&
+                    func.layout.cmp(instr, inst) == Ordering::Less
+            }
+            ValueDef::Param(def_ebb, _) => Some(def_ebb) == ebb,
         };
-        if def_ebb.is_some() && def_ebb == func.layout.inst_ebb(inst) {
+        if defined_before {
             return None;
         }
     }
@@ -277,6 +322,7 @@ pub fn write_operands(
         UnaryIeee64 { imm, .. } => write!(w, " {}", imm),
         UnaryBool { imm, .. } => write!(w, " {}", imm),
         UnaryGlobalVar { global_var, .. } => write!(w, " {}", global_var),
+        UnaryConst { constant_handle, .. } => write!(w, " {}", constant_handle),
         Binary { args, .. } => write!(w, " {}, {}", args[0], args[1]),
         BinaryImm { arg, imm, .. } => write!(w, " {}, {}", arg, imm),
         Ternary { args, .. } => write!(w, " {}, {}, {}", args[0], args[1], args[2]),
@@ -346,9 +392,41 @@ pub fn write_operands(
             write_ebb_args(w, &args[2..])
         }
         BranchTable { arg, table, .. } => write!(w, " {}, {}", arg, table),
+        BranchTableBase { table, .. } => write!(w, " {}", table),
+        BranchTableEntry { args, table, .. } => {
+            write!(w, " {}, {}, {}", args[0], args[1], table)
+        }
         Call { func_ref, ref args, .. } => {
             write!(w, " {}({})", func_ref, DisplayValues(args.as_slice(pool)))
         }
+        Invoke {
+            ref targets,
+            ref args,
+            ..
+        } => {
+            write!(
+                w,
+                " {}({}), {}, {}",
+                targets.func_ref,
+                DisplayValues(args.as_slice(pool)),
+                targets.destination,
+                targets.landing_pad
+            )
+        }
+        CallLandingPad {
+            func_ref,
+            landing_pad,
+            ref args,
+            ..
+        } => {
+            write!(
+                w,
+                " {}({}), {}",
+                func_ref,
+                DisplayValues(args.as_slice(pool)),
+                landing_pad
+            )
+        }
         CallIndirect { sig_ref, ref args, .. } => {
             let args = args.as_slice(pool);
             write!(
@@ -499,8 +577,9 @@ impl<'a> fmt::Display for DisplayValuesWithDelimiter<'a> {
 
 #[cfg(test)]
 mod tests {
+    use cursor::{Cursor, FuncCursor};
     use ir::types;
-    use ir::{ExternalName, Function, StackSlotData, StackSlotKind};
+    use ir::{ExternalName, Function, InstBuilder, StackSlotData, StackSlotKind};
     use std::string::ToString;
 
     #[test]
@@ -536,4 +615,27 @@ mod tests {
             "function %foo() fast {\n    ss0 = explicit_slot 4\n\nebb0(v0: i8, v1: f32x4):\n}\n"
         );
     }
+
+    #[test]
+    fn type_suffix() {
+        let mut func = Function::new();
+        func.name = ExternalName::testcase("foo");
+        let ebb = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb, types::I32);
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb);
+        let v1 = pos.ins().iadd(v0, v0);
+
+        // The type can't be inferred from a value defined later.
+        pos.goto_first_inst(ebb);
+        pos.ins().iadd(v1, v0);
+        assert_eq!(
+            pos.func.to_string(),
+            "function %foo() fast {\n\
+             ebb0(v0: i32):\n    \
+             v2 = iadd.i32 v1, v0\n    \
+             v1 = iadd v0, v0\n\
+             }\n"
+        );
+    }
 }
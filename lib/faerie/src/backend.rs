@@ -6,11 +6,12 @@ use cretonne_codegen::isa::TargetIsa;
 use cretonne_codegen::{self, binemit, ir};
 use cretonne_module::{Backend, DataContext, Linkage, ModuleNamespace, Init, DataDescription,
                       ModuleError};
-use failure::Error;
+use failure::{err_msg, Error};
 use faerie;
 use std::fs::File;
+use std::sync::Arc;
 use target;
-use traps::{FaerieTrapManifest, FaerieTrapSink};
+use traps::{trap_code_number, FaerieTrapManifest, FaerieTrapSink};
 
 #[derive(Debug)]
 /// Setting to enable collection of traps. Setting this to `Enabled` in
@@ -85,6 +86,61 @@ impl FaerieBuilder {
             ir::LibCall::TruncF64 => "trunc".to_owned(),
             ir::LibCall::NearestF32 => "nearbyintf".to_owned(),
             ir::LibCall::NearestF64 => "nearbyint".to_owned(),
+            ir::LibCall::UdivI64 => "__udivdi3".to_owned(),
+            ir::LibCall::SdivI64 => "__divdi3".to_owned(),
+            ir::LibCall::UremI64 => "__umoddi3".to_owned(),
+            ir::LibCall::SremI64 => "__moddi3".to_owned(),
+            ir::LibCall::Memcpy => "memcpy".to_owned(),
+            ir::LibCall::Memset => "memset".to_owned(),
+            ir::LibCall::Memmove => "memmove".to_owned(),
+            ir::LibCall::AddF32 => "__addsf3".to_owned(),
+            ir::LibCall::AddF64 => "__adddf3".to_owned(),
+            ir::LibCall::SubF32 => "__subsf3".to_owned(),
+            ir::LibCall::SubF64 => "__subdf3".to_owned(),
+            ir::LibCall::MulF32 => "__mulsf3".to_owned(),
+            ir::LibCall::MulF64 => "__muldf3".to_owned(),
+            ir::LibCall::DivF32 => "__divsf3".to_owned(),
+            ir::LibCall::DivF64 => "__divdf3".to_owned(),
+            ir::LibCall::SqrtF32 => "sqrtf".to_owned(),
+            ir::LibCall::SqrtF64 => "sqrt".to_owned(),
+            ir::LibCall::FmaF32 => "fmaf".to_owned(),
+            ir::LibCall::FmaF64 => "fma".to_owned(),
+            ir::LibCall::FminF32 => "fminf".to_owned(),
+            ir::LibCall::FminF64 => "fmin".to_owned(),
+            ir::LibCall::FmaxF32 => "fmaxf".to_owned(),
+            ir::LibCall::FmaxF64 => "fmax".to_owned(),
+            ir::LibCall::EqF32 => "__eqsf2".to_owned(),
+            ir::LibCall::EqF64 => "__eqdf2".to_owned(),
+            ir::LibCall::NeF32 => "__nesf2".to_owned(),
+            ir::LibCall::NeF64 => "__nedf2".to_owned(),
+            ir::LibCall::LtF32 => "__ltsf2".to_owned(),
+            ir::LibCall::LtF64 => "__ltdf2".to_owned(),
+            ir::LibCall::LeF32 => "__lesf2".to_owned(),
+            ir::LibCall::LeF64 => "__ledf2".to_owned(),
+            ir::LibCall::GtF32 => "__gtsf2".to_owned(),
+            ir::LibCall::GtF64 => "__gtdf2".to_owned(),
+            ir::LibCall::GeF32 => "__gesf2".to_owned(),
+            ir::LibCall::GeF64 => "__gedf2".to_owned(),
+            ir::LibCall::UnordF32 => "__unordsf2".to_owned(),
+            ir::LibCall::UnordF64 => "__unorddf2".to_owned(),
+            ir::LibCall::PromoteF32 => "__extendsfdf2".to_owned(),
+            ir::LibCall::DemoteF64 => "__truncdfsf2".to_owned(),
+            ir::LibCall::F32ToI32 => "__fixsfsi".to_owned(),
+            ir::LibCall::F32ToI64 => "__fixsfdi".to_owned(),
+            ir::LibCall::F64ToI32 => "__fixdfsi".to_owned(),
+            ir::LibCall::F64ToI64 => "__fixdfdi".to_owned(),
+            ir::LibCall::F32ToU32 => "__fixunssfsi".to_owned(),
+            ir::LibCall::F32ToU64 => "__fixunssfdi".to_owned(),
+            ir::LibCall::F64ToU32 => "__fixunsdfsi".to_owned(),
+            ir::LibCall::F64ToU64 => "__fixunsdfdi".to_owned(),
+            ir::LibCall::I32ToF32 => "__floatsisf".to_owned(),
+            ir::LibCall::I32ToF64 => "__floatsidf".to_owned(),
+            ir::LibCall::I64ToF32 => "__floatdisf".to_owned(),
+            ir::LibCall::I64ToF64 => "__floatdidf".to_owned(),
+            ir::LibCall::U32ToF32 => "__floatunsisf".to_owned(),
+            ir::LibCall::U32ToF64 => "__floatunsidf".to_owned(),
+            ir::LibCall::U64ToF32 => "__floatundisf".to_owned(),
+            ir::LibCall::U64ToF64 => "__floatundidf".to_owned(),
         })
     }
 }
@@ -92,7 +148,7 @@ impl FaerieBuilder {
 
 /// A `FaerieBackend` implements `Backend` and emits ".o" files using the `faerie` library.
 pub struct FaerieBackend {
-    isa: Box<TargetIsa>,
+    isa: Arc<TargetIsa>,
     artifact: faerie::Artifact,
     format: container::Format,
     trap_manifest: Option<FaerieTrapManifest>,
@@ -121,7 +177,7 @@ impl Backend for FaerieBackend {
     /// Create a new `FaerieBackend` using the given Cretonne target.
     fn new(builder: FaerieBuilder) -> Self {
         Self {
-            isa: builder.isa,
+            isa: Arc::from(builder.isa),
             artifact: faerie::Artifact::new(builder.faerie_target, builder.name),
             format: builder.format,
             trap_manifest: match builder.collect_traps {
@@ -136,6 +192,10 @@ impl Backend for FaerieBackend {
         &*self.isa
     }
 
+    fn shared_isa(&self) -> Arc<TargetIsa> {
+        self.isa.clone()
+    }
+
     fn declare_function(&mut self, name: &str, linkage: Linkage) {
         self.artifact
             .declare(name, translate_function_linkage(linkage))
@@ -195,6 +255,11 @@ impl Backend for FaerieBackend {
         self.artifact.define(name, code).expect(
             "inconsistent declaration",
         );
+
+        // TODO: Functions with landing pads need a `.gcc_except_table` entry and `.eh_frame`
+        // unwind info pointing at it, which faerie can't emit yet. Until then, the landing pads in
+        // the object file are unreachable.
+
         Ok(FaerieCompiledFunction {})
     }
 
@@ -292,6 +357,7 @@ impl Backend for FaerieBackend {
 
     fn finish(self) -> FaerieProduct {
         FaerieProduct {
+            pointer_bytes: if self.isa.flags().is_64bit() { 8 } else { 4 },
             artifact: self.artifact,
             format: self.format,
             trap_manifest: self.trap_manifest,
@@ -310,6 +376,8 @@ pub struct FaerieProduct {
     pub trap_manifest: Option<FaerieTrapManifest>,
     /// The format that the builder specified for output.
     format: container::Format,
+    /// The size of a pointer on the target.
+    pointer_bytes: usize,
 }
 
 impl FaerieProduct {
@@ -318,6 +386,55 @@ impl FaerieProduct {
         &self.artifact.name
     }
 
+    /// Add the trap manifest to the artifact as a global read-only data object named `name`, so
+    /// that a runtime linked with the object file can find the trap code of a faulting
+    /// instruction.
+    ///
+    /// The data object starts with the number of trap sites as a pointer-sized integer. It is
+    /// followed by an entry for each trap site, holding the address of the function, the 32-bit
+    /// offset of the trap site in the function, and the 32-bit trap code number given by
+    /// [`trap_code_number`](traps/fn.trap_code_number.html). All numbers are little-endian.
+    ///
+    /// This requires `FaerieTrapCollection::Enabled`.
+    pub fn define_trap_table(&mut self, name: &str) -> Result<(), Error> {
+        let sites = match self.trap_manifest {
+            Some(ref manifest) => {
+                manifest
+                    .sinks
+                    .iter()
+                    .flat_map(|sink| sink.sites.iter().map(move |site| (&sink.name, site)))
+                    .collect::<Vec<_>>()
+            }
+            None => return Err(err_msg("trap collection is disabled")),
+        };
+
+        let mut bytes = Vec::new();
+        push_le(&mut bytes, sites.len() as u64, self.pointer_bytes);
+        for &(_, site) in &sites {
+            push_le(&mut bytes, 0, self.pointer_bytes);
+            push_le(&mut bytes, u64::from(site.offset), 4);
+            push_le(&mut bytes, u64::from(trap_code_number(site.code)), 4);
+        }
+
+        self.artifact.declare(
+            name,
+            faerie::Decl::Data {
+                global: true,
+                writeable: false,
+            },
+        )?;
+        self.artifact.define(name, bytes)?;
+        let entry_size = self.pointer_bytes + 8;
+        for (index, &(func_name, _)) in sites.iter().enumerate() {
+            self.artifact.link(faerie::Link {
+                from: name,
+                to: func_name,
+                at: self.pointer_bytes + index * entry_size,
+            })?;
+        }
+        Ok(())
+    }
+
     /// Call `emit` on the faerie `Artifact`, producing bytes in memory.
     pub fn emit(&self) -> Result<Vec<u8>, Error> {
         match self.format {
@@ -335,6 +452,13 @@ impl FaerieProduct {
     }
 }
 
+/// Append the `size` low bytes of `value` to `bytes` in little-endian order.
+fn push_le(bytes: &mut Vec<u8>, value: u64, size: usize) {
+    for i in 0..size {
+        bytes.push((value >> (8 * i)) as u8);
+    }
+}
+
 fn translate_function_linkage(linkage: Linkage) -> faerie::Decl {
     match linkage {
         Linkage::Import => faerie::Decl::FunctionImport,
@@ -420,7 +544,11 @@ impl<'a> RelocSink for FaerieRelocSink<'a> {
             .expect("faerie relocation error");
     }
 
-    fn reloc_jt(&mut self, _offset: CodeOffset, _reloc: Reloc, _jt: ir::JumpTable) {
-        unimplemented!();
+    fn reloc_jt(&mut self, _offset: CodeOffset, reloc: Reloc, _jt: ir::JumpTable) {
+        match reloc {
+            // Nothing to patch, see the documentation of `Reloc::X86PCRelRodata4`.
+            Reloc::X86PCRelRodata4 => {}
+            _ => panic!("unsupported jump table relocation {:?}", reloc),
+        }
     }
 }
@@ -62,3 +62,24 @@ impl FaerieTrapManifest {
         self.sinks.push(sink);
     }
 }
+
+/// Get the number representing `code` in a trap table written by
+/// [`FaerieProduct::define_trap_table`](../struct.FaerieProduct.html#method.define_trap_table).
+///
+/// The built-in trap codes are numbered from 0 in the order they are declared in `TrapCode`, and
+/// `user(n)` is `0x10000 + n`.
+pub fn trap_code_number(code: ir::TrapCode) -> u32 {
+    use cretonne_codegen::ir::TrapCode::*;
+    match code {
+        StackOverflow => 0,
+        HeapOutOfBounds => 1,
+        OutOfBounds => 2,
+        IndirectCallToNull => 3,
+        BadSignature => 4,
+        IntegerOverflow => 5,
+        IntegerDivisionByZero => 6,
+        BadConversionToInteger => 7,
+        Interrupt => 8,
+        User(n) => 0x10000 + u32::from(n),
+    }
+}
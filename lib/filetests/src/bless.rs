@@ -0,0 +1,313 @@
+//! Rewriting test expectations for `cton-util test --bless`.
+//!
+//! When blessing, a failing test doesn't fail. Instead, the expectations in the test file are
+//! rewritten in place from the actual output:
+//!
+//! - The filecheck directives of a function are replaced by `check:` and `nextln:` directives
+//!   matching the printed function, for tests that send the printed function to filecheck.
+//! - Individual directives such as `bin:` are replaced with the actual output for the instruction
+//!   they are attached to.
+//!
+//! Other comments, including directives in the file preamble, are left alone.
+
+use cretonne_reader::{Comment, Details};
+use filecheck::{CheckerBuilder, NO_VARIABLES};
+use std::ops::Range;
+use subtest::Result;
+
+/// Output recorded while running the tests on a single function.
+pub struct Blessing<'a> {
+    /// Text sent to filecheck by the tests that passed, or were blessed.
+    outputs: Vec<String>,
+
+    /// Text that the filecheck directives should be rewritten to match.
+    expected: Option<String>,
+
+    /// Directive text to replace. The slices point into the test file source.
+    replacements: Vec<(&'a str, String)>,
+}
+
+/// A replacement of a byte range in the test file source.
+pub struct Edit {
+    range: Range<usize>,
+    text: String,
+}
+
+impl<'a> Blessing<'a> {
+    /// Create a blessing with nothing to rewrite.
+    pub fn new() -> Self {
+        Self {
+            outputs: Vec::new(),
+            expected: None,
+            replacements: Vec::new(),
+        }
+    }
+
+    /// Record filecheck input that passed, and must keep passing after blessing.
+    pub fn passed(&mut self, text: &str) {
+        self.outputs.push(text.to_string());
+    }
+
+    /// Record filecheck input that failed. The function's filecheck directives will be rewritten
+    /// to match it.
+    pub fn failed(&mut self, text: &str) {
+        self.outputs.push(text.to_string());
+        if self.expected.is_none() {
+            self.expected = Some(text.to_string());
+        }
+    }
+
+    /// Replace the directive text `old`, which must be a slice of the test file source, with
+    /// `new`.
+    pub fn replace(&mut self, old: &'a str, new: &str) {
+        self.replacements.push((old, new.to_string()));
+    }
+
+    /// Compute the edits to `source` needed to bless the function described by `details`.
+    pub fn edits(
+        self,
+        source: &str,
+        preamble_comments: &[Comment],
+        details: &Details,
+    ) -> Result<Vec<Edit>> {
+        let mut edits = self.replacements
+            .iter()
+            .map(|&(old, ref new)| {
+                let start = offset_in(source, old);
+                Edit {
+                    range: start..start + old.len(),
+                    text: new.clone(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let expected = match self.expected {
+            Some(expected) => expected,
+            None => return Ok(edits),
+        };
+        let directives = filecheck_directives(&expected);
+
+        // Make sure that every test that ran filecheck on this function agrees with the new
+        // directives. They are shared by all the tests in the file.
+        let mut builder = CheckerBuilder::new();
+        for text in preamble_comments
+            .iter()
+            .map(|c| c.text)
+            .chain(directives.iter().map(String::as_str))
+        {
+            builder.directive(text).map_err(
+                |e| format!("filecheck: {}", e),
+            )?;
+        }
+        let checker = builder.finish();
+        for text in &self.outputs {
+            if !checker.check(text, NO_VARIABLES).map_err(|e| {
+                format!("filecheck: {}", e)
+            })?
+            {
+                return Err(format!(
+                    "cannot bless the function at line {}: the tests disagree on its output",
+                    details.location.line_number
+                ));
+            }
+        }
+
+        // Remove the old directives, and put the new ones where the first one was.
+        let mut placed = false;
+        for comment in &details.comments {
+            if !is_filecheck_directive(comment.text)? {
+                continue;
+            }
+            let start = offset_in(source, comment.text);
+            let end = start + comment.text.len();
+            let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+            let indent = &source[line_start..start];
+            let edit = if indent.trim().is_empty() {
+                // The comment is alone on its line, so replace the whole line.
+                let line_end = source[end..].find('\n').map_or(source.len(), |i| end + i + 1);
+                Edit {
+                    range: line_start..line_end,
+                    text: if placed {
+                        String::new()
+                    } else {
+                        indent_directives(&directives, indent)
+                    },
+                }
+            } else {
+                // The comment follows an entity on the same line. Put the new directives on
+                // the lines below it.
+                let comment_start = line_start + indent.trim_right().len();
+                Edit {
+                    range: comment_start..end,
+                    text: if placed {
+                        String::new()
+                    } else {
+                        format!("\n{}", indent_directives(&directives, "    ").trim_right())
+                    },
+                }
+            };
+            edits.push(edit);
+            placed = true;
+        }
+
+        Ok(edits)
+    }
+}
+
+/// Apply `edits` to `source` and return the result.
+pub fn apply_edits(source: &str, mut edits: Vec<Edit>) -> String {
+    edits.sort_by_key(|edit| (edit.range.start, edit.range.end));
+    let mut result = String::with_capacity(source.len());
+    let mut pos = 0;
+    for edit in edits {
+        assert!(edit.range.start >= pos, "overlapping edits");
+        result.push_str(&source[pos..edit.range.start]);
+        result.push_str(&edit.text);
+        pos = edit.range.end;
+    }
+    result.push_str(&source[pos..]);
+    result
+}
+
+/// Get the byte offset of `text` in `source`, where `text` is a slice of `source`.
+fn offset_in(source: &str, text: &str) -> usize {
+    let offset = (text.as_ptr() as usize).wrapping_sub(source.as_ptr() as usize);
+    assert!(
+        offset + text.len() <= source.len(),
+        "text is not a slice of the source"
+    );
+    offset
+}
+
+/// Is the comment `text` a directive that filecheck would use?
+fn is_filecheck_directive(text: &str) -> Result<bool> {
+    CheckerBuilder::new().directive(text).map_err(|e| {
+        format!("filecheck: {}", e)
+    })
+}
+
+/// Generate filecheck directives that match `output` exactly, line by line.
+fn filecheck_directives(output: &str) -> Vec<String> {
+    let mut directives = Vec::new();
+    let mut kind = "check";
+    for line in output.lines().map(str::trim) {
+        // A blank line breaks the `nextln:` chain.
+        if line.is_empty() {
+            kind = "check";
+            continue;
+        }
+        directives.push(format!("; {}: {}", kind, line.replace('$', "$$")));
+        kind = "nextln";
+    }
+    directives
+}
+
+/// Put each directive on its own line, indented with `indent`.
+fn indent_directives(directives: &[String], indent: &str) -> String {
+    let mut text = String::new();
+    for directive in directives {
+        text.push_str(indent);
+        text.push_str(directive);
+        text.push('\n');
+    }
+    text
+}
+
+#[test]
+fn test_filecheck_directives() {
+    assert_eq!(
+        filecheck_directives("function %f() {\nebb0:\n    return $x\n\nebb1:\n}\n"),
+        vec![
+            "; check: function %f() {",
+            "; nextln: ebb0:",
+            "; nextln: return $$x",
+            "; check: ebb1:",
+            "; nextln: }",
+        ]
+    );
+}
+
+#[test]
+fn test_apply_edits() {
+    let edits = vec![
+        Edit {
+            range: 4..5,
+            text: "X".to_string(),
+        },
+        Edit {
+            range: 0..0,
+            text: ">".to_string(),
+        },
+        Edit {
+            range: 1..3,
+            text: String::new(),
+        },
+    ];
+    assert_eq!(apply_edits("abcdef", edits), ">adXf");
+}
+
+#[test]
+fn test_bless_legalizer() {
+    use cretonne_codegen::isa;
+    use runone;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{Read, Write};
+
+    if isa::lookup("riscv").is_err() {
+        return;
+    }
+
+    // The directive on its own line is replaced in place. The trailing directive is replaced by
+    // directives on the lines below its instruction, and the function's other directive is
+    // removed.
+    let source = "test legalizer
+isa riscv
+
+function %f(i32) -> i32 {
+ebb0(v0: i32):
+    return v0
+}
+; check: wrong
+
+function %g(i32) -> i32 {
+ebb0(v0: i32):
+    return v0 ; check: wrong
+}
+; nextln: wrong
+";
+    let path = env::temp_dir().join("cretonne-filetests-bless-legalizer.cton");
+    File::create(&path)
+        .and_then(|mut file| file.write_all(source.as_bytes()))
+        .unwrap();
+    runone::run(&path, true).unwrap();
+    let mut blessed = String::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut blessed))
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(
+        blessed,
+        "test legalizer
+isa riscv
+
+function %f(i32) -> i32 {
+ebb0(v0: i32):
+    return v0
+}
+; check: function %f(i32 [%x10], i32 link [%x1]) -> i32 [%x10], i32 link [%x1] fast {
+; nextln: ebb0(v0: i32, v1: i32):
+; nextln: [Iret#19]                           return v0, v1
+; nextln: }
+
+function %g(i32) -> i32 {
+ebb0(v0: i32):
+    return v0
+    ; check: function %g(i32 [%x10], i32 link [%x1]) -> i32 [%x10], i32 link [%x1] fast {
+    ; nextln: ebb0(v0: i32, v1: i32):
+    ; nextln: [Iret#19]                           return v0, v1
+    ; nextln: }
+}
+"
+    );
+}
@@ -39,7 +39,9 @@ pub struct ConcurrentRunner {
 
 impl ConcurrentRunner {
     /// Create a new `ConcurrentRunner` with threads spun up.
-    pub fn new() -> Self {
+    ///
+    /// When `bless` is set, the workers rewrite the expectations in the test files.
+    pub fn new(bless: bool) -> Self {
         let (request_tx, request_rx) = channel();
         let request_mutex = Arc::new(Mutex::new(request_rx));
         let (reply_tx, reply_rx) = channel();
@@ -48,7 +50,7 @@ impl ConcurrentRunner {
 
         let handles = (0..num_cpus::get())
             .map(|num| {
-                worker_thread(num, request_mutex.clone(), reply_tx.clone())
+                worker_thread(num, bless, request_mutex.clone(), reply_tx.clone())
             })
             .collect();
 
@@ -110,6 +112,7 @@ fn heartbeat_thread(replies: Sender<Reply>) -> thread::JoinHandle<()> {
 /// Spawn a worker thread running tests.
 fn worker_thread(
     thread_num: usize,
+    bless: bool,
     requests: Arc<Mutex<Receiver<Request>>>,
     replies: Sender<Reply>,
 ) -> thread::JoinHandle<timing::PassTimes> {
@@ -127,17 +130,18 @@ fn worker_thread(
                 // The receiver should always be present for this as long as we have jobs.
                 replies.send(Reply::Starting { jobid, thread_num }).unwrap();
 
-                let result = catch_unwind(|| runone::run(path.as_path())).unwrap_or_else(|e| {
-                    // The test panicked, leaving us a `Box<Any>`.
-                    // Panics are usually strings.
-                    if let Some(msg) = e.downcast_ref::<String>() {
-                        Err(format!("panicked in worker #{}: {}", thread_num, msg))
-                    } else if let Some(msg) = e.downcast_ref::<&'static str>() {
-                        Err(format!("panicked in worker #{}: {}", thread_num, msg))
-                    } else {
-                        Err(format!("panicked in worker #{}", thread_num))
-                    }
-                });
+                let result = catch_unwind(|| runone::run(path.as_path(), bless))
+                    .unwrap_or_else(|e| {
+                        // The test panicked, leaving us a `Box<Any>`.
+                        // Panics are usually strings.
+                        if let Some(msg) = e.downcast_ref::<String>() {
+                            Err(format!("panicked in worker #{}: {}", thread_num, msg))
+                        } else if let Some(msg) = e.downcast_ref::<&'static str>() {
+                            Err(format!("panicked in worker #{}: {}", thread_num, msg))
+                        } else {
+                            Err(format!("panicked in worker #{}", thread_num))
+                        }
+                    });
 
                 if let Err(ref msg) = result {
                     dbg!("FAIL: {}", msg);
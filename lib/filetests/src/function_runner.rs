@@ -0,0 +1,334 @@
+//! Calling Cretonne IR functions from the test driver.
+//!
+//! A `FunctionRunner` compiles a function to native code when the target ISA matches the host,
+//! and falls back to interpreting it otherwise.
+
+use cretonne_codegen::binemit::{Addend, CodeOffset, NullTrapSink, Reloc, RelocSink};
+use cretonne_codegen::cursor::{Cursor, FuncCursor};
+use cretonne_codegen::ir::{self, types, AbiParam, ArgumentPurpose, ExternalName, Function,
+                           InstBuilder, MemFlags, Signature};
+use cretonne_codegen::isa::TargetIsa;
+use cretonne_codegen::print_errors::pretty_error;
+use cretonne_codegen::settings::CallConv;
+use cretonne_codegen::Context;
+use cretonne_interpreter::{matches_params, DataValue, Interpreter};
+use subtest::Result;
+
+/// Size of the slot used to pass each argument and result through a trampoline.
+const SLOT_SIZE: usize = 8;
+
+/// A function which can be called with `DataValue` arguments.
+pub enum FunctionRunner<'a> {
+    /// The function was compiled for the host.
+    Compiled(CompiledFunction),
+    /// The function is executed by the interpreter.
+    Interpreted(&'a Function),
+}
+
+impl<'a> FunctionRunner<'a> {
+    /// Prepare `func` for calling.
+    ///
+    /// The function is compiled for `isa` when it is the host ISA and all of the values in the
+    /// signature can be passed through a trampoline. Otherwise it is interpreted.
+    pub fn new(func: &'a Function, isa: Option<&TargetIsa>) -> Result<Self> {
+        match isa {
+            Some(isa) if is_host_isa(isa) && is_callable(&func.signature) => {
+                match CompiledFunction::new(func, isa)? {
+                    Some(compiled) => Ok(FunctionRunner::Compiled(compiled)),
+                    None => {
+                        dbg!("{} needs relocations, interpreting it", func.name);
+                        Ok(FunctionRunner::Interpreted(func))
+                    }
+                }
+            }
+            _ => Ok(FunctionRunner::Interpreted(func)),
+        }
+    }
+
+    /// Call the function with `args`, returning its results.
+    pub fn call(&self, args: &[DataValue]) -> Result<Vec<DataValue>> {
+        match *self {
+            FunctionRunner::Compiled(ref compiled) => compiled.call(args),
+            FunctionRunner::Interpreted(func) => {
+                Interpreter::new().call(func, args).map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+/// Is `isa` the ISA of the host we're running on?
+///
+/// The ISA-specific settings aren't checked, so a test using CPU features that the host doesn't
+/// have will fail.
+fn is_host_isa(isa: &TargetIsa) -> bool {
+    cfg!(all(unix, any(target_arch = "x86", target_arch = "x86_64"))) && isa.name() == "x86" &&
+        isa.flags().is_64bit() == cfg!(target_arch = "x86_64")
+}
+
+/// Can a function with signature `sig` be called through a trampoline?
+pub fn is_callable(sig: &Signature) -> bool {
+    sig.params.iter().chain(&sig.returns).all(|arg| {
+        arg.purpose == ArgumentPurpose::Normal &&
+            match arg.value_type {
+                types::B1 | types::I32 | types::I64 | types::F32 | types::F64 => true,
+                _ => false,
+            }
+    })
+}
+
+/// A function compiled to native code, along with a trampoline for calling it.
+pub struct CompiledFunction {
+    signature: Signature,
+    code: CodeMemory,
+    trampoline: usize,
+}
+
+impl CompiledFunction {
+    /// Compile `func` and its trampoline for `isa`, which must be the host ISA.
+    ///
+    /// Returns `None` if the compiled code needs relocations, since there is nothing to resolve
+    /// them against.
+    pub fn new(func: &Function, isa: &TargetIsa) -> Result<Option<Self>> {
+        let mut bytes = Vec::new();
+        if !compile(func.clone(), isa, &mut bytes)? {
+            return Ok(None);
+        }
+        let trampoline = align(bytes.len());
+        bytes.resize(trampoline, 0);
+        let tramp = make_trampoline(&func.signature, isa);
+        if !compile(tramp, isa, &mut bytes)? {
+            return Err("trampoline needs relocations".to_string());
+        }
+        Ok(Some(Self {
+            signature: func.signature.clone(),
+            code: CodeMemory::new(&bytes)?,
+            trampoline,
+        }))
+    }
+
+    /// Call the compiled function with `args`.
+    ///
+    /// Returns an error if the types of `args` don't match the signature.
+    pub fn call(&self, args: &[DataValue]) -> Result<Vec<DataValue>> {
+        // The trampoline reads a slot for every parameter, so a short argument list would make it
+        // read past the end of `values`.
+        if !matches_params(args, &self.signature.params) {
+            return Err(format!(
+                "arguments don't match the signature {}",
+                self.signature
+            ));
+        }
+        let slots = args.len().max(self.signature.returns.len());
+        let mut values = vec![0u64; slots];
+        for (slot, arg) in values.iter_mut().zip(args) {
+            *slot = arg.to_bits();
+        }
+        unsafe {
+            let base = self.code.as_ptr();
+            let trampoline: extern "C" fn(*const u8, *mut u64) =
+                ::std::mem::transmute(base.offset(self.trampoline as isize));
+            trampoline(base, values.as_mut_ptr());
+        }
+        Ok(
+            self.signature
+                .returns
+                .iter()
+                .zip(values)
+                .map(|(ret, bits)| DataValue::from_bits(ret.value_type, bits))
+                .collect(),
+        )
+    }
+}
+
+/// Compile `func` for `isa`, appending its code to `bytes`.
+///
+/// Returns `false` if the code needs relocations.
+fn compile(func: Function, isa: &TargetIsa, bytes: &mut Vec<u8>) -> Result<bool> {
+    let mut ctx = Context::for_function(func);
+    let mut relocs = RelocCounter { count: 0 };
+    ctx.compile_and_emit(isa, bytes, &mut relocs, &mut NullTrapSink {})
+        .map_err(|e| pretty_error(&ctx.func, Some(isa), e))?;
+    Ok(relocs.count == 0)
+}
+
+/// Round `offset` up to a suitable alignment for a function entry.
+fn align(offset: usize) -> usize {
+    (offset + 15) & !15
+}
+
+/// Build a function which calls a function with signature `sig`.
+///
+/// The trampoline has the signature `(callee, values)`, where `values` points to an array of
+/// 8-byte slots. The arguments are loaded from the slots, and the results are stored back into
+/// them. Booleans are passed as 32-bit integers.
+pub fn make_trampoline(sig: &Signature, isa: &TargetIsa) -> Function {
+    let ptr = if isa.flags().is_64bit() {
+        types::I64
+    } else {
+        types::I32
+    };
+    let mut tramp_sig = Signature::new(CallConv::SystemV);
+    tramp_sig.params.push(AbiParam::new(ptr));
+    tramp_sig.params.push(AbiParam::new(ptr));
+    let mut func = Function::with_name_signature(ExternalName::testcase("trampoline"), tramp_sig);
+    let callee_sig = func.import_signature(sig.clone());
+
+    let ebb = func.dfg.make_ebb();
+    let callee = func.dfg.append_ebb_param(ebb, ptr);
+    let values = func.dfg.append_ebb_param(ebb, ptr);
+    let mut pos = FuncCursor::new(&mut func);
+    pos.insert_ebb(ebb);
+
+    let mut flags = MemFlags::new();
+    flags.set_notrap();
+    flags.set_aligned();
+
+    let args = sig.params
+        .iter()
+        .enumerate()
+        .map(|(i, param)| {
+            let offset = slot_offset(i);
+            let ty = param.value_type;
+            if ty == types::B1 {
+                let int = pos.ins().load(types::I32, flags, values, offset);
+                pos.ins().icmp_imm(ir::condcodes::IntCC::NotEqual, int, 0)
+            } else {
+                pos.ins().load(ty, flags, values, offset)
+            }
+        })
+        .collect::<Vec<_>>();
+    let call = pos.ins().call_indirect(callee_sig, callee, &args);
+    let results = pos.func.dfg.inst_results(call).to_vec();
+    for (i, result) in results.into_iter().enumerate() {
+        let offset = slot_offset(i);
+        let value = if pos.func.dfg.value_type(result) == types::B1 {
+            pos.ins().bint(types::I32, result)
+        } else {
+            result
+        };
+        pos.ins().store(flags, value, values, offset);
+    }
+    pos.ins().return_(&[]);
+    func
+}
+
+/// Get the offset of the slot for the value number `index`.
+fn slot_offset(index: usize) -> i32 {
+    (index * SLOT_SIZE) as i32
+}
+
+/// Relocation sink which only counts the relocations.
+struct RelocCounter {
+    count: usize,
+}
+
+impl RelocSink for RelocCounter {
+    fn reloc_ebb(&mut self, _: CodeOffset, _: Reloc, _: CodeOffset) {
+        self.count += 1;
+    }
+
+    fn reloc_external(&mut self, _: CodeOffset, _: Reloc, _: &ExternalName, _: Addend) {
+        self.count += 1;
+    }
+
+    fn reloc_jt(&mut self, _: CodeOffset, _: Reloc, _: ir::JumpTable) {
+        self.count += 1;
+    }
+}
+
+/// Executable memory holding compiled code.
+struct CodeMemory {
+    ptr: *mut u8,
+    len: usize,
+}
+
+#[cfg(unix)]
+impl CodeMemory {
+    /// Copy `bytes` into newly mapped memory, and make it executable.
+    fn new(bytes: &[u8]) -> Result<Self> {
+        use libc::{mmap, mprotect, MAP_ANON, MAP_FAILED, MAP_PRIVATE, PROT_EXEC, PROT_READ,
+                   PROT_WRITE};
+        use std::ptr;
+
+        let len = bytes.len().max(1);
+        unsafe {
+            let ptr = mmap(
+                ptr::null_mut(),
+                len,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANON,
+                -1,
+                0,
+            );
+            if ptr == MAP_FAILED {
+                return Err("failed to map memory for compiled code".to_string());
+            }
+            let mem = Self {
+                ptr: ptr as *mut u8,
+                len,
+            };
+            ptr::copy_nonoverlapping(bytes.as_ptr(), mem.ptr, bytes.len());
+            if mprotect(ptr, len, PROT_READ | PROT_EXEC) != 0 {
+                return Err("failed to make compiled code executable".to_string());
+            }
+            Ok(mem)
+        }
+    }
+}
+
+#[cfg(not(unix))]
+impl CodeMemory {
+    fn new(_bytes: &[u8]) -> Result<Self> {
+        Err("executing compiled code is only supported on Unix".to_string())
+    }
+}
+
+impl CodeMemory {
+    fn as_ptr(&self) -> *const u8 {
+        self.ptr
+    }
+}
+
+#[cfg(unix)]
+impl Drop for CodeMemory {
+    fn drop(&mut self) {
+        unsafe {
+            ::libc::munmap(self.ptr as *mut ::libc::c_void, self.len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cretonne_codegen::isa;
+    use cretonne_codegen::settings::{self, Configurable};
+    use cretonne_reader::parse_functions;
+
+    #[test]
+    fn call_checks_arguments() {
+        let mut flag_builder = settings::builder();
+        flag_builder.enable("is_64bit").unwrap();
+        let isa = isa::lookup("x86").unwrap().finish(
+            settings::Flags::new(flag_builder),
+        );
+        if !is_host_isa(&*isa) {
+            return;
+        }
+        let func = parse_functions(
+            "function %add(i64, i64) -> i64 system_v {
+             ebb0(v0: i64, v1: i64):
+                 v2 = iadd v0, v1
+                 return v2
+             }",
+        ).unwrap()
+            .remove(0);
+        let compiled = CompiledFunction::new(&func, &*isa).unwrap().unwrap();
+        assert_eq!(
+            compiled.call(&[DataValue::I64(1), DataValue::I64(2)]),
+            Ok(vec![DataValue::I64(3)])
+        );
+        assert!(compiled.call(&[DataValue::I64(1)]).is_err());
+        assert!(compiled.call(&[DataValue::I64(1), DataValue::I32(2)]).is_err());
+    }
+}
@@ -20,21 +20,30 @@
 
 #[macro_use(dbg)]
 extern crate cretonne_codegen;
+extern crate cretonne_interpreter;
 extern crate cretonne_reader;
 extern crate filecheck;
+#[cfg(unix)]
+extern crate libc;
 extern crate num_cpus;
 
+pub use function_runner::{is_callable, make_trampoline, CompiledFunction, FunctionRunner};
+pub use test_run::parse_values;
+
 use cretonne_reader::TestCommand;
 use runner::TestRunner;
 use std::path::Path;
 use std::time;
 
+mod bless;
 mod concurrent;
+mod function_runner;
 mod match_directive;
 mod runner;
 mod runone;
 mod subtest;
 
+mod test_binary;
 mod test_binemit;
 mod test_cat;
 mod test_compile;
@@ -46,6 +55,7 @@ mod test_postopt;
 mod test_preopt;
 mod test_print_cfg;
 mod test_regalloc;
+mod test_run;
 mod test_simple_gvn;
 mod test_verifier;
 
@@ -61,8 +71,11 @@ type TestResult = Result<time::Duration, String>;
 /// Directories are scanned recursively for test cases ending in `.cton`. These test cases are
 /// executed on background threads.
 ///
-pub fn run(verbose: bool, files: &[String]) -> TestResult {
-    let mut runner = TestRunner::new(verbose);
+/// When `bless` is set, failing filecheck and `bin:` expectations are rewritten in place to match
+/// the actual output.
+///
+pub fn run(verbose: bool, bless: bool, files: &[String]) -> TestResult {
+    let mut runner = TestRunner::new(verbose, bless);
 
     for path in files.iter().map(Path::new) {
         if path.is_file() {
@@ -82,6 +95,7 @@ pub fn run(verbose: bool, files: &[String]) -> TestResult {
 /// a `.cton` test file.
 fn new_subtest(parsed: &TestCommand) -> subtest::Result<Box<subtest::SubTest>> {
     match parsed.command {
+        "binary" => test_binary::subtest(parsed),
         "binemit" => test_binemit::subtest(parsed),
         "cat" => test_cat::subtest(parsed),
         "compile" => test_compile::subtest(parsed),
@@ -93,6 +107,7 @@ fn new_subtest(parsed: &TestCommand) -> subtest::Result<Box<subtest::SubTest>> {
         "preopt" => test_preopt::subtest(parsed),
         "print-cfg" => test_print_cfg::subtest(parsed),
         "regalloc" => test_regalloc::subtest(parsed),
+        "run" => test_run::subtest(parsed),
         "simple-gvn" => test_simple_gvn::subtest(parsed),
         "verifier" => test_verifier::subtest(parsed),
         _ => Err(format!("unknown test command '{}'", parsed.command)),
@@ -58,6 +58,9 @@ impl Display for QueueEntry {
 pub struct TestRunner {
     verbose: bool,
 
+    // Rewrite the expectations in the test files instead of failing?
+    bless: bool,
+
     // Directories that have not yet been scanned.
     dir_stack: Vec<PathBuf>,
 
@@ -81,9 +84,10 @@ pub struct TestRunner {
 
 impl TestRunner {
     /// Create a new blank TrstRunner.
-    pub fn new(verbose: bool) -> Self {
+    pub fn new(verbose: bool, bless: bool) -> Self {
         Self {
             verbose,
+            bless,
             dir_stack: Vec::new(),
             tests: Vec::new(),
             new_tests: 0,
@@ -115,7 +119,7 @@ impl TestRunner {
     /// Begin running tests concurrently.
     pub fn start_threads(&mut self) {
         assert!(self.threads.is_none());
-        self.threads = Some(ConcurrentRunner::new());
+        self.threads = Some(ConcurrentRunner::new(self.bless));
     }
 
     /// Scan any directories pushed so far.
@@ -201,7 +205,7 @@ impl TestRunner {
             } else {
                 // Run test synchronously.
                 self.tests[jobid].state = State::Running;
-                let result = runone::run(self.tests[jobid].path());
+                let result = runone::run(self.tests[jobid].path(), self.bless);
                 self.finish_job(jobid, result);
             }
             self.new_tests = jobid + 1;
@@ -1,5 +1,6 @@
 //! Run the tests in a single test file.
 
+use bless::{apply_edits, Blessing};
 use cretonne_codegen::ir::Function;
 use cretonne_codegen::isa::TargetIsa;
 use cretonne_codegen::print_errors::pretty_verifier_error;
@@ -9,8 +10,9 @@ use cretonne_codegen::verify_function;
 use cretonne_reader::IsaSpec;
 use cretonne_reader::parse_test;
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
 use std::path::Path;
 use std::time;
 use subtest::{Context, Result, SubTest};
@@ -24,10 +26,19 @@ fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
     Ok(buffer)
 }
 
+/// Replace the contents of a file with a string.
+fn write_string<P: AsRef<Path>>(path: P, text: &str) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(text.as_bytes())
+}
+
 /// Load `path` and run the test in it.
 ///
+/// When `bless` is set, the expectations in the file are rewritten to match the actual output
+/// instead of failing the test.
+///
 /// If running this test causes a panic, it will propagate as normal.
-pub fn run(path: &Path) -> TestResult {
+pub fn run(path: &Path, bless: bool) -> TestResult {
     let _tt = timing::process_file();
     dbg!("---\nFile: {}", path.to_string_lossy());
     let started = time::Instant::now();
@@ -65,6 +76,7 @@ pub fn run(path: &Path) -> TestResult {
         Some(t) => t,
     };
 
+    let mut edits = Vec::new();
     for (func, details) in testfile.functions {
         let mut context = Context {
             preamble_comments: &testfile.preamble_comments,
@@ -72,6 +84,12 @@ pub fn run(path: &Path) -> TestResult {
             verified: false,
             flags,
             isa: None,
+            blessing: if bless {
+                Some(RefCell::new(Blessing::new()))
+            } else {
+                None
+            },
+            can_bless: false,
         };
 
         for tuple in &tuples {
@@ -80,6 +98,21 @@ pub fn run(path: &Path) -> TestResult {
         // Run the last test with an owned function which means it won't need to clone it before
         // mutating.
         run_one_test(last_tuple, Cow::Owned(func), &mut context)?;
+
+        if let Some(blessing) = context.blessing {
+            edits.extend(blessing.into_inner().edits(
+                &buffer,
+                &testfile.preamble_comments,
+                &context.details,
+            )?);
+        }
+    }
+
+    if !edits.is_empty() {
+        let blessed = apply_edits(&buffer, edits);
+        if blessed != buffer {
+            write_string(path, &blessed).map_err(|e| e.to_string())?;
+        }
     }
 
     Ok(started.elapsed())
@@ -126,6 +159,7 @@ fn run_one_test<'a>(
 
     context.flags = flags;
     context.isa = isa;
+    context.can_bless = test.can_bless();
 
     // Should we run the verifier before this test?
     if !context.verified && test.needs_verifier() {
@@ -1,11 +1,13 @@
 //! `SubTest` trait.
 
+use bless::Blessing;
 use cretonne_codegen::ir::Function;
 use cretonne_codegen::isa::TargetIsa;
 use cretonne_codegen::settings::{Flags, FlagsOrIsa};
 use cretonne_reader::{Comment, Details};
 use filecheck::{Checker, CheckerBuilder, NO_VARIABLES};
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::result;
 
 pub type Result<T> = result::Result<T, String>;
@@ -27,6 +29,13 @@ pub struct Context<'a> {
     /// Target ISA to test against. Only guaranteed to be present for sub-tests whose `needs_isa`
     /// method returned `true`. For other sub-tests, this is set if the test file has a unique ISA.
     pub isa: Option<&'a TargetIsa>,
+
+    /// Output recorded for rewriting the test file, when running with `--bless`.
+    pub blessing: Option<RefCell<Blessing<'a>>>,
+
+    /// Can the output of the current test replace the function's filecheck directives? This is
+    /// the value of `SubTest::can_bless` for the test.
+    pub can_bless: bool,
 }
 
 impl<'a> Context<'a> {
@@ -63,17 +72,33 @@ pub trait SubTest {
         false
     }
 
+    /// Can `--bless` rewrite the filecheck directives from the output of this test?
+    /// This should only be true for tests that send the printed function to filecheck.
+    fn can_bless(&self) -> bool {
+        false
+    }
+
     /// Run this test on `func`.
     fn run(&self, func: Cow<Function>, context: &Context) -> Result<()>;
 }
 
 /// Run filecheck on `text`, using directives extracted from `context`.
+///
+/// When blessing, a mismatch in a test that can bless is recorded instead of failing.
 pub fn run_filecheck(text: &str, context: &Context) -> Result<()> {
     let checker = build_filechecker(context)?;
-    if checker.check(text, NO_VARIABLES).map_err(|e| {
+    let passed = checker.check(text, NO_VARIABLES).map_err(|e| {
         format!("filecheck: {}", e)
-    })?
-    {
+    })?;
+    if let Some(ref blessing) = context.blessing {
+        if passed {
+            blessing.borrow_mut().passed(text);
+        } else if context.can_bless {
+            blessing.borrow_mut().failed(text);
+            return Ok(());
+        }
+    }
+    if passed {
         Ok(())
     } else {
         // Filecheck mismatch. Emit an explanation as output.
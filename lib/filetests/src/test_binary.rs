@@ -0,0 +1,54 @@
+//! The `binary` subtest.
+
+use cretonne_codegen::binary;
+use cretonne_codegen::ir::Function;
+use cretonne_reader::TestCommand;
+use std::borrow::Cow;
+use subtest::{self, Context, Result as STResult, SubTest};
+
+/// Object implementing the `test binary` sub-test.
+///
+/// This command is used for testing the binary IR format. It encodes each function, decodes it
+/// again, and checks that the decoded function prints exactly like the original.
+///
+/// The decoded function is then verified by filecheck.
+struct TestBinary;
+
+pub fn subtest(parsed: &TestCommand) -> STResult<Box<SubTest>> {
+    assert_eq!(parsed.command, "binary");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestBinary))
+    }
+}
+
+impl SubTest for TestBinary {
+    fn name(&self) -> Cow<str> {
+        Cow::from("binary")
+    }
+
+    fn needs_verifier(&self) -> bool {
+        false
+    }
+
+    fn can_bless(&self) -> bool {
+        true
+    }
+
+    fn run(&self, func: Cow<Function>, context: &Context) -> STResult<()> {
+        let text = func.display(context.isa).to_string();
+        let bytes = binary::encode_function(&func);
+        let decoded = binary::decode_function(&bytes).map_err(
+            |e| format!("decoding failed: {}", e),
+        )?;
+        let decoded_text = decoded.display(context.isa).to_string();
+        if decoded_text != text {
+            return Err(format!(
+                "binary round trip changed the function:\n{}",
+                decoded_text
+            ));
+        }
+        subtest::run_filecheck(&decoded_text, context)
+    }
+}
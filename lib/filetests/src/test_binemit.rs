@@ -2,6 +2,8 @@
 //!
 //! The `binemit` test command generates binary machine code for every instruction in the input
 //! functions and compares the results to the expected output.
+//!
+//! When blessing, mismatched `bin:` directives are rewritten with the actual machine code.
 
 use cretonne_codegen::binemit;
 use cretonne_codegen::binemit::RegDiversions;
@@ -272,18 +274,24 @@ impl SubTest for TestBinEmit {
                     }
                     let have = sink.text.trim();
                     if have != want {
-                        return Err(format!(
-                            "Bad machine code for {}: {}\nWant: {}\nGot:  {}",
-                            inst,
-                            func.dfg.display_inst(inst, isa),
-                            want,
-                            have
-                        ));
+                        if let Some(ref blessing) = context.blessing {
+                            blessing.borrow_mut().replace(want, have);
+                        } else {
+                            return Err(format!(
+                                "Bad machine code for {}: {}\nWant: {}\nGot:  {}",
+                                inst,
+                                func.dfg.display_inst(inst, isa),
+                                want,
+                                have
+                            ));
+                        }
                     }
                 }
             }
         }
 
+        binemit::emit_rodata(&func, &mut sink);
+
         if sink.offset != code_size {
             return Err(format!(
                 "Expected code size {}, got {}",
@@ -31,6 +31,10 @@ impl SubTest for TestCat {
         false
     }
 
+    fn can_bless(&self) -> bool {
+        true
+    }
+
     fn run(&self, func: Cow<Function>, context: &Context) -> STResult<()> {
         subtest::run_filecheck(&func.display(context.isa).to_string(), context)
     }
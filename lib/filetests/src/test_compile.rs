@@ -34,6 +34,10 @@ impl SubTest for TestCompile {
         true
     }
 
+    fn can_bless(&self) -> bool {
+        true
+    }
+
     fn run(&self, func: Cow<ir::Function>, context: &Context) -> Result<()> {
         let isa = context.isa.expect("compile needs an ISA");
 
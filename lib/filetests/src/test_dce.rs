@@ -33,6 +33,10 @@ impl SubTest for TestDCE {
         true
     }
 
+    fn can_bless(&self) -> bool {
+        true
+    }
+
     fn run(&self, func: Cow<Function>, context: &Context) -> Result<()> {
         // Create a compilation context, and drop in the function.
         let mut comp_ctx = cretonne_codegen::Context::new();
@@ -35,6 +35,10 @@ impl SubTest for TestLegalizer {
         true
     }
 
+    fn can_bless(&self) -> bool {
+        true
+    }
+
     fn run(&self, func: Cow<Function>, context: &Context) -> Result<()> {
         let mut comp_ctx = cretonne_codegen::Context::new();
         comp_ctx.func = func.into_owned();
@@ -37,6 +37,10 @@ impl SubTest for TestRegalloc {
         true
     }
 
+    fn can_bless(&self) -> bool {
+        true
+    }
+
     fn run(&self, func: Cow<Function>, context: &Context) -> Result<()> {
         let isa = context.isa.expect("register allocator needs an ISA");
 
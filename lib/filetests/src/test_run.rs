@@ -0,0 +1,212 @@
+//! Test command for executing functions.
+//!
+//! The `run` test command calls each function with the arguments given in `run:` directives, and
+//! compares the results with the expected values:
+//!
+//! ```text
+//! ; run: %add(1, 2) == 3
+//! ; run: %add(1, 2) != 4
+//! ```
+//!
+//! A bare `; run` directive calls a function without parameters which must return `true`.
+//!
+//! The function is compiled and executed natively when the test file's unique ISA matches the
+//! host. Otherwise, or when the compiled code needs relocations, it is interpreted.
+
+use cretonne_codegen::ir::immediates::{Ieee32, Ieee64, Imm64};
+use cretonne_codegen::ir::{types, Function, Type};
+use cretonne_interpreter::DataValue;
+use cretonne_reader::TestCommand;
+use function_runner::FunctionRunner;
+use std::borrow::Cow;
+use subtest::{Context, Result, SubTest};
+
+struct TestRun;
+
+pub fn subtest(parsed: &TestCommand) -> Result<Box<SubTest>> {
+    assert_eq!(parsed.command, "run");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestRun))
+    }
+}
+
+impl SubTest for TestRun {
+    fn name(&self) -> Cow<str> {
+        Cow::from("run")
+    }
+
+    fn run(&self, func: Cow<Function>, context: &Context) -> Result<()> {
+        let mut invocations = Vec::new();
+        for comment in &context.details.comments {
+            if let Some(invocation) = Invocation::parse(comment.text, &func)? {
+                invocations.push(invocation);
+            }
+        }
+        if invocations.is_empty() {
+            return Err("no run directives found".to_string());
+        }
+
+        let runner = FunctionRunner::new(&func, context.isa)?;
+        for invocation in &invocations {
+            let results = runner.call(&invocation.args)?;
+            if (results == invocation.expected) != invocation.equal {
+                return Err(format!(
+                    "{} returned {}",
+                    invocation.text,
+                    display_values(&results)
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A call described by a `run` directive.
+struct Invocation<'a> {
+    /// The text of the directive, for error messages.
+    text: &'a str,
+    args: Vec<DataValue>,
+    expected: Vec<DataValue>,
+    /// Should the results be equal to `expected`?
+    equal: bool,
+}
+
+impl<'a> Invocation<'a> {
+    /// Parse the `run` directive in `comment`, if there is one.
+    fn parse(comment: &'a str, func: &Function) -> Result<Option<Self>> {
+        let text = comment.trim_left_matches(';').trim();
+        if text == "run" {
+            let sig = &func.signature;
+            if !sig.params.is_empty() || sig.returns.len() != 1 ||
+                !sig.returns[0].value_type.is_bool()
+            {
+                return Err(format!(
+                    "'{}' requires a function returning a boolean without parameters",
+                    text
+                ));
+            }
+            return Ok(Some(Self {
+                text,
+                args: Vec::new(),
+                expected: vec![DataValue::B(true)],
+                equal: true,
+            }));
+        }
+        if !text.starts_with("run:") {
+            return Ok(None);
+        }
+        Self::parse_call(text, text["run:".len()..].trim(), func)
+            .map(Some)
+            .map_err(|e| format!("{}: {}", text, e))
+    }
+
+    /// Parse `call`, which has the form `%name(args) == results` or `%name(args) != results`.
+    fn parse_call(text: &'a str, call: &str, func: &Function) -> Result<Self> {
+        let name = func.name.to_string();
+        if !call.starts_with(&name) || !call[name.len()..].starts_with('(') {
+            return Err(format!("expected a call to {}", name));
+        }
+        let call = &call[name.len() + 1..];
+        let close = call.find(')').ok_or("missing ')'")?;
+        let (args, rest) = (&call[..close], call[close + 1..].trim());
+        let equal = if rest.starts_with("==") {
+            true
+        } else if rest.starts_with("!=") {
+            false
+        } else {
+            return Err("expected '==' or '!=' after the arguments".to_string());
+        };
+        let sig = &func.signature;
+        Ok(Self {
+            text,
+            args: parse_values(args, sig.params.iter().map(|p| p.value_type))?,
+            expected: parse_values(&rest[2..], sig.returns.iter().map(|r| r.value_type))?,
+            equal,
+        })
+    }
+}
+
+/// Parse a comma-separated list of values with the types produced by `types`.
+///
+/// The values are written like the arguments in a `run` directive, e.g. `1, 0x10, 2.5`.
+pub fn parse_values<I>(text: &str, types: I) -> Result<Vec<DataValue>>
+where
+    I: ExactSizeIterator<Item = Type>,
+{
+    let items = split_list(text);
+    if items.len() != types.len() {
+        return Err(format!(
+            "expected {} values, got {}",
+            types.len(),
+            items.len()
+        ));
+    }
+    items
+        .into_iter()
+        .zip(types)
+        .map(|(item, ty)| parse_value(item, ty))
+        .collect()
+}
+
+/// Split a comma-separated list, keeping bracketed vectors together.
+fn split_list(text: &str) -> Vec<&str> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Vec::new();
+    }
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(text[start..].trim());
+    items
+}
+
+/// Parse a single value of type `ty`.
+fn parse_value(text: &str, ty: Type) -> Result<DataValue> {
+    if ty.is_vector() {
+        if !text.starts_with('[') || !text.ends_with(']') {
+            return Err(format!("expected a {} vector, got '{}'", ty, text));
+        }
+        let lanes = (0..ty.lane_count()).map(|_| ty.lane_type());
+        return Ok(DataValue::V(
+            parse_values(&text[1..text.len() - 1], lanes)?,
+        ));
+    }
+    let value = match ty {
+        types::F32 => text.parse::<Ieee32>().map(DataValue::F32).ok(),
+        types::F64 => text.parse::<Ieee64>().map(DataValue::F64).ok(),
+        _ if ty.is_int() => text.parse::<Imm64>().map(|imm| DataValue::int(ty, imm.into())).ok(),
+        _ if ty.is_bool() => {
+            match text {
+                "true" => Some(DataValue::B(true)),
+                "false" => Some(DataValue::B(false)),
+                _ => None,
+            }
+        }
+        _ => return Err(format!("values of type {} are not supported", ty)),
+    };
+    value.ok_or_else(|| format!("expected a {} value, got '{}'", ty, text))
+}
+
+/// Format a list of values like the right-hand side of a `run` directive.
+fn display_values(values: &[DataValue]) -> String {
+    values
+        .iter()
+        .map(DataValue::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
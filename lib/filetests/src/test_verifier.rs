@@ -8,12 +8,17 @@
 //!
 //! This annotation means that the verifier is expected to given an error for the jump instruction
 //! containing the substring "jump to non-existent EBB".
+//!
+//! The verifier reports all the errors it finds, so a function can have multiple `error:`
+//! annotations. The test fails if any reported error doesn't match an annotation on the same
+//! entity, or if any annotation doesn't match a reported error.
 
 use cretonne_codegen::ir::Function;
 use cretonne_codegen::verify_function;
 use cretonne_reader::TestCommand;
 use match_directive::match_directive;
 use std::borrow::{Borrow, Cow};
+use std::fmt::Write;
 use subtest::{Context, Result, SubTest};
 
 struct TestVerifier;
@@ -41,42 +46,37 @@ impl SubTest for TestVerifier {
         let func = func.borrow();
 
         // Scan source annotations for "error:" directives.
-        let mut expected = None;
+        let mut expected = Vec::new();
         for comment in &context.details.comments {
             if let Some(tail) = match_directive(comment.text, "error:") {
-                // Currently, the verifier can only report one problem at a time.
-                // Reject more than one `error:` directives.
-                if expected.is_some() {
-                    return Err("cannot handle multiple error: directives".to_string());
-                }
-                expected = Some((comment.entity, tail));
+                expected.push((comment.entity, tail));
             }
         }
 
-        match verify_function(func, context.flags_or_isa()) {
-            Ok(_) => {
-                match expected {
-                    None => Ok(()),
-                    Some((_, msg)) => Err(format!("passed, expected error: {}", msg)),
-                }
+        let got = match verify_function(func, context.flags_or_isa()) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.0,
+        };
+
+        // Every reported error must match an expected error on the same entity, and vice versa.
+        let mut msg = String::new();
+        for err in &got {
+            if !expected.iter().any(|&(want_loc, want_msg)| {
+                want_loc == err.location && err.message.contains(want_msg)
+            })
+            {
+                writeln!(msg, "unexpected error: {}", err).unwrap();
             }
-            Err(got) => {
-                match expected {
-                    None => Err(format!("verifier pass, got {}", got)),
-                    Some((want_loc, want_msg)) if got.message.contains(want_msg) => {
-                        if want_loc == got.location {
-                            Ok(())
-                        } else {
-                            Err(format!(
-                                "correct error reported on {}, but wanted {}",
-                                got.location,
-                                want_loc
-                            ))
-                        }
-                    }
-                    Some(_) => Err(format!("mismatching error: {}", got)),
-                }
+        }
+        for &(want_loc, want_msg) in &expected {
+            if !got.iter().any(|err| {
+                want_loc == err.location && err.message.contains(want_msg)
+            })
+            {
+                writeln!(msg, "missing error: {}: {}", want_loc, want_msg).unwrap();
             }
         }
+
+        if msg.is_empty() { Ok(()) } else { Err(msg) }
     }
 }
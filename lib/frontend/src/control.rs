@@ -0,0 +1,425 @@
+//! Structured control flow.
+//!
+//! The builders in this module translate source-level `if`/`else` and loops with `break` and
+//! `continue` into EBBs. They create the EBBs, switch between them and seal them as soon as all
+//! their predecessors are known, so a front end doesn't have to get the ordering right itself.
+//!
+//! Values flowing out of a construct are passed as parameters to its merge point: the results of
+//! both branches of an `IfElse`, or the values passed to `break_` in a `Loop`. Other values can
+//! be threaded through with `use_var` and `def_var` as usual.
+
+use cretonne_codegen::entity::EntityRef;
+use cretonne_codegen::ir::{Ebb, Inst, InstBuilder, Type, Value};
+use frontend::FunctionBuilder;
+use std::fmt::Debug;
+
+/// Builder for an `if` with an optional `else` branch.
+///
+/// `begin` emits the conditional branch and continues with the `then` branch at the current
+/// position. The `else` branch is started with `begin_else`, and `end` moves to the merge point
+/// after both branches.
+///
+/// ```rust
+/// # extern crate cretonne_codegen;
+/// # extern crate cretonne_frontend;
+/// # use cretonne_codegen::ir::{ExternalName, Function, Signature, AbiParam, InstBuilder};
+/// # use cretonne_codegen::ir::types::*;
+/// # use cretonne_codegen::settings::CallConv;
+/// # use cretonne_frontend::{FunctionBuilderContext, FunctionBuilder, IfElse, Variable};
+/// # fn main() {
+/// # let mut sig = Signature::new(CallConv::SystemV);
+/// # sig.params.push(AbiParam::new(I32));
+/// # sig.returns.push(AbiParam::new(I32));
+/// # let mut fn_ctx = FunctionBuilderContext::<Variable>::new();
+/// # let mut func = Function::with_name_signature(ExternalName::user(0, 0), sig);
+/// # let mut builder = FunctionBuilder::<Variable>::new(&mut func, &mut fn_ctx);
+/// # let entry = builder.create_ebb();
+/// # builder.append_ebb_params_for_function_params(entry);
+/// # builder.switch_to_block(entry);
+/// # builder.seal_block(entry);
+/// # let x = builder.ebb_params(entry)[0];
+/// // x == 0 ? 1 : x
+/// let mut if_else = IfElse::begin(&mut builder, x, &[I32]);
+/// let one = builder.ins().iconst(I32, 1);
+/// if_else.begin_else(&mut builder, &[one]);
+/// let merge = if_else.end(&mut builder, &[x]);
+/// let result = builder.ebb_params(merge)[0];
+/// builder.ins().return_(&[result]);
+/// # builder.finalize();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct IfElse {
+    branch: Inst,
+    else_ebb: Ebb,
+    merge: Ebb,
+    has_else: bool,
+}
+
+impl IfElse {
+    /// Start an `if` taking the `then` branch when `cond` is non-zero.
+    ///
+    /// The merge point after the `if` has one parameter for each of the `result_types`.
+    pub fn begin<Variable>(
+        builder: &mut FunctionBuilder<Variable>,
+        cond: Value,
+        result_types: &[Type],
+    ) -> Self
+    where
+        Variable: EntityRef + Debug,
+    {
+        let else_ebb = builder.create_ebb();
+        let merge = builder.create_ebb();
+        for &ty in result_types {
+            builder.append_ebb_param(merge, ty);
+        }
+        let branch = builder.ins().brz(cond, else_ebb, &[]);
+        Self {
+            branch,
+            else_ebb,
+            merge,
+            has_else: false,
+        }
+    }
+
+    /// End the `then` branch, passing `results` to the merge point, and start the `else` branch.
+    pub fn begin_else<Variable>(
+        &mut self,
+        builder: &mut FunctionBuilder<Variable>,
+        results: &[Value],
+    ) where
+        Variable: EntityRef + Debug,
+    {
+        debug_assert!(!self.has_else, "if already has an else branch");
+        jump_unless_filled(builder, self.merge, results);
+        builder.switch_to_block(self.else_ebb);
+        builder.seal_block(self.else_ebb);
+        self.has_else = true;
+    }
+
+    /// End the current branch, passing `results` to the merge point, and continue there.
+    ///
+    /// Returns the merge point EBB, whose parameters are the results of the `if`. An `if` without
+    /// an `else` branch can't have any results.
+    pub fn end<Variable>(self, builder: &mut FunctionBuilder<Variable>, results: &[Value]) -> Ebb
+    where
+        Variable: EntityRef + Debug,
+    {
+        jump_unless_filled(builder, self.merge, results);
+        if !self.has_else {
+            debug_assert!(
+                builder.ebb_params(self.merge).is_empty(),
+                "an if without an else branch can't have results"
+            );
+            builder.change_jump_destination(self.branch, self.merge);
+        }
+        builder.switch_to_block(self.merge);
+        builder.seal_block(self.merge);
+        self.merge
+    }
+}
+
+/// Builder for a loop.
+///
+/// `begin` starts the loop body in a new header EBB, and `end` closes the body by jumping back to
+/// the header. The loop is left with `break_`, `break_if` or `break_unless`, and the exit EBB is
+/// returned by `end`. A `while` loop tests its condition with `break_unless` at the top of the
+/// body:
+///
+/// ```rust
+/// # extern crate cretonne_codegen;
+/// # extern crate cretonne_frontend;
+/// # use cretonne_codegen::entity::EntityRef;
+/// # use cretonne_codegen::ir::{ExternalName, Function, Signature, AbiParam, InstBuilder};
+/// # use cretonne_codegen::ir::types::*;
+/// # use cretonne_codegen::settings::CallConv;
+/// # use cretonne_frontend::{FunctionBuilderContext, FunctionBuilder, Loop, Variable};
+/// # fn main() {
+/// # let mut sig = Signature::new(CallConv::SystemV);
+/// # sig.params.push(AbiParam::new(I32));
+/// # let mut fn_ctx = FunctionBuilderContext::<Variable>::new();
+/// # let mut func = Function::with_name_signature(ExternalName::user(0, 0), sig);
+/// # let mut builder = FunctionBuilder::<Variable>::new(&mut func, &mut fn_ctx);
+/// # let entry = builder.create_ebb();
+/// # builder.append_ebb_params_for_function_params(entry);
+/// # builder.switch_to_block(entry);
+/// # builder.seal_block(entry);
+/// let n = Variable::new(0);
+/// builder.declare_var(n, I32);
+/// let param = builder.ebb_params(entry)[0];
+/// builder.def_var(n, param);
+///
+/// // while n != 0 { n -= 1; }
+/// let body = Loop::begin(&mut builder, &[]);
+/// let cond = builder.use_var(n);
+/// body.break_unless(&mut builder, cond, &[]);
+/// let tmp = builder.use_var(n);
+/// let tmp = builder.ins().iadd_imm(tmp, -1);
+/// builder.def_var(n, tmp);
+/// body.end(&mut builder);
+/// builder.ins().return_(&[]);
+/// # builder.finalize();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Loop {
+    header: Ebb,
+    exit: Ebb,
+}
+
+impl Loop {
+    /// Start a loop at the current position.
+    ///
+    /// The exit of the loop has one parameter for each of the `result_types`.
+    pub fn begin<Variable>(builder: &mut FunctionBuilder<Variable>, result_types: &[Type]) -> Self
+    where
+        Variable: EntityRef + Debug,
+    {
+        let header = builder.create_ebb();
+        let exit = builder.create_ebb();
+        for &ty in result_types {
+            builder.append_ebb_param(exit, ty);
+        }
+        jump_unless_filled(builder, header, &[]);
+        // The header can't be sealed until the back edges are known.
+        builder.switch_to_block(header);
+        Self { header, exit }
+    }
+
+    /// Get the loop header, which is the destination of `continue_`.
+    pub fn header(&self) -> Ebb {
+        self.header
+    }
+
+    /// Get the loop exit, which is the destination of `break_`.
+    pub fn exit(&self) -> Ebb {
+        self.exit
+    }
+
+    /// Leave the loop, passing `results` to the loop exit.
+    ///
+    /// Any code emitted after this until the end of the loop is unreachable.
+    pub fn break_<Variable>(&self, builder: &mut FunctionBuilder<Variable>, results: &[Value])
+    where
+        Variable: EntityRef + Debug,
+    {
+        builder.ins().jump(self.exit, results);
+        switch_to_unreachable_block(builder);
+    }
+
+    /// Leave the loop if `cond` is non-zero, passing `results` to the loop exit.
+    pub fn break_if<Variable>(
+        &self,
+        builder: &mut FunctionBuilder<Variable>,
+        cond: Value,
+        results: &[Value],
+    ) where
+        Variable: EntityRef + Debug,
+    {
+        builder.ins().brnz(cond, self.exit, results);
+    }
+
+    /// Leave the loop if `cond` is zero, passing `results` to the loop exit.
+    pub fn break_unless<Variable>(
+        &self,
+        builder: &mut FunctionBuilder<Variable>,
+        cond: Value,
+        results: &[Value],
+    ) where
+        Variable: EntityRef + Debug,
+    {
+        builder.ins().brz(cond, self.exit, results);
+    }
+
+    /// Go back to the loop header.
+    ///
+    /// Any code emitted after this until the end of the loop is unreachable.
+    pub fn continue_<Variable>(&self, builder: &mut FunctionBuilder<Variable>)
+    where
+        Variable: EntityRef + Debug,
+    {
+        builder.ins().jump(self.header, &[]);
+        switch_to_unreachable_block(builder);
+    }
+
+    /// Go back to the loop header if `cond` is non-zero.
+    pub fn continue_if<Variable>(&self, builder: &mut FunctionBuilder<Variable>, cond: Value)
+    where
+        Variable: EntityRef + Debug,
+    {
+        builder.ins().brnz(cond, self.header, &[]);
+    }
+
+    /// End the loop body by jumping back to the header, and continue at the loop exit.
+    ///
+    /// Returns the loop exit EBB, whose parameters are the values passed to `break_`.
+    pub fn end<Variable>(self, builder: &mut FunctionBuilder<Variable>) -> Ebb
+    where
+        Variable: EntityRef + Debug,
+    {
+        jump_unless_filled(builder, self.header, &[]);
+        builder.seal_block(self.header);
+        builder.switch_to_block(self.exit);
+        builder.seal_block(self.exit);
+        self.exit
+    }
+}
+
+/// Jump to `dest` unless the current block already ends in a terminator.
+fn jump_unless_filled<Variable>(builder: &mut FunctionBuilder<Variable>, dest: Ebb, args: &[Value])
+where
+    Variable: EntityRef + Debug,
+{
+    if !builder.is_filled() {
+        builder.ins().jump(dest, args);
+    }
+}
+
+/// Continue in a new block without predecessors, after an unconditional branch.
+fn switch_to_unreachable_block<Variable>(builder: &mut FunctionBuilder<Variable>)
+where
+    Variable: EntityRef + Debug,
+{
+    let ebb = builder.create_ebb();
+    builder.switch_to_block(ebb);
+    builder.seal_block(ebb);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cretonne_codegen::entity::EntityRef;
+    use cretonne_codegen::ir::condcodes::IntCC;
+    use cretonne_codegen::ir::types::*;
+    use cretonne_codegen::ir::{AbiParam, ExternalName, Function, Signature};
+    use cretonne_codegen::settings::{self, CallConv};
+    use cretonne_codegen::verifier::verify_function;
+    use frontend::FunctionBuilderContext;
+    use std::string::{String, ToString};
+    use Variable;
+
+    /// Build a function `(i32) -> i32` with `body`, which returns the value to return.
+    fn build<F>(body: F) -> String
+    where
+        F: FnOnce(&mut FunctionBuilder<Variable>, Value) -> Value,
+    {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(I32));
+        sig.returns.push(AbiParam::new(I32));
+        let mut fn_ctx = FunctionBuilderContext::<Variable>::new();
+        let mut func = Function::with_name_signature(ExternalName::testcase("control"), sig);
+        {
+            let mut builder = FunctionBuilder::<Variable>::new(&mut func, &mut fn_ctx);
+            let entry = builder.create_ebb();
+            builder.append_ebb_params_for_function_params(entry);
+            builder.switch_to_block(entry);
+            builder.seal_block(entry);
+            let arg = builder.ebb_params(entry)[0];
+            let result = body(&mut builder, arg);
+            builder.ins().return_(&[result]);
+            builder.finalize();
+        }
+
+        let flags = settings::Flags::new(settings::builder());
+        verify_function(&func, &flags).unwrap();
+        func.to_string()
+    }
+
+    #[test]
+    fn if_else_results() {
+        let func = build(|builder, x| {
+            let mut if_else = IfElse::begin(builder, x, &[I32]);
+            let one = builder.ins().iconst(I32, 1);
+            if_else.begin_else(builder, &[one]);
+            let merge = if_else.end(builder, &[x]);
+            builder.ebb_params(merge)[0]
+        });
+        assert_eq!(
+            func,
+            "function %control(i32) -> i32 system_v {\n\
+             ebb0(v0: i32):\n    brz v0, ebb1\n    v2 = iconst.i32 1\n    jump ebb2(v2)\n\n\
+             ebb1:\n    jump ebb2(v0)\n\n\
+             ebb2(v1: i32):\n    return v1\n}\n"
+        );
+    }
+
+    #[test]
+    fn if_without_else() {
+        let x = Variable::new(0);
+        let func = build(|builder, arg| {
+            builder.declare_var(x, I32);
+            builder.def_var(x, arg);
+            let if_else = IfElse::begin(builder, arg, &[]);
+            let zero = builder.ins().iconst(I32, 0);
+            builder.def_var(x, zero);
+            if_else.end(builder, &[]);
+            builder.use_var(x)
+        });
+        assert_eq!(
+            func,
+            "function %control(i32) -> i32 system_v {\n\
+             ebb0(v0: i32):\n    brz v0, ebb2(v0)\n    v1 = iconst.i32 0\n    jump ebb2(v1)\n\n\
+             ebb2(v2: i32):\n    return v2\n}\n"
+        );
+    }
+
+    #[test]
+    fn if_with_return() {
+        let func = build(|builder, x| {
+            let mut if_else = IfElse::begin(builder, x, &[]);
+            builder.ins().return_(&[x]);
+            if_else.begin_else(builder, &[]);
+            if_else.end(builder, &[]);
+            builder.ins().iconst(I32, 0)
+        });
+        assert!(func.contains("brz v0, ebb1\n    return v0\n\nebb1:\n    jump ebb2\n"));
+    }
+
+    #[test]
+    fn loop_break_continue() {
+        let n = Variable::new(0);
+        let func = build(|builder, arg| {
+            builder.declare_var(n, I32);
+            builder.def_var(n, arg);
+
+            // loop { if n == 0 { break n } n -= 1; if n == 10 { continue } n -= 1; }
+            let body = Loop::begin(builder, &[I32]);
+            let cur = builder.use_var(n);
+            body.break_unless(builder, cur, &[cur]);
+            let tmp = builder.ins().iadd_imm(cur, -1);
+            builder.def_var(n, tmp);
+            let is_ten = builder.ins().icmp_imm(IntCC::Equal, tmp, 10);
+            body.continue_if(builder, is_ten);
+            let tmp = builder.ins().iadd_imm(tmp, -1);
+            builder.def_var(n, tmp);
+            let exit = body.end(builder);
+            builder.ebb_params(exit)[0]
+        });
+        assert_eq!(
+            func,
+            "function %control(i32) -> i32 system_v {\n\
+             ebb0(v0: i32):\n    jump ebb1(v0)\n\n\
+             ebb1(v2: i32):\n    brz v2, ebb2(v2)\n    v3 = iadd_imm v2, -1\n    \
+             v4 = icmp_imm eq v3, 10\n    brnz v4, ebb1(v3)\n    v5 = iadd_imm v3, -1\n    \
+             jump ebb1(v5)\n\n\
+             ebb2(v1: i32):\n    return v1\n}\n"
+        );
+    }
+
+    #[test]
+    fn nested_break() {
+        let func = build(|builder, x| {
+            let outer = Loop::begin(builder, &[I32]);
+            let inner = Loop::begin(builder, &[]);
+            outer.break_(builder, &[x]);
+            // This is unreachable.
+            inner.continue_(builder);
+            inner.end(builder);
+            outer.continue_(builder);
+            let exit = outer.end(builder);
+            builder.ebb_params(exit)[0]
+        });
+        assert!(func.contains("ebb3:\n    jump ebb2(v0)\n"));
+        assert!(func.contains("ebb2(v1: i32):\n    return v1\n"));
+    }
+}
@@ -3,14 +3,16 @@ use cretonne_codegen::cursor::{Cursor, FuncCursor};
 use cretonne_codegen::entity::{EntityMap, EntityRef, EntitySet};
 use cretonne_codegen::ir;
 use cretonne_codegen::ir::function::DisplayFunction;
-use cretonne_codegen::ir::{DataFlowGraph, Ebb, ExtFuncData, FuncRef, Function, GlobalVar,
-                           GlobalVarData, Heap, HeapData, Inst, InstBuilderBase, InstructionData,
-                           JumpTable, JumpTableData, SigRef, Signature, StackSlot, StackSlotData,
-                           Type, Value};
+use cretonne_codegen::ir::{types, DataFlowGraph, Ebb, ExtFuncData, FuncRef, Function, GlobalVar,
+                           GlobalVarData, Heap, HeapData, Inst, InstBuilder, InstBuilderBase,
+                           InstructionData, JumpTable, JumpTableData, LibCall, MemFlags, SigRef,
+                           Signature, StackSlot, StackSlotData, Type, Value,
+                           get_memory_libcall_funcref};
 use cretonne_codegen::isa::TargetIsa;
 use cretonne_codegen::packed_option::PackedOption;
 use ssa::{Block, SSABuilder, SideEffects};
 use std::fmt::Debug;
+use std::vec::Vec;
 
 /// Structure used for translating a series of functions into Cretonne IR.
 ///
@@ -182,6 +184,11 @@ where
                             )
                         }
                     }
+// An invoke has a normal destination and a landing pad
+                    if let InstructionData::Invoke { ref targets, .. } = data {
+                        self.builder.declare_successor(targets.destination, inst);
+                        self.builder.declare_successor(targets.landing_pad, inst);
+                    }
                 }
             }
         }
@@ -576,6 +583,188 @@ where
     }
 }
 
+/// Helpers for copying and filling memory. Small operations with a size known at compile time
+/// are expanded inline as loads and stores, and everything else calls the `Memcpy`, `Memset` or
+/// `Memmove` library routines.
+impl<'a, Variable> FunctionBuilder<'a, Variable>
+where
+    Variable: EntityRef + Debug,
+{
+    /// Calls libc.memcpy to copy `size` bytes from `src` to `dest`.
+    ///
+    /// The regions must not overlap, and `size` must have the pointer type of `isa`.
+    pub fn call_memcpy(&mut self, isa: &TargetIsa, dest: Value, src: Value, size: Value) {
+        self.call_memory_libcall(LibCall::Memcpy, isa, &[dest, src, size]);
+    }
+
+    /// Calls libc.memset to fill `size` bytes at `buffer` with the low byte of `ch`.
+    ///
+    /// `ch` must be an `i32` and `size` must have the pointer type of `isa`.
+    pub fn call_memset(&mut self, isa: &TargetIsa, buffer: Value, ch: Value, size: Value) {
+        self.call_memory_libcall(LibCall::Memset, isa, &[buffer, ch, size]);
+    }
+
+    /// Calls libc.memmove to copy `size` bytes from `src` to `dest`, where the regions may
+    /// overlap.
+    ///
+    /// `size` must have the pointer type of `isa`.
+    pub fn call_memmove(&mut self, isa: &TargetIsa, dest: Value, src: Value, size: Value) {
+        self.call_memory_libcall(LibCall::Memmove, isa, &[dest, src, size]);
+    }
+
+    /// Copies `size` bytes from `src` to `dest`, which must not overlap.
+    ///
+    /// Small copies are expanded inline as loads and stores no wider than the given alignments,
+    /// larger ones call libc.memcpy.
+    pub fn emit_small_memcpy(
+        &mut self,
+        isa: &TargetIsa,
+        dest: Value,
+        src: Value,
+        size: u64,
+        dest_align: u8,
+        src_align: u8,
+    ) {
+        if size == 0 {
+            return;
+        }
+        let accesses = memory_accesses(isa, size, dest_align.min(src_align));
+        if accesses.len() > MAX_INLINE_ACCESSES {
+            let size = self.ins().iconst(pointer_type(isa), size as i64);
+            return self.call_memcpy(isa, dest, src, size);
+        }
+        for (ty, offset) in accesses {
+            let value = self.load_piece(ty, src, offset);
+            self.store_piece(ty, value, dest, offset);
+        }
+    }
+
+    /// Fills `size` bytes at `buffer` with the byte `ch`.
+    ///
+    /// Small fills are expanded inline as stores no wider than `buffer_align`, larger ones call
+    /// libc.memset.
+    pub fn emit_small_memset(
+        &mut self,
+        isa: &TargetIsa,
+        buffer: Value,
+        ch: u8,
+        size: u64,
+        buffer_align: u8,
+    ) {
+        if size == 0 {
+            return;
+        }
+        let accesses = memory_accesses(isa, size, buffer_align);
+        if accesses.len() > MAX_INLINE_ACCESSES {
+            let ch = self.ins().iconst(types::I32, i64::from(ch));
+            let size = self.ins().iconst(pointer_type(isa), size as i64);
+            return self.call_memset(isa, buffer, ch, size);
+        }
+        let splat = u64::from(ch) * 0x0101_0101_0101_0101;
+        for (ty, offset) in accesses {
+            let value_type = if ty.bytes() < 4 { types::I32 } else { ty };
+            let bits = splat >> (64 - value_type.bits());
+            let value = self.ins().iconst(value_type, bits as i64);
+            self.store_piece(ty, value, buffer, offset);
+        }
+    }
+
+    /// Copies `size` bytes from `src` to `dest`, where the regions may overlap.
+    ///
+    /// Small moves load all the bytes before storing any of them, larger ones call
+    /// libc.memmove.
+    pub fn emit_small_memmove(
+        &mut self,
+        isa: &TargetIsa,
+        dest: Value,
+        src: Value,
+        size: u64,
+        dest_align: u8,
+        src_align: u8,
+    ) {
+        if size == 0 {
+            return;
+        }
+        let accesses = memory_accesses(isa, size, dest_align.min(src_align));
+        if accesses.len() > MAX_INLINE_ACCESSES {
+            let size = self.ins().iconst(pointer_type(isa), size as i64);
+            return self.call_memmove(isa, dest, src, size);
+        }
+        let values: Vec<_> = accesses
+            .iter()
+            .map(|&(ty, offset)| self.load_piece(ty, src, offset))
+            .collect();
+        for (&(ty, offset), value) in accesses.iter().zip(values) {
+            self.store_piece(ty, value, dest, offset);
+        }
+    }
+
+    fn call_memory_libcall(&mut self, libcall: LibCall, isa: &TargetIsa, args: &[Value]) {
+        let callee = get_memory_libcall_funcref(libcall, self.func, isa);
+        self.ins().call(callee, args);
+    }
+
+    /// Load a piece of memory of type `ty`, zero-extending 8-bit and 16-bit pieces to `i32`.
+    fn load_piece(&mut self, ty: Type, addr: Value, offset: i32) -> Value {
+        let flags = MemFlags::new();
+        match ty {
+            types::I8 => self.ins().uload8(types::I32, flags, addr, offset),
+            types::I16 => self.ins().uload16(types::I32, flags, addr, offset),
+            _ => self.ins().load(ty, flags, addr, offset),
+        }
+    }
+
+    /// Store a piece of memory loaded by `load_piece`.
+    fn store_piece(&mut self, ty: Type, value: Value, addr: Value, offset: i32) {
+        let flags = MemFlags::new();
+        match ty {
+            types::I8 => self.ins().istore8(flags, value, addr, offset),
+            types::I16 => self.ins().istore16(flags, value, addr, offset),
+            _ => self.ins().store(flags, value, addr, offset),
+        };
+    }
+}
+
+/// The largest number of loads or stores `emit_small_*` will expand a memory operation into.
+const MAX_INLINE_ACCESSES: usize = 4;
+
+fn pointer_type(isa: &TargetIsa) -> Type {
+    if isa.flags().is_64bit() {
+        types::I64
+    } else {
+        types::I32
+    }
+}
+
+/// Split `size` bytes into the integer pieces to access and their offsets.
+///
+/// The pieces are as wide as `align` allows, up to the width of a pointer, followed by narrower
+/// pieces for the remaining bytes.
+fn memory_accesses(isa: &TargetIsa, size: u64, align: u8) -> Vec<(Type, i32)> {
+    let max_width = u64::from(pointer_type(isa).bytes());
+    let mut width = max_width.min(u64::from(align.max(1)));
+    // Round a non-power-of-two alignment down.
+    while !width.is_power_of_two() {
+        width &= width - 1;
+    }
+
+    let mut accesses = Vec::new();
+    let mut offset = 0;
+    while offset < size {
+        while size - offset < width {
+            width /= 2;
+        }
+        let ty = Type::int(width as u16 * 8).unwrap();
+        accesses.push((ty, offset as i32));
+        offset += width;
+        // Give up early on large sizes; they will be handled by a library call anyway.
+        if accesses.len() > MAX_INLINE_ACCESSES {
+            break;
+        }
+    }
+    accesses
+}
+
 // Helper functions
 impl<'a, Variable> FunctionBuilder<'a, Variable>
 where
@@ -615,11 +804,14 @@ mod tests {
     use Variable;
     use cretonne_codegen::entity::EntityRef;
     use cretonne_codegen::ir::types::*;
-    use cretonne_codegen::ir::{AbiParam, ExternalName, Function, InstBuilder, Signature};
-    use cretonne_codegen::settings;
+    use cretonne_codegen::ir::{AbiParam, ExtFuncData, ExternalName, Function, InstBuilder,
+                               InvokeTargets, Signature, Value};
+    use cretonne_codegen::isa::{self, TargetIsa};
+    use cretonne_codegen::settings::{self, Configurable};
     use cretonne_codegen::settings::CallConv;
     use cretonne_codegen::verifier::verify_function;
     use frontend::{FunctionBuilder, FunctionBuilderContext};
+    use std::string::{String, ToString};
 
     fn sample_function(lazy_seal: bool) {
         let mut sig = Signature::new(CallConv::SystemV);
@@ -725,4 +917,161 @@ mod tests {
     fn sample_with_lazy_seal() {
         sample_function(true)
     }
+
+    #[test]
+    fn invoke_destination_with_params() {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.returns.push(AbiParam::new(I64));
+        sig.params.push(AbiParam::new(I64));
+
+        let mut fn_ctx = FunctionBuilderContext::<Variable>::new();
+        let mut func = Function::with_name_signature(ExternalName::testcase("invoke"), sig);
+        {
+            let mut builder = FunctionBuilder::<Variable>::new(&mut func, &mut fn_ctx);
+            let callee_sig = builder.import_signature(Signature::new(CallConv::SystemV));
+            let callee = builder.import_function(ExtFuncData {
+                name: ExternalName::testcase("callee"),
+                signature: callee_sig,
+                colocated: false,
+            });
+
+            let block0 = builder.create_ebb();
+            let block1 = builder.create_ebb();
+            let block2 = builder.create_ebb();
+            let block3 = builder.create_ebb();
+            let x = Variable::new(0);
+            builder.declare_var(x, I64);
+            builder.append_ebb_params_for_function_params(block0);
+
+            builder.switch_to_block(block0);
+            let arg = builder.ebb_params(block0)[0];
+            builder.def_var(x, arg);
+            builder.ins().brnz(arg, block3, &[]);
+            let targets = InvokeTargets {
+                func_ref: callee,
+                destination: block1,
+                landing_pad: block2,
+            };
+            builder.ins().invoke(targets, &[]);
+
+            builder.switch_to_block(block3);
+            let five = builder.ins().iconst(I64, 5);
+            builder.def_var(x, five);
+            builder.ins().jump(block1, &[]);
+
+            // `x` needs an EBB parameter here, so the edge from the `invoke` must be split.
+            builder.switch_to_block(block1);
+            let ret = builder.use_var(x);
+            builder.ins().return_(&[ret]);
+
+            builder.switch_to_block(block2);
+            builder.ins().landingpad(I64);
+            let ret = builder.use_var(x);
+            builder.ins().return_(&[ret]);
+
+            builder.seal_all_blocks();
+            builder.finalize();
+        }
+
+        let flags = settings::Flags::new(settings::builder());
+        if let Err(err) = verify_function(&func, &flags) {
+            panic!("{}{}", func.display(None), err);
+        }
+    }
+
+    /// Build a function `(i64, i64)` with `body`, and return its text.
+    fn memory_function<F>(body: F) -> String
+    where
+        F: FnOnce(&mut FunctionBuilder<Variable>, &TargetIsa, Value, Value),
+    {
+        let mut flag_builder = settings::builder();
+        flag_builder.enable("is_64bit").unwrap();
+        let isa = isa::lookup("x86").unwrap().finish(
+            settings::Flags::new(flag_builder),
+        );
+
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(I64));
+        sig.params.push(AbiParam::new(I64));
+        let mut fn_ctx = FunctionBuilderContext::<Variable>::new();
+        let mut func = Function::with_name_signature(ExternalName::testcase("memory"), sig);
+        {
+            let mut builder = FunctionBuilder::<Variable>::new(&mut func, &mut fn_ctx);
+            let ebb = builder.create_ebb();
+            builder.append_ebb_params_for_function_params(ebb);
+            builder.switch_to_block(ebb);
+            builder.seal_block(ebb);
+            let dest = builder.ebb_params(ebb)[0];
+            let src = builder.ebb_params(ebb)[1];
+            body(&mut builder, &*isa, dest, src);
+            builder.ins().return_(&[]);
+            builder.finalize();
+        }
+
+        verify_function(&func, &*isa).unwrap();
+        func.to_string()
+    }
+
+    #[test]
+    fn small_memcpy() {
+        let func = memory_function(|builder, isa, dest, src| {
+            builder.emit_small_memcpy(isa, dest, src, 11, 8, 4);
+        });
+        assert_eq!(
+            func,
+            "function %memory(i64, i64) system_v {\n\
+             ebb0(v0: i64, v1: i64):\n    \
+             v2 = load.i32 v1\n    store v2, v0\n    \
+             v3 = load.i32 v1+4\n    store v3, v0+4\n    \
+             v4 = uload16.i32 v1+8\n    istore16 v4, v0+8\n    \
+             v5 = uload8.i32 v1+10\n    istore8 v5, v0+10\n    \
+             return\n}\n"
+        );
+    }
+
+    #[test]
+    fn large_memcpy() {
+        let func = memory_function(|builder, isa, dest, src| {
+            builder.emit_small_memcpy(isa, dest, src, 1024, 8, 8);
+        });
+        assert_eq!(
+            func,
+            "function %memory(i64, i64) system_v {\n    \
+             sig0 = (i64, i64, i64) fast\n    \
+             fn0 = %Memcpy sig0\n\n\
+             ebb0(v0: i64, v1: i64):\n    \
+             v2 = iconst.i64 1024\n    call fn0(v0, v1, v2)\n    \
+             return\n}\n"
+        );
+    }
+
+    #[test]
+    fn small_memset() {
+        let func = memory_function(|builder, isa, dest, _| {
+            builder.emit_small_memset(isa, dest, 0x2a, 10, 8);
+        });
+        assert_eq!(
+            func,
+            "function %memory(i64, i64) system_v {\n\
+             ebb0(v0: i64, v1: i64):\n    \
+             v2 = iconst.i64 0x2a2a_2a2a_2a2a_2a2a\n    store v2, v0\n    \
+             v3 = iconst.i32 0x2a2a_2a2a\n    istore16 v3, v0+8\n    \
+             return\n}\n"
+        );
+    }
+
+    #[test]
+    fn small_memmove() {
+        let func = memory_function(|builder, isa, dest, src| {
+            builder.emit_small_memmove(isa, dest, src, 16, 8, 8);
+        });
+        assert_eq!(
+            func,
+            "function %memory(i64, i64) system_v {\n\
+             ebb0(v0: i64, v1: i64):\n    \
+             v2 = load.i64 v1\n    v3 = load.i64 v1+8\n    \
+             store v2, v0\n    store v3, v0+8\n    \
+             return\n}\n"
+        );
+    }
 }
@@ -147,13 +147,18 @@
 extern crate cretonne_codegen;
 
 #[cfg(not(feature = "std"))]
+#[macro_use]
 extern crate alloc;
 
+pub use control::{IfElse, Loop};
 pub use frontend::{FunctionBuilder, FunctionBuilderContext};
+pub use switch::{EntryIndex, Switch};
 pub use variable::Variable;
 
+mod control;
 mod frontend;
 mod ssa;
+mod switch;
 mod variable;
 
 #[cfg(not(feature = "std"))]
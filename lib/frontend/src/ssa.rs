@@ -10,7 +10,7 @@ use cretonne_codegen::entity::{EntityMap, EntityRef, PrimaryMap};
 use cretonne_codegen::ir::immediates::{Ieee32, Ieee64};
 use cretonne_codegen::ir::instructions::BranchInfo;
 use cretonne_codegen::ir::types::{F32, F64};
-use cretonne_codegen::ir::{Ebb, Function, Inst, InstBuilder, Type, Value};
+use cretonne_codegen::ir::{Ebb, Function, Inst, InstBuilder, InstructionData, Type, Value};
 use cretonne_codegen::packed_option::PackedOption;
 use cretonne_codegen::packed_option::ReservedValue;
 use std::mem;
@@ -650,6 +650,28 @@ where
                 self.def_var(var, val, middle_block);
                 Some((middle_ebb, middle_block, middle_jump_inst))
             }
+            BranchInfo::Invoke(destination, landing_pad) => {
+                // Landing pads can't have parameters, so the variable must be defined before
+                // the `invoke` instead.
+                if dest_ebb == landing_pad {
+                    panic!("{} is a landing pad and can't have parameters", landing_pad);
+                }
+                debug_assert_eq!(dest_ebb, destination);
+                // The normal destination of an `invoke` can't have parameters either, so we
+                // split the edge like for a jump table.
+                let middle_ebb = func.dfg.make_ebb();
+                func.layout.append_ebb(middle_ebb);
+                let middle_block = self.declare_ebb_header_block(middle_ebb);
+                self.blocks[middle_block].add_predecessor(jump_inst_block, jump_inst);
+                self.mark_ebb_header_block_sealed(middle_block);
+                if let InstructionData::Invoke { ref mut targets, .. } = func.dfg[jump_inst] {
+                    targets.destination = middle_ebb;
+                }
+                let mut cur = FuncCursor::new(func).at_bottom(middle_ebb);
+                let middle_jump_inst = cur.ins().jump(dest_ebb, &[val]);
+                self.def_var(var, val, middle_block);
+                Some((middle_ebb, middle_block, middle_jump_inst))
+            }
         }
     }
 
@@ -1026,7 +1048,7 @@ mod tests {
             Ok(()) => {}
             Err(_err) => {
                 #[cfg(feature = "std")]
-                panic!(_err.message);
+                panic!("{}", _err);
                 #[cfg(not(feature = "std"))]
                 panic!("function failed to verify");
             }
@@ -1205,7 +1227,7 @@ mod tests {
             Ok(()) => {}
             Err(_err) => {
                 #[cfg(feature = "std")]
-                panic!(_err.message);
+                panic!("{}", _err);
                 #[cfg(not(feature = "std"))]
                 panic!("function failed to verify");
             }
@@ -1256,7 +1278,7 @@ mod tests {
             Ok(()) => {}
             Err(_err) => {
                 #[cfg(feature = "std")]
-                panic!(_err.message);
+                panic!("{}", _err);
                 #[cfg(not(feature = "std"))]
                 panic!("function failed to verify");
             }
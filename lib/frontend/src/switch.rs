@@ -0,0 +1,335 @@
+//! Lowering of multi-way branches.
+//!
+//! A `Switch` collects the cases of a multi-way branch and emits them through a
+//! `FunctionBuilder`. Dense groups of cases become `br_table` instructions while sparse cases
+//! are compared one at a time, and a binary search tree is used to pick the right group.
+
+use cretonne_codegen::entity::EntityRef;
+use cretonne_codegen::ir::condcodes::IntCC;
+use cretonne_codegen::ir::{Ebb, InstBuilder, JumpTableData, Value};
+use frontend::FunctionBuilder;
+use std::fmt::Debug;
+use std::vec::Vec;
+
+/// Index of a case in a `Switch`.
+pub type EntryIndex = u64;
+
+/// Minimum number of cases in a cluster for it to be emitted as a jump table.
+const MIN_JUMP_TABLE_CASES: usize = 4;
+
+/// Maximum number of clusters handled by a linear sequence of tests rather than a search tree.
+const MAX_LINEAR_CLUSTERS: usize = 3;
+
+/// Builder for a multi-way branch on an integer value.
+///
+/// Cases are registered with `set_entry`, and the whole switch is emitted at the current
+/// position of a `FunctionBuilder` with `emit`:
+///
+/// ```rust
+/// # extern crate cretonne_codegen;
+/// # extern crate cretonne_frontend;
+/// # use cretonne_codegen::ir::{ExternalName, Function, Signature, AbiParam, InstBuilder};
+/// # use cretonne_codegen::ir::types::*;
+/// # use cretonne_codegen::settings::CallConv;
+/// # use cretonne_frontend::{FunctionBuilderContext, FunctionBuilder, Switch, Variable};
+/// # fn main() {
+/// # let mut sig = Signature::new(CallConv::SystemV);
+/// # sig.params.push(AbiParam::new(I32));
+/// # let mut fn_ctx = FunctionBuilderContext::<Variable>::new();
+/// # let mut func = Function::with_name_signature(ExternalName::user(0, 0), sig);
+/// # let mut builder = FunctionBuilder::<Variable>::new(&mut func, &mut fn_ctx);
+/// # let entry = builder.create_ebb();
+/// # builder.append_ebb_params_for_function_params(entry);
+/// # builder.switch_to_block(entry);
+/// # builder.seal_block(entry);
+/// # let val = builder.ebb_params(entry)[0];
+/// let bar_ebb = builder.create_ebb();
+/// let baz_ebb = builder.create_ebb();
+/// let default_ebb = builder.create_ebb();
+///
+/// let mut switch = Switch::new();
+/// switch.set_entry(0, bar_ebb);
+/// switch.set_entry(1, bar_ebb);
+/// switch.set_entry(7, baz_ebb);
+/// switch.emit(&mut builder, val, default_ebb);
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Switch {
+    cases: Vec<(EntryIndex, Ebb)>,
+}
+
+/// A group of cases emitted together, either as a jump table or as a sequence of comparisons.
+#[derive(Debug)]
+struct Cluster {
+    /// Index of the first entry in `ebbs`.
+    first_index: EntryIndex,
+    /// Destinations for the indices `first_index..first_index + ebbs.len()`. Holes are `None`.
+    ebbs: Vec<Option<Ebb>>,
+    /// Number of cases in the cluster, not counting holes.
+    num_cases: usize,
+}
+
+impl Cluster {
+    fn single(index: EntryIndex, ebb: Ebb) -> Self {
+        Self {
+            first_index: index,
+            ebbs: vec![Some(ebb)],
+            num_cases: 1,
+        }
+    }
+
+    fn last_index(&self) -> EntryIndex {
+        self.first_index + (self.ebbs.len() - 1) as EntryIndex
+    }
+}
+
+impl Switch {
+    /// Create a new empty switch.
+    pub fn new() -> Self {
+        Self { cases: Vec::new() }
+    }
+
+    /// Branch to `ebb` when the switch value is `index`.
+    ///
+    /// Each index can only be set once.
+    pub fn set_entry(&mut self, index: EntryIndex, ebb: Ebb) {
+        debug_assert!(
+            self.cases.iter().all(|&(i, _)| i != index),
+            "switch entry {} set twice",
+            index
+        );
+        self.cases.push((index, ebb));
+    }
+
+    /// Get the cases registered so far, in the order they were added.
+    pub fn entries(&self) -> &[(EntryIndex, Ebb)] {
+        &self.cases
+    }
+
+    /// Emit the switch at the current position of `builder`, branching on `val`.
+    ///
+    /// Control is transferred to `otherwise` when `val` doesn't match any case. The current
+    /// block is filled by this function; the blocks created for the search tree are sealed.
+    pub fn emit<Variable>(self, builder: &mut FunctionBuilder<Variable>, val: Value, otherwise: Ebb)
+    where
+        Variable: EntityRef + Debug,
+    {
+        let clusters = self.collect_clusters();
+        Self::build_search_tree(builder, val, otherwise, &clusters);
+    }
+
+    /// Sort the cases and group them into clusters that are dense enough for a jump table.
+    ///
+    /// A case is added to the current cluster when the cluster would still have at least one
+    /// case for every two table entries.
+    fn collect_clusters(mut self) -> Vec<Cluster> {
+        self.cases.sort_by_key(|&(index, _)| index);
+
+        let mut clusters: Vec<Cluster> = Vec::new();
+        for (index, ebb) in self.cases {
+            if let Some(cluster) = clusters.last_mut() {
+                let span = (index - cluster.first_index).saturating_add(1);
+                if span <= 2 * (cluster.num_cases as EntryIndex + 1) {
+                    let gap = (index - cluster.last_index() - 1) as usize;
+                    cluster.ebbs.extend((0..gap).map(|_| None));
+                    cluster.ebbs.push(Some(ebb));
+                    cluster.num_cases += 1;
+                    continue;
+                }
+            }
+            clusters.push(Cluster::single(index, ebb));
+        }
+
+        // Clusters too small for a jump table are split back into single cases, which avoids
+        // testing the holes between them.
+        let mut result = Vec::with_capacity(clusters.len());
+        for cluster in clusters {
+            if cluster.num_cases >= MIN_JUMP_TABLE_CASES {
+                result.push(cluster);
+                continue;
+            }
+            for (offset, ebb) in cluster.ebbs.into_iter().enumerate() {
+                if let Some(ebb) = ebb {
+                    result.push(Cluster::single(
+                        cluster.first_index + offset as EntryIndex,
+                        ebb,
+                    ));
+                }
+            }
+        }
+        result
+    }
+
+    /// Emit a binary search tree over `clusters`, with linear tests at the leaves.
+    fn build_search_tree<Variable>(
+        builder: &mut FunctionBuilder<Variable>,
+        val: Value,
+        otherwise: Ebb,
+        clusters: &[Cluster],
+    ) where
+        Variable: EntityRef + Debug,
+    {
+        if clusters.len() <= MAX_LINEAR_CLUSTERS {
+            for cluster in clusters {
+                Self::build_cluster(builder, val, cluster);
+            }
+            builder.ins().jump(otherwise, &[]);
+            return;
+        }
+
+        let (left, right) = clusters.split_at(clusters.len() / 2);
+        let right_ebb = builder.create_ebb();
+        let is_right = builder.ins().icmp_imm(
+            IntCC::UnsignedGreaterThanOrEqual,
+            val,
+            right[0].first_index as i64,
+        );
+        builder.ins().brnz(is_right, right_ebb, &[]);
+        Self::build_search_tree(builder, val, otherwise, left);
+
+        builder.switch_to_block(right_ebb);
+        builder.seal_block(right_ebb);
+        Self::build_search_tree(builder, val, otherwise, right);
+    }
+
+    /// Emit the branches for a single cluster. Execution continues after the cluster's
+    /// instructions when `val` doesn't match any of its cases.
+    fn build_cluster<Variable>(
+        builder: &mut FunctionBuilder<Variable>,
+        val: Value,
+        cluster: &Cluster,
+    ) where
+        Variable: EntityRef + Debug,
+    {
+        if cluster.ebbs.len() == 1 {
+            let ebb = cluster.ebbs[0].expect("cluster without cases");
+            let is_equal = builder
+                .ins()
+                .icmp_imm(IntCC::Equal, val, cluster.first_index as i64);
+            builder.ins().brnz(is_equal, ebb, &[]);
+            return;
+        }
+
+        let mut jt_data = JumpTableData::with_capacity(cluster.ebbs.len());
+        for (index, ebb) in cluster.ebbs.iter().enumerate() {
+            match *ebb {
+                Some(ebb) => jt_data.set_entry(index, ebb),
+                None => jt_data.clear_entry(index),
+            }
+        }
+        let jt = builder.create_jump_table(jt_data);
+
+        // Indices below `first_index` wrap around and fall through the table.
+        let index = if cluster.first_index == 0 {
+            val
+        } else {
+            builder
+                .ins()
+                .iadd_imm(val, (cluster.first_index as i64).wrapping_neg())
+        };
+        builder.ins().br_table(index, jt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cretonne_codegen::entity::EntityRef;
+    use cretonne_codegen::ir::types::*;
+    use cretonne_codegen::ir::{AbiParam, ExternalName, Function, Signature};
+    use cretonne_codegen::settings::{self, CallConv};
+    use cretonne_codegen::verifier::verify_function;
+    use frontend::FunctionBuilderContext;
+    use std::string::{String, ToString};
+    use Variable;
+
+    /// Build a function switching on its argument, with one destination EBB per case.
+    fn switch_function(indices: &[EntryIndex]) -> String {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(I32));
+        let mut fn_ctx = FunctionBuilderContext::<Variable>::new();
+        let mut func = Function::with_name_signature(ExternalName::testcase("switch"), sig);
+        {
+            let mut builder = FunctionBuilder::<Variable>::new(&mut func, &mut fn_ctx);
+            let entry = builder.create_ebb();
+            builder.append_ebb_params_for_function_params(entry);
+            let otherwise = builder.create_ebb();
+
+            let mut switch = Switch::new();
+            let mut dests = Vec::new();
+            for &index in indices {
+                let ebb = builder.create_ebb();
+                switch.set_entry(index, ebb);
+                dests.push(ebb);
+            }
+
+            builder.switch_to_block(entry);
+            builder.seal_block(entry);
+            let val = builder.ebb_params(entry)[0];
+            switch.emit(&mut builder, val, otherwise);
+
+            for ebb in dests.into_iter().chain(Some(otherwise)) {
+                builder.switch_to_block(ebb);
+                builder.seal_block(ebb);
+                builder.ins().return_(&[]);
+            }
+            builder.finalize();
+        }
+
+        let flags = settings::Flags::new(settings::builder());
+        verify_function(&func, &flags).unwrap();
+        func.to_string()
+    }
+
+    #[test]
+    fn switch_empty() {
+        let func = switch_function(&[]);
+        assert!(func.contains("ebb0(v0: i32):\n    jump ebb1\n"));
+    }
+
+    #[test]
+    fn switch_sparse() {
+        let func = switch_function(&[20, 0, 1000]);
+        assert!(func.contains(
+            "    v1 = icmp_imm eq v0, 0\n    brnz v1, ebb3\n    \
+             v2 = icmp_imm eq v0, 20\n    brnz v2, ebb2\n    \
+             v3 = icmp_imm eq v0, 1000\n    brnz v3, ebb4\n    jump ebb1\n"
+        ));
+        assert!(!func.contains("br_table"));
+    }
+
+    #[test]
+    fn switch_dense() {
+        let func = switch_function(&[0, 1, 2, 4]);
+        assert!(func.contains("jt0 = jump_table ebb2, ebb3, ebb4, 0, ebb5\n"));
+        assert!(func.contains("ebb0(v0: i32):\n    br_table v0, jt0\n    jump ebb1\n"));
+    }
+
+    #[test]
+    fn switch_offset_table() {
+        let func = switch_function(&[10, 11, 12, 13]);
+        assert!(func.contains("jt0 = jump_table ebb2, ebb3, ebb4, ebb5\n"));
+        assert!(func.contains("v1 = iadd_imm v0, -10\n    br_table v1, jt0\n    jump ebb1\n"));
+    }
+
+    #[test]
+    fn switch_search_tree() {
+        let func = switch_function(&[0, 1, 2, 3, 100, 200, 300, 400]);
+        // The clusters are [0, 3], 100, 200, 300 and 400, split in two at 200.
+        assert!(func.contains("v1 = icmp_imm uge v0, 200\n    brnz v1, ebb10\n"));
+        assert!(func.contains("br_table v0, jt0\n    v2 = icmp_imm eq v0, 100\n"));
+        assert!(func.contains(
+            "ebb10:\n    v3 = icmp_imm.i32 eq v0, 200\n    brnz v3, ebb7\n    \
+             v4 = icmp_imm.i32 eq v0, 300\n    brnz v4, ebb8\n"
+        ));
+    }
+
+    #[test]
+    fn switch_entries() {
+        let mut switch = Switch::new();
+        switch.set_entry(3, Ebb::new(1));
+        switch.set_entry(1, Ebb::new(2));
+        assert_eq!(switch.entries(), &[(3, Ebb::new(1)), (1, Ebb::new(2))]);
+    }
+}
@@ -0,0 +1,597 @@
+//! Generating random functions.
+
+use cretonne_codegen::cursor::{Cursor, FuncCursor};
+use cretonne_codegen::ir::condcodes::{FloatCC, IntCC};
+use cretonne_codegen::ir::immediates::{Ieee32, Ieee64};
+use cretonne_codegen::ir::types::{B1, F32, F64, I32, I64};
+use cretonne_codegen::ir::{AbiParam, Ebb, ExternalName, Function, InstBuilder, Signature,
+                           StackSlot, StackSlotData, StackSlotKind, Type, Value};
+use cretonne_codegen::settings::CallConv;
+use cretonne_interpreter::DataValue;
+use input::Input;
+use std::vec::Vec;
+
+/// Types of function parameters, return values and EBB parameters.
+const TYPES: [Type; 4] = [I32, I64, F32, F64];
+
+const INT_CCS: [IntCC; 10] = [
+    IntCC::Equal,
+    IntCC::NotEqual,
+    IntCC::SignedLessThan,
+    IntCC::SignedGreaterThanOrEqual,
+    IntCC::SignedGreaterThan,
+    IntCC::SignedLessThanOrEqual,
+    IntCC::UnsignedLessThan,
+    IntCC::UnsignedGreaterThanOrEqual,
+    IntCC::UnsignedGreaterThan,
+    IntCC::UnsignedLessThanOrEqual,
+];
+
+const FLOAT_CCS: [FloatCC; 8] = [
+    FloatCC::Ordered,
+    FloatCC::Unordered,
+    FloatCC::Equal,
+    FloatCC::NotEqual,
+    FloatCC::LessThan,
+    FloatCC::LessThanOrEqual,
+    FloatCC::GreaterThan,
+    FloatCC::GreaterThanOrEqual,
+];
+
+/// Integers which are more likely to expose bugs than random ones.
+const INTERESTING_INTS: [i64; 8] = [0, 1, -1, 2, 0x7fff_ffff, -0x8000_0000, ::std::i64::MAX,
+                                    ::std::i64::MIN];
+
+/// Limits on the size of generated test cases.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Maximum number of function parameters.
+    pub max_params: usize,
+    /// Maximum number of function return values.
+    pub max_returns: usize,
+    /// Maximum number of EBBs in a function.
+    pub max_ebbs: usize,
+    /// Maximum number of parameters of an EBB other than the entry block.
+    pub max_ebb_params: usize,
+    /// Maximum number of instructions generated in each EBB, not counting terminators.
+    pub max_insts: usize,
+    /// Maximum number of explicit stack slots.
+    pub max_stack_slots: usize,
+    /// Maximum number of argument lists to call the function with.
+    pub max_inputs: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_params: 4,
+            max_returns: 2,
+            max_ebbs: 6,
+            max_ebb_params: 3,
+            max_insts: 16,
+            max_stack_slots: 2,
+            max_inputs: 4,
+        }
+    }
+}
+
+/// A randomly generated function along with arguments to call it with.
+pub struct TestCase {
+    /// The function to test. It passes the verifier, terminates for all arguments, and doesn't
+    /// trap.
+    pub func: Function,
+    /// Lists of arguments matching the function's signature.
+    pub inputs: Vec<Vec<DataValue>>,
+}
+
+impl TestCase {
+    /// Generate a test case from the bytes in `data`.
+    pub fn generate(data: &[u8], config: &Config) -> Self {
+        let mut input = Input::new(data);
+        let func = generate_function(&mut input, config);
+        let count = input.range(1, config.max_inputs.max(1));
+        let inputs = (0..count)
+            .map(|_| {
+                func.signature
+                    .params
+                    .iter()
+                    .map(|param| generate_value(&mut input, param.value_type))
+                    .collect()
+            })
+            .collect();
+        Self { func, inputs }
+    }
+}
+
+/// Generate a random argument of type `ty`.
+fn generate_value(input: &mut Input, ty: Type) -> DataValue {
+    let bits = if input.chance(64) {
+        *input.choose(&INTERESTING_INTS) as u64
+    } else {
+        input.u64()
+    };
+    DataValue::from_bits(ty, bits)
+}
+
+/// An EBB being generated, along with the parameters needed to branch to it.
+struct EbbInfo {
+    ebb: Ebb,
+    /// Is this EBB a loop header? If so, its first parameter is an `i32` trip counter and it
+    /// branches back to itself until the counter reaches zero.
+    is_loop: bool,
+}
+
+/// Generate a random function.
+fn generate_function(input: &mut Input, config: &Config) -> Function {
+    let mut sig = Signature::new(CallConv::SystemV);
+    for _ in 0..input.range(0, config.max_params) {
+        sig.params.push(AbiParam::new(*input.choose(&TYPES)));
+    }
+    for _ in 0..input.range(1, config.max_returns.max(1)) {
+        sig.returns.push(AbiParam::new(*input.choose(&TYPES)));
+    }
+    let mut func = Function::with_name_signature(ExternalName::testcase("fuzz"), sig);
+    FunctionGenerator {
+        input,
+        config,
+        pos: FuncCursor::new(&mut func),
+        ebbs: Vec::new(),
+        slots: Vec::new(),
+        entry_values: Vec::new(),
+        values: Vec::new(),
+    }.generate();
+    func
+}
+
+struct FunctionGenerator<'a, 'b: 'a> {
+    input: &'a mut Input<'b>,
+    config: &'a Config,
+    pos: FuncCursor<'a>,
+    ebbs: Vec<EbbInfo>,
+    slots: Vec<(StackSlot, u32)>,
+    /// Values defined in the entry block, which dominates the whole function.
+    entry_values: Vec<Value>,
+    /// Values defined in the current EBB.
+    values: Vec<Value>,
+}
+
+impl<'a, 'b> FunctionGenerator<'a, 'b> {
+    fn generate(mut self) {
+        for _ in 0..self.input.range(0, self.config.max_stack_slots) {
+            let size = 8 * self.input.range(1, 4) as u32;
+            let slot = self.pos.func.create_stack_slot(
+                StackSlotData::new(StackSlotKind::ExplicitSlot, size),
+            );
+            self.slots.push((slot, size));
+        }
+
+        let num_ebbs = self.input.range(1, self.config.max_ebbs.max(1));
+        for i in 0..num_ebbs {
+            let ebb = self.pos.func.dfg.make_ebb();
+            let is_loop = i > 0 && self.input.chance(64);
+            if i == 0 {
+                for param in 0..self.pos.func.signature.params.len() {
+                    let ty = self.pos.func.signature.params[param].value_type;
+                    self.pos.func.dfg.append_ebb_param(ebb, ty);
+                }
+            } else {
+                if is_loop {
+                    self.pos.func.dfg.append_ebb_param(ebb, I32);
+                }
+                for _ in 0..self.input.range(0, self.config.max_ebb_params) {
+                    let ty = *self.input.choose(&TYPES);
+                    self.pos.func.dfg.append_ebb_param(ebb, ty);
+                }
+            }
+            self.ebbs.push(EbbInfo { ebb, is_loop });
+        }
+
+        for i in 0..num_ebbs {
+            self.generate_ebb(i);
+        }
+    }
+
+    /// Fill in the EBB number `index`.
+    fn generate_ebb(&mut self, index: usize) {
+        let ebb = self.ebbs[index].ebb;
+        self.pos.insert_ebb(ebb);
+        self.values = self.pos.func.dfg.ebb_params(ebb).to_vec();
+
+        if index == 0 && !self.slots.is_empty() {
+            // Stack slots start out uninitialized, so clear them to make the results
+            // deterministic.
+            let zero = self.pos.ins().iconst(I64, 0);
+            for i in 0..self.slots.len() {
+                let (slot, size) = self.slots[i];
+                for offset in (0..size).step_by(8) {
+                    self.pos.ins().stack_store(zero, slot, offset as i32);
+                }
+            }
+        }
+
+        for _ in 0..self.input.range(0, self.config.max_insts) {
+            self.generate_inst();
+        }
+        if index == 0 {
+            // Values defined after the first branch don't dominate its destination.
+            self.entry_values = self.values.clone();
+        }
+
+        let num_ebbs = self.ebbs.len();
+        if index + 1 < num_ebbs && self.input.chance(96) {
+            let target = self.input.range(index + 1, num_ebbs - 1);
+            let cond = self.condition();
+            let args = self.ebb_args(target);
+            let dest = self.ebbs[target].ebb;
+            if self.input.chance(128) {
+                self.pos.ins().brz(cond, dest, &args);
+            } else {
+                self.pos.ins().brnz(cond, dest, &args);
+            }
+        }
+
+        if self.ebbs[index].is_loop {
+            let counter = self.pos.func.dfg.ebb_params(ebb)[0];
+            let next = self.pos.ins().iadd_imm(counter, -1);
+            let mut args = self.ebb_args(index);
+            args[0] = next;
+            self.pos.ins().brnz(next, ebb, &args);
+        }
+
+        if index + 1 < num_ebbs {
+            let args = self.ebb_args(index + 1);
+            let dest = self.ebbs[index + 1].ebb;
+            self.pos.ins().jump(dest, &args);
+        } else {
+            let returns = self.pos.func
+                .signature
+                .returns
+                .iter()
+                .map(|ret| ret.value_type)
+                .collect::<Vec<_>>();
+            let args = returns.into_iter().map(|ty| self.value(ty)).collect::<Vec<_>>();
+            self.pos.ins().return_(&args);
+        }
+    }
+
+    /// Make `value` available to the following instructions.
+    fn define(&mut self, value: Value) -> Value {
+        self.values.push(value);
+        value
+    }
+
+    /// Get the arguments for branching to the EBB number `index`.
+    fn ebb_args(&mut self, index: usize) -> Vec<Value> {
+        let ebb = self.ebbs[index].ebb;
+        let types = self.pos.func
+            .dfg
+            .ebb_params(ebb)
+            .iter()
+            .map(|&param| self.pos.func.dfg.value_type(param))
+            .collect::<Vec<_>>();
+        let mut args = Vec::with_capacity(types.len());
+        for (i, ty) in types.into_iter().enumerate() {
+            if i == 0 && self.ebbs[index].is_loop {
+                let trips = self.input.range(1, 4) as i64;
+                args.push(self.pos.ins().iconst(I32, trips));
+            } else {
+                args.push(self.value(ty));
+            }
+        }
+        args
+    }
+
+    /// Pick an available value of type `ty`, or create a new one.
+    fn value(&mut self, ty: Type) -> Value {
+        let candidates = self.entry_values
+            .iter()
+            .chain(&self.values)
+            .cloned()
+            .filter(|&v| self.pos.func.dfg.value_type(v) == ty)
+            .collect::<Vec<_>>();
+        if candidates.is_empty() || self.input.chance(16) {
+            self.constant(ty)
+        } else {
+            *self.input.choose(&candidates)
+        }
+    }
+
+    /// Create a constant of type `ty`.
+    fn constant(&mut self, ty: Type) -> Value {
+        let bits = match self.input.below(3) {
+            0 => *self.input.choose(&INTERESTING_INTS) as u64,
+            1 => u64::from(self.input.byte()),
+            _ => self.input.u64(),
+        };
+        let value = match ty {
+            F32 => self.pos.ins().f32const(Ieee32::with_bits(bits as u32)),
+            F64 => self.pos.ins().f64const(Ieee64::with_bits(bits)),
+            B1 => self.pos.ins().bconst(B1, bits & 1 != 0),
+            _ => self.pos.ins().iconst(ty, bits as i64),
+        };
+        self.define(value)
+    }
+
+    /// Pick a value to branch on.
+    fn condition(&mut self) -> Value {
+        let ty = *self.input.choose(&[B1, I32, I64]);
+        self.value(ty)
+    }
+
+    /// Pick a type for the result of a new instruction.
+    fn result_type(&mut self) -> Type {
+        *self.input.choose(&[I32, I64, F32, F64, B1])
+    }
+
+    /// Generate a random instruction.
+    fn generate_inst(&mut self) {
+        if !self.slots.is_empty() && self.input.chance(32) {
+            return self.generate_stack_access();
+        }
+        let ty = self.result_type();
+        let value = match ty {
+            I32 | I64 => self.generate_int(ty),
+            F32 | F64 => self.generate_float(ty),
+            _ => self.generate_bool(),
+        };
+        self.define(value);
+    }
+
+    /// Generate an instruction with a single integer result of type `ty`.
+    fn generate_int(&mut self, ty: Type) -> Value {
+        let x = self.value(ty);
+        let imm = if self.input.chance(128) {
+            i64::from(self.input.byte() as i8)
+        } else {
+            *self.input.choose(&INTERESTING_INTS)
+        };
+        let shift = i64::from(self.input.byte() % 64);
+        match self.input.below(28) {
+            0 => {
+                let y = self.value(ty);
+                self.pos.ins().iadd(x, y)
+            }
+            1 => {
+                let y = self.value(ty);
+                self.pos.ins().isub(x, y)
+            }
+            2 => {
+                let y = self.value(ty);
+                self.pos.ins().imul(x, y)
+            }
+            3 => {
+                let y = self.value(ty);
+                self.pos.ins().band(x, y)
+            }
+            4 => {
+                let y = self.value(ty);
+                self.pos.ins().bor(x, y)
+            }
+            5 => {
+                let y = self.value(ty);
+                self.pos.ins().bxor(x, y)
+            }
+            6 => {
+                let y = self.value(ty);
+                self.pos.ins().ishl(x, y)
+            }
+            7 => {
+                let y = self.value(ty);
+                self.pos.ins().ushr(x, y)
+            }
+            8 => {
+                let y = self.value(ty);
+                self.pos.ins().sshr(x, y)
+            }
+            9 => {
+                let y = self.value(ty);
+                self.pos.ins().rotl(x, y)
+            }
+            10 => {
+                let y = self.value(ty);
+                self.pos.ins().rotr(x, y)
+            }
+            11 => self.pos.ins().iadd_imm(x, imm),
+            12 => self.pos.ins().imul_imm(x, imm),
+            13 => self.pos.ins().band_imm(x, imm),
+            14 => self.pos.ins().bor_imm(x, imm),
+            15 => self.pos.ins().bxor_imm(x, imm),
+            16 => self.pos.ins().ishl_imm(x, shift),
+            17 => self.pos.ins().ushr_imm(x, shift),
+            18 => self.pos.ins().sshr_imm(x, shift),
+            19 => self.pos.ins().bnot(x),
+            20 => self.pos.ins().clz(x),
+            21 => self.pos.ins().ctz(x),
+            22 => self.pos.ins().popcnt(x),
+            23 => {
+                // Make the divisor odd, so it can't be zero.
+                let y = self.value(ty);
+                let divisor = self.pos.ins().bor_imm(y, 1);
+                if self.input.chance(128) {
+                    self.pos.ins().udiv(x, divisor)
+                } else {
+                    self.pos.ins().urem(x, divisor)
+                }
+            }
+            24 => {
+                // Division by a constant is turned into a multiplication by `preopt`. Dividing by
+                // 0 traps, and so does dividing the minimum value by -1.
+                let shift = 64 - ty.bits();
+                let divisor = match imm << shift >> shift {
+                    0 | -1 => 7,
+                    d => d,
+                };
+                let y = self.pos.ins().iconst(ty, divisor);
+                match self.input.below(4) {
+                    0 => self.pos.ins().udiv(x, y),
+                    1 => self.pos.ins().urem(x, y),
+                    2 => self.pos.ins().sdiv(x, y),
+                    _ => self.pos.ins().srem(x, y),
+                }
+            }
+            25 => {
+                let cond = self.condition();
+                let y = self.value(ty);
+                self.pos.ins().select(cond, x, y)
+            }
+            26 => {
+                let b = self.generate_bool();
+                self.pos.ins().bint(ty, b)
+            }
+            _ => {
+                if ty == I64 {
+                    let y = self.value(I32);
+                    if self.input.chance(128) {
+                        self.pos.ins().uextend(I64, y)
+                    } else {
+                        self.pos.ins().sextend(I64, y)
+                    }
+                } else {
+                    let y = self.value(I64);
+                    self.pos.ins().ireduce(I32, y)
+                }
+            }
+        }
+    }
+
+    /// Generate an instruction with a single float result of type `ty`.
+    fn generate_float(&mut self, ty: Type) -> Value {
+        let x = self.value(ty);
+        let int_ty = if ty == F32 { I32 } else { I64 };
+        match self.input.below(10) {
+            0 => {
+                let y = self.value(ty);
+                self.pos.ins().fadd(x, y)
+            }
+            1 => {
+                let y = self.value(ty);
+                self.pos.ins().fsub(x, y)
+            }
+            2 => {
+                let y = self.value(ty);
+                self.pos.ins().fmul(x, y)
+            }
+            3 => {
+                let y = self.value(ty);
+                self.pos.ins().fdiv(x, y)
+            }
+            4 => self.pos.ins().fneg(x),
+            5 => self.pos.ins().fabs(x),
+            6 => self.pos.ins().sqrt(x),
+            7 => {
+                let y = self.value(int_ty);
+                self.pos.ins().bitcast(ty, y)
+            }
+            8 => {
+                let int_ty = *self.input.choose(&[I32, I64]);
+                let y = self.value(int_ty);
+                self.pos.ins().fcvt_from_sint(ty, y)
+            }
+            _ => {
+                if ty == F64 {
+                    let y = self.value(F32);
+                    self.pos.ins().fpromote(F64, y)
+                } else {
+                    let y = self.value(F64);
+                    self.pos.ins().fdemote(F32, y)
+                }
+            }
+        }
+    }
+
+    /// Generate a comparison with a `b1` result.
+    fn generate_bool(&mut self) -> Value {
+        let ty = *self.input.choose(&TYPES);
+        let x = self.value(ty);
+        if ty.is_float() {
+            let cc = *self.input.choose(&FLOAT_CCS);
+            let y = self.value(ty);
+            self.pos.ins().fcmp(cc, x, y)
+        } else {
+            let cc = *self.input.choose(&INT_CCS);
+            if self.input.chance(128) {
+                let y = self.value(ty);
+                self.pos.ins().icmp(cc, x, y)
+            } else {
+                let imm = *self.input.choose(&INTERESTING_INTS);
+                self.pos.ins().icmp_imm(cc, x, imm)
+            }
+        }
+    }
+
+    /// Generate a load or store of a stack slot.
+    fn generate_stack_access(&mut self) {
+        let (slot, size) = *self.input.choose(&self.slots);
+        let ty = *self.input.choose(&TYPES);
+        let bytes = ty.bytes();
+        let offset = (bytes * self.input.below((size / bytes) as usize) as u32) as i32;
+        if self.input.chance(128) {
+            let x = self.value(ty);
+            self.pos.ins().stack_store(x, slot, offset);
+        } else {
+            let value = self.pos.ins().stack_load(ty, slot, offset);
+            self.define(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cretonne_codegen::settings;
+    use cretonne_codegen::verifier::verify_function;
+    use cretonne_interpreter::Interpreter;
+
+    /// Generate test cases from a deterministic sequence of inputs of increasing length.
+    fn test_cases(count: usize) -> Vec<TestCase> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        (0..count)
+            .map(|i| {
+                let data = (0..i * 8)
+                    .map(|_| {
+                        state ^= state << 13;
+                        state ^= state >> 7;
+                        state ^= state << 17;
+                        (state >> 24) as u8
+                    })
+                    .collect::<Vec<_>>();
+                TestCase::generate(&data, &Config::default())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn empty_input() {
+        let testcase = TestCase::generate(&[], &Config::default());
+        assert_eq!(
+            testcase.func.to_string(),
+            "function %fuzz() -> i32 system_v {\n\
+             ebb0:\n    \
+             v0 = iconst.i32 0\n    \
+             return v0\n\
+             }\n"
+        );
+        assert_eq!(testcase.inputs, vec![Vec::new()]);
+    }
+
+    #[test]
+    fn verify() {
+        let flags = settings::Flags::new(settings::builder());
+        for testcase in test_cases(200) {
+            if let Err(errors) = verify_function(&testcase.func, &flags) {
+                panic!("{}\n{}", testcase.func, errors);
+            }
+        }
+    }
+
+    #[test]
+    fn interpret() {
+        for testcase in test_cases(200) {
+            for args in &testcase.inputs {
+                if let Err(e) = Interpreter::new().call(&testcase.func, args) {
+                    panic!("{:?}: {}\n{}", args, e, testcase.func);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Reading decisions from the fuzzer's input.
+
+/// A source of random decisions backed by the bytes supplied by a fuzzer.
+///
+/// When the bytes run out, every decision takes its smallest value, so any input produces a
+/// finite result.
+pub struct Input<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Input<'a> {
+    /// Create an input reading from `data`.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Have all of the bytes been used?
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Get the next byte, or 0 if there are none left.
+    pub fn byte(&mut self) -> u8 {
+        match self.data.split_first() {
+            Some((&b, rest)) => {
+                self.data = rest;
+                b
+            }
+            None => 0,
+        }
+    }
+
+    /// Get a 64-bit number made from the next 8 bytes.
+    pub fn u64(&mut self) -> u64 {
+        (0..8).fold(0, |acc, _| acc << 8 | u64::from(self.byte()))
+    }
+
+    /// Get a number in the range `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        debug_assert!(n > 0 && n <= 256);
+        usize::from(self.byte()) % n
+    }
+
+    /// Get a number in the range `lo..=hi`.
+    pub fn range(&mut self, lo: usize, hi: usize) -> usize {
+        lo + self.below(hi - lo + 1)
+    }
+
+    /// Return `true` with probability `n / 256`.
+    pub fn chance(&mut self, n: u8) -> bool {
+        self.byte() > 255 - n
+    }
+
+    /// Choose one of `items`.
+    pub fn choose<'t, T>(&mut self, items: &'t [T]) -> &'t T {
+        &items[self.below(items.len())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exhausted() {
+        let mut input = Input::new(&[7, 1, 2]);
+        assert_eq!(input.range(3, 5), 4);
+        assert_eq!(input.u64(), 0x0102_0000_0000_0000);
+        assert!(input.is_empty());
+        assert_eq!(input.byte(), 0);
+        assert_eq!(*input.choose(&['a', 'b']), 'a');
+        assert!(!input.chance(255));
+    }
+}
@@ -0,0 +1,32 @@
+//! Random generation of Cretonne IR functions.
+//!
+//! This crate turns a string of bytes, typically supplied by a fuzzer, into a function which
+//! passes the verifier, along with arguments to call it with. The generated functions have
+//! arbitrary control flow between EBBs with parameters, bounded loops, integer and floating point
+//! arithmetic, and memory accesses through stack slots. They never trap and always terminate, so
+//! they can be executed to compare the results of different compilation pipelines.
+//!
+//! Every byte string produces a valid function: when the input runs out, all remaining decisions
+//! take their smallest value.
+
+#![deny(missing_docs, trivial_numeric_casts, unused_extern_crates)]
+#![warn(unused_import_braces, unstable_features)]
+#![cfg_attr(feature = "clippy", plugin(clippy(conf_file = "../../clippy.toml")))]
+#![cfg_attr(feature="cargo-clippy", warn(
+                mut_mut,
+                nonminimal_bool,
+                option_map_unwrap_or,
+                option_map_unwrap_or_else,
+                print_stdout,
+                unicode_not_nfc,
+                use_self,
+                ))]
+
+extern crate cretonne_codegen;
+extern crate cretonne_interpreter;
+
+mod generator;
+mod input;
+
+pub use generator::{Config, TestCase};
+pub use input::Input;
@@ -0,0 +1,1336 @@
+//! Interpreting Cretonne IR functions.
+
+use cretonne_codegen::entity::EntityMap;
+use cretonne_codegen::ir::condcodes::{FloatCC, IntCC};
+use cretonne_codegen::ir::immediates::Offset32;
+use cretonne_codegen::ir::{self, types, AbiParam, ArgumentPurpose, Ebb, ExternalName, Function,
+                           GlobalVar, Inst, InstructionData, Opcode, Signature, StackSlot,
+                           TrapCode, Type, Value};
+use memory::{Memory, VecMemory};
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::string::{String, ToString};
+use std::vec::Vec;
+use value::DataValue;
+
+/// The address of the interpreter's stack.
+///
+/// The stack slots of the active functions are allocated in the address range starting here, and
+/// memory accesses in that range don't go to the `Memory`.
+pub const STACK_BASE: u64 = 0x4000_0000;
+
+/// The address given to the first function whose address is taken by `func_addr`.
+///
+/// Functions get increasing addresses in the order their addresses are taken. The addresses
+/// can only be used with `call_indirect`.
+pub const FUNCTION_BASE: u64 = 0x3000_0000;
+
+/// The distance between the addresses of consecutive functions.
+const FUNCTION_ALIGN: u64 = 16;
+
+/// The alignment of stack slots.
+const STACK_ALIGN: usize = 16;
+
+/// The default size of the interpreter's stack in bytes.
+const DEFAULT_STACK_SIZE: usize = 1 << 20;
+
+/// The maximum number of active function calls.
+const MAX_CALL_DEPTH: usize = 1000;
+
+/// An error encountered while interpreting a function.
+#[derive(Fail, Debug, Clone, PartialEq, Eq)]
+pub enum InterpreterError {
+    /// The interpreted code trapped.
+    #[fail(display = "trap: {}", _0)]
+    Trap(TrapCode),
+    /// The interpreter doesn't support an instruction.
+    #[fail(display = "unsupported instruction: {}", _0)]
+    Unsupported(String),
+    /// A called function isn't known to the interpreter.
+    #[fail(display = "unknown function: {}", _0)]
+    UnknownFunction(ExternalName),
+    /// A called function has no body.
+    #[fail(display = "{} has no body", _0)]
+    EmptyFunction(ExternalName),
+    /// A symbol used by a global variable wasn't defined.
+    #[fail(display = "unknown symbol: {}", _0)]
+    UnknownSymbol(ExternalName),
+    /// The values passed to or returned from a function don't match its signature.
+    #[fail(display = "values don't match the signature of {}", _0)]
+    SignatureMismatch(ExternalName),
+    /// An indirect call used an address which isn't the address of a function.
+    #[fail(display = "invalid function address: {:#x}", _0)]
+    InvalidFunctionAddress(u64),
+}
+
+impl From<TrapCode> for InterpreterError {
+    fn from(code: TrapCode) -> Self {
+        InterpreterError::Trap(code)
+    }
+}
+
+/// A result from the interpreter.
+pub type InterpreterResult<T> = Result<T, InterpreterError>;
+
+/// A function implemented by the host instead of in Cretonne IR.
+pub type HostFunction<'a> = Fn(&mut Memory, &[DataValue]) -> Result<Vec<DataValue>, TrapCode> + 'a;
+
+/// An interpreter for Cretonne IR.
+///
+/// The interpreter executes functions before legalization. It can call the functions that have
+/// been added to it with `add_function`, which play the role of the functions in a module, and
+/// functions implemented by the host that have been defined with `define_host_function`.
+///
+/// Values in memory are stored in little-endian byte order. The stack slots of a function are
+/// allocated in the interpreter's own stack, and all other memory accesses are forwarded to a
+/// `Memory` provided by the user.
+pub struct Interpreter<'a> {
+    functions: HashMap<ExternalName, &'a Function>,
+    host_functions: HashMap<ExternalName, Box<HostFunction<'a>>>,
+    symbols: HashMap<ExternalName, u64>,
+    function_addrs: Vec<ExternalName>,
+    memory: Box<Memory + 'a>,
+    stack: Vec<u8>,
+    stack_size: usize,
+}
+
+/// The state of an active function call.
+struct Frame<'a> {
+    func: &'a Function,
+    values: HashMap<Value, DataValue>,
+    stack_slots: EntityMap<StackSlot, u64>,
+    /// The size of the interpreter's stack before this frame's stack slots were allocated.
+    stack_top: usize,
+    /// The next instruction to execute, or the call instruction waiting for its results.
+    next: Option<Inst>,
+}
+
+impl<'a> Frame<'a> {
+    fn get(&self, value: Value) -> DataValue {
+        let value = self.func.dfg.resolve_aliases(value);
+        match self.values.get(&value) {
+            Some(x) => x.clone(),
+            None => panic!("{} used before it is defined", value),
+        }
+    }
+
+    fn set_all(&mut self, values: &[Value], xs: Vec<DataValue>) {
+        debug_assert_eq!(values.len(), xs.len());
+        for (&value, x) in values.iter().zip(xs) {
+            self.values.insert(value, x);
+        }
+    }
+
+    /// Continue execution at `ebb` with the parameters set to `args`.
+    fn jump(&mut self, ebb: Ebb, args: Vec<DataValue>) {
+        let func = self.func;
+        self.set_all(func.dfg.ebb_params(ebb), args);
+        self.next = func.layout.first_inst(ebb);
+    }
+
+    /// Continue execution after the instruction `inst`, which has produced `results`.
+    fn advance(&mut self, inst: Inst, results: Vec<DataValue>) {
+        let func = self.func;
+        self.set_all(func.dfg.inst_results(inst), results);
+        self.next = func.layout.next_inst(inst);
+    }
+}
+
+/// What happens after an instruction has executed.
+enum Control<'a> {
+    /// Continue with the next instruction, after setting the results of the current one.
+    Continue(Vec<DataValue>),
+    /// Jump to an EBB with arguments.
+    Jump(Ebb, Vec<DataValue>),
+    /// Return from the function.
+    Return(Vec<DataValue>),
+    /// Call a function, continuing after the current instruction when it returns.
+    Call(&'a Function, Vec<DataValue>),
+    /// Call a function which returns directly to the caller of the current function.
+    TailCall(&'a Function, Vec<DataValue>),
+}
+
+impl<'a> Interpreter<'a> {
+    /// Create an interpreter whose memory consists of only its stack.
+    pub fn new() -> Self {
+        Self::with_memory(Box::new(VecMemory::new(0, 0)))
+    }
+
+    /// Create an interpreter which forwards accesses outside its stack to `memory`.
+    pub fn with_memory(memory: Box<Memory + 'a>) -> Self {
+        Self {
+            functions: HashMap::new(),
+            host_functions: HashMap::new(),
+            symbols: HashMap::new(),
+            function_addrs: Vec::new(),
+            memory,
+            stack: Vec::new(),
+            stack_size: DEFAULT_STACK_SIZE,
+        }
+    }
+
+    /// Make `func` callable by its name.
+    pub fn add_function(&mut self, func: &'a Function) {
+        self.functions.insert(func.name.clone(), func);
+    }
+
+    /// Define a function named `name` which is implemented by the host.
+    ///
+    /// The function is called with the interpreter's memory and the arguments of the call.
+    pub fn define_host_function<F>(&mut self, name: ExternalName, f: F)
+    where
+        F: Fn(&mut Memory, &[DataValue]) -> Result<Vec<DataValue>, TrapCode> + 'a,
+    {
+        self.host_functions.insert(name, Box::new(f));
+    }
+
+    /// Define the address of the symbol `name`, used by `globalsym` global variables.
+    pub fn define_symbol(&mut self, name: ExternalName, addr: u64) {
+        self.symbols.insert(name, addr);
+    }
+
+    /// Set the size of the stack in bytes.
+    ///
+    /// Calls that need more stack space trap with `TrapCode::StackOverflow`.
+    pub fn set_stack_size(&mut self, size: usize) {
+        self.stack_size = size;
+    }
+
+    /// Get the memory outside the interpreter's stack.
+    pub fn memory(&self) -> &Memory {
+        &*self.memory
+    }
+
+    /// Get the memory outside the interpreter's stack for modification.
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut *self.memory
+    }
+
+    /// Call the function or host function named `name`.
+    pub fn call_by_name(
+        &mut self,
+        name: &ExternalName,
+        args: &[DataValue],
+    ) -> InterpreterResult<Vec<DataValue>> {
+        match self.functions.get(name).cloned() {
+            Some(func) => self.call(func, args),
+            None => self.call_host(name, None, args),
+        }
+    }
+
+    /// Call `func` with `args`, returning the values it returns.
+    pub fn call(
+        &mut self,
+        func: &'a Function,
+        args: &[DataValue],
+    ) -> InterpreterResult<Vec<DataValue>> {
+        let stack_top = self.stack.len();
+        let result = self.run(func, args.to_vec());
+        self.stack.truncate(stack_top);
+        result
+    }
+
+    /// Call a host function, checking its results against `sig` if given.
+    fn call_host(
+        &mut self,
+        name: &ExternalName,
+        sig: Option<&Signature>,
+        args: &[DataValue],
+    ) -> InterpreterResult<Vec<DataValue>> {
+        let host = match self.host_functions.get(name) {
+            Some(host) => host,
+            None => return Err(InterpreterError::UnknownFunction(name.clone())),
+        };
+        let results = host(&mut *self.memory, args)?;
+        if let Some(sig) = sig {
+            if !matches_params(&results, &sig.returns) {
+                return Err(InterpreterError::SignatureMismatch(name.clone()));
+            }
+        }
+        Ok(results)
+    }
+
+    /// Create a frame for calling `func` with `args`, allocating its stack slots.
+    fn enter(&mut self, func: &'a Function, args: Vec<DataValue>) -> InterpreterResult<Frame<'a>> {
+        if !matches_params(&args, &func.signature.params) {
+            return Err(InterpreterError::SignatureMismatch(func.name.clone()));
+        }
+        let entry = func.layout.entry_block().ok_or_else(|| {
+            InterpreterError::EmptyFunction(func.name.clone())
+        })?;
+
+        let mut frame = Frame {
+            func,
+            values: HashMap::new(),
+            stack_slots: EntityMap::new(),
+            stack_top: self.stack.len(),
+            next: None,
+        };
+        for (ss, data) in func.stack_slots.iter() {
+            let offset = (self.stack.len() + STACK_ALIGN - 1) / STACK_ALIGN * STACK_ALIGN;
+            let end = offset + data.size as usize;
+            if end > self.stack_size {
+                return Err(TrapCode::StackOverflow.into());
+            }
+            self.stack.resize(end, 0);
+            frame.stack_slots[ss] = STACK_BASE + offset as u64;
+        }
+        frame.jump(entry, args);
+        Ok(frame)
+    }
+
+    /// Run `func` until it returns.
+    ///
+    /// The active calls are kept in a stack of frames rather than on the native stack, so deep
+    /// recursion in the interpreted code can't overflow the native stack.
+    fn run(
+        &mut self,
+        func: &'a Function,
+        args: Vec<DataValue>,
+    ) -> InterpreterResult<Vec<DataValue>> {
+        let mut frames = vec![self.enter(func, args)?];
+        loop {
+            let control = {
+                let frame = frames.last_mut().unwrap();
+                let inst = match frame.next {
+                    Some(inst) => inst,
+                    None => {
+                        return Err(InterpreterError::Unsupported(
+                            format!("{} falls off the end of an EBB", frame.func.name),
+                        ))
+                    }
+                };
+                match self.step(frame, inst)? {
+                    Control::Continue(results) => {
+                        frame.advance(inst, results);
+                        continue;
+                    }
+                    Control::Jump(dest, args) => {
+                        frame.jump(dest, args);
+                        continue;
+                    }
+                    control => control,
+                }
+            };
+
+            match control {
+                Control::Return(results) => {
+                    let frame = frames.pop().unwrap();
+                    self.stack.truncate(frame.stack_top);
+                    match frames.last_mut() {
+                        Some(caller) => {
+                            let call = caller.next.unwrap();
+                            caller.advance(call, results);
+                        }
+                        None => return Ok(results),
+                    }
+                }
+                Control::Call(callee, args) => {
+                    if frames.len() == MAX_CALL_DEPTH {
+                        return Err(TrapCode::StackOverflow.into());
+                    }
+                    let frame = self.enter(callee, args)?;
+                    frames.push(frame);
+                }
+                Control::TailCall(callee, args) => {
+                    let frame = frames.pop().unwrap();
+                    self.stack.truncate(frame.stack_top);
+                    let frame = self.enter(callee, args)?;
+                    frames.push(frame);
+                }
+                Control::Continue(_) | Control::Jump(..) => unreachable!(),
+            }
+        }
+    }
+
+    /// Execute a single instruction.
+    fn step(&mut self, frame: &Frame<'a>, inst: Inst) -> InterpreterResult<Control<'a>> {
+        let func = frame.func;
+        let dfg = &func.dfg;
+        let opcode = dfg[inst].opcode();
+        let args: Vec<DataValue> = dfg.inst_args(inst).iter().map(|&v| frame.get(v)).collect();
+
+        let taken = match dfg[inst] {
+            InstructionData::Jump { destination, .. } => Some((destination, &args[..])),
+            InstructionData::Branch { destination, .. } => {
+                if args[0].to_bool() == (opcode == Opcode::Brnz) {
+                    Some((destination, &args[1..]))
+                } else {
+                    None
+                }
+            }
+            InstructionData::BranchIcmp { cond, destination, .. } => {
+                if icmp(cond, &args[0], &args[1]) {
+                    Some((destination, &args[2..]))
+                } else {
+                    None
+                }
+            }
+            InstructionData::BranchInt { cond, destination, .. } => {
+                if int_flags(cond, &args[0]) {
+                    Some((destination, &args[1..]))
+                } else {
+                    None
+                }
+            }
+            InstructionData::BranchFloat { cond, destination, .. } => {
+                if float_flags(cond, &args[0]) {
+                    Some((destination, &args[1..]))
+                } else {
+                    None
+                }
+            }
+            InstructionData::BranchTable { table, .. } => {
+                let index = args[0].to_bits();
+                if index > usize::max_value() as u64 {
+                    None
+                } else {
+                    func.jump_tables[table].get_entry(index as usize).map(
+                        |dest| (dest, &[][..]),
+                    )
+                }
+            }
+            InstructionData::Trap { code, .. } => return Err(code.into()),
+            InstructionData::CondTrap { code, .. } => {
+                if args[0].to_bool() == (opcode == Opcode::Trapnz) {
+                    return Err(code.into());
+                }
+                None
+            }
+            InstructionData::IntCondTrap { cond, code, .. } => {
+                if int_flags(cond, &args[0]) {
+                    return Err(code.into());
+                }
+                None
+            }
+            InstructionData::FloatCondTrap { cond, code, .. } => {
+                if float_flags(cond, &args[0]) {
+                    return Err(code.into());
+                }
+                None
+            }
+            InstructionData::MultiAry { .. } if opcode == Opcode::Return => {
+                return Ok(Control::Return(args));
+            }
+            InstructionData::Call { func_ref, .. } => {
+                let data = &dfg.ext_funcs[func_ref];
+                let sig = &dfg.signatures[data.signature];
+                return self.call_inst(opcode, &data.name, sig, args);
+            }
+            InstructionData::CallIndirect { sig_ref, .. } => {
+                let name = self.function_at(args[0].to_bits())?;
+                let sig = &dfg.signatures[sig_ref];
+                let matches = match self.functions.get(&name) {
+                    Some(callee) => {
+                        same_types(&callee.signature.params, &sig.params) &&
+                            same_types(&callee.signature.returns, &sig.returns)
+                    }
+                    None => matches_params(&args[1..], &sig.params),
+                };
+                if !matches {
+                    return Err(TrapCode::BadSignature.into());
+                }
+                return self.call_inst(opcode, &name, sig, args[1..].to_vec());
+            }
+            _ => return Ok(Control::Continue(self.eval(frame, inst, &args)?)),
+        };
+
+        Ok(match taken {
+            Some((dest, dest_args)) => Control::Jump(dest, dest_args.to_vec()),
+            None => Control::Continue(Vec::new()),
+        })
+    }
+
+    /// Execute a call instruction calling the function named `name` with the signature `sig`.
+    fn call_inst(
+        &mut self,
+        opcode: Opcode,
+        name: &ExternalName,
+        sig: &Signature,
+        args: Vec<DataValue>,
+    ) -> InterpreterResult<Control<'a>> {
+        // Calls that are terminators are tail calls.
+        let tail = opcode.is_terminator();
+        match self.functions.get(name).cloned() {
+            Some(callee) => {
+                if !same_types(&callee.signature.returns, &sig.returns) {
+                    return Err(InterpreterError::SignatureMismatch(name.clone()));
+                }
+                Ok(if tail {
+                    Control::TailCall(callee, args)
+                } else {
+                    Control::Call(callee, args)
+                })
+            }
+            None => {
+                let results = self.call_host(name, Some(sig), &args)?;
+                Ok(if tail {
+                    Control::Return(results)
+                } else {
+                    Control::Continue(results)
+                })
+            }
+        }
+    }
+
+    /// Compute the results of an instruction which doesn't change the control flow.
+    fn eval(
+        &mut self,
+        frame: &Frame<'a>,
+        inst: Inst,
+        args: &[DataValue],
+    ) -> InterpreterResult<Vec<DataValue>> {
+        let func = frame.func;
+        let dfg = &func.dfg;
+        let opcode = dfg[inst].opcode();
+        let ctrl_ty = dfg.ctrl_typevar(inst);
+        let unsupported = || {
+            Err(InterpreterError::Unsupported(
+                dfg.display_inst(inst, None).to_string(),
+            ))
+        };
+
+        let result = match dfg[inst] {
+            InstructionData::FuncAddr { func_ref, .. } => {
+                let addr = self.function_addr(&dfg.ext_funcs[func_ref].name);
+                DataValue::int(ctrl_ty, addr as i64)
+            }
+            InstructionData::Unary { .. } => {
+                match opcode {
+                    Opcode::Isplit => {
+                        let half = ctrl_ty.half_width().unwrap();
+                        let x = args[0].to_bits();
+                        return Ok(vec![
+                            DataValue::from_bits(half, x),
+                            DataValue::from_bits(half, x >> half.bits()),
+                        ]);
+                    }
+                    Opcode::Vsplit => {
+                        let lanes = args[0].lanes();
+                        let (lo, hi) = lanes.split_at(lanes.len() / 2);
+                        return Ok(vec![DataValue::V(lo.to_vec()), DataValue::V(hi.to_vec())]);
+                    }
+                    Opcode::Splat => {
+                        DataValue::V(vec![args[0].clone(); usize::from(ctrl_ty.lane_count())])
+                    }
+                    Opcode::Bitcast => DataValue::from_bits(ctrl_ty, args[0].to_bits()),
+                    _ => {
+                        let lane_ty = ctrl_ty.lane_type();
+                        args[0].map(|x| unary(opcode, lane_ty, x))?
+                    }
+                }
+            }
+            InstructionData::UnaryImm { imm, .. } => DataValue::int(ctrl_ty, imm.into()),
+            InstructionData::UnaryIeee32 { imm, .. } => DataValue::F32(imm),
+            InstructionData::UnaryIeee64 { imm, .. } => DataValue::F64(imm),
+            InstructionData::UnaryBool { imm, .. } => DataValue::B(imm),
+            InstructionData::UnaryGlobalVar { global_var, .. } => {
+                DataValue::int(ctrl_ty, self.global_addr(frame, global_var, ctrl_ty)? as i64)
+            }
+            InstructionData::UnaryConst { constant_handle, .. } if opcode == Opcode::Vconst => {
+                DataValue::from_bytes(ctrl_ty, dfg.constants.get(constant_handle).as_slice())
+            }
+            InstructionData::Binary { .. } => {
+                let (x, y) = (&args[0], &args[1]);
+                match opcode {
+                    Opcode::IaddCout | Opcode::IsubBout => return Ok(carry(opcode, x, y, 0)),
+                    Opcode::Ifcmp => DataValue::IFlags(Box::new(x.clone()), Box::new(y.clone())),
+                    Opcode::Ffcmp => DataValue::FFlags(Box::new(x.clone()), Box::new(y.clone())),
+                    Opcode::Iconcat => {
+                        let bits = x.lane_bits();
+                        DataValue::from_bits(
+                            ctrl_ty.double_width().unwrap(),
+                            x.to_bits() | y.to_bits() << bits,
+                        )
+                    }
+                    Opcode::Vconcat => {
+                        DataValue::V(x.lanes().iter().chain(y.lanes()).cloned().collect())
+                    }
+                    Opcode::Ishl | Opcode::Ushr | Opcode::Sshr | Opcode::Rotl | Opcode::Rotr => {
+                        let amount = y.to_bits();
+                        x.map::<(), _>(|x| Ok(shift(opcode, x, amount))).unwrap()
+                    }
+                    _ => x.zip(y, |x, y| binary(opcode, x, y))?,
+                }
+            }
+            InstructionData::BinaryImm { imm, .. } => {
+                let x = &args[0];
+                let imm = DataValue::int(ctrl_ty, imm.into());
+                match opcode {
+                    Opcode::IfcmpImm => DataValue::IFlags(Box::new(x.clone()), Box::new(imm)),
+                    Opcode::IrsubImm => binary(Opcode::Isub, &imm, x)?,
+                    Opcode::IshlImm | Opcode::UshrImm | Opcode::SshrImm | Opcode::RotlImm |
+                    Opcode::RotrImm => shift(imm_opcode(opcode), x, imm.to_bits()),
+                    _ => binary(imm_opcode(opcode), x, &imm)?,
+                }
+            }
+            InstructionData::Ternary { .. } => {
+                let (x, y, z) = (&args[0], &args[1], &args[2]);
+                match opcode {
+                    Opcode::Select => if x.to_bool() { y.clone() } else { z.clone() },
+                    Opcode::Vselect => {
+                        DataValue::V(x.lanes()
+                            .iter()
+                            .zip(y.lanes().iter().zip(z.lanes()))
+                            .map(|(c, (y, z))| if c.to_bool() { y.clone() } else { z.clone() })
+                            .collect())
+                    }
+                    Opcode::Fma => {
+                        let lanes: Vec<_> = x.lanes()
+                            .iter()
+                            .zip(y.lanes().iter().zip(z.lanes()))
+                            .map(|(x, (y, z))| fma(x, y, z))
+                            .collect();
+                        match *x {
+                            DataValue::V(_) => DataValue::V(lanes),
+                            _ => lanes[0].clone(),
+                        }
+                    }
+                    Opcode::IaddCin | Opcode::IsubBin => {
+                        let mut results = carry(opcode, x, y, i128::from(z.to_bool()));
+                        results.truncate(1);
+                        return Ok(results);
+                    }
+                    Opcode::IaddCarry | Opcode::IsubBorrow => {
+                        return Ok(carry(opcode, x, y, i128::from(z.to_bool())));
+                    }
+                    _ => return unsupported(),
+                }
+            }
+            InstructionData::InsertLane { lane, .. } => {
+                let mut lanes = args[0].lanes().to_vec();
+                lanes[usize::from(lane)] = args[1].clone();
+                DataValue::V(lanes)
+            }
+            InstructionData::ExtractLane { lane, .. } => args[0].lanes()[usize::from(lane)].clone(),
+            InstructionData::IntCompare { cond, .. } => {
+                args[0]
+                    .zip::<(), _>(&args[1], |x, y| Ok(DataValue::B(icmp(cond, x, y))))
+                    .unwrap()
+            }
+            InstructionData::IntCompareImm { cond, imm, .. } => {
+                let imm = DataValue::int(ctrl_ty, imm.into());
+                DataValue::B(icmp(cond, &args[0], &imm))
+            }
+            InstructionData::IntCond { cond, .. } => DataValue::B(int_flags(cond, &args[0])),
+            InstructionData::FloatCompare { cond, .. } => {
+                args[0]
+                    .zip::<(), _>(&args[1], |x, y| Ok(DataValue::B(fcmp(cond, x, y))))
+                    .unwrap()
+            }
+            InstructionData::FloatCond { cond, .. } => DataValue::B(float_flags(cond, &args[0])),
+            InstructionData::IntSelect { cond, .. } => {
+                if int_flags(cond, &args[0]) {
+                    args[1].clone()
+                } else {
+                    args[2].clone()
+                }
+            }
+            InstructionData::Load { offset, .. } |
+            InstructionData::LoadComplex { offset, .. } => {
+                let addr = address(args, offset);
+                self.load(opcode, ctrl_ty, addr)?
+            }
+            InstructionData::Store { offset, .. } |
+            InstructionData::StoreComplex { offset, .. } => {
+                let addr = address(&args[1..], offset);
+                self.store(opcode, &args[0], addr)?;
+                return Ok(Vec::new());
+            }
+            InstructionData::StackLoad { stack_slot, offset, .. } => {
+                let addr = slot_address(frame, stack_slot, offset);
+                match opcode {
+                    Opcode::StackAddr => DataValue::int(ctrl_ty, addr as i64),
+                    _ => self.load(Opcode::Load, ctrl_ty, addr)?,
+                }
+            }
+            InstructionData::StackStore { stack_slot, offset, .. } => {
+                let addr = slot_address(frame, stack_slot, offset);
+                self.store(Opcode::Store, &args[0], addr)?;
+                return Ok(Vec::new());
+            }
+            InstructionData::HeapAddr { heap, imm, .. } => {
+                let addr = self.heap_addr(frame, heap, &args[0], imm.into(), ctrl_ty)?;
+                DataValue::int(ctrl_ty, addr as i64)
+            }
+            _ => return unsupported(),
+        };
+        Ok(vec![result])
+    }
+
+    /// Get the address of the global variable `gv`, as an integer of type `addr_ty`.
+    fn global_addr(
+        &mut self,
+        frame: &Frame<'a>,
+        gv: GlobalVar,
+        addr_ty: Type,
+    ) -> InterpreterResult<u64> {
+        let func = frame.func;
+        let addr = match func.global_vars[gv] {
+            ir::GlobalVarData::VMContext { offset } => {
+                let vmctx = match func.special_param(ArgumentPurpose::VMContext) {
+                    Some(vmctx) => vmctx,
+                    None => {
+                        return Err(InterpreterError::Unsupported(
+                            format!("{} without a vmctx parameter", gv),
+                        ))
+                    }
+                };
+                offset_address(frame.get(vmctx).to_bits(), offset)
+            }
+            ir::GlobalVarData::Deref { base, offset } => {
+                let base_addr = self.global_addr(frame, base, addr_ty)?;
+                let base = self.load(Opcode::Load, addr_ty, base_addr)?;
+                offset_address(base.to_bits(), offset)
+            }
+            ir::GlobalVarData::Sym { ref name, .. } => {
+                match self.symbols.get(name).cloned() {
+                    Some(addr) => addr,
+                    None if self.functions.contains_key(name) ||
+                                self.host_functions.contains_key(name) => self.function_addr(name),
+                    None => return Err(InterpreterError::UnknownSymbol(name.clone())),
+                }
+            }
+        };
+        Ok(DataValue::from_bits(addr_ty, addr).to_bits())
+    }
+
+    /// Compute the address accessed by a `heap_addr` instruction, checking the heap bounds.
+    fn heap_addr(
+        &mut self,
+        frame: &Frame<'a>,
+        heap: ir::Heap,
+        offset: &DataValue,
+        size: u32,
+        addr_ty: Type,
+    ) -> InterpreterResult<u64> {
+        let data = &frame.func.heaps[heap];
+        let base = match data.base {
+            ir::HeapBase::GlobalVar(gv) => {
+                let base_addr = self.global_addr(frame, gv, addr_ty)?;
+                self.load(Opcode::Load, addr_ty, base_addr)?.to_bits()
+            }
+            ir::HeapBase::ReservedReg => {
+                return Err(InterpreterError::Unsupported(
+                    format!("{} in a reserved register", heap),
+                ))
+            }
+        };
+        let bound = match data.style {
+            ir::HeapStyle::Static { bound } => {
+                let bound: i64 = bound.into();
+                bound as u64
+            }
+            ir::HeapStyle::Dynamic { bound_gv } => {
+                let bound_addr = self.global_addr(frame, bound_gv, addr_ty)?;
+                let bound_ty = Type::int(offset.lane_bits() as u16).unwrap();
+                self.load(Opcode::Load, bound_ty, bound_addr)?.to_bits()
+            }
+        };
+        let offset = offset.to_bits();
+        match offset.checked_add(u64::from(size)) {
+            Some(end) if end <= bound => Ok(base.wrapping_add(offset)),
+            _ => Err(TrapCode::HeapOutOfBounds.into()),
+        }
+    }
+
+    /// Get the address of the function named `name`.
+    fn function_addr(&mut self, name: &ExternalName) -> u64 {
+        let index = match self.function_addrs.iter().position(|n| n == name) {
+            Some(index) => index,
+            None => {
+                self.function_addrs.push(name.clone());
+                self.function_addrs.len() - 1
+            }
+        };
+        FUNCTION_BASE + FUNCTION_ALIGN * index as u64
+    }
+
+    /// Get the name of the function at `addr`.
+    fn function_at(&self, addr: u64) -> InterpreterResult<ExternalName> {
+        if addr == 0 {
+            return Err(TrapCode::IndirectCallToNull.into());
+        }
+        addr.checked_sub(FUNCTION_BASE)
+            .and_then(|offset| if offset % FUNCTION_ALIGN == 0 {
+                self.function_addrs.get((offset / FUNCTION_ALIGN) as usize)
+            } else {
+                None
+            })
+            .cloned()
+            .ok_or(InterpreterError::InvalidFunctionAddress(addr))
+    }
+
+    /// Perform a load instruction with the controlling type `ty`.
+    fn load(&self, opcode: Opcode, ty: Type, addr: u64) -> InterpreterResult<DataValue> {
+        let (mem_ty, signed) = match opcode {
+            Opcode::Uload8 | Opcode::Uload8Complex => (types::I8, false),
+            Opcode::Sload8 | Opcode::Sload8Complex => (types::I8, true),
+            Opcode::Uload16 | Opcode::Uload16Complex => (types::I16, false),
+            Opcode::Sload16 | Opcode::Sload16Complex => (types::I16, true),
+            Opcode::Uload32 | Opcode::Uload32Complex => (types::I32, false),
+            Opcode::Sload32 | Opcode::Sload32Complex => (types::I32, true),
+            _ => (ty, false),
+        };
+        let mut bytes = vec![0; mem_ty.bytes() as usize];
+        self.read(addr, &mut bytes)?;
+        let x = DataValue::from_bytes(mem_ty, &bytes);
+        Ok(if mem_ty == ty {
+            x
+        } else if signed {
+            DataValue::int(ty, x.to_i64())
+        } else {
+            DataValue::int(ty, x.to_bits() as i64)
+        })
+    }
+
+    /// Perform a store instruction storing `x`.
+    fn store(&mut self, opcode: Opcode, x: &DataValue, addr: u64) -> InterpreterResult<()> {
+        let x = match opcode {
+            Opcode::Istore8 | Opcode::Istore8Complex => DataValue::int(types::I8, x.to_i64()),
+            Opcode::Istore16 | Opcode::Istore16Complex => DataValue::int(types::I16, x.to_i64()),
+            Opcode::Istore32 | Opcode::Istore32Complex => DataValue::int(types::I32, x.to_i64()),
+            _ => x.clone(),
+        };
+        let mut bytes = Vec::new();
+        x.write_bytes(&mut bytes);
+        self.write(addr, &bytes)
+    }
+
+    /// Get the range of the stack containing the `size` bytes at `addr`, if `addr` is in the stack.
+    fn stack_range(&self, addr: u64, size: usize) -> Option<Result<(usize, usize), TrapCode>> {
+        if addr < STACK_BASE || addr - STACK_BASE >= self.stack_size as u64 {
+            return None;
+        }
+        let start = (addr - STACK_BASE) as usize;
+        Some(match start.checked_add(size) {
+            Some(end) if end <= self.stack.len() => Ok((start, end)),
+            _ => Err(TrapCode::HeapOutOfBounds),
+        })
+    }
+
+    /// Read `bytes.len()` bytes of memory at `addr`.
+    fn read(&self, addr: u64, bytes: &mut [u8]) -> InterpreterResult<()> {
+        match self.stack_range(addr, bytes.len()) {
+            Some(range) => {
+                let (start, end) = range?;
+                bytes.copy_from_slice(&self.stack[start..end]);
+                Ok(())
+            }
+            None => Ok(self.memory.load(addr, bytes)?),
+        }
+    }
+
+    /// Write `bytes` to memory at `addr`.
+    fn write(&mut self, addr: u64, bytes: &[u8]) -> InterpreterResult<()> {
+        match self.stack_range(addr, bytes.len()) {
+            Some(range) => {
+                let (start, end) = range?;
+                self.stack[start..end].copy_from_slice(bytes);
+                Ok(())
+            }
+            None => Ok(self.memory.store(addr, bytes)?),
+        }
+    }
+}
+
+/// Do `values` have the types of `params`?
+pub fn matches_params(values: &[DataValue], params: &[AbiParam]) -> bool {
+    values.len() == params.len() &&
+        values.iter().zip(params).all(
+            |(x, p)| x.is_of_type(p.value_type),
+        )
+}
+
+/// Do `a` and `b` have the same value types?
+fn same_types(a: &[AbiParam], b: &[AbiParam]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.value_type == b.value_type)
+}
+
+/// Add `offset` to `addr`.
+fn offset_address(addr: u64, offset: Offset32) -> u64 {
+    let offset: i32 = offset.into();
+    addr.wrapping_add(i64::from(offset) as u64)
+}
+
+/// Compute the address accessed by a load or store from its address arguments and offset.
+fn address(args: &[DataValue], offset: Offset32) -> u64 {
+    let sum = args.iter().fold(0u64, |sum, x| sum.wrapping_add(x.to_bits()));
+    args[0].with_bits(offset_address(sum, offset)).to_bits()
+}
+
+/// Get the address of `offset` bytes into `slot`.
+fn slot_address(frame: &Frame, slot: StackSlot, offset: Offset32) -> u64 {
+    offset_address(frame.stack_slots[slot], offset)
+}
+
+/// Get the bit mask covering the low `bits` bits.
+fn mask(bits: u32) -> u64 {
+    if bits == 64 { !0 } else { (1 << bits) - 1 }
+}
+
+/// Map an opcode taking an immediate to the opcode taking the immediate as a value.
+fn imm_opcode(opcode: Opcode) -> Opcode {
+    match opcode {
+        Opcode::IaddImm => Opcode::Iadd,
+        Opcode::ImulImm => Opcode::Imul,
+        Opcode::UdivImm => Opcode::Udiv,
+        Opcode::SdivImm => Opcode::Sdiv,
+        Opcode::UremImm => Opcode::Urem,
+        Opcode::SremImm => Opcode::Srem,
+        Opcode::BandImm => Opcode::Band,
+        Opcode::BorImm => Opcode::Bor,
+        Opcode::BxorImm => Opcode::Bxor,
+        Opcode::RotlImm => Opcode::Rotl,
+        Opcode::RotrImm => Opcode::Rotr,
+        Opcode::IshlImm => Opcode::Ishl,
+        Opcode::UshrImm => Opcode::Ushr,
+        Opcode::SshrImm => Opcode::Sshr,
+        _ => opcode,
+    }
+}
+
+/// Compute a unary operation on a single lane, converting it to `ty` if needed.
+fn unary(opcode: Opcode, ty: Type, x: &DataValue) -> InterpreterResult<DataValue> {
+    if let Opcode::Copy | Opcode::Breduce | Opcode::Bextend = opcode {
+        return Ok(x.clone());
+    }
+    let bits = x.lane_bits();
+    let sign = 1 << (bits - 1);
+    Ok(match opcode {
+        Opcode::Bnot => x.with_bits(!x.to_bits()),
+        Opcode::Clz => x.with_bits(u64::from(x.to_bits().leading_zeros() - (64 - bits))),
+        Opcode::Cls => {
+            let y = if x.to_i64() < 0 { !x.to_bits() } else { x.to_bits() } & mask(bits);
+            x.with_bits(u64::from(y.leading_zeros() - (64 - bits) - 1))
+        }
+        Opcode::Ctz => x.with_bits(u64::from(x.to_bits().trailing_zeros().min(bits))),
+        Opcode::Popcnt => x.with_bits(u64::from(x.to_bits().count_ones())),
+        Opcode::Fneg => x.with_bits(x.to_bits() ^ sign),
+        Opcode::Fabs => x.with_bits(x.to_bits() & !sign),
+        Opcode::Sqrt => x.with_f64(x.to_f64().sqrt()),
+        Opcode::Ceil => x.with_f64(x.to_f64().ceil()),
+        Opcode::Floor => x.with_f64(x.to_f64().floor()),
+        Opcode::Trunc => x.with_f64(x.to_f64().trunc()),
+        Opcode::Nearest => x.with_f64(nearest(x.to_f64())),
+        Opcode::Bint => DataValue::int(ty, i64::from(x.to_bool())),
+        Opcode::Bmask => DataValue::int(ty, -i64::from(x.to_bool())),
+        Opcode::Ireduce | Opcode::Sextend => DataValue::int(ty, x.to_i64()),
+        Opcode::Uextend => DataValue::int(ty, x.to_bits() as i64),
+        Opcode::Fpromote => DataValue::from(x.to_f64()),
+        Opcode::Fdemote => DataValue::from(x.to_f64() as f32),
+        Opcode::FcvtToUint | Opcode::FcvtToSint => fcvt_to_int(opcode, ty, x)?,
+        Opcode::FcvtFromUint => {
+            match ty {
+                types::F32 => DataValue::from(x.to_bits() as f32),
+                _ => DataValue::from(x.to_bits() as f64),
+            }
+        }
+        Opcode::FcvtFromSint => {
+            match ty {
+                types::F32 => DataValue::from(x.to_i64() as f32),
+                _ => DataValue::from(x.to_i64() as f64),
+            }
+        }
+        _ => return Err(InterpreterError::Unsupported(opcode.to_string())),
+    })
+}
+
+/// Round `x` to the nearest integer, with ties to even.
+fn nearest(x: f64) -> f64 {
+    let r = x.round();
+    if (r - x).abs() == 0.5 {
+        2.0 * (x / 2.0).round()
+    } else {
+        r
+    }
+}
+
+/// Convert the float `x` to the integer type `ty`, trapping if the result can't be represented.
+fn fcvt_to_int(opcode: Opcode, ty: Type, x: &DataValue) -> Result<DataValue, TrapCode> {
+    let x = x.to_f64();
+    if x.is_nan() {
+        return Err(TrapCode::BadConversionToInteger);
+    }
+    let x = x.trunc();
+    let bits = i32::from(ty.lane_bits());
+    let in_range = if opcode == Opcode::FcvtToSint {
+        let limit = 2f64.powi(bits - 1);
+        x >= -limit && x < limit
+    } else {
+        x > -1.0 && x < 2f64.powi(bits)
+    };
+    if !in_range {
+        return Err(TrapCode::IntegerOverflow);
+    }
+    Ok(if opcode == Opcode::FcvtToSint {
+        DataValue::int(ty, x as i64)
+    } else {
+        DataValue::int(ty, x as u64 as i64)
+    })
+}
+
+/// Compute a binary operation on a single lane.
+fn binary(opcode: Opcode, x: &DataValue, y: &DataValue) -> InterpreterResult<DataValue> {
+    let (a, b) = (x.to_bits(), y.to_bits());
+    let bits = x.lane_bits();
+    let result = match opcode {
+        Opcode::Iadd => a.wrapping_add(b),
+        Opcode::Isub => a.wrapping_sub(b),
+        Opcode::Imul => a.wrapping_mul(b),
+        Opcode::Umulhi => ((u128::from(a) * u128::from(b)) >> bits) as u64,
+        Opcode::Smulhi => ((i128::from(x.to_i64()) * i128::from(y.to_i64())) >> bits) as u64,
+        Opcode::Udiv | Opcode::Urem | Opcode::Sdiv | Opcode::Srem if b == 0 => {
+            return Err(TrapCode::IntegerDivisionByZero.into());
+        }
+        Opcode::Udiv => a / b,
+        Opcode::Urem => a % b,
+        Opcode::Sdiv => {
+            let min = (!0u64 << (bits - 1)) as i64;
+            if x.to_i64() == min && y.to_i64() == -1 {
+                return Err(TrapCode::IntegerOverflow.into());
+            }
+            x.to_i64().wrapping_div(y.to_i64()) as u64
+        }
+        Opcode::Srem => x.to_i64().wrapping_rem(y.to_i64()) as u64,
+        Opcode::Band => a & b,
+        Opcode::Bor => a | b,
+        Opcode::Bxor => a ^ b,
+        Opcode::BandNot => a & !b,
+        Opcode::BorNot => a | !b,
+        Opcode::BxorNot => a ^ !b,
+        Opcode::Fadd => return Ok(x.with_f64(x.to_f64() + y.to_f64())),
+        Opcode::Fsub => return Ok(x.with_f64(x.to_f64() - y.to_f64())),
+        Opcode::Fmul => return Ok(x.with_f64(x.to_f64() * y.to_f64())),
+        Opcode::Fdiv => return Ok(x.with_f64(x.to_f64() / y.to_f64())),
+        Opcode::Fcopysign => {
+            let sign = 1 << (bits - 1);
+            (a & !sign) | (b & sign)
+        }
+        Opcode::Fmin | Opcode::Fmax => {
+            let (p, q) = (x.to_f64(), y.to_f64());
+            if p.is_nan() || q.is_nan() {
+                return Ok(x.with_f64(p + q));
+            }
+            match (opcode, p == q) {
+                // Equal values only differ in the sign of zero.
+                (Opcode::Fmin, true) => a | b,
+                (Opcode::Fmax, true) => a & b,
+                (Opcode::Fmin, false) => if p < q { a } else { b },
+                _ => if p > q { a } else { b },
+            }
+        }
+        _ => return Err(InterpreterError::Unsupported(opcode.to_string())),
+    };
+    Ok(x.with_bits(result))
+}
+
+/// Compute a shift or rotate of `x` by `amount` modulo the number of bits in a lane.
+fn shift(opcode: Opcode, x: &DataValue, amount: u64) -> DataValue {
+    let bits = x.lane_bits();
+    let s = (amount % u64::from(bits)) as u32;
+    let a = x.to_bits();
+    x.with_bits(match opcode {
+        Opcode::Ishl => a << s,
+        Opcode::Ushr => a >> s,
+        Opcode::Sshr => (x.to_i64() >> s) as u64,
+        Opcode::Rotl if s != 0 => (a << s) | (a >> (bits - s)),
+        Opcode::Rotr if s != 0 => (a >> s) | (a << (bits - s)),
+        _ => a,
+    })
+}
+
+/// Compute an addition with carry or a subtraction with borrow.
+///
+/// Returns the result and the carry or borrow out.
+fn carry(opcode: Opcode, x: &DataValue, y: &DataValue, carry_in: i128) -> Vec<DataValue> {
+    let (a, b) = (i128::from(x.to_bits()), i128::from(y.to_bits()));
+    let (result, carry_out) = match opcode {
+        Opcode::IaddCout | Opcode::IaddCin | Opcode::IaddCarry => {
+            let sum = a + b + carry_in;
+            (sum, sum >> x.lane_bits() != 0)
+        }
+        _ => {
+            let difference = a - b - carry_in;
+            (difference, difference < 0)
+        }
+    };
+    vec![x.with_bits(result as u64), DataValue::B(carry_out)]
+}
+
+/// Compute a fused multiply-add on a single lane.
+fn fma(x: &DataValue, y: &DataValue, z: &DataValue) -> DataValue {
+    match *x {
+        DataValue::F32(_) => {
+            let f = |v: &DataValue| f32::from_bits(v.to_bits() as u32);
+            DataValue::from(f(x).mul_add(f(y), f(z)))
+        }
+        _ => DataValue::from(x.to_f64().mul_add(y.to_f64(), z.to_f64())),
+    }
+}
+
+/// Evaluate an integer condition code.
+fn icmp(cond: IntCC, x: &DataValue, y: &DataValue) -> bool {
+    let (a, b) = (x.to_bits(), y.to_bits());
+    let (p, q) = (x.to_i64(), y.to_i64());
+    match cond {
+        IntCC::Equal => a == b,
+        IntCC::NotEqual => a != b,
+        IntCC::SignedLessThan => p < q,
+        IntCC::SignedGreaterThanOrEqual => p >= q,
+        IntCC::SignedGreaterThan => p > q,
+        IntCC::SignedLessThanOrEqual => p <= q,
+        IntCC::UnsignedLessThan => a < b,
+        IntCC::UnsignedGreaterThanOrEqual => a >= b,
+        IntCC::UnsignedGreaterThan => a > b,
+        IntCC::UnsignedLessThanOrEqual => a <= b,
+    }
+}
+
+/// Evaluate a floating point condition code.
+fn fcmp(cond: FloatCC, x: &DataValue, y: &DataValue) -> bool {
+    let (x, y) = (x.to_f64(), y.to_f64());
+    let unordered = x.is_nan() || y.is_nan();
+    match cond {
+        FloatCC::Ordered => !unordered,
+        FloatCC::Unordered => unordered,
+        FloatCC::Equal => x == y,
+        FloatCC::NotEqual => x != y,
+        FloatCC::OrderedNotEqual => x < y || x > y,
+        FloatCC::UnorderedOrEqual => unordered || x == y,
+        FloatCC::LessThan => x < y,
+        FloatCC::LessThanOrEqual => x <= y,
+        FloatCC::GreaterThan => x > y,
+        FloatCC::GreaterThanOrEqual => x >= y,
+        FloatCC::UnorderedOrLessThan => unordered || x < y,
+        FloatCC::UnorderedOrLessThanOrEqual => unordered || x <= y,
+        FloatCC::UnorderedOrGreaterThan => unordered || x > y,
+        FloatCC::UnorderedOrGreaterThanOrEqual => unordered || x >= y,
+    }
+}
+
+/// Test the integer flags produced by `ifcmp`.
+fn int_flags(cond: IntCC, flags: &DataValue) -> bool {
+    match *flags {
+        DataValue::IFlags(ref x, ref y) => icmp(cond, x, y),
+        _ => panic!("{} is not iflags", flags),
+    }
+}
+
+/// Test the floating point flags produced by `ffcmp`.
+fn float_flags(cond: FloatCC, flags: &DataValue) -> bool {
+    match *flags {
+        DataValue::FFlags(ref x, ref y) => fcmp(cond, x, y),
+        _ => panic!("{} is not fflags", flags),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cretonne_reader::parse_functions;
+
+    /// Parse the functions in `text` and call the first one with `args`.
+    fn run(text: &str, args: &[DataValue]) -> InterpreterResult<Vec<DataValue>> {
+        let funcs = parse_functions(text).unwrap();
+        let mut interpreter = Interpreter::new();
+        for func in &funcs {
+            interpreter.add_function(func);
+        }
+        interpreter.call(&funcs[0], args)
+    }
+
+    #[test]
+    fn loops() {
+        let text = "
+            function %sum(i32) -> i32 {
+            ebb0(v0: i32):
+                v1 = iconst.i32 0
+                jump ebb1(v0, v1)
+            ebb1(v2: i32, v3: i32):
+                brz v2, ebb2
+                v4 = iadd v3, v2
+                v5 = iadd_imm v2, -1
+                jump ebb1(v5, v4)
+            ebb2:
+                return v3
+            }";
+        assert_eq!(run(text, &[DataValue::I32(10)]), Ok(vec![DataValue::I32(55)]));
+        assert_eq!(
+            run(text, &[DataValue::I64(10)]),
+            Err(InterpreterError::SignatureMismatch(ExternalName::testcase("sum")))
+        );
+    }
+
+    #[test]
+    fn integers() {
+        let text = "
+            function %ints(i8, i8) -> i8, i8, i8, b1, i8, i8 {
+            ebb0(v0: i8, v1: i8):
+                v2 = imul v0, v1
+                v3 = sdiv v0, v1
+                v4 = urem v0, v1
+                v5 = icmp ult v0, v1
+                v6 = rotl_imm v0, 9
+                v7 = clz v1
+                return v2, v3, v4, v5, v6, v7
+            }";
+        assert_eq!(
+            run(text, &[DataValue::I8(-100), DataValue::I8(7)]),
+            Ok(vec![
+                DataValue::I8(68),
+                DataValue::I8(-14),
+                DataValue::I8(2),
+                DataValue::B(false),
+                DataValue::I8(57),
+                DataValue::I8(5),
+            ])
+        );
+        assert_eq!(
+            run(text, &[DataValue::I8(1), DataValue::I8(0)]),
+            Err(InterpreterError::Trap(TrapCode::IntegerDivisionByZero))
+        );
+        assert_eq!(
+            run(text, &[DataValue::I8(-128), DataValue::I8(-1)]),
+            Err(InterpreterError::Trap(TrapCode::IntegerOverflow))
+        );
+    }
+
+    #[test]
+    fn floats() {
+        let text = "
+            function %floats(f32, f32) -> f32, f32, f32, i32 {
+            ebb0(v0: f32, v1: f32):
+                v2 = fmin v0, v1
+                v3 = nearest v0
+                v4 = fcopysign v1, v0
+                v5 = fcvt_to_sint.i32 v1
+                return v2, v3, v4, v5
+            }";
+        assert_eq!(
+            run(text, &[DataValue::from(2.5f32), DataValue::from(-0.0f32)]),
+            Ok(vec![
+                DataValue::from(-0.0f32),
+                DataValue::from(2.0f32),
+                DataValue::from(0.0f32),
+                DataValue::I32(0),
+            ])
+        );
+        assert_eq!(
+            run(text, &[DataValue::from(1.0f32), DataValue::from(3e9f32)]),
+            Err(InterpreterError::Trap(TrapCode::IntegerOverflow))
+        );
+    }
+
+    #[test]
+    fn vectors() {
+        let text = "
+            function %vectors(i32x4, i32) -> i32x4, i32, b1 {
+            ebb0(v0: i32x4, v1: i32):
+                v2 = splat.i32x4 v1
+                v3 = iadd v0, v2
+                v4 = extractlane v3, 2
+                v5 = icmp eq v0, v2
+                v6 = extractlane v5, 1
+                return v3, v4, v6
+            }";
+        let v = DataValue::V((0..4).map(DataValue::I32).collect());
+        assert_eq!(
+            run(text, &[v, DataValue::I32(1)]),
+            Ok(vec![
+                DataValue::V((1..5).map(DataValue::I32).collect()),
+                DataValue::I32(3),
+                DataValue::B(true),
+            ])
+        );
+    }
+
+    #[test]
+    fn calls() {
+        let text = "
+            function %caller(i64) -> i64 {
+                sig0 = (i64) -> i64
+                fn0 = %callee sig0
+                fn1 = %host sig0
+            ebb0(v0: i64):
+                v1 = call fn0(v0)
+                v2 = func_addr.i64 fn1
+                v3 = call_indirect sig0, v2(v1)
+                return v3
+            }
+
+            function %callee(i64) -> i64 {
+            ebb0(v0: i64):
+                v1 = imul_imm v0, 3
+                return v1
+            }";
+        let funcs = parse_functions(text).unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.add_function(&funcs[1]);
+        interpreter.define_host_function(ExternalName::testcase("host"), |_, args| {
+            Ok(vec![DataValue::I64(args[0].to_i64() + 1)])
+        });
+        assert_eq!(
+            interpreter.call(&funcs[0], &[DataValue::I64(4)]),
+            Ok(vec![DataValue::I64(13)])
+        );
+    }
+
+    #[test]
+    fn recursion() {
+        let text = "
+            function %forever() {
+                fn0 = %forever()
+            ebb0:
+                call fn0()
+                return
+            }";
+        assert_eq!(run(text, &[]), Err(InterpreterError::Trap(TrapCode::StackOverflow)));
+
+        // Tail calls don't use up the call stack.
+        let text = "
+            function %countdown(i32) -> i32 {
+                fn0 = %countdown(i32) -> i32
+            ebb0(v0: i32):
+                brz v0, ebb1
+                v1 = iadd_imm v0, -1
+                return_call fn0(v1)
+            ebb1:
+                v2 = iconst.i32 7
+                return v2
+            }";
+        assert_eq!(run(text, &[DataValue::I32(100_000)]), Ok(vec![DataValue::I32(7)]));
+    }
+
+    #[test]
+    fn memory() {
+        let text = "
+            function %memory(i32, i64 vmctx) -> i32, i64 {
+                ss0 = explicit_slot 8
+                gv0 = vmctx
+                heap0 = static gv0, min 0x10, bound 0x10, guard 0
+            ebb0(v0: i32, v1: i64):
+                stack_store v0, ss0+4
+                v2 = stack_load.i32 ss0+4
+                v3 = heap_addr.i64 heap0, v2, 8
+                store v1, v3
+                v4 = uload8.i32 v3+1
+                v5 = stack_addr.i64 ss0
+                v6 = load.i64 v5
+                return v4, v6
+            }";
+        let funcs = parse_functions(text).unwrap();
+        let mut memory = VecMemory::new(0x1000, 0x20);
+        // The heap base is stored at the vmctx address, followed by the heap itself.
+        memory.bytes_mut()[..8].copy_from_slice(&[0x08, 0x10, 0, 0, 0, 0, 0, 0]);
+        let mut interpreter = Interpreter::with_memory(Box::new(memory));
+        assert_eq!(
+            interpreter.call(&funcs[0], &[DataValue::I32(8), DataValue::I64(0x1000)]),
+            Ok(vec![DataValue::I32(0x10), DataValue::I64(8 << 32)])
+        );
+        assert_eq!(
+            interpreter.call(&funcs[0], &[DataValue::I32(9), DataValue::I64(0x1000)]),
+            Err(InterpreterError::Trap(TrapCode::HeapOutOfBounds))
+        );
+    }
+}
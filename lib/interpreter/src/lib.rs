@@ -0,0 +1,72 @@
+//! Cretonne IR interpreter.
+//!
+//! This crate executes Cretonne IR functions directly, without generating native code. It works
+//! on functions before legalization, so it can be used to test front ends on any host, and to
+//! check the results of optimizations against a reference.
+//!
+//! Create an [`Interpreter`](struct.Interpreter.html), add the functions of a module to it, and
+//! call them with [`DataValue`](enum.DataValue.html) arguments. Traps in the interpreted code are
+//! reported as an `InterpreterError::Trap` with the corresponding `TrapCode`.
+//!
+//! # Example
+//!
+//! ```rust
+//! extern crate cretonne_codegen;
+//! extern crate cretonne_interpreter;
+//!
+//! use cretonne_codegen::ir::types::I32;
+//! use cretonne_codegen::ir::{AbiParam, ExternalName, Function, InstBuilder, Signature};
+//! use cretonne_codegen::cursor::{Cursor, FuncCursor};
+//! use cretonne_codegen::settings::CallConv;
+//! use cretonne_interpreter::{DataValue, Interpreter};
+//!
+//! fn main() {
+//!     let mut sig = Signature::new(CallConv::SystemV);
+//!     sig.params.push(AbiParam::new(I32));
+//!     sig.returns.push(AbiParam::new(I32));
+//!     let mut func = Function::with_name_signature(ExternalName::testcase("double"), sig);
+//!     {
+//!         let ebb = func.dfg.make_ebb();
+//!         let x = func.dfg.append_ebb_param(ebb, I32);
+//!         let mut pos = FuncCursor::new(&mut func);
+//!         pos.insert_ebb(ebb);
+//!         let y = pos.ins().iadd(x, x);
+//!         pos.ins().return_(&[y]);
+//!     }
+//!
+//!     let mut interpreter = Interpreter::new();
+//!     let results = interpreter.call(&func, &[DataValue::I32(21)]).unwrap();
+//!     assert_eq!(results, [DataValue::I32(42)]);
+//! }
+//! ```
+
+#![deny(missing_docs, trivial_numeric_casts, unused_extern_crates)]
+#![warn(unused_import_braces, unstable_features)]
+#![cfg_attr(feature = "clippy", plugin(clippy(conf_file = "../../clippy.toml")))]
+#![cfg_attr(feature = "cargo-clippy",
+            allow(new_without_default, new_without_default_derive))]
+#![cfg_attr(feature="cargo-clippy", warn(
+                mut_mut,
+                nonminimal_bool,
+                option_map_unwrap_or,
+                option_map_unwrap_or_else,
+                print_stdout,
+                unicode_not_nfc,
+                use_self,
+                ))]
+
+extern crate cretonne_codegen;
+#[macro_use]
+extern crate failure;
+
+#[cfg(test)]
+extern crate cretonne_reader;
+
+mod interpreter;
+mod memory;
+mod value;
+
+pub use interpreter::{matches_params, HostFunction, Interpreter, InterpreterError,
+                      InterpreterResult, FUNCTION_BASE, STACK_BASE};
+pub use memory::{Memory, VecMemory};
+pub use value::DataValue;
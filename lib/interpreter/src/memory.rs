@@ -0,0 +1,92 @@
+//! Memory accessible to interpreted functions.
+
+use cretonne_codegen::ir::TrapCode;
+use std::vec::Vec;
+
+/// Memory outside the interpreter's stack, such as the heaps of a WebAssembly module.
+///
+/// The interpreter forwards every load and store outside its own stack to an implementation of
+/// this trait, which decides how addresses are mapped to memory and which accesses trap.
+pub trait Memory {
+    /// Read `bytes.len()` bytes starting at `addr`.
+    fn load(&self, addr: u64, bytes: &mut [u8]) -> Result<(), TrapCode>;
+
+    /// Write `bytes` starting at `addr`.
+    fn store(&mut self, addr: u64, bytes: &[u8]) -> Result<(), TrapCode>;
+}
+
+/// A contiguous range of memory backed by a vector of bytes.
+///
+/// Accesses outside the range trap with `TrapCode::HeapOutOfBounds`.
+pub struct VecMemory {
+    base: u64,
+    bytes: Vec<u8>,
+}
+
+impl VecMemory {
+    /// Create `size` bytes of zeroed memory starting at address `base`.
+    pub fn new(base: u64, size: usize) -> Self {
+        Self {
+            base,
+            bytes: vec![0; size],
+        }
+    }
+
+    /// Get the address of the first byte.
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// Get the contents of the memory.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Get the contents of the memory for modification.
+    pub fn bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+
+    /// Get the offset of the `size` bytes at `addr`, if they are in bounds.
+    fn offset(&self, addr: u64, size: usize) -> Result<usize, TrapCode> {
+        addr.checked_sub(self.base)
+            .and_then(|offset| offset.checked_add(size as u64))
+            .and_then(|end| if end <= self.bytes.len() as u64 {
+                Some(end as usize - size)
+            } else {
+                None
+            })
+            .ok_or(TrapCode::HeapOutOfBounds)
+    }
+}
+
+impl Memory for VecMemory {
+    fn load(&self, addr: u64, bytes: &mut [u8]) -> Result<(), TrapCode> {
+        let offset = self.offset(addr, bytes.len())?;
+        bytes.copy_from_slice(&self.bytes[offset..offset + bytes.len()]);
+        Ok(())
+    }
+
+    fn store(&mut self, addr: u64, bytes: &[u8]) -> Result<(), TrapCode> {
+        let offset = self.offset(addr, bytes.len())?;
+        self.bytes[offset..offset + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds() {
+        let mut mem = VecMemory::new(0x1000, 16);
+        assert_eq!(mem.store(0x100c, &[1, 2, 3, 4]), Ok(()));
+        let mut bytes = [0; 2];
+        assert_eq!(mem.load(0x100d, &mut bytes), Ok(()));
+        assert_eq!(bytes, [2, 3]);
+        assert_eq!(mem.store(0x100d, &[1, 2, 3, 4]), Err(TrapCode::HeapOutOfBounds));
+        assert_eq!(mem.load(0xfff, &mut bytes), Err(TrapCode::HeapOutOfBounds));
+        assert_eq!(mem.load(!0, &mut bytes), Err(TrapCode::HeapOutOfBounds));
+    }
+}
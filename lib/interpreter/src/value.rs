@@ -0,0 +1,327 @@
+//! Values computed by the interpreter.
+
+use cretonne_codegen::ir::immediates::{Ieee32, Ieee64};
+use cretonne_codegen::ir::{types, Type};
+use std::fmt;
+use std::vec::Vec;
+
+/// A value computed by the interpreter.
+///
+/// Integers are stored in two's complement form. Like in Cretonne IR, the signedness of an
+/// integer is determined by the instructions using it, not by the value itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DataValue {
+    /// A boolean of any width.
+    B(bool),
+    /// An `i8` integer.
+    I8(i8),
+    /// An `i16` integer.
+    I16(i16),
+    /// An `i32` integer.
+    I32(i32),
+    /// An `i64` integer.
+    I64(i64),
+    /// An `f32` floating point number.
+    F32(Ieee32),
+    /// An `f64` floating point number.
+    F64(Ieee64),
+    /// A vector with one value per lane.
+    V(Vec<DataValue>),
+    /// The integer CPU flags produced by comparing two integers with `ifcmp`.
+    IFlags(Box<DataValue>, Box<DataValue>),
+    /// The floating point CPU flags produced by comparing two floats with `ffcmp`.
+    FFlags(Box<DataValue>, Box<DataValue>),
+}
+
+impl DataValue {
+    /// Create an integer of type `ty` from the low bits of `x`.
+    ///
+    /// Booleans are true when `x` is odd, and floats are created from their bit pattern.
+    pub fn from_bits(ty: Type, x: u64) -> Self {
+        match ty {
+            types::I8 => DataValue::I8(x as i8),
+            types::I16 => DataValue::I16(x as i16),
+            types::I32 => DataValue::I32(x as i32),
+            types::I64 => DataValue::I64(x as i64),
+            types::F32 => DataValue::F32(Ieee32::with_bits(x as u32)),
+            types::F64 => DataValue::F64(Ieee64::with_bits(x)),
+            _ if ty.is_bool() && ty.is_lane() => DataValue::B(x & 1 != 0),
+            _ => panic!("can't create a {} from bits", ty),
+        }
+    }
+
+    /// Create an integer of type `ty` from the low bits of `x`.
+    pub fn int(ty: Type, x: i64) -> Self {
+        debug_assert!(ty.is_int(), "{} is not an integer type", ty);
+        Self::from_bits(ty, x as u64)
+    }
+
+    /// Create a value of type `ty` from the little-endian bytes in `bytes`.
+    pub fn from_bytes(ty: Type, bytes: &[u8]) -> Self {
+        debug_assert_eq!(bytes.len(), ty.bytes() as usize);
+        if ty.is_vector() {
+            let lane = ty.lane_type();
+            let size = lane.bytes() as usize;
+            return DataValue::V(
+                bytes
+                    .chunks(size)
+                    .map(|chunk| Self::from_bytes(lane, chunk))
+                    .collect(),
+            );
+        }
+        let bits = bytes.iter().rev().fold(0, |acc, &b| acc << 8 | u64::from(b));
+        Self::from_bits(ty, bits)
+    }
+
+    /// Append the little-endian representation of this value to `bytes`.
+    pub fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        if let DataValue::V(ref lanes) = *self {
+            for lane in lanes {
+                lane.write_bytes(bytes);
+            }
+            return;
+        }
+        let bits = self.to_bits();
+        for i in 0..self.lane_bytes() {
+            bytes.push((bits >> (8 * i)) as u8);
+        }
+    }
+
+    /// Does this value have type `ty`?
+    ///
+    /// All boolean types are represented the same way, so this only checks that a boolean value
+    /// has a boolean type.
+    pub fn is_of_type(&self, ty: Type) -> bool {
+        match *self {
+            DataValue::B(_) => ty.is_bool() && ty.is_lane(),
+            DataValue::I8(_) => ty == types::I8,
+            DataValue::I16(_) => ty == types::I16,
+            DataValue::I32(_) => ty == types::I32,
+            DataValue::I64(_) => ty == types::I64,
+            DataValue::F32(_) => ty == types::F32,
+            DataValue::F64(_) => ty == types::F64,
+            DataValue::V(ref lanes) => {
+                ty.is_vector() && lanes.len() == usize::from(ty.lane_count()) &&
+                    lanes.iter().all(|lane| lane.is_of_type(ty.lane_type()))
+            }
+            DataValue::IFlags(..) => ty == types::IFLAGS,
+            DataValue::FFlags(..) => ty == types::FFLAGS,
+        }
+    }
+
+    /// Get the bits of a scalar value, zero-extended to 64 bits.
+    pub fn to_bits(&self) -> u64 {
+        match *self {
+            DataValue::B(b) => u64::from(b),
+            DataValue::I8(x) => u64::from(x as u8),
+            DataValue::I16(x) => u64::from(x as u16),
+            DataValue::I32(x) => u64::from(x as u32),
+            DataValue::I64(x) => x as u64,
+            DataValue::F32(x) => u64::from(x.bits()),
+            DataValue::F64(x) => x.bits(),
+            _ => panic!("{} is not a scalar", self),
+        }
+    }
+
+    /// Get the value of a scalar integer, sign-extended to 64 bits.
+    pub fn to_i64(&self) -> i64 {
+        match *self {
+            DataValue::I8(x) => i64::from(x),
+            DataValue::I16(x) => i64::from(x),
+            DataValue::I32(x) => i64::from(x),
+            DataValue::I64(x) => x,
+            _ => panic!("{} is not an integer", self),
+        }
+    }
+
+    /// Get the truth value of a boolean or an integer.
+    pub fn to_bool(&self) -> bool {
+        match *self {
+            DataValue::B(b) => b,
+            _ => self.to_i64() != 0,
+        }
+    }
+
+    /// Get the value of a scalar float, converted to `f64`.
+    pub fn to_f64(&self) -> f64 {
+        match *self {
+            DataValue::F32(x) => f64::from(f32::from_bits(x.bits())),
+            DataValue::F64(x) => f64::from_bits(x.bits()),
+            _ => panic!("{} is not a float", self),
+        }
+    }
+
+    /// Create a value of the same type as this scalar from the low bits of `x`.
+    pub fn with_bits(&self, x: u64) -> Self {
+        match *self {
+            DataValue::B(_) => DataValue::B(x & 1 != 0),
+            DataValue::I8(_) => DataValue::I8(x as i8),
+            DataValue::I16(_) => DataValue::I16(x as i16),
+            DataValue::I32(_) => DataValue::I32(x as i32),
+            DataValue::I64(_) => DataValue::I64(x as i64),
+            DataValue::F32(_) => DataValue::F32(Ieee32::with_bits(x as u32)),
+            DataValue::F64(_) => DataValue::F64(Ieee64::with_bits(x)),
+            _ => panic!("{} is not a scalar", self),
+        }
+    }
+
+    /// Create a float of the same type as this scalar float, rounding `x` if necessary.
+    pub fn with_f64(&self, x: f64) -> Self {
+        match *self {
+            DataValue::F32(_) => DataValue::F32(Ieee32::with_float(x as f32)),
+            DataValue::F64(_) => DataValue::F64(Ieee64::with_float(x)),
+            _ => panic!("{} is not a float", self),
+        }
+    }
+
+    /// Get the number of bits in a scalar value, or in a lane of a vector.
+    pub fn lane_bits(&self) -> u32 {
+        match *self {
+            DataValue::B(_) => 1,
+            DataValue::I8(_) => 8,
+            DataValue::I16(_) => 16,
+            DataValue::I32(_) | DataValue::F32(_) => 32,
+            DataValue::I64(_) | DataValue::F64(_) => 64,
+            DataValue::V(ref lanes) => lanes[0].lane_bits(),
+            _ => panic!("{} has no lanes", self),
+        }
+    }
+
+    fn lane_bytes(&self) -> u32 {
+        (self.lane_bits() + 7) / 8
+    }
+
+    /// Get the lanes of a vector, or the value itself if it is a scalar.
+    pub fn lanes(&self) -> &[Self] {
+        match *self {
+            DataValue::V(ref lanes) => lanes,
+            _ => ::std::slice::from_ref(self),
+        }
+    }
+
+    /// Apply `f` to each lane of this value.
+    ///
+    /// If this is a vector, the result is a vector with the same number of lanes.
+    pub fn map<E, F>(&self, mut f: F) -> Result<Self, E>
+    where
+        F: FnMut(&Self) -> Result<Self, E>,
+    {
+        match *self {
+            DataValue::V(ref lanes) => Ok(DataValue::V(
+                lanes.iter().map(f).collect::<Result<_, _>>()?,
+            )),
+            _ => f(self),
+        }
+    }
+
+    /// Apply `f` to the corresponding lanes of this value and `other`.
+    pub fn zip<E, F>(&self, other: &Self, mut f: F) -> Result<Self, E>
+    where
+        F: FnMut(&Self, &Self) -> Result<Self, E>,
+    {
+        match (self, other) {
+            (&DataValue::V(ref xs), &DataValue::V(ref ys)) => {
+                Ok(DataValue::V(xs.iter()
+                    .zip(ys)
+                    .map(|(x, y)| f(x, y))
+                    .collect::<Result<_, _>>()?))
+            }
+            _ => f(self, other),
+        }
+    }
+}
+
+impl fmt::Display for DataValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DataValue::B(b) => write!(f, "{}", b),
+            DataValue::I8(x) => write!(f, "{}", x),
+            DataValue::I16(x) => write!(f, "{}", x),
+            DataValue::I32(x) => write!(f, "{}", x),
+            DataValue::I64(x) => write!(f, "{}", x),
+            DataValue::F32(x) => write!(f, "{}", x),
+            DataValue::F64(x) => write!(f, "{}", x),
+            DataValue::V(ref lanes) => {
+                write!(f, "[")?;
+                for (i, lane) in lanes.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", lane)?;
+                }
+                write!(f, "]")
+            }
+            DataValue::IFlags(..) => write!(f, "iflags"),
+            DataValue::FFlags(..) => write!(f, "fflags"),
+        }
+    }
+}
+
+impl From<bool> for DataValue {
+    fn from(x: bool) -> Self {
+        DataValue::B(x)
+    }
+}
+
+impl From<i8> for DataValue {
+    fn from(x: i8) -> Self {
+        DataValue::I8(x)
+    }
+}
+
+impl From<i16> for DataValue {
+    fn from(x: i16) -> Self {
+        DataValue::I16(x)
+    }
+}
+
+impl From<i32> for DataValue {
+    fn from(x: i32) -> Self {
+        DataValue::I32(x)
+    }
+}
+
+impl From<i64> for DataValue {
+    fn from(x: i64) -> Self {
+        DataValue::I64(x)
+    }
+}
+
+impl From<f32> for DataValue {
+    fn from(x: f32) -> Self {
+        DataValue::F32(Ieee32::with_float(x))
+    }
+}
+
+impl From<f64> for DataValue {
+    fn from(x: f64) -> Self {
+        DataValue::F64(Ieee64::with_float(x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits() {
+        assert_eq!(DataValue::int(types::I8, -1), DataValue::I8(-1));
+        assert_eq!(DataValue::int(types::I16, 0x12345), DataValue::I16(0x2345));
+        assert_eq!(DataValue::I8(-1).to_bits(), 0xff);
+        assert_eq!(DataValue::I8(-1).to_i64(), -1);
+        assert_eq!(DataValue::from_bits(types::B8, 3), DataValue::B(true));
+        assert_eq!(DataValue::from(1.5f32).to_f64(), 1.5);
+    }
+
+    #[test]
+    fn bytes() {
+        let v = DataValue::V(vec![DataValue::I16(1), DataValue::I16(-2)]);
+        let mut bytes = Vec::new();
+        v.write_bytes(&mut bytes);
+        assert_eq!(bytes, [1, 0, 0xfe, 0xff]);
+        assert_eq!(DataValue::from_bytes(types::I16.by(2).unwrap(), &bytes), v);
+        assert!(v.is_of_type(types::I16.by(2).unwrap()));
+        assert!(!v.is_of_type(types::I32));
+        assert_eq!(v.to_string(), "[1, -2]");
+    }
+}
@@ -8,6 +8,7 @@ use cretonne_codegen::Context;
 use cretonne_codegen::isa::TargetIsa;
 use cretonne_codegen::{binemit, ir};
 use std::marker;
+use std::sync::Arc;
 
 /// A `Backend` implements the functionality needed to support a `Module`.
 pub trait Backend
@@ -42,6 +43,12 @@ where
     /// Return the `TargetIsa` to compile for.
     fn isa(&self) -> &TargetIsa;
 
+    /// Return a shared handle to the `TargetIsa` to compile for.
+    ///
+    /// `Module::define_functions` hands this to its compilation threads, so they can keep
+    /// compiling while the backend defines the functions that are already compiled.
+    fn shared_isa(&self) -> Arc<TargetIsa>;
+
     /// Declare a function.
     fn declare_function(&mut self, name: &str, linkage: Linkage);
 
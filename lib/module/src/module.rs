@@ -8,9 +8,14 @@
 use Backend;
 use cretonne_codegen::entity::{EntityRef, PrimaryMap};
 use cretonne_codegen::result::CtonError;
-use cretonne_codegen::{binemit, ir, Context};
+use cretonne_codegen::{binemit, ir, isa, Context};
 use data_context::DataContext;
+use std::cmp;
 use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+use std::thread;
 
 /// A function identifier for use in the `Module` interface.
 #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -322,6 +327,11 @@ where
         self.names.get(name).map(|e| *e)
     }
 
+    /// Return the target isa.
+    pub fn isa(&self) -> &isa::TargetIsa {
+        self.backend.isa()
+    }
+
     /// Return then pointer type for the current target.
     pub fn pointer_type(&self) -> ir::types::Type {
         if self.backend.isa().flags().is_64bit() {
@@ -474,16 +484,132 @@ where
 
     /// Define a function, producing the function body from the given `Context`.
     pub fn define_function(&mut self, func: FuncId, ctx: &mut Context) -> Result<(), ModuleError> {
-        let compiled = {
-            let code_size = ctx.compile(self.backend.isa()).map_err(|e| {
-                dbg!(
-                    "defining function {}: {}",
-                    func,
-                    ctx.func.display(self.backend.isa())
-                );
-                ModuleError::Compilation(e)
-            })?;
+        let code_size = ctx.compile(self.backend.isa()).map_err(|e| {
+            dbg!(
+                "defining function {}: {}",
+                func,
+                ctx.func.display(self.backend.isa())
+            );
+            ModuleError::Compilation(e)
+        })?;
+        self.define_compiled_function(func, ctx, code_size)
+    }
 
+    /// Define several functions at once, compiling them in parallel on `num_threads` threads.
+    ///
+    /// Each thread compiles functions in its own `Context`, which is reused for every function it
+    /// compiles. The compiled functions are handed to the `Backend` in declaration order while
+    /// the remaining functions are being compiled, so the result doesn't depend on the number of
+    /// threads or how the work was scheduled. If a function fails to compile, the functions
+    /// declared before it are still defined.
+    pub fn define_functions(
+        &mut self,
+        funcs: Vec<(FuncId, ir::Function)>,
+        num_threads: usize,
+    ) -> Result<(), ModuleError> {
+        let mut funcs = funcs;
+        funcs.sort_by_key(|&(func, _)| func);
+
+        // Reject invalid definitions before spending time compiling anything.
+        for (i, &(func, _)) in funcs.iter().enumerate() {
+            let info = &self.contents.functions[func];
+            if info.compiled.is_some() || (i > 0 && funcs[i - 1].0 == func) {
+                return Err(ModuleError::DuplicateDefinition(info.decl.name.clone()));
+            }
+            if !info.decl.linkage.is_definable() {
+                return Err(ModuleError::InvalidImportDefinition(info.decl.name.clone()));
+            }
+        }
+
+        let num_threads = cmp::max(1, cmp::min(num_threads, funcs.len()));
+
+        // The workers are declared first so that they are dropped last, after the channels they
+        // wait on have been closed.
+        let mut workers = Workers(Vec::with_capacity(num_threads));
+        let (request_tx, request_rx) = channel::<(usize, ir::Function)>();
+        let request_rx = Arc::new(Mutex::new(request_rx));
+        let (reply_tx, reply_rx) = channel();
+        let mut return_txs = Vec::with_capacity(num_threads);
+        for worker in 0..num_threads {
+            let (return_tx, return_rx) = channel::<Context>();
+            return_txs.push(return_tx);
+            let isa = self.backend.shared_isa();
+            let request_rx = request_rx.clone();
+            let reply_tx = reply_tx.clone();
+            workers.0.push(thread::spawn(move || {
+                let mut ctx = Context::new();
+                loop {
+                    // Only hold the lock while waiting for a request, not while compiling.
+                    let request = request_rx.lock().unwrap().recv();
+                    let (index, func) = match request {
+                        Ok(request) => request,
+                        Err(_) => return,
+                    };
+                    ctx.clear();
+                    ctx.func = func;
+                    // A panic is passed on to the main thread, which would otherwise wait for
+                    // this function forever.
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| ctx.compile(&*isa)));
+                    if reply_tx.send((index, worker, ctx, result)).is_err() {
+                        return;
+                    }
+                    // Wait for the context to be handed back once the function is defined.
+                    ctx = match return_rx.recv() {
+                        Ok(ctx) => ctx,
+                        Err(_) => return,
+                    };
+                }
+            }));
+        }
+        drop(reply_tx);
+
+        let mut ids = Vec::with_capacity(funcs.len());
+        for (index, (id, func)) in funcs.into_iter().enumerate() {
+            ids.push(id);
+            request_tx.send((index, func)).expect(
+                "compilation threads exited early",
+            );
+        }
+        drop(request_tx);
+
+        // The replies arrive in whatever order the workers finish. Each worker waits for its
+        // context to be handed back, so at most one reply per worker is held here.
+        let mut pending = HashMap::new();
+        for (index, &id) in ids.iter().enumerate() {
+            let (worker, ctx, result) = loop {
+                if let Some(reply) = pending.remove(&index) {
+                    break reply;
+                }
+                let (index, worker, ctx, result) =
+                    reply_rx.recv().expect("compilation threads exited early");
+                pending.insert(index, (worker, ctx, result));
+            };
+            let defined = match result.unwrap_or_else(|payload| panic::resume_unwind(payload)) {
+                Ok(code_size) => self.define_compiled_function(id, &ctx, code_size),
+                Err(e) => {
+                    dbg!(
+                        "defining function {}: {}",
+                        id,
+                        ctx.func.display(self.backend.isa())
+                    );
+                    Err(ModuleError::Compilation(e))
+                }
+            };
+            // The worker may have exited already if another worker panicked.
+            let _ = return_txs[worker].send(ctx);
+            defined?;
+        }
+        Ok(())
+    }
+
+    /// Hand a function that has been compiled in `ctx` to the backend.
+    fn define_compiled_function(
+        &mut self,
+        func: FuncId,
+        ctx: &Context,
+        code_size: u32,
+    ) -> Result<(), ModuleError> {
+        let compiled = {
             let info = &self.contents.functions[func];
             if !info.compiled.is_none() {
                 return Err(ModuleError::DuplicateDefinition(info.decl.name.clone()));
@@ -640,3 +766,171 @@ where
         self.backend.finish()
     }
 }
+
+/// Joins the compilation threads of `Module::define_functions` when dropped, even when
+/// unwinding.
+struct Workers(Vec<thread::JoinHandle<()>>);
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        let mut panicked = false;
+        for worker in self.0.drain(..) {
+            panicked |= worker.join().is_err();
+        }
+        if panicked && !thread::panicking() {
+            panic!("compilation thread panicked");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cretonne_codegen::cursor::{Cursor, FuncCursor};
+    use cretonne_codegen::ir::{types, AbiParam, InstBuilder};
+    use cretonne_codegen::isa::{self, TargetIsa};
+    use cretonne_codegen::settings::{self, CallConv, Configurable};
+
+    /// A backend that records the functions it is given.
+    struct RecordingBackend {
+        isa: Arc<TargetIsa>,
+        defined: Vec<(String, u32)>,
+    }
+
+    impl Backend for RecordingBackend {
+        type Builder = Box<TargetIsa>;
+        type CompiledFunction = u32;
+        type CompiledData = ();
+        type FinalizedFunction = ();
+        type FinalizedData = ();
+        type Product = Vec<(String, u32)>;
+
+        fn new(isa: Box<TargetIsa>) -> Self {
+            Self {
+                isa: Arc::from(isa),
+                defined: Vec::new(),
+            }
+        }
+
+        fn isa(&self) -> &TargetIsa {
+            &*self.isa
+        }
+
+        fn shared_isa(&self) -> Arc<TargetIsa> {
+            self.isa.clone()
+        }
+
+        fn declare_function(&mut self, _name: &str, _linkage: Linkage) {}
+
+        fn declare_data(&mut self, _name: &str, _linkage: Linkage, _writable: bool) {}
+
+        fn define_function(
+            &mut self,
+            name: &str,
+            _ctx: &Context,
+            _namespace: &ModuleNamespace<Self>,
+            code_size: u32,
+        ) -> Result<u32, ModuleError> {
+            self.defined.push((name.to_owned(), code_size));
+            Ok(code_size)
+        }
+
+        fn define_data(
+            &mut self,
+            _name: &str,
+            _data_ctx: &DataContext,
+            _namespace: &ModuleNamespace<Self>,
+        ) -> Result<(), ModuleError> {
+            Ok(())
+        }
+
+        fn write_data_funcaddr(&mut self, _data: &mut (), _offset: usize, _what: ir::FuncRef) {}
+
+        fn write_data_dataaddr(
+            &mut self,
+            _data: &mut (),
+            _offset: usize,
+            _what: ir::GlobalVar,
+            _addend: binemit::Addend,
+        ) {
+        }
+
+        fn finalize_function(&mut self, _func: &u32, _namespace: &ModuleNamespace<Self>) {}
+
+        fn finalize_data(&mut self, _data: &(), _namespace: &ModuleNamespace<Self>) {}
+
+        fn finish(self) -> Vec<(String, u32)> {
+            self.defined
+        }
+    }
+
+    fn make_module() -> Module<RecordingBackend> {
+        let mut flag_builder = settings::builder();
+        flag_builder.enable("is_64bit").unwrap();
+        let isa = isa::lookup("x86").unwrap().finish(
+            settings::Flags::new(flag_builder),
+        );
+        Module::new(isa)
+    }
+
+    /// Build a function that returns the sum of `n` constants.
+    fn make_function(sig: &ir::Signature, n: i64) -> ir::Function {
+        let mut func = ir::Function::with_name_signature(ir::ExternalName::user(0, 0), sig.clone());
+        let ebb = func.dfg.make_ebb();
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb);
+        let mut sum = pos.ins().iconst(types::I64, 0);
+        for i in 0..n {
+            sum = pos.ins().iadd_imm(sum, i);
+        }
+        pos.ins().return_(&[sum]);
+        func
+    }
+
+    #[test]
+    fn define_functions_in_order() {
+        let mut sig = ir::Signature::new(CallConv::SystemV);
+        sig.returns.push(AbiParam::new(types::I64));
+
+        // Compile the same functions sequentially and in parallel.
+        let mut sequential = make_module();
+        let mut ctx = sequential.make_context();
+        for i in 0..7 {
+            let id = sequential
+                .declare_function(&format!("f{}", i), Linkage::Local, &sig)
+                .unwrap();
+            ctx.func = make_function(&sig, i * 3);
+            sequential.define_function(id, &mut ctx).unwrap();
+            sequential.clear_context(&mut ctx);
+        }
+
+        let mut parallel = make_module();
+        let mut funcs = Vec::new();
+        for i in 0..7 {
+            let id = parallel
+                .declare_function(&format!("f{}", i), Linkage::Local, &sig)
+                .unwrap();
+            funcs.push((id, make_function(&sig, i * 3)));
+        }
+        // Hand over the functions out of order; they must still be defined in declaration order.
+        funcs.reverse();
+        parallel.define_functions(funcs, 3).unwrap();
+
+        assert_eq!(parallel.finish(), sequential.finish());
+    }
+
+    #[test]
+    fn define_functions_duplicate() {
+        let mut sig = ir::Signature::new(CallConv::SystemV);
+        sig.returns.push(AbiParam::new(types::I64));
+
+        let mut module = make_module();
+        let id = module.declare_function("f", Linkage::Local, &sig).unwrap();
+        let funcs = vec![(id, make_function(&sig, 1)), (id, make_function(&sig, 2))];
+        match module.define_functions(funcs, 2) {
+            Err(ModuleError::DuplicateDefinition(ref name)) if name == "f" => {}
+            _ => panic!("expected a duplicate definition error"),
+        }
+        assert!(module.finish().is_empty());
+    }
+}
@@ -25,6 +25,7 @@ pub enum Token<'a> {
     Plus, // '+'
     Comma, // ','
     Dot, // '.'
+    Ellipsis, // '...'
     Colon, // ':'
     Equal, // '='
     Arrow, // '->'
@@ -37,6 +38,7 @@ pub enum Token<'a> {
     GlobalVar(u32), // gv3
     Heap(u32), // heap2
     JumpTable(u32), // jt2
+    Constant(u32), // const2
     FuncRef(u32), // fn2
     SigRef(u32), // sig2
     UserRef(u32), // u345
@@ -340,6 +342,7 @@ impl<'a> Lexer<'a> {
             "gv" => Some(Token::GlobalVar(number)),
             "heap" => Some(Token::Heap(number)),
             "jt" => Some(Token::JumpTable(number)),
+            "const" => Some(Token::Constant(number)),
             "fn" => Some(Token::FuncRef(number)),
             "sig" => Some(Token::SigRef(number)),
             "u" => Some(Token::UserRef(number)),
@@ -444,7 +447,13 @@ impl<'a> Lexer<'a> {
                 Some('[') => Some(self.scan_char(Token::LBracket)),
                 Some(']') => Some(self.scan_char(Token::RBracket)),
                 Some(',') => Some(self.scan_char(Token::Comma)),
-                Some('.') => Some(self.scan_char(Token::Dot)),
+                Some('.') => {
+                    if self.looking_at("...") {
+                        Some(self.scan_chars(3, Token::Ellipsis))
+                    } else {
+                        Some(self.scan_char(Token::Dot))
+                    }
+                }
                 Some(':') => Some(self.scan_char(Token::Colon)),
                 Some('=') => Some(self.scan_char(Token::Equal)),
                 Some('+') => Some(self.scan_number()),
@@ -545,7 +554,7 @@ mod tests {
 
     #[test]
     fn lex_chars() {
-        let mut lex = Lexer::new("(); hello\n = :{, }.");
+        let mut lex = Lexer::new("(); hello\n = :{, }. ...");
         assert_eq!(lex.next(), token(Token::LPar, 1));
         assert_eq!(lex.next(), token(Token::RPar, 1));
         assert_eq!(lex.next(), token(Token::Comment("; hello"), 1));
@@ -555,6 +564,7 @@ mod tests {
         assert_eq!(lex.next(), token(Token::Comma, 2));
         assert_eq!(lex.next(), token(Token::RBrace, 2));
         assert_eq!(lex.next(), token(Token::Dot, 2));
+        assert_eq!(lex.next(), token(Token::Ellipsis, 2));
         assert_eq!(lex.next(), None);
     }
 
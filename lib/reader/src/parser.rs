@@ -6,11 +6,11 @@ use cretonne_codegen::ir::entities::AnyEntity;
 use cretonne_codegen::ir::immediates::{Ieee32, Ieee64, Imm64, Offset32, Uimm32};
 use cretonne_codegen::ir::instructions::{InstructionData, InstructionFormat, VariableArgs};
 use cretonne_codegen::ir::types::VOID;
-use cretonne_codegen::ir::{AbiParam, ArgumentExtension, ArgumentLoc, Ebb, ExtFuncData,
-                           ExternalName, FuncRef, Function, GlobalVar, GlobalVarData, Heap,
-                           HeapBase, HeapData, HeapStyle, JumpTable, JumpTableData, MemFlags,
-                           Opcode, SigRef, Signature, StackSlot, StackSlotData, StackSlotKind,
-                           Type, Value, ValueLoc};
+use cretonne_codegen::ir::{AbiParam, ArgumentExtension, ArgumentLoc, Constant, ConstantData,
+                           Ebb, ExtFuncData, ExternalName, FuncRef, Function, GlobalVar,
+                           GlobalVarData, Heap, HeapBase, HeapData, HeapStyle, JumpTable,
+                           JumpTableData, MemFlags, Opcode, SigRef, Signature, StackSlot,
+                           StackSlotData, StackSlotKind, Type, Value, ValueLoc};
 use cretonne_codegen::isa::{self, Encoding, RegUnit, TargetIsa};
 use cretonne_codegen::packed_option::ReservedValue;
 use cretonne_codegen::settings::CallConv;
@@ -20,6 +20,7 @@ use isaspec;
 use lexer::{self, Lexer, Token};
 use sourcemap::SourceMap;
 use std::mem;
+use std::fmt;
 use std::str::FromStr;
 use std::{u16, u32};
 use testcommand::TestCommand;
@@ -61,6 +62,21 @@ pub fn parse_test(text: &str) -> Result<TestFile> {
     })
 }
 
+/// The largest entity number accepted by the parser.
+///
+/// The parser allocates all of the entities numbered below the ones it sees in the text, so a
+/// reasonable limit prevents a short input like `v300000000` from exhausting memory.
+const MAX_ENTITY_NUMBER: usize = 1 << 20;
+
+// Check that `entity` doesn't exceed `MAX_ENTITY_NUMBER`.
+fn check_entity_number<E: EntityRef + fmt::Display>(entity: E, loc: &Location) -> Result<()> {
+    if entity.index() > MAX_ENTITY_NUMBER {
+        err!(loc, "entity number too large: {}", entity)
+    } else {
+        Ok(())
+    }
+}
+
 pub struct Parser<'a> {
     lex: Lexer<'a>,
 
@@ -123,6 +139,7 @@ impl<'a> Context<'a> {
 
     // Allocate a new stack slot.
     fn add_ss(&mut self, ss: StackSlot, data: StackSlotData, loc: &Location) -> Result<()> {
+        check_entity_number(ss, loc)?;
         while self.function.stack_slots.next_key().index() <= ss.index() {
             self.function.create_stack_slot(
                 StackSlotData::new(StackSlotKind::SpillSlot, 0),
@@ -143,6 +160,7 @@ impl<'a> Context<'a> {
 
     // Allocate a global variable slot.
     fn add_gv(&mut self, gv: GlobalVar, data: GlobalVarData, loc: &Location) -> Result<()> {
+        check_entity_number(gv, loc)?;
         while self.function.global_vars.next_key().index() <= gv.index() {
             self.function.create_global_var(GlobalVarData::Sym {
                 name: ExternalName::testcase(""),
@@ -164,6 +182,7 @@ impl<'a> Context<'a> {
 
     // Allocate a heap slot.
     fn add_heap(&mut self, heap: Heap, data: HeapData, loc: &Location) -> Result<()> {
+        check_entity_number(heap, loc)?;
         while self.function.heaps.next_key().index() <= heap.index() {
             self.function.create_heap(HeapData {
                 base: HeapBase::ReservedReg,
@@ -187,6 +206,7 @@ impl<'a> Context<'a> {
 
     // Allocate a new signature.
     fn add_sig(&mut self, sig: SigRef, data: Signature, loc: &Location) -> Result<()> {
+        check_entity_number(sig, loc)?;
         while self.function.dfg.signatures.next_key().index() <= sig.index() {
             self.function.import_signature(
                 Signature::new(CallConv::Fast),
@@ -207,6 +227,7 @@ impl<'a> Context<'a> {
 
     // Allocate a new external function.
     fn add_fn(&mut self, fn_: FuncRef, data: ExtFuncData, loc: &Location) -> Result<()> {
+        check_entity_number(fn_, loc)?;
         while self.function.dfg.ext_funcs.next_key().index() <= fn_.index() {
             self.function.import_function(ExtFuncData {
                 name: ExternalName::testcase(""),
@@ -229,6 +250,7 @@ impl<'a> Context<'a> {
 
     // Allocate a new jump table.
     fn add_jt(&mut self, jt: JumpTable, data: JumpTableData, loc: &Location) -> Result<()> {
+        check_entity_number(jt, loc)?;
         while self.function.jump_tables.next_key().index() <= jt.index() {
             self.function.create_jump_table(JumpTableData::new());
         }
@@ -245,13 +267,39 @@ impl<'a> Context<'a> {
         }
     }
 
+    // Allocate a new constant.
+    fn add_constant(
+        &mut self,
+        constant: Constant,
+        data: ConstantData,
+        loc: &Location,
+    ) -> Result<()> {
+        check_entity_number(constant, loc)?;
+        while self.function.dfg.constants.next_key().index() <= constant.index() {
+            self.function.dfg.constants.push(ConstantData::default());
+        }
+        self.function.dfg.constants.set(constant, data);
+        self.map.def_constant(constant, loc)
+    }
+
+    // Resolve a reference to a constant.
+    fn check_constant(&self, constant: Constant, loc: &Location) -> Result<()> {
+        if !self.map.contains_constant(constant) {
+            err!(loc, "undefined constant {}", constant)
+        } else {
+            Ok(())
+        }
+    }
+
     // Allocate a new EBB.
     fn add_ebb(&mut self, ebb: Ebb, loc: &Location) -> Result<Ebb> {
+        check_entity_number(ebb, loc)?;
         while self.function.dfg.num_ebbs() <= ebb.index() {
             self.function.dfg.make_ebb();
         }
+        self.map.def_ebb(ebb, loc)?;
         self.function.layout.append_ebb(ebb);
-        self.map.def_ebb(ebb, loc).and(Ok(ebb))
+        Ok(ebb)
     }
 }
 
@@ -443,6 +491,17 @@ impl<'a> Parser<'a> {
         err!(self.loc, "expected jump table number: jt«n»")
     }
 
+    // Match and consume a constant reference.
+    fn match_constant(&mut self) -> Result<Constant> {
+        if let Some(Token::Constant(c)) = self.token() {
+            self.consume();
+            if let Some(c) = Constant::with_number(c) {
+                return Ok(c);
+            }
+        }
+        err!(self.loc, "expected constant number: const«n»")
+    }
+
     // Match and consume an ebb reference.
     fn match_ebb(&mut self, err_msg: &str) -> Result<Ebb> {
         if let Some(Token::Ebb(ebb)) = self.token() {
@@ -872,9 +931,9 @@ impl<'a> Parser<'a> {
             Token::LPar,
             "expected function signature: ( args... )",
         )?;
-        // signature ::=  "(" * [abi-param-list] ")" ["->" retlist] [callconv]
+        // signature ::=  "(" * [param-list] ")" ["->" retlist] [callconv]
         if self.token() != Some(Token::RPar) {
-            sig.params = self.parse_abi_param_list(unique_isa)?;
+            self.parse_signature_params(&mut sig, unique_isa)?;
         }
         self.match_token(
             Token::RPar,
@@ -902,6 +961,31 @@ impl<'a> Parser<'a> {
         Ok(sig)
     }
 
+    // Parse the list of function parameters, which may contain a `...` marker separating the fixed
+    // parameters from the variadic ones.
+    //
+    // param-list ::= * param-or-ellipsis { "," param-or-ellipsis }
+    // param-or-ellipsis ::= abi-param | "..."
+    fn parse_signature_params(
+        &mut self,
+        sig: &mut Signature,
+        unique_isa: Option<&TargetIsa>,
+    ) -> Result<()> {
+        loop {
+            if self.optional(Token::Ellipsis) {
+                if sig.fixed_params.is_some() {
+                    return err!(self.loc, "multiple '...' in signature");
+                }
+                sig.fixed_params = Some(sig.params.len());
+            } else {
+                sig.params.push(self.parse_abi_param(unique_isa)?);
+            }
+            if !self.optional(Token::Comma) {
+                return Ok(());
+            }
+        }
+    }
+
     // Parse list of function parameter / return value types.
     //
     // paramlist ::= * param { "," param }
@@ -1037,6 +1121,12 @@ impl<'a> Parser<'a> {
                         ctx.add_jt(jt, dat, &self.loc)
                     })
                 }
+                Some(Token::Constant(..)) => {
+                    self.start_gathering_comments();
+                    self.parse_constant_decl().and_then(|(constant, dat)| {
+                        ctx.add_constant(constant, dat, &self.loc)
+                    })
+                }
                 // More to come..
                 _ => return Ok(()),
             }?;
@@ -1090,7 +1180,7 @@ impl<'a> Parser<'a> {
     // global-var-decl ::= * GlobalVar(gv) "=" global-var-desc
     // global-var-desc ::= "vmctx" offset32
     //                   | "deref" "(" GlobalVar(base) ")" offset32
-    //                   | globalsym ["colocated"] name
+    //                   | ["colocated"] globalsym name
     //
     fn parse_global_var_decl(&mut self) -> Result<(GlobalVar, GlobalVarData)> {
         let gv = self.match_gv("expected global variable number: gv«n»")?;
@@ -1100,7 +1190,12 @@ impl<'a> Parser<'a> {
             "expected '=' in global variable declaration",
         )?;
 
-        let data = match self.match_any_identifier("expected global variable kind")? {
+        let colocated = self.optional(Token::Identifier("colocated"));
+        let kind = self.match_any_identifier("expected global variable kind")?;
+        if colocated && kind != "globalsym" {
+            return err!(self.loc, "only globalsym global variables can be colocated");
+        }
+        let data = match kind {
             "vmctx" => {
                 let offset = self.optional_offset32()?;
                 GlobalVarData::VMContext { offset }
@@ -1119,7 +1214,6 @@ impl<'a> Parser<'a> {
                 GlobalVarData::Deref { base, offset }
             }
             "globalsym" => {
-                let colocated = self.optional(Token::Identifier("colocated"));
                 let name = self.parse_external_name()?;
                 GlobalVarData::Sym { name, colocated }
             }
@@ -1324,6 +1418,30 @@ impl<'a> Parser<'a> {
         err!(self.loc, "jump_table too long")
     }
 
+    // Parse a constant decl.
+    //
+    // constant-decl ::= * Constant(c) "=" Integer(bytes)
+    fn parse_constant_decl(&mut self) -> Result<(Constant, ConstantData)> {
+        let constant = self.match_constant()?;
+        self.match_token(
+            Token::Equal,
+            "expected '=' in constant decl",
+        )?;
+
+        let data = if let Some(Token::Integer(text)) = self.token() {
+            self.consume();
+            text.parse().map_err(|e| self.error(e))?
+        } else {
+            return err!(self.loc, "expected hexadecimal constant data");
+        };
+
+        // Collect any trailing comments.
+        self.token();
+        self.claim_gathered_comments(constant);
+
+        Ok((constant, data))
+    }
+
     // jt-entry ::= * Ebb(dest) | "0"
     fn parse_jump_table_entry(&mut self) -> Result<Option<Ebb>> {
         match self.token() {
@@ -1422,6 +1540,7 @@ impl<'a> Parser<'a> {
             let results = self.parse_inst_results()?;
 
             for result in &results {
+                check_entity_number(*result, &self.loc)?;
                 while ctx.function.dfg.num_values() <= result.index() {
                     ctx.function.dfg.make_invalid_value_for_parser();
                 }
@@ -1505,6 +1624,7 @@ impl<'a> Parser<'a> {
         )?;
         // ebb-param ::= Value(v) ":" * Type(t) arg-loc?
 
+        check_entity_number(v, &v_location)?;
         while ctx.function.dfg.num_values() <= v.index() {
             ctx.function.dfg.make_invalid_value_for_parser();
         }
@@ -1696,7 +1816,7 @@ impl<'a> Parser<'a> {
         };
 
         // instruction ::=  [inst-results "="] Opcode(opc) ["." Type] * ...
-        let inst_data = self.parse_inst_operands(ctx, opcode)?;
+        let inst_data = self.parse_inst_operands(ctx, opcode, &opcode_loc)?;
 
         // We're done parsing the instruction now.
         //
@@ -1906,12 +2026,13 @@ impl<'a> Parser<'a> {
         Ok(args)
     }
 
-    // Parse the operands following the instruction opcode.
+    // Parse the operands following the instruction opcode at `opcode_loc`.
     // This depends on the format of the opcode.
     fn parse_inst_operands(
         &mut self,
         ctx: &mut Context,
         opcode: Opcode,
+        opcode_loc: &Location,
     ) -> Result<InstructionData> {
         let idata = match opcode.format() {
             InstructionFormat::Unary => InstructionData::Unary {
@@ -1942,6 +2063,14 @@ impl<'a> Parser<'a> {
                     global_var: gv,
                 }
             }
+            InstructionFormat::UnaryConst => {
+                let constant = self.match_constant()?;
+                ctx.check_constant(constant, &self.loc)?;
+                InstructionData::UnaryConst {
+                    opcode,
+                    constant_handle: constant,
+                }
+            }
             InstructionFormat::Binary => {
                 let lhs = self.match_value("expected SSA value first operand")?;
                 self.match_token(
@@ -1989,7 +2118,14 @@ impl<'a> Parser<'a> {
                 }
             }
             InstructionFormat::MultiAry => {
-                let args = self.parse_value_list()?;
+                // The arguments aren't delimited, so a value on the next line belongs to the next
+                // instruction, as in `return` followed by `v1 = ...`.
+                self.token();
+                let args = if self.loc.line_number == opcode_loc.line_number {
+                    self.parse_value_list()?
+                } else {
+                    VariableArgs::new()
+                };
                 InstructionData::MultiAry {
                     opcode,
                     args: args.into_value_list(&[], &mut ctx.function.dfg.value_lists),
@@ -2083,6 +2219,30 @@ impl<'a> Parser<'a> {
                 ctx.check_jt(table, &self.loc)?;
                 InstructionData::BranchTable { opcode, arg, table }
             }
+            InstructionFormat::BranchTableBase => {
+                let table = self.match_jt()?;
+                ctx.check_jt(table, &self.loc)?;
+                InstructionData::BranchTableBase { opcode, table }
+            }
+            InstructionFormat::BranchTableEntry => {
+                let index = self.match_value("expected SSA value operand")?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let base = self.match_value("expected SSA value operand")?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let table = self.match_jt()?;
+                ctx.check_jt(table, &self.loc)?;
+                InstructionData::BranchTableEntry {
+                    opcode,
+                    args: [index, base],
+                    table,
+                }
+            }
             InstructionFormat::InsertLane => {
                 let lhs = self.match_value("expected SSA value first operand")?;
                 self.match_token(
@@ -2200,6 +2360,62 @@ impl<'a> Parser<'a> {
                     args: args.into_value_list(&[], &mut ctx.function.dfg.value_lists),
                 }
             }
+            InstructionFormat::Invoke => {
+                let func_ref = self.match_fn("expected function reference")?;
+                ctx.check_fn(func_ref, &self.loc)?;
+                self.match_token(
+                    Token::LPar,
+                    "expected '(' before arguments",
+                )?;
+                let args = self.parse_value_list()?;
+                self.match_token(
+                    Token::RPar,
+                    "expected ')' after arguments",
+                )?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let destination = self.match_ebb("expected normal destination EBB")?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let landing_pad = self.match_ebb("expected landing pad EBB")?;
+                InstructionData::Invoke {
+                    opcode,
+                    targets: Box::new(ir::InvokeTargets {
+                        func_ref,
+                        destination,
+                        landing_pad,
+                    }),
+                    args: args.into_value_list(&[], &mut ctx.function.dfg.value_lists),
+                }
+            }
+            InstructionFormat::CallLandingPad => {
+                let func_ref = self.match_fn("expected function reference")?;
+                ctx.check_fn(func_ref, &self.loc)?;
+                self.match_token(
+                    Token::LPar,
+                    "expected '(' before arguments",
+                )?;
+                let args = self.parse_value_list()?;
+                self.match_token(
+                    Token::RPar,
+                    "expected ')' after arguments",
+                )?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let landing_pad = self.match_ebb("expected landing pad EBB")?;
+                InstructionData::CallLandingPad {
+                    opcode,
+                    func_ref,
+                    landing_pad,
+                    args: args.into_value_list(&[], &mut ctx.function.dfg.value_lists),
+                }
+            }
             InstructionFormat::CallIndirect => {
                 let sig_ref = self.match_sig("expected signature reference")?;
                 ctx.check_sig(sig_ref, &self.loc)?;
@@ -2494,6 +2710,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn multiary_args_on_one_line() {
+        let (func, _) = Parser::new(
+            "function %ret(i32) system_v {
+                                           ebb0(v0: i32):
+                                             return
+                                             v1 = iadd v0, v0
+                                             return v1,
+                                               v0
+                                           }",
+        ).parse_function(None)
+            .unwrap();
+        let ebb0 = func.layout.entry_block().unwrap();
+        let args = func.layout
+            .ebb_insts(ebb0)
+            .map(|inst| func.dfg.inst_args(inst).len())
+            .collect::<Vec<_>>();
+        assert_eq!(args, [0, 2, 2]);
+    }
+
+    #[test]
+    fn entity_number_too_large() {
+        let error = Parser::new(
+            "function %big() system_v {
+                                           ebb0:
+                                             v300000000 = iconst.i32 0
+                                             return
+                                           }",
+        ).parse_function(None)
+            .unwrap_err();
+        assert_eq!(error.location.line_number, 3);
+        assert_eq!(error.message, "entity number too large: v300000000");
+
+        let error = Parser::new("function %big() system_v { ebb9999999: return }")
+            .parse_function(None)
+            .unwrap_err();
+        assert_eq!(error.message, "entity number too large: ebb9999999");
+    }
+
     #[test]
     fn signature() {
         let sig = Parser::new("()system_v").parse_signature(None).unwrap();
@@ -2510,6 +2765,26 @@ mod tests {
         );
         assert_eq!(sig2.call_conv, CallConv::Baldrdash);
 
+        let sig3 = Parser::new("(i64, ..., f64, i32) -> i32 system_v")
+            .parse_signature(None)
+            .unwrap();
+        assert_eq!(sig3.fixed_params, Some(1));
+        assert_eq!(sig3.to_string(), "(i64, ..., f64, i32) -> i32 system_v");
+        assert_eq!(
+            Parser::new("(...)")
+                .parse_signature(None)
+                .unwrap()
+                .to_string(),
+            "(...) fast"
+        );
+        assert_eq!(
+            Parser::new("(i32, ..., ...)")
+                .parse_signature(None)
+                .unwrap_err()
+                .to_string(),
+            "1: multiple '...' in signature"
+        );
+
         // Old-style signature without a calling convention.
         assert_eq!(
             Parser::new("()").parse_signature(None).unwrap().to_string(),
@@ -2584,6 +2859,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn constant_decl() {
+        let (func, _) = Parser::new(
+            "function %foo() system_v {
+                                       const1 = 0x0102
+                                     }",
+        ).parse_function(None)
+            .unwrap();
+        let const1 = Constant::with_number(1).unwrap();
+        assert_eq!(func.dfg.constants.len(), 2);
+        assert_eq!(func.dfg.constants.get(const1).as_slice(), &[2, 1]);
+
+        // Catch bad data and undefined constants.
+        assert_eq!(
+            Parser::new(
+                "function %bar() system_v {
+                                    const0 = 0x12g4
+                                }",
+            ).parse_function(None)
+                .unwrap_err()
+                .to_string(),
+            "2: Invalid character in hexadecimal constant"
+        );
+        assert_eq!(
+            Parser::new(
+                "function %baz() system_v {
+                                ebb0:
+                                    v0 = const_addr.i64 const0
+                                }",
+            ).parse_function(None)
+                .unwrap_err()
+                .to_string(),
+            "3: undefined constant const0"
+        );
+    }
+
     #[test]
     fn ebb_header() {
         let (func, _) = Parser::new(
@@ -2604,6 +2915,45 @@ mod tests {
         let ebb4_args = func.dfg.ebb_params(ebb4);
         assert_eq!(ebb4_args.len(), 1);
         assert_eq!(func.dfg.value_type(ebb4_args[0]), types::I32);
+
+        // Catch duplicate definitions.
+        assert_eq!(
+            Parser::new(
+                "function %ebbs() system_v {
+                                     ebb0:
+                                     ebb0:
+                                     }",
+            ).parse_function(None)
+                .unwrap_err()
+                .to_string(),
+            "3: duplicate entity: ebb0"
+        );
+    }
+
+    #[test]
+    fn global_var_decl() {
+        let (func, _) = Parser::new(
+            "function %gvs() system_v {
+                                     gv0 = globalsym %foo
+                                     gv1 = colocated globalsym %bar
+                                     }",
+        ).parse_function(None)
+            .unwrap();
+        let mut gvs = func.global_vars.values().map(ToString::to_string);
+        assert_eq!(gvs.next().unwrap(), "globalsym %foo");
+        assert_eq!(gvs.next().unwrap(), "colocated globalsym %bar");
+        assert_eq!(gvs.next(), None);
+
+        assert_eq!(
+            Parser::new(
+                "function %gvs() system_v {
+                                     gv0 = colocated vmctx+8
+                                     }",
+            ).parse_function(None)
+                .unwrap_err()
+                .to_string(),
+            "2: only globalsym global variables can be colocated"
+        );
     }
 
     #[test]
@@ -7,7 +7,8 @@
 //! to parser clients.
 
 use cretonne_codegen::ir::entities::AnyEntity;
-use cretonne_codegen::ir::{Ebb, FuncRef, GlobalVar, Heap, JumpTable, SigRef, StackSlot, Value};
+use cretonne_codegen::ir::{Constant, Ebb, FuncRef, GlobalVar, Heap, JumpTable, SigRef, StackSlot,
+                           Value};
 use error::{Location, Result};
 use lexer::split_entity_name;
 use std::collections::HashMap;
@@ -61,6 +62,11 @@ impl SourceMap {
         self.locations.contains_key(&jt.into())
     }
 
+    /// Look up a constant entity.
+    pub fn contains_constant(&self, constant: Constant) -> bool {
+        self.locations.contains_key(&constant.into())
+    }
+
     /// Look up an entity by source name.
     /// Returns the entity reference corresponding to `name`, if it exists.
     pub fn lookup_str(&self, name: &str) -> Option<AnyEntity> {
@@ -121,6 +127,13 @@ impl SourceMap {
                     Some(jt.into())
                 })
             }
+            "const" => {
+                Constant::with_number(num).and_then(|c| if !self.contains_constant(c) {
+                    None
+                } else {
+                    Some(c.into())
+                })
+            }
             _ => None,
         })
     }
@@ -177,6 +190,11 @@ impl SourceMap {
         self.def_entity(entity.into(), loc)
     }
 
+    /// Define the constant `entity`.
+    pub fn def_constant(&mut self, entity: Constant, loc: &Location) -> Result<()> {
+        self.def_entity(entity.into(), loc)
+    }
+
     /// Define an entity. This can be used for instructions whose numbers never
     /// appear in source, or implicitly defined signatures.
     pub fn def_entity(&mut self, entity: AnyEntity, loc: &Location) -> Result<()> {
@@ -1,6 +1,6 @@
 //! Defines `SimpleJITBackend`.
 
-use cretonne_codegen::binemit::{Addend, CodeOffset, Reloc, RelocSink, NullTrapSink};
+use cretonne_codegen::binemit::{Addend, CodeOffset, Reloc, RelocSink};
 use cretonne_codegen::isa::TargetIsa;
 use cretonne_codegen::{self, ir, settings};
 use cretonne_module::{Backend, DataContext, Linkage, ModuleNamespace, Writability,
@@ -8,10 +8,12 @@ use cretonne_module::{Backend, DataContext, Linkage, ModuleNamespace, Writabilit
 use cretonne_native;
 use std::ffi::CString;
 use std::ptr;
+use std::sync::Arc;
 use libc;
 #[cfg(windows)]
 use winapi;
 use memory::Memory;
+use traps::{SimpleJITTrapManifest, SimpleJITTrapSink};
 
 /// A builder for `SimpleJITBackend`.
 pub struct SimpleJITBuilder {
@@ -44,10 +46,11 @@ impl SimpleJITBuilder {
 /// A `SimpleJITBackend` implements `Backend` and emits code and data into memory where it can be
 /// directly called and accessed.
 pub struct SimpleJITBackend {
-    isa: Box<TargetIsa>,
+    isa: Arc<TargetIsa>,
     code_memory: Memory,
     readonly_memory: Memory,
     writable_memory: Memory,
+    trap_manifest: SimpleJITTrapManifest,
 }
 
 /// A record of a relocation to perform.
@@ -85,17 +88,18 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
     type FinalizedFunction = *const u8;
     type FinalizedData = (*mut u8, usize);
 
-    /// SimpleJIT emits code and data into memory as it processes them, so it
-    /// doesn't need to provide anything after the `Module` is complete.
-    type Product = ();
+    /// SimpleJIT emits code and data into memory as it processes them, so the
+    /// product only holds the trap sites of the functions.
+    type Product = SimpleJITProduct;
 
     /// Create a new `SimpleJITBackend`.
     fn new(builder: SimpleJITBuilder) -> Self {
         Self {
-            isa: builder.isa,
+            isa: Arc::from(builder.isa),
             code_memory: Memory::new(),
             readonly_memory: Memory::new(),
             writable_memory: Memory::new(),
+            trap_manifest: SimpleJITTrapManifest::new(),
         }
     }
 
@@ -103,6 +107,10 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
         &*self.isa
     }
 
+    fn shared_isa(&self) -> Arc<TargetIsa> {
+        self.isa.clone()
+    }
+
     fn declare_function(&mut self, _name: &str, _linkage: Linkage) {
         // Nothing to do.
     }
@@ -113,7 +121,7 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
 
     fn define_function(
         &mut self,
-        _name: &str,
+        name: &str,
         ctx: &cretonne_codegen::Context,
         _namespace: &ModuleNamespace<Self>,
         code_size: u32,
@@ -123,10 +131,9 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
             "TODO: handle OOM etc.",
         );
         let mut reloc_sink = SimpleJITRelocSink::new();
-        // Ignore traps for now. For now, frontends should just avoid generating code
-        // that traps.
-        let mut trap_sink = NullTrapSink {};
+        let mut trap_sink = SimpleJITTrapSink::new(name, ptr, code_size);
         unsafe { ctx.emit_to_memory(&*self.isa, ptr, &mut reloc_sink, &mut trap_sink) };
+        self.trap_manifest.add_sink(trap_sink);
 
         Ok(Self::CompiledFunction {
             code: ptr,
@@ -233,6 +240,12 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
     ) -> Self::FinalizedFunction {
         use std::ptr::write_unaligned;
 
+        // If another function was finalized after this one was defined, this one's memory was
+        // protected along with it. Make it writable again to patch it.
+        if !func.relocs.is_empty() {
+            self.code_memory.set_writable(func.code, func.size);
+        }
+
         for &RelocRecord {
             reloc,
             offset,
@@ -343,7 +356,17 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
 
     /// SimpleJIT emits code and data into memory as it processes them, so it
     /// doesn't need to provide anything after the `Module` is complete.
-    fn finish(self) -> () {}
+    fn finish(self) -> SimpleJITProduct {
+        SimpleJITProduct { trap_manifest: self.trap_manifest }
+    }
+}
+
+/// This is the output of `Module`'s
+/// [`finish`](../cretonne_module/struct.Module.html#method.finish) function.
+/// The code and data stay in memory, so it only holds information about them.
+pub struct SimpleJITProduct {
+    /// The trap sites in every function defined in the module.
+    pub trap_manifest: SimpleJITTrapManifest,
 }
 
 #[cfg(not(windows))]
@@ -419,7 +442,11 @@ impl RelocSink for SimpleJITRelocSink {
         });
     }
 
-    fn reloc_jt(&mut self, _offset: CodeOffset, _reloc: Reloc, _jt: ir::JumpTable) {
-        unimplemented!();
+    fn reloc_jt(&mut self, _offset: CodeOffset, reloc: Reloc, _jt: ir::JumpTable) {
+        match reloc {
+            // Nothing to patch, see the documentation of `Reloc::X86PCRelRodata4`.
+            Reloc::X86PCRelRodata4 => {}
+            _ => panic!("unsupported jump table relocation {:?}", reloc),
+        }
     }
 }
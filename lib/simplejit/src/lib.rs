@@ -28,5 +28,6 @@ extern crate winapi;
 
 mod backend;
 mod memory;
+pub mod traps;
 
-pub use backend::{SimpleJITBuilder, SimpleJITBackend};
+pub use backend::{SimpleJITBuilder, SimpleJITBackend, SimpleJITProduct};
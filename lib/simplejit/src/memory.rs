@@ -75,7 +75,10 @@ impl PtrLen {
 /// accessible memory.
 pub struct Memory {
     allocations: Vec<PtrLen>,
+    /// The number of `allocations` which have already been protected.
     executable: usize,
+    /// Indices of protected `allocations` which were made writable again to be patched.
+    reopened: Vec<usize>,
     current: PtrLen,
     position: usize,
 }
@@ -85,6 +88,7 @@ impl Memory {
         Self {
             allocations: Vec::new(),
             executable: 0,
+            reopened: Vec::new(),
             current: PtrLen::new(),
             position: 0,
         }
@@ -117,31 +121,51 @@ impl Memory {
 
     /// Set all memory allocated in this `Memory` up to now as executable.
     pub fn set_executable(&mut self) {
-        self.finish_current();
+        self.protect(region::Protection::Execute, "unable to make memory executable");
+    }
 
-        for &PtrLen { ptr, len } in &self.allocations[self.executable..] {
-            if len != 0 {
-                unsafe {
-                    region::protect(ptr, len, region::Protection::Execute)
-                        .expect("unable to make memory executable");
-                }
+    /// Set all memory allocated in this `Memory` up to now as readonly.
+    pub fn set_readonly(&mut self) {
+        self.protect(region::Protection::Read, "unable to make memory readonly");
+    }
+
+    /// Make the already protected memory containing `ptr..ptr+len` writable again, so that
+    /// code allocated before the last `set_executable` can still be patched. The next
+    /// `set_executable` or `set_readonly` protects it again.
+    pub fn set_writable(&mut self, ptr: *const u8, len: usize) {
+        let start = ptr as usize;
+        let end = start + len;
+        for index in 0..self.executable {
+            let alloc = &self.allocations[index];
+            let alloc_start = alloc.ptr as usize;
+            if alloc.len == 0 || end <= alloc_start || alloc_start + alloc.len <= start ||
+                self.reopened.contains(&index)
+            {
+                continue;
             }
+            unsafe {
+                region::protect(alloc.ptr, alloc.len, region::Protection::ReadWrite)
+                    .expect("unable to make memory writable");
+            }
+            self.reopened.push(index);
         }
     }
 
-    /// Set all memory allocated in this `Memory` up to now as readonly.
-    pub fn set_readonly(&mut self) {
+    /// Protect the memory allocated since the last call, and the memory reopened by
+    /// `set_writable`, with `protection`.
+    fn protect(&mut self, protection: region::Protection, msg: &str) {
         self.finish_current();
 
-        for &PtrLen { ptr, len } in &self.allocations[self.executable..] {
+        let fresh = self.executable..self.allocations.len();
+        for index in self.reopened.drain(..).chain(fresh) {
+            let PtrLen { ptr, len } = self.allocations[index];
             if len != 0 {
                 unsafe {
-                    region::protect(ptr, len, region::Protection::Read).expect(
-                        "unable to make memory readonly",
-                    );
+                    region::protect(ptr, len, protection).expect(msg);
                 }
             }
         }
+        self.executable = self.allocations.len();
     }
 }
 
@@ -0,0 +1,66 @@
+//! SimpleJIT trap manifests record every `TrapCode` that cretonne outputs during code generation,
+//! for every function in the module, so a signal handler can tell which trap a fault came from.
+
+use cretonne_codegen::{ir, binemit};
+
+/// Record of the arguments cretonne passes to `TrapSink::trap`
+pub struct SimpleJITTrapSite {
+    /// Offset into function
+    pub offset: binemit::CodeOffset,
+    /// Source location given to cretonne
+    pub srcloc: ir::SourceLoc,
+    /// Trap code, as determined by cretonne
+    pub code: ir::TrapCode,
+}
+
+/// Record of the trap sites for a given function
+pub struct SimpleJITTrapSink {
+    /// Name of function
+    pub name: String,
+    /// Address of the code of the function
+    pub code: *const u8,
+    /// Total code size of function
+    pub code_size: u32,
+    /// All trap sites collected in function
+    pub sites: Vec<SimpleJITTrapSite>,
+}
+
+impl SimpleJITTrapSink {
+    /// Create an empty `SimpleJITTrapSink`
+    pub fn new(name: &str, code: *const u8, code_size: u32) -> Self {
+        Self {
+            sites: Vec::new(),
+            name: name.to_owned(),
+            code,
+            code_size,
+        }
+    }
+}
+
+impl binemit::TrapSink for SimpleJITTrapSink {
+    fn trap(&mut self, offset: binemit::CodeOffset, srcloc: ir::SourceLoc, code: ir::TrapCode) {
+        self.sites.push(SimpleJITTrapSite {
+            offset,
+            srcloc,
+            code,
+        });
+    }
+}
+
+/// Collection of all `SimpleJITTrapSink`s for the module
+pub struct SimpleJITTrapManifest {
+    /// All `SimpleJITTrapSink` for the module
+    pub sinks: Vec<SimpleJITTrapSink>,
+}
+
+impl SimpleJITTrapManifest {
+    /// Create an empty `SimpleJITTrapManifest`
+    pub fn new() -> Self {
+        Self { sinks: Vec::new() }
+    }
+
+    /// Put a `SimpleJITTrapSink` into manifest
+    pub fn add_sink(&mut self, sink: SimpleJITTrapSink) {
+        self.sinks.push(sink);
+    }
+}
@@ -0,0 +1,63 @@
+extern crate cretonne_codegen;
+extern crate cretonne_frontend;
+extern crate cretonne_module;
+extern crate cretonne_simplejit;
+
+use cretonne_codegen::ir::types::I32;
+use cretonne_codegen::ir::{AbiParam, InstBuilder};
+use cretonne_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cretonne_module::{Linkage, Module};
+use cretonne_simplejit::{SimpleJITBackend, SimpleJITBuilder};
+use std::mem;
+
+#[test]
+fn finalize_after_defining_all_functions() {
+    let mut module: Module<SimpleJITBackend> = Module::new(SimpleJITBuilder::new());
+    let mut ctx = module.make_context();
+    let mut fn_builder_ctx = FunctionBuilderContext::<Variable>::new();
+
+    ctx.func.signature.returns.push(AbiParam::new(I32));
+    let sig = ctx.func.signature.clone();
+    let callee = module
+        .declare_function("callee", Linkage::Local, &sig)
+        .unwrap();
+    let caller = module
+        .declare_function("caller", Linkage::Local, &sig)
+        .unwrap();
+
+    {
+        let mut builder = FunctionBuilder::<Variable>::new(&mut ctx.func, &mut fn_builder_ctx);
+        let ebb = builder.create_ebb();
+        builder.switch_to_block(ebb);
+        builder.seal_block(ebb);
+        let value = builder.ins().iconst(I32, 42);
+        builder.ins().return_(&[value]);
+        builder.finalize();
+    }
+    module.define_function(callee, &mut ctx).unwrap();
+    module.clear_context(&mut ctx);
+
+    ctx.func.signature = sig;
+    let callee_ref = module.declare_func_in_func(callee, &mut ctx.func);
+    {
+        let mut builder = FunctionBuilder::<Variable>::new(&mut ctx.func, &mut fn_builder_ctx);
+        let ebb = builder.create_ebb();
+        builder.switch_to_block(ebb);
+        builder.seal_block(ebb);
+        let call = builder.ins().call(callee_ref, &[]);
+        let value = builder.inst_results(call)[0];
+        builder.ins().return_(&[value]);
+        builder.finalize();
+    }
+    module.define_function(caller, &mut ctx).unwrap();
+
+    // Finalizing `callee` protects the memory `caller` was emitted to as well, but `caller`'s
+    // call to `callee` still needs to be patched when it is finalized.
+    let callee_code = module.finalize_function(callee);
+    let caller_code = module.finalize_function(caller);
+
+    let callee_fn: extern "C" fn() -> i32 = unsafe { mem::transmute(callee_code) };
+    let caller_fn: extern "C" fn() -> i32 = unsafe { mem::transmute(caller_code) };
+    assert_eq!(callee_fn(), 42);
+    assert_eq!(caller_fn(), 42);
+}
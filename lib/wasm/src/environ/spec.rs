@@ -101,6 +101,9 @@ pub trait FuncEnvironment {
     /// by `index`.
     ///
     /// The index space covers both imported and locally declared memories.
+    ///
+    /// Bounds checks on the returned heap are hardened against speculative execution when the
+    /// `enable_heap_access_spectre_mitigation` setting is enabled.
     fn make_heap(&mut self, func: &mut ir::Function, index: MemoryIndex) -> ir::Heap;
 
     /// Set up a signature definition in the preamble of `func` that can be used for an indirect
@@ -0,0 +1,656 @@
+//! CLI tool to reduce Cretonne IR test cases which make the code generator fail.
+//!
+//! The functions in the test file are compiled for the target ISA, and each function which fails
+//! in the requested way is simplified one step at a time: EBBs, instructions and EBB parameters
+//! are removed, instructions are replaced with constants, traps or one of their arguments, and
+//! unused stack slots, global variables, heaps, jump tables, signatures, external functions and
+//! constants are deleted. A step is kept only if the function still fails in the same way, and the
+//! reduction stops when none of the steps apply any more.
+
+use CommandResult;
+use cretonne_codegen::cursor::{Cursor, FuncCursor};
+use cretonne_codegen::entity::{EntityRef, PrimaryMap};
+use cretonne_codegen::ir::entities::AnyEntity;
+use cretonne_codegen::ir::immediates::{Ieee32, Ieee64};
+use cretonne_codegen::ir::{self, types, ConstantPool, Ebb, Function, GlobalVarData, HeapBase,
+                           HeapStyle, Inst, InstBuilder, InstructionData, StackSlots, TrapCode,
+                           Type, Value, ValueLoc};
+use cretonne_codegen::isa::TargetIsa;
+use cretonne_codegen::result::CtonError;
+use cretonne_codegen::{verifier, verify_function, Context};
+use cretonne_reader::{parse_test, Comment, Details};
+use filecheck::{Checker, CheckerBuilder, NO_VARIABLES};
+use std::cell::RefCell;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use utils::{parse_sets_and_isa, read_to_string};
+
+/// The kinds of failure that can be reproduced.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    /// The code generator panics.
+    Panic,
+    /// The verifier reports an error, either in the input function or after a compiler pass.
+    Verifier,
+    /// The compiled function doesn't match the filecheck directives in the test file.
+    Filecheck,
+}
+
+thread_local! {
+    /// The source location of the last panic.
+    static PANIC_LOCATION: RefCell<String> = RefCell::new(String::new());
+}
+
+/// What happened when a function was compiled.
+enum Outcome {
+    /// The function compiled and matched the filecheck directives, if any.
+    Success,
+    /// The input function failed verification.
+    Invalid(verifier::Errors),
+    /// The verifier failed after one of the compiler passes.
+    VerifierError(verifier::Errors),
+    /// The code generator panicked.
+    Panic {
+        message: String,
+        /// The source location of the panic, which identifies the bug.
+        location: String,
+    },
+    /// The compiled function didn't match the filecheck directives.
+    CheckFailed,
+    /// Compilation failed for another reason.
+    Error(CtonError),
+}
+
+pub fn run(
+    files: &[String],
+    flag_fail: &str,
+    flag_verbose: bool,
+    flag_set: &[String],
+    flag_isa: &str,
+) -> CommandResult {
+    let failure = match flag_fail {
+        "panic" => Failure::Panic,
+        "verifier" => Failure::Verifier,
+        "filecheck" => Failure::Filecheck,
+        _ => {
+            return Err(format!(
+                "unknown failure '{}', expected panic, verifier or filecheck",
+                flag_fail
+            ))
+        }
+    };
+    let parsed = parse_sets_and_isa(flag_set, flag_isa)?;
+    let name = &files[0];
+    let buffer = read_to_string(name).map_err(
+        |e| format!("{}: {}", name, e),
+    )?;
+    let test_file = parse_test(&buffer).map_err(|e| format!("{}: {}", name, e))?;
+
+    // If we have an isa from the command-line, use that. Otherwise if the
+    // file contains a unique isa, use that.
+    let isa = if let Some(isa) = parsed.as_fisa().isa {
+        isa
+    } else if let Some(isa) = test_file.isa_spec.unique_isa() {
+        isa
+    } else {
+        return Err(String::from("bugpoint requires a target isa"));
+    };
+
+    // Silence the panic messages from all the failing compilations, but keep track of where
+    // they come from.
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|info| {
+        let location = info.location().map_or(String::new(), |loc| {
+            format!("{}:{}", loc.file(), loc.line())
+        });
+        PANIC_LOCATION.with(|l| *l.borrow_mut() = location);
+    }));
+    let reduced = reduce_functions(
+        test_file.functions,
+        &test_file.preamble_comments,
+        isa,
+        failure,
+        flag_verbose,
+    );
+    panic::set_hook(hook);
+    let reduced = reduced?;
+
+    if reduced.is_empty() {
+        return Err(format!("{}: no function fails in the requested way", name));
+    }
+    for func in &reduced {
+        println!("{}", func.display(isa));
+    }
+    Ok(())
+}
+
+/// Reduce the functions which fail like `failure`.
+fn reduce_functions(
+    functions: Vec<(Function, Details)>,
+    preamble_comments: &[Comment],
+    isa: &TargetIsa,
+    failure: Failure,
+    verbose: bool,
+) -> Result<Vec<Function>, String> {
+    let mut reduced = Vec::new();
+    for (func, details) in functions {
+        let checker = build_checker(preamble_comments.iter().chain(&details.comments))?;
+        if failure == Failure::Filecheck && checker.is_empty() {
+            return Err(format!("{}: no filecheck directives found", func.name));
+        }
+        let mut reducer = Reducer {
+            isa,
+            checker,
+            original: Outcome::Success,
+            verbose,
+        };
+        if reducer.start(&func, failure) {
+            reduced.push(reducer.reduce(func));
+        } else if verbose {
+            println!("{} doesn't fail in the requested way, skipping it", func.name);
+        }
+    }
+    Ok(reduced)
+}
+
+/// Build a filechecker from the directives in `comments`.
+fn build_checker<'a, I>(comments: I) -> Result<Checker, String>
+where
+    I: Iterator<Item = &'a Comment<'a>>,
+{
+    let mut builder = CheckerBuilder::new();
+    for comment in comments {
+        builder.directive(comment.text).map_err(
+            |e| format!("filecheck: {}", e),
+        )?;
+    }
+    Ok(builder.finish())
+}
+
+/// Reduce a function while preserving a failure.
+struct Reducer<'a> {
+    isa: &'a TargetIsa,
+    checker: Checker,
+    /// How the original function fails.
+    original: Outcome,
+    verbose: bool,
+}
+
+impl<'a> Reducer<'a> {
+    /// Check the original function, and remember how it fails.
+    ///
+    /// Returns `false` if it doesn't fail like `failure`.
+    fn start(&mut self, func: &Function, failure: Failure) -> bool {
+        let outcome = self.outcome(func);
+        if self.verbose {
+            match outcome {
+                Outcome::Success => {}
+                Outcome::Invalid(ref errors) |
+                Outcome::VerifierError(ref errors) => {
+                    println!("{} fails verification: {}", func.name, errors.0[0])
+                }
+                Outcome::Panic {
+                    ref message,
+                    ref location,
+                } => println!("{} panics at {}: {}", func.name, location, message),
+                Outcome::CheckFailed => {
+                    println!("{} doesn't match the filecheck directives", func.name)
+                }
+                Outcome::Error(ref e) => println!("{} fails to compile: {}", func.name, e),
+            }
+        }
+        let fails = match (failure, &outcome) {
+            (Failure::Panic, &Outcome::Panic { .. }) |
+            (Failure::Verifier, &Outcome::Invalid(_)) |
+            (Failure::Verifier, &Outcome::VerifierError(_)) |
+            (Failure::Filecheck, &Outcome::CheckFailed) => true,
+            _ => false,
+        };
+        self.original = outcome;
+        fails
+    }
+
+    /// Compile `func` and report what happened.
+    fn outcome(&self, func: &Function) -> Outcome {
+        if self.isa.flags().enable_verifier() {
+            if let Err(errors) = verify_function(func, self.isa) {
+                return Outcome::Invalid(errors);
+            }
+        }
+        let mut ctx = Context::for_function(func.clone());
+        let isa = self.isa;
+        match panic::catch_unwind(AssertUnwindSafe(|| ctx.compile(isa))) {
+            Err(payload) => {
+                let message = if let Some(s) = payload.downcast_ref::<String>() {
+                    s.clone()
+                } else if let Some(s) = payload.downcast_ref::<&str>() {
+                    s.to_string()
+                } else {
+                    String::new()
+                };
+                let location = PANIC_LOCATION.with(|l| l.borrow().clone());
+                Outcome::Panic { message, location }
+            }
+            Ok(Err(CtonError::Verifier(errors))) => Outcome::VerifierError(errors),
+            Ok(Err(e)) => Outcome::Error(e),
+            Ok(Ok(_)) => {
+                let text = ctx.func.display(isa).to_string();
+                match self.checker.check(&text, NO_VARIABLES) {
+                    Ok(true) => Outcome::Success,
+                    _ => Outcome::CheckFailed,
+                }
+            }
+        }
+    }
+
+    /// Does `func` still fail in the same way as the original function?
+    ///
+    /// A panic must come from the same place. A verifier failure must still report the first
+    /// error of the original function, and when the input was invalid, no new errors may appear.
+    fn reproduces(&self, func: &Function) -> bool {
+        match (&self.original, &self.outcome(func)) {
+            (&Outcome::Panic { location: ref a, .. }, &Outcome::Panic { location: ref b, .. }) => {
+                a == b
+            }
+            (&Outcome::Invalid(ref original), &Outcome::Invalid(ref errors)) => {
+                errors.0.contains(&original.0[0]) &&
+                    errors.0.iter().all(|e| original.0.contains(e))
+            }
+            (&Outcome::VerifierError(ref original), &Outcome::VerifierError(ref errors)) => {
+                errors.0.contains(&original.0[0])
+            }
+            (&Outcome::CheckFailed, &Outcome::CheckFailed) => true,
+            _ => false,
+        }
+    }
+
+    /// Apply all of the reduction steps until none of them make progress.
+    fn reduce(&self, mut func: Function) -> Function {
+        loop {
+            if self.verbose {
+                println!(
+                    "{}: {} EBBs, {} instructions",
+                    func.name,
+                    func.layout.ebbs().count(),
+                    all_insts(&func).len()
+                );
+            }
+            let mut changed = false;
+            changed |= self.remove_ebbs(&mut func);
+            changed |= self.remove_insts(&mut func);
+            changed |= self.simplify_insts(&mut func);
+            changed |= self.bypass_insts(&mut func);
+            changed |= self.remove_ebb_params(&mut func);
+            changed |= self.remove_entities(&mut func);
+            if !changed {
+                return func;
+            }
+        }
+    }
+
+    /// Apply `mutate` to a copy of `func`, and keep the copy if it still fails.
+    ///
+    /// The mutation returns `false` if it doesn't apply to the function.
+    fn try_mutation<F>(&self, func: &mut Function, mutate: F) -> bool
+    where
+        F: FnOnce(&mut Function) -> bool,
+    {
+        let mut candidate = func.clone();
+        if mutate(&mut candidate) && self.reproduces(&candidate) {
+            *func = candidate;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Apply `mutate` to chunks of `items`, starting with half of the items at a time and
+    /// halving the chunks down to single items.
+    ///
+    /// This quickly gets rid of large irrelevant parts of big functions.
+    fn reduce_chunks<T, F>(&self, func: &mut Function, mut items: Vec<T>, mutate: F) -> bool
+    where
+        T: Copy,
+        F: Fn(&mut Function, T) -> bool,
+    {
+        let mut changed = false;
+        let mut size = (items.len() / 2).max(1);
+        loop {
+            let mut start = 0;
+            while start < items.len() {
+                let end = (start + size).min(items.len());
+                let chunk = items[start..end].to_vec();
+                if self.try_mutation(func, |f| chunk.iter().all(|&item| mutate(f, item))) {
+                    items.drain(start..end);
+                    changed = true;
+                } else {
+                    start = end;
+                }
+            }
+            if size == 1 {
+                return changed;
+            }
+            size /= 2;
+        }
+    }
+
+    /// Remove EBBs other than the entry block.
+    fn remove_ebbs(&self, func: &mut Function) -> bool {
+        let ebbs = func.layout.ebbs().skip(1).collect();
+        self.reduce_chunks(func, ebbs, remove_ebb)
+    }
+
+    /// Remove instructions.
+    fn remove_insts(&self, func: &mut Function) -> bool {
+        let insts = all_insts(func);
+        self.reduce_chunks(func, insts, |func, inst| {
+            func.layout.remove_inst(inst);
+            true
+        })
+    }
+
+    /// Replace instructions with constants, and terminators with traps.
+    fn simplify_insts(&self, func: &mut Function) -> bool {
+        let insts = all_insts(func)
+            .into_iter()
+            .filter(|&inst| can_simplify(func, inst))
+            .collect();
+        self.reduce_chunks(func, insts, simplify_inst)
+    }
+
+    /// Replace the results of instructions with their arguments.
+    fn bypass_insts(&self, func: &mut Function) -> bool {
+        let mut bypasses = Vec::new();
+        for inst in all_insts(func) {
+            bypasses.extend(bypass_args(func, inst).into_iter().map(|arg| (inst, arg)));
+        }
+        self.reduce_chunks(func, bypasses, |func, (inst, arg)| bypass_inst(func, inst, arg))
+    }
+
+    /// Remove EBB parameters, replacing them with constants.
+    fn remove_ebb_params(&self, func: &mut Function) -> bool {
+        let mut params = Vec::new();
+        for ebb in func.layout.ebbs() {
+            let is_entry = Some(ebb) == func.layout.entry_block();
+            if is_entry && func.signature.params.len() != func.dfg.num_ebb_params(ebb) {
+                continue;
+            }
+            params.extend(func.dfg.ebb_params(ebb).iter().filter(|&&param| {
+                has_zero(func.dfg.value_type(param))
+            }));
+        }
+        self.reduce_chunks(func, params, remove_ebb_param)
+    }
+
+    /// Delete unused entities from the function preamble.
+    fn remove_entities(&self, func: &mut Function) -> bool {
+        let mut entities: Vec<AnyEntity> = Vec::new();
+        entities.extend(func.stack_slots.keys().map(AnyEntity::from));
+        entities.extend(func.global_vars.keys().map(AnyEntity::from));
+        entities.extend(func.heaps.keys().map(AnyEntity::from));
+        entities.extend(func.jump_tables.keys().map(AnyEntity::from));
+        entities.extend(func.dfg.signatures.keys().map(AnyEntity::from));
+        entities.extend(func.dfg.ext_funcs.keys().map(AnyEntity::from));
+        entities.extend(func.dfg.constants.iter().map(|(c, _)| AnyEntity::from(c)));
+
+        // Removing an entity renumbers the ones after it, so go backwards.
+        let mut changed = false;
+        for &entity in entities.iter().rev() {
+            changed |= self.try_mutation(func, |func| remove_entity(func, entity));
+        }
+        changed
+    }
+}
+
+/// Get all the instructions in the layout of `func`.
+fn all_insts(func: &Function) -> Vec<Inst> {
+    func.layout
+        .ebbs()
+        .flat_map(|ebb| func.layout.ebb_insts(ebb))
+        .collect()
+}
+
+/// Remove `ebb` and all of its instructions.
+fn remove_ebb(func: &mut Function, ebb: Ebb) -> bool {
+    let mut pos = FuncCursor::new(func).at_top(ebb);
+    while pos.next_inst().is_some() {
+        pos.remove_inst_and_step_back();
+    }
+    func.layout.remove_ebb(ebb);
+    true
+}
+
+/// Can we build a zero constant of type `ty`?
+fn has_zero(ty: Type) -> bool {
+    ty.is_int() || ty.is_bool() || ty.is_float()
+}
+
+/// Build a zero constant of type `ty` with `builder`.
+fn build_zero<'f, B: InstBuilder<'f>>(builder: B, ty: Type) {
+    match ty {
+        types::F32 => builder.f32const(Ieee32::with_bits(0)),
+        types::F64 => builder.f64const(Ieee64::with_bits(0)),
+        _ if ty.is_bool() => builder.bconst(ty, false),
+        _ => builder.iconst(ty, 0),
+    };
+}
+
+/// Can `inst` be replaced with a constant or a trap?
+fn can_simplify(func: &Function, inst: Inst) -> bool {
+    let opcode = func.dfg[inst].opcode();
+    if opcode.is_terminator() {
+        return opcode != ir::Opcode::Trap;
+    }
+    match opcode {
+        ir::Opcode::Iconst | ir::Opcode::Bconst | ir::Opcode::F32const |
+        ir::Opcode::F64const => false,
+        _ => {
+            let results = func.dfg.inst_results(inst);
+            results.len() == 1 && has_zero(func.dfg.value_type(results[0]))
+        }
+    }
+}
+
+/// Replace `inst` with a constant if it has a result, or with a trap if it is a terminator.
+fn simplify_inst(func: &mut Function, inst: Inst) -> bool {
+    if func.dfg[inst].opcode().is_terminator() {
+        func.dfg.replace(inst).trap(TrapCode::User(0));
+    } else {
+        let ty = func.dfg.value_type(func.dfg.first_result(inst));
+        build_zero(func.dfg.replace(inst), ty);
+    }
+    true
+}
+
+/// Find the arguments of `inst` which can replace its result.
+fn bypass_args(func: &Function, inst: Inst) -> Vec<Value> {
+    let results = func.dfg.inst_results(inst);
+    if results.len() != 1 {
+        return Vec::new();
+    }
+    let ty = func.dfg.value_type(results[0]);
+    func.dfg
+        .inst_args(inst)
+        .iter()
+        .map(|&arg| func.dfg.resolve_aliases(arg))
+        .filter(|&arg| arg != results[0] && func.dfg.value_type(arg) == ty)
+        .collect()
+}
+
+/// Remove `inst`, and turn its result into an alias of its argument `arg`.
+fn bypass_inst(func: &mut Function, inst: Inst, arg: Value) -> bool {
+    if func.layout.inst_ebb(inst).is_none() {
+        return false;
+    }
+    let result = func.dfg.first_result(inst);
+    func.layout.remove_inst(inst);
+    func.dfg.clear_results(inst);
+    func.dfg.change_to_alias(result, arg);
+    for inst in all_insts(func) {
+        func.dfg.resolve_aliases_in_arguments(inst);
+    }
+    true
+}
+
+/// Remove the EBB parameter `param` along with the corresponding branch arguments, and define
+/// it with a constant instead.
+fn remove_ebb_param(func: &mut Function, param: Value) -> bool {
+    let (ebb, num) = match func.dfg.value_def(param) {
+        ir::ValueDef::Param(ebb, num) => (ebb, num),
+        ir::ValueDef::Result(..) => return false,
+    };
+    if Some(ebb) == func.layout.entry_block() {
+        func.signature.params.remove(num);
+    }
+    for inst in all_insts(func) {
+        if func.dfg[inst].branch_destination() != Some(ebb) {
+            continue;
+        }
+        let index = func.dfg.inst_fixed_args(inst).len() + num;
+        let mut args = func.dfg[inst].take_value_list().expect(
+            "branches have value lists",
+        );
+        args.remove(index, &mut func.dfg.value_lists);
+        func.dfg[inst].put_value_list(args);
+    }
+
+    let ty = func.dfg.value_type(param);
+    func.dfg.remove_ebb_param(param);
+    let mut pos = FuncCursor::new(func).at_first_insertion_point(ebb);
+    build_zero(pos.ins().with_result(param), ty);
+    true
+}
+
+/// Renumbers references to entities of one kind after one of them is removed.
+struct Renumber {
+    removed: AnyEntity,
+    index: usize,
+    /// Was a reference to the removed entity found?
+    used: bool,
+}
+
+impl Renumber {
+    fn visit<E: EntityRef + Into<AnyEntity>>(&mut self, entity: &mut E) {
+        let any = (*entity).into();
+        if any == self.removed {
+            self.used = true;
+        } else if mem::discriminant(&any) == mem::discriminant(&self.removed) &&
+                   entity.index() > self.index
+        {
+            *entity = E::new(entity.index() - 1);
+        }
+    }
+}
+
+/// Remove `entity` if it isn't used, renumbering the entities of the same kind after it.
+fn remove_entity(func: &mut Function, entity: AnyEntity) -> bool {
+    let index = match entity {
+        AnyEntity::StackSlot(e) => e.index(),
+        AnyEntity::GlobalVar(e) => e.index(),
+        AnyEntity::Heap(e) => e.index(),
+        AnyEntity::JumpTable(e) => e.index(),
+        AnyEntity::SigRef(e) => e.index(),
+        AnyEntity::FuncRef(e) => e.index(),
+        AnyEntity::Constant(e) => e.index(),
+        _ => return false,
+    };
+    let mut renumber = Renumber {
+        removed: entity,
+        index,
+        used: false,
+    };
+    renumber_references(func, &mut renumber);
+    if renumber.used {
+        return false;
+    }
+
+    match entity {
+        AnyEntity::StackSlot(ss) => {
+            let mut slots = StackSlots::new();
+            for (key, data) in func.stack_slots.iter() {
+                if key != ss {
+                    slots.push(data.clone());
+                }
+            }
+            func.stack_slots = slots;
+        }
+        AnyEntity::GlobalVar(gv) => func.global_vars = without(&func.global_vars, gv),
+        AnyEntity::Heap(heap) => func.heaps = without(&func.heaps, heap),
+        AnyEntity::JumpTable(jt) => func.jump_tables = without(&func.jump_tables, jt),
+        AnyEntity::SigRef(sig) => func.dfg.signatures = without(&func.dfg.signatures, sig),
+        AnyEntity::FuncRef(fn_) => func.dfg.ext_funcs = without(&func.dfg.ext_funcs, fn_),
+        AnyEntity::Constant(constant) => {
+            let mut pool = ConstantPool::new();
+            for (key, data) in func.dfg.constants.iter() {
+                if key != constant {
+                    pool.push(data.clone());
+                }
+            }
+            func.dfg.constants = pool;
+        }
+        _ => unreachable!(),
+    }
+    true
+}
+
+/// Copy `map` without the entry for `key`.
+fn without<K: EntityRef, V: Clone>(map: &PrimaryMap<K, V>, key: K) -> PrimaryMap<K, V> {
+    let mut copy = PrimaryMap::new();
+    for (k, v) in map.iter() {
+        if k != key {
+            copy.push(v.clone());
+        }
+    }
+    copy
+}
+
+/// Visit all the references to preamble entities in `func`.
+fn renumber_references(func: &mut Function, renumber: &mut Renumber) {
+    for inst in all_insts(func) {
+        match func.dfg[inst] {
+            InstructionData::UnaryGlobalVar { ref mut global_var, .. } => {
+                renumber.visit(global_var)
+            }
+            InstructionData::UnaryConst { ref mut constant_handle, .. } => {
+                renumber.visit(constant_handle)
+            }
+            InstructionData::BranchTable { ref mut table, .. } |
+            InstructionData::BranchTableBase { ref mut table, .. } |
+            InstructionData::BranchTableEntry { ref mut table, .. } => renumber.visit(table),
+            InstructionData::Call { ref mut func_ref, .. } |
+            InstructionData::CallLandingPad { ref mut func_ref, .. } |
+            InstructionData::FuncAddr { ref mut func_ref, .. } => renumber.visit(func_ref),
+            InstructionData::Invoke { ref mut targets, .. } => {
+                renumber.visit(&mut targets.func_ref)
+            }
+            InstructionData::CallIndirect { ref mut sig_ref, .. } => renumber.visit(sig_ref),
+            InstructionData::StackLoad { ref mut stack_slot, .. } |
+            InstructionData::StackStore { ref mut stack_slot, .. } => renumber.visit(stack_slot),
+            InstructionData::RegSpill { ref mut dst, .. } => renumber.visit(dst),
+            InstructionData::RegFill { ref mut src, .. } => renumber.visit(src),
+            InstructionData::HeapAddr { ref mut heap, .. } => renumber.visit(heap),
+            _ => {}
+        }
+    }
+    for loc in func.locations.values_mut() {
+        if let ValueLoc::Stack(ref mut ss) = *loc {
+            renumber.visit(ss);
+        }
+    }
+    for data in func.global_vars.values_mut() {
+        if let GlobalVarData::Deref { ref mut base, .. } = *data {
+            renumber.visit(base);
+        }
+    }
+    for data in func.heaps.values_mut() {
+        if let HeapBase::GlobalVar(ref mut base) = data.base {
+            renumber.visit(base);
+        }
+        if let HeapStyle::Dynamic { ref mut bound_gv } = data.style {
+            renumber.visit(bound_gv);
+        }
+    }
+    for data in func.dfg.ext_funcs.values_mut() {
+        renumber.visit(&mut data.signature);
+    }
+}
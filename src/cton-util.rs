@@ -14,9 +14,14 @@
 extern crate cfg_if;
 extern crate cretonne_codegen;
 extern crate cretonne_filetests;
+extern crate cretonne_interpreter;
+extern crate cretonne_module;
+extern crate cretonne_native;
 extern crate cretonne_reader;
+extern crate cretonne_simplejit;
 extern crate docopt;
 extern crate filecheck;
+extern crate libc;
 #[macro_use]
 extern crate serde_derive;
 extern crate capstone;
@@ -24,9 +29,11 @@ extern crate term;
 
 cfg_if! {
     if #[cfg(feature = "wasm")] {
+        extern crate cretonne_faerie;
         extern crate cretonne_wasm;
         extern crate wabt;
         mod wasm;
+        mod wasm2obj;
     }
 }
 
@@ -35,22 +42,27 @@ use docopt::Docopt;
 use std::io::{self, Write};
 use std::process;
 
+mod bugpoint;
 mod cat;
 mod compile;
 mod print_cfg;
 mod rsfilecheck;
+mod run;
 mod utils;
 
 const USAGE: &str = "
 Cretonne code generator utility
 
 Usage:
-    cton-util test [-vT] <file>...
+    cton-util test [-vT] [--bless] <file>...
     cton-util cat <file>...
     cton-util filecheck [-v] <file>
     cton-util print-cfg <file>...
     cton-util compile [-vpT] [--set <set>]... [--isa <isa>] <file>...
     cton-util wasm [-ctvpTs] [--set <set>]... [--isa <isa>] <file>...
+    cton-util wasm2obj [-v] [--set <set>]... [--isa <isa>] <file> <output>
+    cton-util bugpoint [-v] [--fail <failure>] [--set <set>]... [--isa <isa>] <file>
+    cton-util run [--set <set>]... [--call <call>]... <file>
     cton-util --help | --version
 
 Options:
@@ -64,9 +76,14 @@ Options:
     -c, --check-translation
                     just checks the correctness of Cretonne IR translated from WebAssembly
     -p, --print     print the resulting Cretonne IR
+    --bless         rewrite failing filecheck and bin: directives from the actual output
     -h, --help      print this help message
     --set=<set>     configure Cretonne settings
     --isa=<isa>     specify the Cretonne ISA
+    --fail=<failure>
+                    the failure to reproduce: panic, verifier or filecheck
+                    [default: panic]
+    --call=<call>   call a function, like '%add(1, 2)', and print the results
     --version       print the Cretonne version
 
 ";
@@ -79,15 +96,22 @@ struct Args {
     cmd_print_cfg: bool,
     cmd_compile: bool,
     cmd_wasm: bool,
+    cmd_wasm2obj: bool,
+    cmd_bugpoint: bool,
+    cmd_run: bool,
     arg_file: Vec<String>,
+    arg_output: String,
     flag_just_decode: bool,
     flag_check_translation: bool,
     flag_print: bool,
     flag_verbose: bool,
+    flag_bless: bool,
     flag_set: Vec<String>,
     flag_isa: String,
     flag_time_passes: bool,
     flag_print_size: bool,
+    flag_fail: String,
+    flag_call: Vec<String>,
 }
 
 /// A command either succeeds or fails with an error message.
@@ -106,7 +130,8 @@ fn cton_util() -> CommandResult {
 
     // Find the sub-command to execute.
     let result = if args.cmd_test {
-        cretonne_filetests::run(args.flag_verbose, &args.arg_file).map(|_time| ())
+        cretonne_filetests::run(args.flag_verbose, args.flag_bless, &args.arg_file)
+            .map(|_time| ())
     } else if args.cmd_cat {
         cat::run(&args.arg_file)
     } else if args.cmd_filecheck {
@@ -139,6 +164,32 @@ fn cton_util() -> CommandResult {
         );
 
         result
+    } else if args.cmd_wasm2obj {
+        #[cfg(feature = "wasm")]
+        let result = wasm2obj::run(
+            &args.arg_file[0],
+            &args.arg_output,
+            args.flag_verbose,
+            &args.flag_set,
+            &args.flag_isa,
+        );
+
+        #[cfg(not(feature = "wasm"))]
+        let result = Err(
+            "Error: cton-util was compiled without wasm support.".to_owned(),
+        );
+
+        result
+    } else if args.cmd_bugpoint {
+        bugpoint::run(
+            &args.arg_file,
+            &args.flag_fail,
+            args.flag_verbose,
+            &args.flag_set,
+            &args.flag_isa,
+        )
+    } else if args.cmd_run {
+        run::run(&args.arg_file, &args.flag_call, &args.flag_set)
     } else {
         // Debugging / shouldn't happen with proper command line handling above.
         Err(format!("Unhandled args: {:?}", args))
@@ -0,0 +1,395 @@
+//! CLI tool to compile Cretonne IR files for the host and call the functions in them.
+//!
+//! The functions are compiled with `cretonne-simplejit`, so they can call each other as well as
+//! the functions in the `cton-util` process, like the ones in the C library. Each `--call` option
+//! calls a function and prints the results in the syntax of a `run` test directive:
+//!
+//! ```text
+//! $ cton-util run add.cton --call '%add(1, 2)'
+//! %add(1, 2) == 3
+//! ```
+//!
+//! A trap in the compiled code is reported on stderr with its trap code, and ends the command.
+
+use CommandResult;
+use cretonne_codegen::binemit::CodeOffset;
+use cretonne_codegen::ir::{self, ExternalName, Function, GlobalVarData, TrapCode};
+use cretonne_codegen::isa::TargetIsa;
+use cretonne_codegen::print_errors::pretty_error;
+use cretonne_codegen::settings;
+use cretonne_codegen::Context;
+use cretonne_filetests::{is_callable, make_trampoline, parse_values};
+use cretonne_interpreter::DataValue;
+use cretonne_module::{FuncId, Linkage, Module, ModuleError};
+use cretonne_native;
+use cretonne_reader::{parse_options, parse_test, Location};
+use cretonne_simplejit::{SimpleJITBackend, SimpleJITBuilder};
+use std::collections::HashMap;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use utils::read_to_string;
+
+pub fn run(files: &[String], calls: &[String], flag_set: &[String]) -> CommandResult {
+    let name = &files[0];
+    let buffer = read_to_string(name).map_err(
+        |e| format!("{}: {}", name, e),
+    )?;
+    let test_file = parse_test(&buffer).map_err(|e| format!("{}: {}", name, e))?;
+    if calls.is_empty() {
+        return Err("nothing to run, use --call to call a function".to_string());
+    }
+
+    let mut jit = Jit::new(host_isa(flag_set)?);
+    for (func, _) in test_file.functions {
+        jit.define(func)?;
+    }
+    let calls = calls
+        .iter()
+        .map(|call| jit.prepare_call(call))
+        .collect::<Result<Vec<_>, _>>()?;
+    let code = jit.finalize();
+
+    for call in &calls {
+        let results = code.call(call);
+        println!(
+            "{}({}) == {}",
+            call.name,
+            display_values(&call.args),
+            display_values(&results)
+        );
+    }
+    Ok(())
+}
+
+/// Build a `TargetIsa` for the host, with the settings in `flag_set`.
+fn host_isa(flag_set: &[String]) -> Result<Box<TargetIsa>, String> {
+    let (mut flag_builder, isa_builder) = cretonne_native::builders()?;
+    parse_options(
+        flag_set.iter().map(|x| x.as_str()),
+        &mut flag_builder,
+        &Location { line_number: 0 },
+    ).map_err(|err| err.to_string())?;
+    let isa = isa_builder.finish(settings::Flags::new(flag_builder));
+    if isa.flags().is_pic() {
+        return Err("cton-util run doesn't support is_pic".to_string());
+    }
+    Ok(isa)
+}
+
+/// A function from the test file, defined in the JIT.
+struct JitFunction {
+    /// The name of the function in the test file.
+    name: String,
+    signature: ir::Signature,
+}
+
+/// A call to make once the functions are finalized.
+struct Call {
+    /// The name of the called function in the test file.
+    name: String,
+    func: FuncId,
+    /// The trampoline for the signature of the called function.
+    trampoline: FuncId,
+    args: Vec<DataValue>,
+    returns: Vec<ir::Type>,
+}
+
+/// The functions from a test file, compiled into a `SimpleJITBackend` module.
+struct Jit {
+    module: Module<SimpleJITBackend>,
+    functions: HashMap<FuncId, JitFunction>,
+    /// Trampolines for calling functions, by signature. They aren't in `functions`, so they can't
+    /// be called with `--call`.
+    trampolines: Vec<(ir::Signature, FuncId)>,
+}
+
+impl Jit {
+    fn new(isa: Box<TargetIsa>) -> Self {
+        Self {
+            module: Module::new(SimpleJITBuilder::with_isa(isa)),
+            functions: HashMap::new(),
+            trampolines: Vec::new(),
+        }
+    }
+
+    /// Compile the test file function `func` and define it in the module.
+    fn define(&mut self, func: Function) -> Result<FuncId, String> {
+        let name = func.name.to_string();
+        let signature = func.signature.clone();
+        let id = self.compile(func)?;
+        self.functions.insert(id, JitFunction { name, signature });
+        Ok(id)
+    }
+
+    /// Compile `func` and define it in the module.
+    ///
+    /// The external functions and global symbols referenced by `func` are declared in the module,
+    /// so they resolve to the other functions in the test file or to symbols in this process.
+    fn compile(&mut self, mut func: Function) -> Result<FuncId, String> {
+        let name = func.name.to_string();
+        let id = self.module
+            .declare_function(&symbol(&func.name), Linkage::Export, &func.signature)
+            .map_err(|e| format!("{}: {}", name, e))?;
+
+        for ext_func in func.dfg.ext_funcs.values_mut() {
+            let callee = self.module
+                .declare_function(
+                    &symbol(&ext_func.name),
+                    Linkage::Import,
+                    &func.dfg.signatures[ext_func.signature],
+                )
+                .map_err(|e| format!("{}: {}", name, e))?;
+            ext_func.name = ExternalName::from(callee);
+        }
+        for gv in func.global_vars.values_mut() {
+            if let GlobalVarData::Sym { ref mut name, .. } = *gv {
+                let data = self.module
+                    .declare_data(&symbol(name), Linkage::Import, true)
+                    .map_err(|e| e.to_string())?;
+                *name = ExternalName::from(data);
+            }
+        }
+
+        let mut ctx = Context::for_function(func);
+        self.module.define_function(id, &mut ctx).map_err(|e| match e {
+            ModuleError::Compilation(e) => pretty_error(&ctx.func, Some(self.module.isa()), e),
+            e => format!("{}: {}", name, e),
+        })?;
+        Ok(id)
+    }
+
+    /// Parse `call`, which looks like `%name(args)`, and define the trampoline needed to make it.
+    fn prepare_call(&mut self, call: &str) -> Result<Call, String> {
+        let call = call.trim();
+        let open = call.find('(').ok_or_else(
+            || format!("expected '(' in {}", call),
+        )?;
+        if !call.ends_with(')') {
+            return Err(format!("expected ')' at the end of {}", call));
+        }
+        let name = call[..open].trim().trim_left_matches('%');
+        let (id, func_name, signature) = {
+            let (&id, func) = self.functions
+                .iter()
+                .find(|&(_, func)| func.name.trim_left_matches('%') == name)
+                .ok_or_else(|| format!("no function named %{}", name))?;
+            (id, func.name.clone(), func.signature.clone())
+        };
+        if !is_callable(&signature) {
+            return Err(format!(
+                "can't call {} with signature {}",
+                func_name,
+                signature
+            ));
+        }
+        let args = parse_values(
+            &call[open + 1..call.len() - 1],
+            signature.params.iter().map(|p| p.value_type),
+        ).map_err(|e| format!("{}: {}", call, e))?;
+        Ok(Call {
+            name: func_name,
+            func: id,
+            trampoline: self.trampoline(&signature)?,
+            args,
+            returns: signature.returns.iter().map(|r| r.value_type).collect(),
+        })
+    }
+
+    /// Get the trampoline for calling functions with `signature`, defining it if necessary.
+    fn trampoline(&mut self, signature: &ir::Signature) -> Result<FuncId, String> {
+        if let Some(&(_, id)) = self.trampolines.iter().find(|&&(ref sig, _)| sig == signature) {
+            return Ok(id);
+        }
+        let mut func = make_trampoline(signature, self.module.isa());
+        func.name = ExternalName::testcase(format!("trampoline{}", self.trampolines.len()));
+        let id = self.compile(func)?;
+        self.trampolines.push((signature.clone(), id));
+        Ok(id)
+    }
+
+    /// Perform the relocations in all the functions and trampolines, and make them executable.
+    fn finalize(mut self) -> JitCode {
+        let mut code = HashMap::new();
+        let ids = self.functions.keys().cloned().chain(
+            self.trampolines.iter().map(|&(_, id)| id),
+        );
+        for id in ids.collect::<Vec<_>>() {
+            code.insert(id, self.module.finalize_function(id));
+        }
+        let trap_sites = self.module
+            .finish()
+            .trap_manifest
+            .sinks
+            .into_iter()
+            .map(|sink| {
+                let start = sink.code as usize;
+                let traps = sink.sites.iter().map(|site| (site.offset, site.code)).collect();
+                (start, start + sink.code_size as usize, traps)
+            })
+            .collect();
+        JitCode { code, trap_sites }
+    }
+}
+
+/// The finalized code of the functions and trampolines in a `Jit`.
+///
+/// The `SimpleJITBackend` never frees its memory, so the code outlives the module.
+struct JitCode {
+    /// The address of the code of each function, by `FuncId`.
+    code: HashMap<FuncId, *const u8>,
+    trap_sites: TrapSites,
+}
+
+impl JitCode {
+    /// Make `call` through its trampoline, and return the results.
+    fn call(&self, call: &Call) -> Vec<DataValue> {
+        let mut values = vec![0u64; call.args.len().max(call.returns.len())];
+        for (slot, arg) in values.iter_mut().zip(&call.args) {
+            *slot = arg.to_bits();
+        }
+        unsafe {
+            let trampoline: extern "C" fn(*const u8, *mut u64) =
+                mem::transmute(self.code[&call.trampoline]);
+            with_trap_handler(self.trap_sites.clone(), || {
+                trampoline(self.code[&call.func], values.as_mut_ptr())
+            });
+        }
+        call.returns
+            .iter()
+            .zip(values)
+            .map(|(&ty, bits)| DataValue::from_bits(ty, bits))
+            .collect()
+    }
+}
+
+/// Get the symbol name for `name`.
+///
+/// Test case names like `%malloc` are used without the `%`, so they can refer to C functions.
+fn symbol(name: &ExternalName) -> String {
+    name.to_string().trim_left_matches('%').to_string()
+}
+
+/// Format a list of values like the arguments of a `run` directive.
+fn display_values(values: &[DataValue]) -> String {
+    values
+        .iter()
+        .map(DataValue::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The code ranges of the compiled functions, along with their trap sites.
+type TrapSites = Vec<(usize, usize, Vec<(CodeOffset, TrapCode)>)>;
+
+/// The trap sites of the functions being called, for the signal handler. This is null when no
+/// functions are being called.
+static TRAP_SITES: AtomicPtr<TrapSites> = AtomicPtr::new(ptr::null_mut());
+
+/// Call `f` with a signal handler that reports traps in the code described by `sites`.
+#[cfg(unix)]
+unsafe fn with_trap_handler<F: FnOnce()>(sites: TrapSites, f: F) {
+    use libc::{sigaction, sigemptyset, SA_ONSTACK, SA_SIGINFO, SIGBUS, SIGFPE, SIGILL, SIGSEGV};
+
+    TRAP_SITES.store(Box::into_raw(Box::new(sites)), Ordering::SeqCst);
+    let mut action: sigaction = mem::zeroed();
+    action.sa_sigaction = trap_handler as usize;
+    // Use the alternate signal stack set up by the Rust runtime, to handle stack overflows.
+    action.sa_flags = SA_SIGINFO | SA_ONSTACK;
+    sigemptyset(&mut action.sa_mask);
+    let signals = [SIGILL, SIGFPE, SIGSEGV, SIGBUS];
+    let mut old_actions: [sigaction; 4] = mem::zeroed();
+    for (&signal, old) in signals.iter().zip(&mut old_actions) {
+        sigaction(signal, &action, old);
+    }
+    f();
+    for (&signal, old) in signals.iter().zip(&old_actions) {
+        sigaction(signal, old, ptr::null_mut());
+    }
+    drop(Box::from_raw(TRAP_SITES.swap(ptr::null_mut(), Ordering::SeqCst)));
+}
+
+#[cfg(not(unix))]
+unsafe fn with_trap_handler<F: FnOnce()>(_sites: TrapSites, f: F) {
+    f()
+}
+
+/// Buffer for the message printed by the signal handler, which can't allocate.
+#[cfg(unix)]
+struct TrapMessage {
+    bytes: [u8; 64],
+    len: usize,
+}
+
+#[cfg(unix)]
+impl ::std::fmt::Write for TrapMessage {
+    fn write_str(&mut self, s: &str) -> ::std::fmt::Result {
+        // Truncate rather than fail, there is no way to report an error from the signal handler.
+        let len = s.len().min(self.bytes.len() - self.len);
+        self.bytes[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+        Ok(())
+    }
+}
+
+/// The message printed by the signal handler. The handler exits the process, so it only runs once.
+#[cfg(unix)]
+static mut TRAP_MESSAGE: TrapMessage = TrapMessage {
+    bytes: [0; 64],
+    len: 0,
+};
+
+/// Signal handler which reports the trap code of the faulting instruction and exits.
+///
+/// Only async-signal-safe functions may be called here, so the message is formatted into a static
+/// buffer and written to stderr with `write`, and the process exits with `_exit`.
+#[cfg(unix)]
+extern "C" fn trap_handler(
+    signal: ::libc::c_int,
+    _info: *mut ::libc::siginfo_t,
+    context: *mut ::libc::c_void,
+) {
+    let pc = unsafe { program_counter(context) };
+    let code = pc.and_then(|pc| unsafe {
+        TRAP_SITES.load(Ordering::SeqCst).as_ref().and_then(|sites| {
+            sites
+                .iter()
+                .find(|&&(start, end, _)| start <= pc && pc < end)
+                .and_then(|&(start, _, ref traps)| {
+                    traps
+                        .iter()
+                        .find(|&&(offset, _)| start + offset as usize == pc)
+                        .map(|&(_, code)| code)
+                })
+        })
+    });
+    use std::fmt::Write;
+
+    unsafe {
+        let message = &mut TRAP_MESSAGE;
+        let _ = match code {
+            Some(code) => writeln!(message, "trap: {}", code),
+            None => writeln!(message, "signal {} outside of a trap site", signal),
+        };
+        ::libc::write(2, message.bytes.as_ptr() as *const ::libc::c_void, message.len);
+        ::libc::_exit(1)
+    }
+}
+
+/// Get the program counter from the signal context.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+unsafe fn program_counter(context: *mut ::libc::c_void) -> Option<usize> {
+    let context = &*(context as *const ::libc::ucontext_t);
+    Some(context.uc_mcontext.gregs[::libc::REG_RIP as usize] as usize)
+}
+
+#[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+unsafe fn program_counter(context: *mut ::libc::c_void) -> Option<usize> {
+    let context = &*(context as *const ::libc::ucontext_t);
+    Some((*context.uc_mcontext).__ss.__rip as usize)
+}
+
+#[cfg(all(unix, not(all(any(target_os = "linux", target_os = "macos"), target_arch = "x86_64"))))]
+unsafe fn program_counter(_context: *mut ::libc::c_void) -> Option<usize> {
+    None
+}
@@ -0,0 +1,797 @@
+//! CLI tool to compile WebAssembly modules ahead of time into native object files.
+//!
+//! Reads a Wasm binary or text file, translates it with
+//! [cretonne-wasm](../cretonne_wasm/index.html), compiles the functions for the target ISA, and
+//! writes them to an ELF object file with [cretonne-faerie](../cretonne_faerie/index.html). The
+//! ISA must be 64-bit and position independent:
+//!
+//! ```text
+//! $ cton-util wasm2obj --set is_64bit --set is_pic --isa x86 module.wasm module.o
+//! ```
+//!
+//! The object file is meant to be linked with a small runtime that allocates the linear memory
+//! and the `vmctx` structure, and handles traps.
+//!
+//! # Symbols
+//!
+//! - Each exported function is a global symbol named by its export name. The other functions
+//!   defined by the module are local symbols named `wasm_function_N`, where `N` is the wasm
+//!   function index.
+//! - Imported functions are undefined symbols named by their field name, ignoring the module name.
+//! - `wasm_grow_memory` is an undefined symbol which the runtime must define. It implements
+//!   `grow_memory`.
+//! - `wasm_module` is a global read-only `struct wasm_module` describing the module.
+//! - `wasm_traps` is the global trap table written by [`FaerieProduct::define_trap_table`].
+//! - The initial table, the initial values of the globals and the data segments are local data
+//!   objects referenced by `wasm_module`.
+//!
+//! All functions take a pointer to the `vmctx` structure as an extra last argument, including the
+//! imported functions and `wasm_grow_memory`. The other arguments and the results use the
+//! platform's C calling convention:
+//!
+//! ```c
+//! struct vmctx {
+//!     uint8_t *memory;          /* Base of the linear memory. */
+//!     uint32_t memory_pages;    /* Current size of the linear memory in 64 KiB pages. */
+//!     uint32_t padding;
+//!     struct wasm_table_entry *table;
+//!     uint64_t table_len;       /* Number of entries in `table`. */
+//!     uint64_t globals[];       /* The wasm globals, in the low bytes of each slot. */
+//! };
+//!
+//! struct wasm_table_entry {
+//!     void *func;               /* NULL for an empty entry. */
+//!     uint64_t signature;       /* Signature number, see below. */
+//! };
+//!
+//! struct wasm_data_segment {
+//!     const uint8_t *data;
+//!     uint64_t offset;          /* Offset in the linear memory. */
+//!     uint64_t len;
+//! };
+//!
+//! struct wasm_module {
+//!     uint64_t vmctx_size;      /* Size of `struct vmctx` with all the globals. */
+//!     uint64_t memory_min_pages;
+//!     uint64_t memory_max_pages; /* UINT64_MAX if the memory has no maximum. */
+//!     const struct wasm_table_entry *table; /* Initial table contents, or NULL. */
+//!     uint64_t table_len;
+//!     const uint64_t *globals;  /* Initial values of the globals, or NULL. */
+//!     uint64_t num_globals;
+//!     const struct wasm_data_segment *data_segments;
+//!     uint64_t num_data_segments;
+//!     void (*start)(struct vmctx *); /* The start function, or NULL. */
+//! };
+//!
+//! extern const struct wasm_module wasm_module;
+//! uint32_t wasm_grow_memory(uint32_t delta_pages, struct vmctx *vmctx);
+//! ```
+//!
+//! To instantiate the module, the runtime reserves 6 GiB of address space for the linear memory,
+//! since bounds checks are elided by relying on guard pages. Only the first `memory_min_pages`
+//! pages are accessible. It copies the data segments into the memory, copies the table and the
+//! globals into the `vmctx`, fills in the values of imported globals, and calls `start` if it
+//! isn't NULL. `wasm_grow_memory` must grow the memory in place, update `memory_pages`, and return
+//! the old number of pages, or `0xffffffff` if the memory can't grow.
+//!
+//! A table entry's signature number is one more than the index of the first signature in the
+//! module's type section which is equal to the function's signature, so empty entries never
+//! match. Indirect calls trap with `oob` for an index past `table_len`, `icall_null` for an empty
+//! entry, and `bad_sig` for a signature mismatch.
+//!
+//! Data segment and table element offsets based on an imported global, globals initialized from
+//! an imported global, and functions exported under more than one name are not supported.
+//!
+//! [`FaerieProduct::define_trap_table`]:
+//! ../cretonne_faerie/struct.FaerieProduct.html#method.define_trap_table
+
+use cretonne_codegen::cursor::FuncCursor;
+use cretonne_codegen::entity::EntityRef;
+use cretonne_codegen::ir::condcodes::IntCC;
+use cretonne_codegen::ir::types::{I32, I64};
+use cretonne_codegen::ir::{self, InstBuilder};
+use cretonne_codegen::print_errors::pretty_error;
+use cretonne_codegen::settings::{self, CallConv};
+use cretonne_codegen::Context;
+use cretonne_faerie::{FaerieBackend, FaerieBuilder, FaerieTrapCollection, Format};
+use cretonne_module::{DataContext, DataId, FuncId, Linkage, Module, ModuleError, Writability};
+use cretonne_wasm::{translate_module, FuncEnvironment, FuncTranslator, FunctionIndex, Global,
+                    GlobalIndex, GlobalInit, GlobalValue, Memory, MemoryIndex, ModuleEnvironment,
+                    SignatureIndex, Table, TableIndex, WasmResult};
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use utils::{parse_sets_and_isa, read_to_end, OwnedFlagsOrIsa};
+use wabt::wat2wasm;
+
+/// Offset of the linear memory base in the `vmctx`.
+const VMCTX_MEMORY: i32 = 0;
+/// Offset of the current number of memory pages in the `vmctx`.
+const VMCTX_MEMORY_PAGES: i32 = 8;
+/// Offset of the table pointer in the `vmctx`.
+const VMCTX_TABLE: i32 = 16;
+/// Offset of the table length in the `vmctx`.
+const VMCTX_TABLE_LEN: i32 = 24;
+/// Offset of the first global in the `vmctx`.
+const VMCTX_GLOBALS: i32 = 32;
+
+/// Size of a `struct wasm_table_entry`.
+const TABLE_ENTRY_SIZE: usize = 16;
+/// Size of a `struct wasm_data_segment`.
+const DATA_SEGMENT_SIZE: usize = 24;
+
+pub fn run(
+    file: &str,
+    output: &str,
+    flag_verbose: bool,
+    flag_set: &[String],
+    flag_isa: &str,
+) -> Result<(), String> {
+    let isa = match parse_sets_and_isa(flag_set, flag_isa)? {
+        OwnedFlagsOrIsa::Isa(isa) => isa,
+        OwnedFlagsOrIsa::Flags(_) => return Err(String::from("wasm2obj requires a target isa")),
+    };
+    if !isa.flags().is_64bit() {
+        return Err(String::from("wasm2obj requires is_64bit"));
+    }
+
+    let path = Path::new(file);
+    let mut data = read_to_end(path).map_err(|err| String::from(err.description()))?;
+    if !data.starts_with(&[b'\0', b'a', b's', b'm']) {
+        data = wat2wasm(&data).map_err(|e| String::from(e.description()))?;
+    }
+
+    let mut environ = ObjectEnvironment::new(isa.flags().clone());
+    translate_module(&data, &mut environ).map_err(|e| e.to_string())?;
+    if flag_verbose {
+        println!(
+            "Translated {} functions from {}",
+            environ.info.function_bodies.len(),
+            path.display()
+        );
+    }
+
+    let builder = FaerieBuilder::new(
+        isa,
+        output.to_string(),
+        Format::ELF,
+        FaerieTrapCollection::Enabled,
+        FaerieBuilder::default_libcall_names(),
+    ).map_err(|e| e.to_string())?;
+    let mut module = Module::<FaerieBackend>::new(builder);
+    define_module(&mut module, &environ.info)?;
+
+    let mut product = module.finish();
+    product.define_trap_table("wasm_traps").map_err(
+        |e| e.to_string(),
+    )?;
+    let file = File::create(output).map_err(|e| format!("{}: {}", output, e))?;
+    product.write(file).map_err(|e| format!("{}: {}", output, e))?;
+    if flag_verbose {
+        println!("Wrote {}", output);
+    }
+    Ok(())
+}
+
+/// The initial contents of a range of a memory or table.
+struct Initializer<T> {
+    index: usize,
+    base: Option<GlobalIndex>,
+    offset: usize,
+    contents: T,
+}
+
+/// The parts of a wasm module needed to write an object file.
+pub struct ObjectModuleInfo {
+    flags: settings::Flags,
+    signatures: Vec<ir::Signature>,
+    imported_funcs: Vec<String>,
+    functions: Vec<SignatureIndex>,
+    function_exports: Vec<Vec<String>>,
+    function_bodies: Vec<ir::Function>,
+    tables: Vec<Table>,
+    table_elements: Vec<Initializer<Vec<FunctionIndex>>>,
+    memories: Vec<Memory>,
+    data_segments: Vec<Initializer<Vec<u8>>>,
+    globals: Vec<Global>,
+    start_func: Option<FunctionIndex>,
+}
+
+impl ObjectModuleInfo {
+    /// Get the signature of the function `index`, including the `vmctx` parameter.
+    fn function_signature(&self, index: FunctionIndex) -> ir::Signature {
+        vmctx_sig(&self.signatures[self.functions[index]])
+    }
+
+    /// Get the signature number stored in table entries for the signature `index`.
+    fn signature_number(&self, index: SignatureIndex) -> i64 {
+        let sig = &self.signatures[index];
+        let first = self.signatures.iter().position(|s| s == sig).unwrap();
+        first as i64 + 1
+    }
+
+    /// Get the index of the `wasm_grow_memory` function, which is declared after the functions of
+    /// the wasm module.
+    fn grow_memory_index(&self) -> FunctionIndex {
+        self.functions.len()
+    }
+}
+
+/// Add the `vmctx` parameter to `sig`.
+fn vmctx_sig(sig: &ir::Signature) -> ir::Signature {
+    let mut sig = sig.clone();
+    sig.params.push(
+        ir::AbiParam::special(I64, ir::ArgumentPurpose::VMContext),
+    );
+    sig
+}
+
+/// Get the signature of `wasm_grow_memory`.
+fn grow_memory_sig() -> ir::Signature {
+    let mut sig = ir::Signature::new(CallConv::SystemV);
+    sig.params.push(ir::AbiParam::new(I32));
+    sig.returns.push(ir::AbiParam::new(I32));
+    vmctx_sig(&sig)
+}
+
+/// A `ModuleEnvironment` which translates functions for the `vmctx` layout described in the
+/// module documentation.
+pub struct ObjectEnvironment {
+    info: ObjectModuleInfo,
+    trans: FuncTranslator,
+}
+
+impl ObjectEnvironment {
+    fn new(flags: settings::Flags) -> Self {
+        Self {
+            info: ObjectModuleInfo {
+                flags,
+                signatures: Vec::new(),
+                imported_funcs: Vec::new(),
+                functions: Vec::new(),
+                function_exports: Vec::new(),
+                function_bodies: Vec::new(),
+                tables: Vec::new(),
+                table_elements: Vec::new(),
+                memories: Vec::new(),
+                data_segments: Vec::new(),
+                globals: Vec::new(),
+                start_func: None,
+            },
+            trans: FuncTranslator::new(),
+        }
+    }
+}
+
+impl<'data> ModuleEnvironment<'data> for ObjectEnvironment {
+    fn flags(&self) -> &settings::Flags {
+        &self.info.flags
+    }
+
+    fn get_func_name(&self, func_index: FunctionIndex) -> ir::ExternalName {
+        ir::ExternalName::user(0, func_index as u32)
+    }
+
+    fn declare_signature(&mut self, sig: &ir::Signature) {
+        // Use the C calling convention, so the runtime can call the exported functions, and
+        // provide the imported ones.
+        let mut sig = sig.clone();
+        sig.call_conv = CallConv::SystemV;
+        self.info.signatures.push(sig);
+    }
+
+    fn get_signature(&self, sig_index: SignatureIndex) -> &ir::Signature {
+        &self.info.signatures[sig_index]
+    }
+
+    fn declare_func_import(
+        &mut self,
+        sig_index: SignatureIndex,
+        _module: &'data str,
+        field: &'data str,
+    ) {
+        self.info.functions.push(sig_index);
+        self.info.function_exports.push(Vec::new());
+        self.info.imported_funcs.push(String::from(field));
+    }
+
+    fn get_num_func_imports(&self) -> usize {
+        self.info.imported_funcs.len()
+    }
+
+    fn declare_func_type(&mut self, sig_index: SignatureIndex) {
+        self.info.functions.push(sig_index);
+        self.info.function_exports.push(Vec::new());
+    }
+
+    fn get_func_type(&self, func_index: FunctionIndex) -> SignatureIndex {
+        self.info.functions[func_index]
+    }
+
+    fn declare_global(&mut self, global: Global) {
+        self.info.globals.push(global);
+    }
+
+    fn get_global(&self, global_index: GlobalIndex) -> &Global {
+        &self.info.globals[global_index]
+    }
+
+    fn declare_table(&mut self, table: Table) {
+        self.info.tables.push(table);
+    }
+
+    fn declare_table_elements(
+        &mut self,
+        table_index: TableIndex,
+        base: Option<GlobalIndex>,
+        offset: usize,
+        elements: Vec<FunctionIndex>,
+    ) {
+        self.info.table_elements.push(Initializer {
+            index: table_index,
+            base,
+            offset,
+            contents: elements,
+        });
+    }
+
+    fn declare_memory(&mut self, memory: Memory) {
+        self.info.memories.push(memory);
+    }
+
+    fn declare_data_initialization(
+        &mut self,
+        memory_index: MemoryIndex,
+        base: Option<GlobalIndex>,
+        offset: usize,
+        data: &'data [u8],
+    ) {
+        // The data of a segment is declared in chunks, so extend the last segment if this chunk
+        // continues it.
+        if let Some(last) = self.info.data_segments.last_mut() {
+            if last.index == memory_index && last.base == base &&
+                last.offset + last.contents.len() == offset
+            {
+                last.contents.extend_from_slice(data);
+                return;
+            }
+        }
+        self.info.data_segments.push(Initializer {
+            index: memory_index,
+            base,
+            offset,
+            contents: data.to_vec(),
+        });
+    }
+
+    fn declare_func_export(&mut self, func_index: FunctionIndex, name: &'data str) {
+        self.info.function_exports[func_index].push(String::from(name));
+    }
+
+    fn declare_table_export(&mut self, _table_index: TableIndex, _name: &'data str) {}
+
+    fn declare_memory_export(&mut self, _memory_index: MemoryIndex, _name: &'data str) {}
+
+    fn declare_global_export(&mut self, _global_index: GlobalIndex, _name: &'data str) {}
+
+    fn declare_start_func(&mut self, func_index: FunctionIndex) {
+        debug_assert!(self.info.start_func.is_none());
+        self.info.start_func = Some(func_index);
+    }
+
+    fn define_function_body(&mut self, body_bytes: &'data [u8]) -> WasmResult<()> {
+        let function_index = self.get_num_func_imports() + self.info.function_bodies.len();
+        let mut func = ir::Function::with_name_signature(
+            self.get_func_name(function_index),
+            self.info.function_signature(function_index),
+        );
+        self.trans.translate(
+            body_bytes,
+            &mut func,
+            &mut ObjectFuncEnvironment { info: &self.info },
+        )?;
+        self.info.function_bodies.push(func);
+        Ok(())
+    }
+}
+
+/// The `FuncEnvironment` used by `ObjectEnvironment`.
+struct ObjectFuncEnvironment<'a> {
+    info: &'a ObjectModuleInfo,
+}
+
+impl<'a> ObjectFuncEnvironment<'a> {
+    /// Get the flags for loads and stores of `vmctx` fields.
+    fn vmctx_flags() -> ir::MemFlags {
+        let mut flags = ir::MemFlags::new();
+        flags.set_notrap();
+        flags.set_aligned();
+        flags
+    }
+}
+
+impl<'a> FuncEnvironment for ObjectFuncEnvironment<'a> {
+    fn flags(&self) -> &settings::Flags {
+        &self.info.flags
+    }
+
+    fn make_global(&mut self, func: &mut ir::Function, index: GlobalIndex) -> GlobalValue {
+        let offset = (VMCTX_GLOBALS + 8 * index as i32).into();
+        let gv = func.create_global_var(ir::GlobalVarData::VMContext { offset });
+        GlobalValue::Memory {
+            gv,
+            ty: self.info.globals[index].ty,
+        }
+    }
+
+    fn make_heap(&mut self, func: &mut ir::Function, _index: MemoryIndex) -> ir::Heap {
+        let gv = func.create_global_var(ir::GlobalVarData::VMContext {
+            offset: VMCTX_MEMORY.into(),
+        });
+        func.create_heap(ir::HeapData {
+            base: ir::HeapBase::GlobalVar(gv),
+            min_size: 0.into(),
+            guard_size: 0x8000_0000.into(),
+            style: ir::HeapStyle::Static { bound: 0x1_0000_0000.into() },
+        })
+    }
+
+    fn make_indirect_sig(&mut self, func: &mut ir::Function, index: SignatureIndex) -> ir::SigRef {
+        func.import_signature(vmctx_sig(&self.info.signatures[index]))
+    }
+
+    fn make_direct_func(&mut self, func: &mut ir::Function, index: FunctionIndex) -> ir::FuncRef {
+        let signature = func.import_signature(self.info.function_signature(index));
+        func.import_function(ir::ExtFuncData {
+            name: ir::ExternalName::user(0, index as u32),
+            signature,
+            colocated: index >= self.info.imported_funcs.len(),
+        })
+    }
+
+    fn translate_call_indirect(
+        &mut self,
+        mut pos: FuncCursor,
+        _table_index: TableIndex,
+        sig_index: SignatureIndex,
+        sig_ref: ir::SigRef,
+        callee: ir::Value,
+        call_args: &[ir::Value],
+    ) -> WasmResult<ir::Inst> {
+        let vmctx = pos.func
+            .special_param(ir::ArgumentPurpose::VMContext)
+            .expect("Missing vmctx parameter");
+        let flags = Self::vmctx_flags();
+
+        let table = pos.ins().load(I64, flags, vmctx, VMCTX_TABLE);
+        let table_len = pos.ins().load(I64, flags, vmctx, VMCTX_TABLE_LEN);
+        let index = pos.ins().uextend(I64, callee);
+        let oob = pos.ins().icmp(
+            IntCC::UnsignedGreaterThanOrEqual,
+            index,
+            table_len,
+        );
+        pos.ins().trapnz(oob, ir::TrapCode::OutOfBounds);
+
+        let offset = pos.ins().imul_imm(index, TABLE_ENTRY_SIZE as i64);
+        let entry = pos.ins().iadd(table, offset);
+        let func_ptr = pos.ins().load(I64, flags, entry, 0);
+        pos.ins().trapz(func_ptr, ir::TrapCode::IndirectCallToNull);
+        let signature = pos.ins().load(I64, flags, entry, 8);
+        let bad_sig = pos.ins().icmp_imm(
+            IntCC::NotEqual,
+            signature,
+            self.info.signature_number(sig_index),
+        );
+        pos.ins().trapnz(bad_sig, ir::TrapCode::BadSignature);
+
+        let mut args = call_args.to_vec();
+        args.push(vmctx);
+        Ok(pos.ins().call_indirect(sig_ref, func_ptr, &args))
+    }
+
+    fn translate_call(
+        &mut self,
+        mut pos: FuncCursor,
+        _callee_index: FunctionIndex,
+        callee: ir::FuncRef,
+        call_args: &[ir::Value],
+    ) -> WasmResult<ir::Inst> {
+        let vmctx = pos.func
+            .special_param(ir::ArgumentPurpose::VMContext)
+            .expect("Missing vmctx parameter");
+        let mut args = call_args.to_vec();
+        args.push(vmctx);
+        Ok(pos.ins().call(callee, &args))
+    }
+
+    fn translate_grow_memory(
+        &mut self,
+        mut pos: FuncCursor,
+        _index: MemoryIndex,
+        _heap: ir::Heap,
+        val: ir::Value,
+    ) -> WasmResult<ir::Value> {
+        let vmctx = pos.func
+            .special_param(ir::ArgumentPurpose::VMContext)
+            .expect("Missing vmctx parameter");
+        let signature = pos.func.import_signature(grow_memory_sig());
+        let grow_memory = pos.func.import_function(ir::ExtFuncData {
+            name: ir::ExternalName::user(0, self.info.grow_memory_index() as u32),
+            signature,
+            colocated: false,
+        });
+        let call = pos.ins().call(grow_memory, &[val, vmctx]);
+        Ok(pos.func.dfg.first_result(call))
+    }
+
+    fn translate_current_memory(
+        &mut self,
+        mut pos: FuncCursor,
+        _index: MemoryIndex,
+        _heap: ir::Heap,
+    ) -> WasmResult<ir::Value> {
+        let vmctx = pos.func
+            .special_param(ir::ArgumentPurpose::VMContext)
+            .expect("Missing vmctx parameter");
+        Ok(pos.ins().load(
+            I32,
+            Self::vmctx_flags(),
+            vmctx,
+            VMCTX_MEMORY_PAGES,
+        ))
+    }
+}
+
+/// Declare and define the functions and data objects of the wasm module `info` in `module`.
+fn define_module(
+    module: &mut Module<FaerieBackend>,
+    info: &ObjectModuleInfo,
+) -> Result<(), String> {
+    // The function names in the IR are `u0:N`, where `N` is the wasm function index, so the
+    // functions must be declared first and in order, for their `FuncId`s to match.
+    let num_imports = info.imported_funcs.len();
+    for (index, exports) in info.function_exports.iter().enumerate() {
+        let (name, linkage) = if index < num_imports {
+            (info.imported_funcs[index].clone(), Linkage::Import)
+        } else {
+            match exports.len() {
+                0 => (format!("wasm_function_{}", index), Linkage::Local),
+                1 => (exports[0].clone(), Linkage::Export),
+                // Faerie can't define several symbols for one function.
+                _ => {
+                    return Err(format!(
+                        "function {} is exported as {}, but only one export name is supported",
+                        index,
+                        exports.join(" and ")
+                    ))
+                }
+            }
+        };
+        let id = module
+            .declare_function(&name, linkage, &info.function_signature(index))
+            .map_err(|e| e.to_string())?;
+        if id.index() != index {
+            return Err(format!("function symbol {} is declared twice", name));
+        }
+    }
+    let id = module
+        .declare_function("wasm_grow_memory", Linkage::Import, &grow_memory_sig())
+        .map_err(|e| e.to_string())?;
+    if id.index() != info.grow_memory_index() {
+        return Err(String::from("wasm_grow_memory is declared twice"));
+    }
+
+    for (def_index, body) in info.function_bodies.iter().enumerate() {
+        let mut ctx = Context::for_function(body.clone());
+        let id = FuncId::new(num_imports + def_index);
+        module.define_function(id, &mut ctx).map_err(|e| match e {
+            ModuleError::Compilation(e) => pretty_error(&ctx.func, Some(module.isa()), e),
+            e => e.to_string(),
+        })?;
+    }
+
+    define_descriptor(module, info)
+}
+
+/// Define the `wasm_module` descriptor and the data objects it refers to.
+fn define_descriptor(
+    module: &mut Module<FaerieBackend>,
+    info: &ObjectModuleInfo,
+) -> Result<(), String> {
+    let mut data_ctx = DataContext::new();
+    let mut bytes = Vec::new();
+
+    let vmctx_size = VMCTX_GLOBALS as usize + 8 * info.globals.len();
+    push_u64(&mut bytes, vmctx_size as u64);
+    match info.memories.first() {
+        Some(memory) => {
+            push_u64(&mut bytes, memory.pages_count as u64);
+            push_u64(&mut bytes, memory.maximum.map_or(u64::max_value(), |m| m as u64));
+        }
+        None => {
+            push_u64(&mut bytes, 0);
+            push_u64(&mut bytes, 0);
+        }
+    }
+
+    let table = define_table(module, info)?;
+    push_data_addr(module, &mut data_ctx, &mut bytes, table);
+    push_u64(&mut bytes, info.tables.first().map_or(0, |t| t.size as u64));
+
+    let globals = define_globals(module, info)?;
+    push_data_addr(module, &mut data_ctx, &mut bytes, globals);
+    push_u64(&mut bytes, info.globals.len() as u64);
+
+    let segments = define_data_segments(module, info)?;
+    push_data_addr(module, &mut data_ctx, &mut bytes, segments);
+    push_u64(&mut bytes, info.data_segments.len() as u64);
+
+    if let Some(start) = info.start_func {
+        let func_ref = module.declare_func_in_data(FuncId::new(start), &mut data_ctx);
+        data_ctx.write_function_addr(bytes.len() as u32, func_ref);
+    }
+    push_u64(&mut bytes, 0);
+
+    define_data(module, "wasm_module", Linkage::Export, bytes, data_ctx)?;
+    Ok(())
+}
+
+/// Define the initial contents of the table, if there is one.
+fn define_table(
+    module: &mut Module<FaerieBackend>,
+    info: &ObjectModuleInfo,
+) -> Result<Option<DataId>, String> {
+    let table = match info.tables.first() {
+        Some(table) => table,
+        None => return Ok(None),
+    };
+    let mut data_ctx = DataContext::new();
+    let mut bytes = vec![0; table.size * TABLE_ENTRY_SIZE];
+    for elements in &info.table_elements {
+        if elements.base.is_some() {
+            return Err(String::from(
+                "table element offsets based on a global aren't supported",
+            ));
+        }
+        if elements.offset + elements.contents.len() > table.size {
+            return Err(String::from("table elements are out of bounds"));
+        }
+        for (i, &func_index) in elements.contents.iter().enumerate() {
+            let at = (elements.offset + i) * TABLE_ENTRY_SIZE;
+            let func_ref = module.declare_func_in_data(FuncId::new(func_index), &mut data_ctx);
+            data_ctx.write_function_addr(at as u32, func_ref);
+            let signature = info.signature_number(info.functions[func_index]) as u64;
+            bytes[at + 8..at + 16].copy_from_slice(&u64_bytes(signature));
+        }
+    }
+    define_data(module, "wasm_table", Linkage::Local, bytes, data_ctx).map(Some)
+}
+
+/// Define the initial values of the globals, if there are any.
+fn define_globals(
+    module: &mut Module<FaerieBackend>,
+    info: &ObjectModuleInfo,
+) -> Result<Option<DataId>, String> {
+    if info.globals.is_empty() {
+        return Ok(None);
+    }
+    let mut bytes = Vec::new();
+    for global in &info.globals {
+        let initializer = match global.initializer {
+            // The runtime fills in the values of the imported globals.
+            GlobalInit::Import() => {
+                push_u64(&mut bytes, 0);
+                continue;
+            }
+            GlobalInit::GlobalRef(index) => info.globals[index].initializer,
+            initializer => initializer,
+        };
+        let value = match initializer {
+            GlobalInit::I32Const(x) => u64::from(x as u32),
+            GlobalInit::I64Const(x) => x as u64,
+            GlobalInit::F32Const(x) => u64::from(x),
+            GlobalInit::F64Const(x) => x,
+            GlobalInit::Import() |
+            GlobalInit::GlobalRef(_) => {
+                return Err(String::from(
+                    "globals initialized from an imported global aren't supported",
+                ))
+            }
+        };
+        push_u64(&mut bytes, value);
+    }
+    define_data(
+        module,
+        "wasm_globals",
+        Linkage::Local,
+        bytes,
+        DataContext::new(),
+    ).map(Some)
+}
+
+/// Define the data segments and the array of `struct wasm_data_segment` describing them, if there
+/// are any.
+fn define_data_segments(
+    module: &mut Module<FaerieBackend>,
+    info: &ObjectModuleInfo,
+) -> Result<Option<DataId>, String> {
+    if info.data_segments.is_empty() {
+        return Ok(None);
+    }
+    let mut data_ctx = DataContext::new();
+    let mut bytes = Vec::new();
+    for (index, segment) in info.data_segments.iter().enumerate() {
+        if segment.base.is_some() {
+            return Err(String::from(
+                "data segment offsets based on a global aren't supported",
+            ));
+        }
+        let mut segment_ctx = DataContext::new();
+        segment_ctx.define(segment.contents.clone().into_boxed_slice(), Writability::Readonly);
+        let name = format!("wasm_data_segment_{}", index);
+        let id = module.declare_data(&name, Linkage::Local, false).map_err(
+            |e| e.to_string(),
+        )?;
+        module.define_data(id, &segment_ctx).map_err(
+            |e| e.to_string(),
+        )?;
+
+        debug_assert_eq!(bytes.len(), index * DATA_SEGMENT_SIZE);
+        push_data_addr(module, &mut data_ctx, &mut bytes, Some(id));
+        push_u64(&mut bytes, segment.offset as u64);
+        push_u64(&mut bytes, segment.contents.len() as u64);
+    }
+    define_data(
+        module,
+        "wasm_data_segments",
+        Linkage::Local,
+        bytes,
+        data_ctx,
+    ).map(Some)
+}
+
+/// Declare a read-only data object `name` and define it with `bytes` and the relocations in
+/// `data_ctx`.
+fn define_data(
+    module: &mut Module<FaerieBackend>,
+    name: &str,
+    linkage: Linkage,
+    bytes: Vec<u8>,
+    mut data_ctx: DataContext,
+) -> Result<DataId, String> {
+    data_ctx.define(bytes.into_boxed_slice(), Writability::Readonly);
+    let id = module.declare_data(name, linkage, false).map_err(
+        |e| e.to_string(),
+    )?;
+    module.define_data(id, &data_ctx).map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+/// Append the address of the data object `data` to `bytes`, or a null pointer for `None`.
+fn push_data_addr(
+    module: &Module<FaerieBackend>,
+    data_ctx: &mut DataContext,
+    bytes: &mut Vec<u8>,
+    data: Option<DataId>,
+) {
+    if let Some(data) = data {
+        let gv = module.declare_data_in_data(data, data_ctx);
+        data_ctx.write_data_addr(bytes.len() as u32, gv, 0);
+    }
+    push_u64(bytes, 0);
+}
+
+/// Get the little-endian bytes of `value`.
+fn u64_bytes(value: u64) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (value >> (8 * i)) as u8;
+    }
+    bytes
+}
+
+/// Append `value` to `bytes` in little-endian order.
+fn push_u64(bytes: &mut Vec<u8>, value: u64) {
+    bytes.extend_from_slice(&u64_bytes(value));
+}
@@ -0,0 +1,84 @@
+//! Compile a small WebAssembly module with `cton-util wasm2obj`, and check the symbols in the
+//! object file.
+
+#![cfg(feature = "wasm")]
+
+extern crate goblin;
+
+use goblin::elf::sym::{Sym, STB_GLOBAL, STB_LOCAL, STT_FUNC, STT_OBJECT};
+use goblin::elf::Elf;
+use std::env;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::Command;
+
+const WAT: &str = r#"
+(module
+  (memory 1)
+  (func $add (export "add") (param i32 i32) (result i32)
+    (i32.add (get_local 0) (get_local 1)))
+  (func $one (result i32)
+    (i32.const 1))
+  (func $div (export "div") (param i32 i32) (result i32)
+    (i32.div_u (get_local 0) (get_local 1))))
+"#;
+
+/// Get the directory of the `cton-util` binary, which cargo builds before running the tests.
+fn bin_dir() -> PathBuf {
+    let mut dir = env::current_exe().expect("test executable path");
+    dir.pop();
+    if dir.ends_with("deps") {
+        dir.pop();
+    }
+    dir
+}
+
+#[test]
+fn wasm2obj() {
+    let dir = bin_dir();
+    let wat = dir.join("wasm2obj-smoke.wat");
+    let obj = dir.join("wasm2obj-smoke.o");
+    File::create(&wat)
+        .and_then(|mut file| file.write_all(WAT.as_bytes()))
+        .expect("write the .wat file");
+
+    let status = Command::new(dir.join(format!("cton-util{}", env::consts::EXE_SUFFIX)))
+        .args(&["wasm2obj", "--set", "is_64bit", "--set", "is_pic", "--isa", "x86"])
+        .arg(&wat)
+        .arg(&obj)
+        .status()
+        .expect("run cton-util");
+    assert!(status.success(), "cton-util wasm2obj failed");
+
+    let mut bytes = Vec::new();
+    File::open(&obj)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .expect("read the object file");
+    let elf = Elf::parse(&bytes).expect("parse the object file");
+    let symbol = |name: &str| -> Sym {
+        elf.syms
+            .iter()
+            .find(|sym| {
+                elf.strtab.get(sym.st_name).and_then(|s| s.ok()) == Some(name)
+            })
+            .unwrap_or_else(|| panic!("no symbol named {}", name))
+    };
+
+    for &(name, bind, typ) in &[
+        ("add", STB_GLOBAL, STT_FUNC),
+        ("div", STB_GLOBAL, STT_FUNC),
+        ("wasm_function_1", STB_LOCAL, STT_FUNC),
+        ("wasm_module", STB_GLOBAL, STT_OBJECT),
+        ("wasm_traps", STB_GLOBAL, STT_OBJECT),
+    ]
+    {
+        let sym = symbol(name);
+        assert_eq!(sym.st_bind(), bind, "binding of {}", name);
+        assert_eq!(sym.st_type(), typ, "type of {}", name);
+        assert!(sym.st_shndx != 0, "{} is undefined", name);
+    }
+    // The number of trap sites, followed by 16 bytes for each site. `div` has at least one.
+    assert!(symbol("wasm_traps").st_size >= 8 + 16);
+    assert!(symbol("wasm_grow_memory").is_import());
+}